@@ -0,0 +1,117 @@
+//! Benchmarks for the per-packet hot path, from number parsing up to a
+//! finalized book.
+//!
+//! Run with `cargo bench`; pass a name such as `cargo bench compact` to run
+//! one group.
+
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use rs_orderbook_streamer::driver::okx::OkxDriver;
+use rs_orderbook_streamer::driver::{ExchangeDriver, parse_levels};
+use rs_orderbook_streamer::model::{BOOK_DEPTH, L1FriendlyBook, Level};
+use rs_orderbook_streamer::util::parse_i64_with_precision;
+
+/// An OKX `books5`-style push with `depth` levels a side.
+fn okx_push(depth: usize) -> Vec<u8> {
+    let side = |base: f64, step: f64| {
+        (0..depth)
+            .map(|i| format!(r#"["{:.2}","{}.{:03}","0","{}"]"#, base + step * i as f64, i + 1, i * 7 % 1000, i % 9 + 1))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    format!(
+        r#"{{"arg":{{"channel":"books5","instId":"BTC-USDT"}},"data":[{{"asks":[{}],"bids":[{}],"instId":"BTC-USDT","ts":"1597026383085"}}]}}"#,
+        side(64_000.01, 0.5),
+        side(64_000.00, -0.5),
+    )
+    .into_bytes()
+}
+
+fn bench_parse_number(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_i64_with_precision");
+    for (name, input) in [
+        ("integer", &b"400900217,"[..]),
+        ("price", b"25.35190000\""),
+        ("negative", b"-0.00012345\""),
+        ("exponent", b"1.5e-3\""),
+    ] {
+        group.bench_function(name, |b| b.iter(|| parse_i64_with_precision(black_box(input), 0, 8)));
+    }
+    group.finish();
+}
+
+fn bench_parse_levels(c: &mut Criterion) {
+    let levels: Vec<_> = (0..BOOK_DEPTH).map(|i| format!(r#"["{}.5","{}.25"]"#, 64_000 - i, i + 1)).collect();
+    let array = format!("[{}]", levels.join(",")).into_bytes();
+    let mut side = [Level::default(); BOOK_DEPTH];
+    c.bench_function("parse_levels", |b| {
+        b.iter(|| parse_levels(black_box(&array), 0, 2, 8, &mut side).unwrap())
+    });
+}
+
+fn bench_compact(c: &mut Criterion) {
+    let levels: [Level; BOOK_DEPTH] = core::array::from_fn(|i| Level { price: 1_000 - i as i64, qty: 1 });
+    // Every third level removed, as after a busy update
+    let mut marked = 0;
+    for i in (0..BOOK_DEPTH).step_by(3) {
+        L1FriendlyBook::mark_removal(&mut marked, i);
+    }
+    // A different scatter of removals each run, so the branch predictor
+    // cannot learn one
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let churned: Vec<u32> = (0..1024)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as u32
+        })
+        .collect();
+    let mut run = 0;
+    let mut group = c.benchmark_group("compact");
+    for (name, compact) in [
+        ("branch_light", L1FriendlyBook::compact as fn(&mut [Level; BOOK_DEPTH], &mut u32) -> usize),
+        ("scalar", L1FriendlyBook::compact_scalar),
+    ] {
+        let compact = |(side, tombstones): &mut ([Level; BOOK_DEPTH], u32)| compact(side, tombstones);
+        group.bench_function(format!("{name}/sparse"), |b| {
+            b.iter_batched_ref(|| (levels, marked), compact, BatchSize::SmallInput)
+        });
+        group.bench_function(format!("{name}/churned"), |b| {
+            b.iter_batched_ref(
+                || {
+                    run += 1;
+                    (levels, churned[run % churned.len()])
+                },
+                compact,
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(format!("{name}/dense"), |b| {
+            b.iter_batched_ref(|| (levels, 0), compact, BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+/// A whole packet, finalized the way the connector does it.
+fn bench_apply_packet(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply_packet");
+    for depth in [5, BOOK_DEPTH] {
+        let msg = okx_push(depth);
+        let mut driver = OkxDriver::new();
+        let mut book = L1FriendlyBook::new();
+        group.bench_function(format!("okx_{depth}"), |b| {
+            b.iter(|| {
+                book.begin_write();
+                if driver.parse_message(black_box(&msg), &mut book).unwrap() {
+                    book.compact_sides();
+                }
+                book.end_write();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse_number, bench_parse_levels, bench_compact, bench_apply_packet);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+//! Feeds arbitrary frames to the venues' depth decoders.
+//!
+//! The first byte picks the venue and the rest is one frame, applied and,
+//! when the driver reports a change, finalized like the connector does.
+//! Venues whose drivers fetch a REST snapshot from `parse_message` are left
+//! out, so a run never touches the network.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rs_orderbook_streamer::broker::{Exchange, Feed, ProductType, SymbolKey};
+use rs_orderbook_streamer::driver::driver_for;
+use rs_orderbook_streamer::model::{L1FriendlyBook, Level};
+
+const VENUES: [(Exchange, ProductType, Feed); 14] = [
+    (Exchange::Binance, ProductType::Spot, Feed::Top { levels: 20, interval_ms: 100 }),
+    (Exchange::Binance, ProductType::Spot, Feed::Bbo),
+    (Exchange::Okx, ProductType::Spot, Feed::Depth),
+    (Exchange::Deribit, ProductType::Perpetual, Feed::Depth),
+    (Exchange::Bitfinex, ProductType::Spot, Feed::Depth),
+    (Exchange::Kucoin, ProductType::Spot, Feed::Depth),
+    (Exchange::Gate, ProductType::Spot, Feed::Depth),
+    (Exchange::Htx, ProductType::Spot, Feed::Depth),
+    (Exchange::Bitmex, ProductType::Perpetual, Feed::Depth),
+    (Exchange::Dydx, ProductType::Perpetual, Feed::Depth),
+    (Exchange::Hyperliquid, ProductType::Perpetual, Feed::Depth),
+    (Exchange::Kraken, ProductType::Perpetual, Feed::Depth),
+    (Exchange::Cme, ProductType::Future, Feed::Depth),
+    (Exchange::Nasdaq, ProductType::Spot, Feed::Depth),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&venue, frame)) = data.split_first() else {
+        return;
+    };
+    let (exchange, product, feed) = VENUES[venue as usize % VENUES.len()];
+    let key = SymbolKey {
+        exchange,
+        symbol: "BTC-USDT".to_string(),
+        product,
+        feed,
+    };
+    let mut driver = driver_for(&key).expect("venue has a driver");
+    // Binance picks its stream flavor from the key
+    if exchange == Exchange::Binance && driver.handshake(&key).is_err() {
+        return;
+    }
+
+    let mut book = L1FriendlyBook::new();
+    book.begin_write();
+    let applied = driver.parse_message(frame, &mut book);
+    if applied == Ok(true) {
+        book.compact_sides();
+    }
+    book.end_write();
+    if applied == Ok(true) {
+        // Compaction clears the marks, leaving live levels in front and
+        // nothing but empty slots after
+        assert_eq!((book.bid_tombstones, book.ask_tombstones), (0, 0));
+        for (side, count) in [(&book.bids, book.bid_count), (&book.asks, book.ask_count)] {
+            let live = usize::from(count);
+            assert!(side[..live].iter().all(|level| *level != Level::default()));
+            assert!(side[live..].iter().all(|level| *level == Level::default()));
+        }
+    }
+});
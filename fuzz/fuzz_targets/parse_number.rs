@@ -0,0 +1,26 @@
+//! Feeds arbitrary bytes to the fixed-point parser.
+//!
+//! The first byte picks the scale. Beyond not panicking, which includes
+//! not overflowing in debug builds, every value parsed must come back
+//! unchanged from its formatted text.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rs_orderbook_streamer::util::{MAX_FORMATTED_LEN, format_i64_with_precision, parse_i64_with_precision};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&scale, input)) = data.split_first() else {
+        return;
+    };
+    let scale = u32::from(scale % 20);
+
+    let Ok((value, next)) = parse_i64_with_precision(input, 0, scale) else {
+        return;
+    };
+    assert!(next <= input.len());
+
+    let mut buf = [0u8; MAX_FORMATTED_LEN];
+    let len = format_i64_with_precision(value, scale, &mut buf);
+    assert_eq!(parse_i64_with_precision(&buf[..len], 0, scale), Ok((value, len)));
+});
@@ -1,7 +1,12 @@
-use std::collections::HashMap;
-use std::sync::Arc;
-use parking_lot::RwLock;
-use crate::model::L1FriendlyBook;
+pub mod instruments;
+
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, LazyLock};
+use parking_lot::{Mutex, RwLock};
+use crate::connector::{CmdResult, ConnectorCmd, ExchangeConnector, PrivateEvent, StatusEvent, TransportConfig};
+use crate::model::SharedBook;
+use core_affinity::CoreId;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Represents the specific instrument class.
@@ -19,6 +24,91 @@ pub enum Exchange {
     Binance,
     Coinbase,
     Kraken,
+    Okx,
+    Deribit,
+    Bitfinex,
+    Kucoin,
+    Gate,
+    Htx,
+    Bitstamp,
+    Bitmex,
+    Dydx,
+    Hyperliquid,
+    Cme,
+    Nasdaq,
+    Mexc,
+    /// A venue served by a driver registered with
+    /// [register_custom](crate::driver::register_custom) under this name.
+    ///
+    /// Use [Exchange::custom] for names only known at runtime.
+    Custom(&'static str),
+}
+
+/// Names of runtime-defined venues, leaked once so [Exchange] stays `Copy`.
+static CUSTOM_NAMES: LazyLock<Mutex<HashSet<&'static str>>> = LazyLock::new(Default::default);
+
+impl Exchange {
+    /// Returns the [Exchange::Custom] venue called `name`, interning the name
+    /// on first use.
+    ///
+    /// Each distinct name is allocated once for the life of the process, so
+    /// this is meant for venue names read from configuration rather than
+    /// per-message data.
+    pub fn custom(name: &str) -> Self {
+        let mut names = CUSTOM_NAMES.lock();
+        match names.get(name) {
+            Some(interned) => Exchange::Custom(interned),
+            None => {
+                let interned: &'static str = Box::leak(name.into());
+                names.insert(interned);
+                Exchange::Custom(interned)
+            }
+        }
+    }
+}
+
+/// Which part of the book a stream maintains.
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub enum Feed {
+    /// The full depth-of-book stream.
+    #[default]
+    Depth,
+    /// Best bid and offer only, written to `bids[0]` and `asks[0]`.
+    ///
+    /// Venues without a dedicated top-of-book channel serve this from their
+    /// depth stream, so the book may carry more than one level.
+    Bbo,
+    /// The depth stream pushed every `interval_ms`, e.g. Binance
+    /// `depth@100ms` rather than its default one-second `depth`.
+    ///
+    /// Venues round to the nearest speed they offer, so 0 asks for the
+    /// fastest. Those with a single speed serve their depth stream.
+    DepthEvery { interval_ms: u16 },
+    /// Snapshots of the top `levels` levels every `interval_ms`, e.g.
+    /// Binance `depth20@100ms`.
+    ///
+    /// Each message replaces the book, so no REST snapshot or sequencing is
+    /// needed, at the cost of seeing nothing below `levels`. Venues round up
+    /// to the nearest size they offer; those without such a stream serve
+    /// their depth stream.
+    Top { levels: u8, interval_ms: u16 },
+    /// One channel of the account's authenticated user-data stream, logged
+    /// in with the exchange's [credentials](crate::connector::auth::configure).
+    ///
+    /// Nothing is written to the book; each event is reported as a
+    /// [PrivateEvent] instead.
+    Private(PrivateChannel),
+}
+
+/// Account events carried by a [Feed::Private] stream.
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
+pub enum PrivateChannel {
+    /// New, amended, filled and cancelled orders.
+    Orders,
+    /// Asset balance changes.
+    Balances,
+    /// Derivatives position changes.
+    Positions,
 }
 
 /// A unique identifier for a market data stream.
@@ -27,6 +117,7 @@ pub struct SymbolKey {
     pub exchange: Exchange,
     pub symbol: String, // e.g., "BTC-USDT"
     pub product: ProductType,
+    pub feed: Feed,
 }
 
 /// Trait for handling subscription teardown logic.
@@ -39,6 +130,9 @@ pub trait SubscriptionTeardown: Send + Sync {
 pub struct MarketBroker {
     /// Maps symbols to their L1-resident book and active handle count.
     subscriptions: Arc<RwLock<HashMap<SymbolKey, Arc<SubscriptionData>>>>,
+
+    /// One pinned connector per core in the broker's core mask.
+    connectors: Arc<Vec<ExchangeConnector>>,
 }
 
 /// Internal container for shared market data and its lifecycle state.
@@ -47,7 +141,7 @@ pub struct MarketBroker {
 /// across all active [SubscriptionHandle]s for a specific [SymbolKey].
 struct SubscriptionData {
     /// The L1-resident order book shared by all subscribers.
-    book: Arc<L1FriendlyBook>,
+    book: Arc<SharedBook>,
 
     /// The number of active [SubscriptionHandle]s currently held.
     ///
@@ -62,19 +156,97 @@ struct SubscriptionData {
 /// trigger an unsubscription from the exchange.
 pub struct SubscriptionHandle {
     pub key: SymbolKey,
-    pub book: Arc<L1FriendlyBook>,
+    pub book: Arc<SharedBook>,
     registry: Arc<RwLock<HashMap<SymbolKey, Arc<SubscriptionData>>>>,
     teardown: Box<dyn SubscriptionTeardown>,
 }
 
 impl MarketBroker {
     /// Creates a new broker instance.
-    pub fn new() -> Self {
+    ///
+    /// One [ExchangeConnector] is spawned and pinned for every set bit in
+    /// `core_mask` (e.g. `0b1100` pins workers to cores 2 and 3).
+    ///
+    /// # Panics
+    /// Panics if `core_mask` is zero.
+    pub fn new(core_mask: u64) -> Self {
+        Self::with_transport(core_mask, &TransportConfig::default())
+    }
+
+    /// Creates a broker whose connectors reach the venues as set by
+    /// `transport`, e.g. through a proxy or with custom TLS roots.
+    ///
+    /// # Panics
+    /// Panics if `core_mask` is zero.
+    pub fn with_transport(core_mask: u64, transport: &TransportConfig) -> Self {
+        assert!(core_mask != 0, "core_mask must select at least one core");
+
+        let connectors = (0..u64::BITS as usize)
+            .filter(|id| core_mask & (1 << id) != 0)
+            .map(|id| ExchangeConnector::new(CoreId { id }, transport))
+            .collect();
+
         Self {
             subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            connectors: Arc::new(connectors),
         }
     }
 
+    /// Creates a broker whose exchanges all share a single pinned
+    /// [event loop](ExchangeConnector::event_loop) worker on `core`.
+    ///
+    /// Suits deployments with many symbols but few spare cores.
+    pub fn event_loop(core: usize, transport: &TransportConfig) -> std::io::Result<Self> {
+        let connector = ExchangeConnector::event_loop(CoreId { id: core }, transport)?;
+        Ok(Self {
+            subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            connectors: Arc::new(vec![connector]),
+        })
+    }
+
+    /// Stops every connector, closing their streams.
+    ///
+    /// Books of live subscriptions are marked stale and stop updating;
+    /// subscribing afterwards returns books that never fill.
+    pub fn shutdown(&self) {
+        for connector in self.connectors.iter() {
+            connector.shutdown();
+        }
+    }
+
+    /// Drains the [CmdResult]s every connector has reported since the last
+    /// call, e.g. to learn whether a new subscription reached its venue.
+    ///
+    /// Clones of the broker share one set of results.
+    pub fn results(&self) -> Vec<CmdResult> {
+        self.connectors
+            .iter()
+            .flat_map(|connector| connector.results().try_iter())
+            .collect()
+    }
+
+    /// Drains the [StatusEvent]s every connector has emitted since the last
+    /// call, e.g. to pause a strategy while a venue reconnects.
+    ///
+    /// Clones of the broker share one set of events.
+    pub fn status_events(&self) -> Vec<StatusEvent> {
+        self.connectors
+            .iter()
+            .flat_map(|connector| connector.status().try_iter())
+            .collect()
+    }
+
+    /// Drains the [PrivateEvent]s every connector has reported since the
+    /// last call, e.g. to feed fills to an execution component.
+    ///
+    /// Clones of the broker share one set of events.
+    pub fn private_events(&self) -> Vec<PrivateEvent> {
+        self.connectors
+            .iter()
+            .flat_map(|connector| connector.private_events().try_iter())
+            .collect()
+    }
+
     /// Subscribes to a specific market product.
     ///
     /// If this is the first subscription for a given `SymbolKey`, it initiates the subscription
@@ -87,25 +259,74 @@ impl MarketBroker {
         symbol: &str,
         product: ProductType
     ) -> SubscriptionHandle {
-        let key = SymbolKey {
+        self.subscribe_feed(exchange, symbol, product, Feed::Depth)
+    }
+
+    /// Subscribes to the best bid and offer of a specific market product.
+    ///
+    /// The book is shared with other top-of-book subscribers only; depth
+    /// subscriptions to the same product use a separate stream.
+    pub fn subscribe_bbo(
+        &self,
+        exchange: Exchange,
+        symbol: &str,
+        product: ProductType
+    ) -> SubscriptionHandle {
+        self.subscribe_feed(exchange, symbol, product, Feed::Bbo)
+    }
+
+    /// Subscribes to one [Feed] flavor of a specific market product.
+    ///
+    /// Each flavor is a separate stream with its own shared book, so
+    /// strategies wanting different trade-offs between latency and depth do
+    /// not interfere.
+    pub fn subscribe_feed(
+        &self,
+        exchange: Exchange,
+        symbol: &str,
+        product: ProductType,
+        feed: Feed,
+    ) -> SubscriptionHandle {
+        self.subscribe_key(SymbolKey {
             exchange,
             symbol: symbol.to_string(),
             product,
-        };
+            feed,
+        })
+    }
 
+    /// Subscribes to one channel of the account's private stream.
+    ///
+    /// `symbol` narrows the stream to one instrument where the venue allows
+    /// it, and otherwise only picks the market, e.g. Binance spot versus
+    /// COIN-margined futures; an empty symbol covers the whole account.
+    /// Events are drained with [MarketBroker::private_events] and the
+    /// handle's book stays empty. Exchanges without
+    /// [credentials](crate::connector::auth::configure) reject the key.
+    pub fn subscribe_private(
+        &self,
+        exchange: Exchange,
+        symbol: &str,
+        product: ProductType,
+        channel: PrivateChannel,
+    ) -> SubscriptionHandle {
+        self.subscribe_feed(exchange, symbol, product, Feed::Private(channel))
+    }
+
+    fn subscribe_key(&self, key: SymbolKey) -> SubscriptionHandle {
         let mut subs = self.subscriptions.write();
 
         // Entry API handles the atomic check-and-insert
         let data = subs.entry(key.clone()).or_insert_with(|| {
             Arc::new(SubscriptionData {
-                book: Arc::new(L1FriendlyBook::new()),
+                book: Arc::new(SharedBook::new()),
                 ref_count: AtomicUsize::new(0),
             })
         });
 
         // If the previous value was 0, this is the first active handle.
         if data.ref_count.fetch_add(1, Ordering::SeqCst) == 0 {
-            self.initiate_subscription(&key);
+            self.initiate_subscription(&key, &data.book);
         }
 
         SubscriptionHandle {
@@ -116,12 +337,27 @@ impl MarketBroker {
         }
     }
 
-    fn initiate_subscription(&self, key: &SymbolKey) {
-        todo!()
+    /// Returns the connector responsible for `key`.
+    ///
+    /// Streams are partitioned by exchange so that each venue's sockets stay
+    /// on a single pipeline unit.
+    fn connector_for(&self, key: &SymbolKey) -> &ExchangeConnector {
+        let mut hasher = DefaultHasher::new();
+        key.exchange.hash(&mut hasher);
+        &self.connectors[hasher.finish() as usize % self.connectors.len()]
+    }
+
+    // Commands refused by a full queue show up in `results` as dropped
+    fn initiate_subscription(&self, key: &SymbolKey, book: &Arc<SharedBook>) {
+        let _ = self
+            .connector_for(key)
+            .send_cmd(ConnectorCmd::Subscribe(key.clone(), Arc::clone(book)));
     }
 
     fn terminate_subscription(&self, key: &SymbolKey) {
-        todo!()
+        let _ = self
+            .connector_for(key)
+            .send_cmd(ConnectorCmd::Unsubscribe(key.clone()));
     }
 }
 
@@ -135,11 +371,11 @@ impl Drop for SubscriptionHandle {
     /// Decrements the reference count and performs cleanup.
     fn drop(&mut self) {
         let mut subs = self.registry.write();
-        if let Some(data) = subs.get(&self.key) {
-            if data.ref_count.fetch_sub(1, Ordering::SeqCst) == 1 {
-                subs.remove(&self.key);
-                self.teardown.teardown(&self.key);
-            }
+        if let Some(data) = subs.get(&self.key)
+            && data.ref_count.fetch_sub(1, Ordering::SeqCst) == 1
+        {
+            subs.remove(&self.key);
+            self.teardown.teardown(&self.key);
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,302 @@
+//! Tick and lot sizes of each instrument, and the scales derived from them.
+//!
+//! Drivers parse prices and quantities into integers with a fixed number of
+//! decimals. Rather than one hard-coded scale per venue, the connector has
+//! the driver look each instrument up on the venue's REST endpoint before
+//! its first handshake ([detect]), and drivers then parse at the scales of
+//! the instrument's tick and lot sizes, published on the book as its
+//! exponents. Venues without such an endpoint keep their defaults.
+//!
+//! Instruments can also be [register]ed up front, e.g. from a config file,
+//! which spares the REST call.
+//!
+//! Derivatives also [register_contract] what their quantities count, so
+//! books of contracts can be read as base-asset sizes.
+
+use crate::broker::{Exchange, ProductType, SymbolKey};
+use crate::driver::{DriverError, ExchangeDriver};
+use crate::model::Level;
+use crate::util::parse_i128_with_precision;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// An instrument, whatever the feed.
+type Instrument = (Exchange, ProductType, String);
+
+static REGISTRY: LazyLock<RwLock<HashMap<Instrument, Precision>>> = LazyLock::new(Default::default);
+
+static CONTRACTS: LazyLock<RwLock<HashMap<Instrument, Contract>>> = LazyLock::new(Default::default);
+
+/// Most decimals a derived scale may have.
+const MAX_SCALE: u32 = 18;
+
+/// The tick and lot sizes of an instrument, as integers at the fewest
+/// decimals that hold them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precision {
+    /// Decimals of the price tick, e.g. 2 for `0.01` or 1 for `0.5`.
+    pub price_scale: u32,
+    /// Decimals of the quantity step.
+    pub qty_scale: u32,
+    /// The price tick at `price_scale`, e.g. 5 for `0.5`.
+    pub tick: i64,
+    /// The quantity step at `qty_scale`.
+    pub lot: i64,
+}
+
+impl Precision {
+    /// Derives the scales from a tick and lot size as the venue writes them,
+    /// such as `0.01000000`, or returns `None` if either is not a positive
+    /// number with at most 18 decimals.
+    ///
+    /// # Examples
+    /// ```
+    /// use rs_orderbook_streamer::broker::instruments::Precision;
+    ///
+    /// let precision = Precision::from_sizes(b"0.50000000", b"100").unwrap();
+    /// assert_eq!((precision.price_scale, precision.tick), (1, 5));
+    /// assert_eq!((precision.qty_scale, precision.lot), (0, 100));
+    /// ```
+    pub fn from_sizes(tick: &[u8], lot: &[u8]) -> Option<Self> {
+        let (tick, price_scale) = increment(tick)?;
+        let (lot, qty_scale) = increment(lot)?;
+        Some(Self { price_scale, qty_scale, tick, lot })
+    }
+
+    /// For venues that publish how many decimals prices and quantities
+    /// have, rather than tick and lot sizes.
+    pub fn from_decimals(price_scale: u32, qty_scale: u32) -> Option<Self> {
+        (price_scale <= MAX_SCALE && qty_scale <= MAX_SCALE).then_some(Self {
+            price_scale,
+            qty_scale,
+            tick: 1,
+            lot: 1,
+        })
+    }
+}
+
+/// What one unit of an instrument's book quantity stands for.
+///
+/// Books hold quantities as the venue publishes them: base-asset sizes on
+/// spot books, but contracts on most derivatives, which on coin-margined
+/// (inverse) perpetuals are each worth a fixed amount of the quote
+/// currency. [Contract::base_qty] turns any of them into a base-asset size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Contract {
+    /// Quantities are base-asset sizes already.
+    #[default]
+    Base,
+    /// A contract is worth `size` of the base asset at `scale` decimals,
+    /// e.g. `size` 1 at `scale` 2 for 0.01 BTC.
+    Linear { size: i64, scale: u32 },
+    /// A contract is worth `size` of the quote currency at `scale`
+    /// decimals, e.g. 100 USD, so its base-asset size falls as the price
+    /// rises.
+    Inverse { size: i64, scale: u32 },
+}
+
+impl Contract {
+    /// Converts the quantity of `level`, whose price and quantity are at
+    /// the book's `price_exponent` and `qty_exponent`, into a base-asset
+    /// size at `exponent`, truncated toward zero.
+    ///
+    /// Returns `None` if the size does not fit an `i64` at `exponent` or,
+    /// for inverse contracts, the price is not positive.
+    ///
+    /// # Examples
+    /// ```
+    /// use rs_orderbook_streamer::broker::instruments::Contract;
+    /// use rs_orderbook_streamer::model::Level;
+    ///
+    /// // 1,000 one-dollar contracts at 50,000.0 are worth 0.02 BTC
+    /// let xbtusd = Contract::Inverse { size: 1, scale: 0 };
+    /// let level = Level { price: 500_000, qty: 1_000 };
+    /// assert_eq!(xbtusd.base_qty(level, -1, 0, -8), Some(2_000_000));
+    /// ```
+    pub fn base_qty(self, level: Level, price_exponent: i8, qty_exponent: i8, exponent: i8) -> Option<i64> {
+        let (qty_exponent, exponent) = (i32::from(qty_exponent), i32::from(exponent));
+        let qty = i128::from(level.qty);
+        match self {
+            Self::Base => scaled(qty, qty_exponent - exponent, 1),
+            Self::Linear { size, scale } => {
+                scaled(qty * i128::from(size), qty_exponent - scale as i32 - exponent, 1)
+            }
+            Self::Inverse { .. } if level.price <= 0 => None,
+            Self::Inverse { size, scale } => scaled(
+                qty * i128::from(size),
+                qty_exponent - scale as i32 - i32::from(price_exponent) - exponent,
+                level.price.into(),
+            ),
+        }
+    }
+}
+
+/// Returns `value × 10^pow / divisor` as an `i64`, multiplying before
+/// dividing so no digits are lost.
+fn scaled(value: i128, pow: i32, divisor: i128) -> Option<i64> {
+    let power = 10i128.checked_pow(pow.unsigned_abs())?;
+    let result = match pow {
+        0.. => value.checked_mul(power)? / divisor,
+        _ => value / divisor.checked_mul(power)?,
+    };
+    i64::try_from(result).ok()
+}
+
+/// Returns an increment as an integer and its decimals, trailing zeros
+/// dropped.
+fn increment(text: &[u8]) -> Option<(i64, u32)> {
+    let (mut value, end) = parse_i128_with_precision(text, 0, MAX_SCALE).ok()?;
+    if end != text.len() || value <= 0 {
+        return None;
+    }
+    let mut scale = MAX_SCALE;
+    while scale > 0 && value % 10 == 0 {
+        value /= 10;
+        scale -= 1;
+    }
+    Some((i64::try_from(value).ok()?, scale))
+}
+
+fn instrument(key: &SymbolKey) -> Instrument {
+    (key.exchange, key.product, key.symbol.clone())
+}
+
+/// Sets the precision of `key`'s instrument, whatever its feed.
+pub fn register(key: &SymbolKey, precision: Precision) {
+    REGISTRY.write().insert(instrument(key), precision);
+}
+
+/// Returns the precision registered for `key`'s instrument.
+pub fn get(key: &SymbolKey) -> Option<Precision> {
+    REGISTRY.read().get(&instrument(key)).copied()
+}
+
+/// Forgets `key`'s instrument, so the next [detect] fetches it again.
+pub fn remove(key: &SymbolKey) {
+    REGISTRY.write().remove(&instrument(key));
+}
+
+/// Sets what the quantities of `key`'s instrument count, whatever its feed.
+pub fn register_contract(key: &SymbolKey, contract: Contract) {
+    CONTRACTS.write().insert(instrument(key), contract);
+}
+
+/// Returns what the quantities of `key`'s instrument count, base-asset
+/// sizes unless a contract was registered.
+pub fn contract(key: &SymbolKey) -> Contract {
+    CONTRACTS.read().get(&instrument(key)).copied().unwrap_or_default()
+}
+
+/// Returns the precision of `key`, having `driver` fetch it from the venue
+/// the first time.
+///
+/// `Ok(None)` means the venue has no instrument endpoint the driver knows.
+pub fn detect(key: &SymbolKey, driver: &dyn ExchangeDriver) -> Result<Option<Precision>, DriverError> {
+    if let Some(precision) = get(key) {
+        return Ok(Some(precision));
+    }
+    let precision = driver.fetch_precision(key)?;
+    if let Some(precision) = precision {
+        register(key, precision);
+    }
+    Ok(precision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::Feed;
+    use crate::driver::Transport;
+    use crate::model::L1FriendlyBook;
+    use std::cell::Cell;
+
+    /// Counts lookups and describes every instrument the same way.
+    struct Counting(Cell<usize>);
+
+    impl ExchangeDriver for Counting {
+        fn transport(&self) -> Transport {
+            Transport::Tcp
+        }
+
+        fn endpoint(&self, _key: &SymbolKey) -> String {
+            String::new()
+        }
+
+        fn subscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+            None
+        }
+
+        fn unsubscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+            None
+        }
+
+        fn parse_message(&mut self, _msg: &[u8], _book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+            Ok(false)
+        }
+
+        fn fetch_precision(&self, _key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+            self.0.set(self.0.get() + 1);
+            Ok(Precision::from_sizes(b"0.01", b"0.001"))
+        }
+    }
+
+    #[test]
+    fn test_sizes() {
+        let precision = |tick: &str, lot: &str| Precision::from_sizes(tick.as_bytes(), lot.as_bytes());
+        assert_eq!(precision("0.01000000", "0.00001000"), Precision::from_decimals(2, 5));
+        assert_eq!(precision("1", "1e-4"), Precision::from_decimals(0, 4));
+        assert_eq!(precision("0.0005", "10").map(|p| (p.tick, p.lot)), Some((5, 10)));
+        assert_eq!(precision("0", "1"), None);
+        assert_eq!(precision("-0.1", "1"), None);
+        assert_eq!(precision("0.1x", "1"), None);
+        assert_eq!(precision("0.0000000000000000001", "1"), None);
+        assert_eq!(Precision::from_decimals(19, 0), None);
+    }
+
+    #[test]
+    fn test_registry() {
+        let key = SymbolKey {
+            exchange: Exchange::custom("instruments-test"),
+            symbol: "BTC-USD".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let driver = Counting(Cell::new(0));
+        assert_eq!(get(&key), None);
+        assert_eq!(detect(&key, &driver), Ok(Precision::from_decimals(2, 3)));
+        // Cached for every feed of the instrument, but not for its other products
+        let bbo = SymbolKey { feed: Feed::Bbo, ..key.clone() };
+        assert_eq!(detect(&bbo, &driver), Ok(Precision::from_decimals(2, 3)));
+        assert_eq!(driver.0.get(), 1);
+        assert_eq!(get(&SymbolKey { product: ProductType::Perpetual, ..key.clone() }), None);
+
+        register(&key, Precision::from_decimals(4, 0).unwrap());
+        assert_eq!(get(&bbo), Precision::from_decimals(4, 0));
+        remove(&key);
+        assert_eq!(get(&key), None);
+    }
+
+    #[test]
+    fn test_contracts() {
+        let key = SymbolKey {
+            exchange: Exchange::custom("contracts-test"),
+            symbol: "BTC-USD".to_string(),
+            product: ProductType::Perpetual,
+            feed: Feed::Depth,
+        };
+        assert_eq!(contract(&key), Contract::Base);
+        register_contract(&key, Contract::Inverse { size: 100, scale: 0 });
+        assert_eq!(contract(&SymbolKey { feed: Feed::Bbo, ..key.clone() }), Contract::Inverse { size: 100, scale: 0 });
+
+        // 1.50 BTC, 5 contracts of 0.01 BTC, 3 contracts of 100 USD at 60,000.00
+        let level = |price, qty| Level { price, qty };
+        assert_eq!(Contract::Base.base_qty(level(0, 150), 0, -2, -8), Some(150_000_000));
+        assert_eq!(Contract::Linear { size: 1, scale: 2 }.base_qty(level(0, 5), 0, 0, -8), Some(5_000_000));
+        assert_eq!(contract(&key).base_qty(level(6_000_000, 3), -2, 0, -8), Some(500_000));
+        // Truncated toward zero, and undefined without a price
+        assert_eq!(contract(&key).base_qty(level(6_000_000, 3), -2, 0, -2), Some(0));
+        assert_eq!(contract(&key).base_qty(level(0, 3), -2, 0, -8), None);
+        assert_eq!(Contract::Base.base_qty(level(0, i64::MAX), 0, 0, -1), None);
+    }
+}
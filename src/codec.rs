@@ -0,0 +1,357 @@
+//! Fixed-layout binary encoding of normalized market data.
+//!
+//! Book states and trades are written in one little-endian wire format
+//! modelled on Simple Binary Encoding, so components that store, forward or
+//! read back normalized data can share it without going through JSON.
+//!
+//! Each message starts with an 8-byte header: the root block length, the
+//! template id, [SCHEMA_ID] and [SCHEMA_VERSION], all `u16`. Repeating
+//! groups follow the root block, each led by a `u16` entry length and a
+//! `u16` entry count. Decoders skip root and entry bytes past the fields
+//! they know, so later versions can append fields without breaking them.
+//!
+//! Prices and quantities are the raw values of [crate::model::Level], with
+//! their decimal exponents carried alongside.
+
+use crate::model::{L1FriendlyBook, Level};
+use std::sync::atomic::Ordering;
+
+pub const SCHEMA_ID: u16 = 1;
+pub const SCHEMA_VERSION: u16 = 1;
+
+/// Template id of a [BookUpdate].
+pub const BOOK_UPDATE: u16 = 1;
+/// Template id of a [Trade].
+pub const TRADE: u16 = 2;
+
+const HEADER_LEN: usize = 8;
+const GROUP_HEADER_LEN: usize = 4;
+const BOOK_BLOCK_LEN: usize = 24;
+const LEVEL_LEN: usize = 16;
+const TRADE_BLOCK_LEN: usize = 40;
+
+/// Book flag set while the stream was being rebuilt.
+const STALE: u8 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum CodecError {
+    /// The buffer ends inside the message.
+    Truncated,
+    /// The message belongs to another schema, or a newer one.
+    UnknownSchema { id: u16, version: u16 },
+    UnknownTemplate(u16),
+}
+
+/// A decoded message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message<'a> {
+    Book(BookUpdate<'a>),
+    Trade(Trade),
+}
+
+/// The levels of one instrument's book after an applied update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookUpdate<'a> {
+    /// Id of the instrument, as assigned by whoever writes the stream.
+    pub instrument: u32,
+    /// The book's version once the update was applied.
+    pub version: u64,
+    /// Nanoseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub price_exponent: i8,
+    pub qty_exponent: i8,
+    /// Whether the book was being rebuilt, so the levels are incomplete.
+    pub stale: bool,
+    pub bids: Levels<'a>,
+    pub asks: Levels<'a>,
+}
+
+/// The levels of one side of a [BookUpdate], read from the buffer as
+/// they are iterated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Levels<'a> {
+    entries: &'a [u8],
+    entry_len: usize,
+}
+
+impl Iterator for Levels<'_> {
+    type Item = Level;
+
+    fn next(&mut self) -> Option<Level> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let level = Level {
+            price: read_i64(self.entries, 0),
+            qty: read_i64(self.entries, 8),
+        };
+        self.entries = &self.entries[self.entry_len..];
+        Some(level)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.entries.len() / self.entry_len;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Levels<'_> {}
+
+/// Which side took liquidity in a [Trade].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Aggressor {
+    #[default]
+    Unknown,
+    Buy,
+    Sell,
+}
+
+/// One execution on a venue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Trade {
+    pub instrument: u32,
+    /// The venue's trade id, or 0 if it has none.
+    pub trade_id: u64,
+    /// Nanoseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub price: i64,
+    pub qty: i64,
+    pub price_exponent: i8,
+    pub qty_exponent: i8,
+    pub aggressor: Aggressor,
+}
+
+impl Trade {
+    /// Appends the trade to `buf`.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        write_header(buf, TRADE_BLOCK_LEN, TRADE);
+        buf.extend_from_slice(&self.instrument.to_le_bytes());
+        buf.push(self.price_exponent as u8);
+        buf.push(self.qty_exponent as u8);
+        buf.push(match self.aggressor {
+            Aggressor::Unknown => 0,
+            Aggressor::Buy => 1,
+            Aggressor::Sell => 2,
+        });
+        buf.push(0);
+        buf.extend_from_slice(&self.trade_id.to_le_bytes());
+        buf.extend_from_slice(&self.timestamp.to_le_bytes());
+        buf.extend_from_slice(&self.price.to_le_bytes());
+        buf.extend_from_slice(&self.qty.to_le_bytes());
+    }
+
+    fn decode(block: &[u8]) -> Trade {
+        Trade {
+            instrument: read_u32(block, 0),
+            price_exponent: block[4] as i8,
+            qty_exponent: block[5] as i8,
+            aggressor: match block[6] {
+                1 => Aggressor::Buy,
+                2 => Aggressor::Sell,
+                _ => Aggressor::Unknown,
+            },
+            trade_id: read_u64(block, 8),
+            timestamp: read_u64(block, 16),
+            price: read_i64(block, 24),
+            qty: read_i64(block, 32),
+        }
+    }
+}
+
+/// Appends the current state of `book` to `buf` as a [BookUpdate].
+///
+/// Only levels with a positive quantity are written, so empty slots and
+/// removals not yet compacted away are left out.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::codec::{self, Message};
+/// use rs_orderbook_streamer::model::{L1FriendlyBook, Level};
+///
+/// let mut book = L1FriendlyBook::new();
+/// book.begin_write();
+/// book.bids[0] = Level { price: 10_050, qty: 3 };
+/// book.end_write();
+///
+/// let mut buf = Vec::new();
+/// codec::encode_book(&mut buf, 7, 1_700_000_000_000_000_000, &book);
+/// let (Message::Book(update), len) = codec::decode(&buf).unwrap() else { unreachable!() };
+/// assert_eq!((update.instrument, update.version, len), (7, 2, buf.len()));
+/// assert_eq!(update.bids.collect::<Vec<_>>(), [Level { price: 10_050, qty: 3 }]);
+/// assert_eq!(update.asks.len(), 0);
+/// ```
+pub fn encode_book(buf: &mut Vec<u8>, instrument: u32, timestamp: u64, book: &L1FriendlyBook) {
+    write_header(buf, BOOK_BLOCK_LEN, BOOK_UPDATE);
+    buf.extend_from_slice(&instrument.to_le_bytes());
+    buf.push(book.price_exponent as u8);
+    buf.push(book.qty_exponent as u8);
+    buf.push(if book.is_stale() { STALE } else { 0 });
+    buf.push(0);
+    buf.extend_from_slice(&book.version.load(Ordering::Acquire).to_le_bytes());
+    buf.extend_from_slice(&timestamp.to_le_bytes());
+    for side in [&book.bids, &book.asks] {
+        let count = side.iter().filter(|level| level.qty > 0).count();
+        buf.extend_from_slice(&(LEVEL_LEN as u16).to_le_bytes());
+        buf.extend_from_slice(&(count as u16).to_le_bytes());
+        for level in side.iter().filter(|level| level.qty > 0) {
+            buf.extend_from_slice(&level.price.to_le_bytes());
+            buf.extend_from_slice(&level.qty.to_le_bytes());
+        }
+    }
+}
+
+/// Decodes the message at the start of `bytes` and returns it with the
+/// number of bytes it took up.
+pub fn decode(bytes: &[u8]) -> Result<(Message<'_>, usize), CodecError> {
+    let header = bytes.get(..HEADER_LEN).ok_or(CodecError::Truncated)?;
+    let block_len = read_u16(header, 0) as usize;
+    let template = read_u16(header, 2);
+    let (id, version) = (read_u16(header, 4), read_u16(header, 6));
+    if id != SCHEMA_ID || version > SCHEMA_VERSION {
+        return Err(CodecError::UnknownSchema { id, version });
+    }
+
+    let block = bytes.get(HEADER_LEN..HEADER_LEN + block_len).ok_or(CodecError::Truncated)?;
+    let mut idx = HEADER_LEN + block_len;
+    match template {
+        BOOK_UPDATE => {
+            if block_len < BOOK_BLOCK_LEN {
+                return Err(CodecError::Truncated);
+            }
+            let bids = read_group(bytes, &mut idx)?;
+            let asks = read_group(bytes, &mut idx)?;
+            let update = BookUpdate {
+                instrument: read_u32(block, 0),
+                price_exponent: block[4] as i8,
+                qty_exponent: block[5] as i8,
+                stale: block[6] & STALE != 0,
+                version: read_u64(block, 8),
+                timestamp: read_u64(block, 16),
+                bids,
+                asks,
+            };
+            Ok((Message::Book(update), idx))
+        }
+        TRADE => {
+            if block_len < TRADE_BLOCK_LEN {
+                return Err(CodecError::Truncated);
+            }
+            Ok((Message::Trade(Trade::decode(block)), idx))
+        }
+        _ => Err(CodecError::UnknownTemplate(template)),
+    }
+}
+
+fn write_header(buf: &mut Vec<u8>, block_len: usize, template: u16) {
+    for field in [block_len as u16, template, SCHEMA_ID, SCHEMA_VERSION] {
+        buf.extend_from_slice(&field.to_le_bytes());
+    }
+}
+
+/// Reads the group of levels at `idx` and moves `idx` past it.
+fn read_group<'a>(bytes: &'a [u8], idx: &mut usize) -> Result<Levels<'a>, CodecError> {
+    let header = bytes.get(*idx..*idx + GROUP_HEADER_LEN).ok_or(CodecError::Truncated)?;
+    let entry_len = read_u16(header, 0) as usize;
+    let count = read_u16(header, 2) as usize;
+    if entry_len < LEVEL_LEN {
+        return Err(CodecError::Truncated);
+    }
+    let start = *idx + GROUP_HEADER_LEN;
+    let entries = bytes.get(start..start + entry_len * count).ok_or(CodecError::Truncated)?;
+    *idx = start + entries.len();
+    Ok(Levels { entries, entry_len })
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn read_i64(bytes: &[u8], at: usize) -> i64 {
+    i64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_round_trip() {
+        let mut book = L1FriendlyBook::new();
+        book.price_exponent = -2;
+        book.bids[0] = Level { price: 10_050, qty: 3 };
+        book.bids[1] = Level { price: 10_040, qty: -1 }; // marked for removal
+        book.bids[2] = Level { price: 10_030, qty: 5 };
+        book.asks[0] = Level { price: 10_060, qty: 2 };
+        book.stale.store(true, Ordering::Relaxed);
+        book.begin_write();
+        book.end_write();
+
+        let trade = Trade {
+            instrument: 7,
+            trade_id: 42,
+            timestamp: 2,
+            price: 10_060,
+            qty: 1,
+            price_exponent: -2,
+            qty_exponent: -8,
+            aggressor: Aggressor::Buy,
+        };
+
+        let mut buf = Vec::new();
+        encode_book(&mut buf, 7, 1, &book);
+        trade.encode(&mut buf);
+
+        let (Message::Book(update), len) = decode(&buf).unwrap() else {
+            panic!("expected a book update");
+        };
+        assert_eq!((update.version, update.timestamp, update.price_exponent, update.stale), (2, 1, -2, true));
+        assert_eq!(
+            update.bids.collect::<Vec<_>>(),
+            [Level { price: 10_050, qty: 3 }, Level { price: 10_030, qty: 5 }]
+        );
+        assert_eq!(update.asks.len(), 1);
+        assert_eq!(decode(&buf[len..]).unwrap(), (Message::Trade(trade), buf.len() - len));
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let mut buf = Vec::new();
+        encode_book(&mut buf, 1, 1, &L1FriendlyBook::new());
+        for len in 0..buf.len() {
+            assert_eq!(decode(&buf[..len]), Err(CodecError::Truncated));
+        }
+
+        let mut other = buf.clone();
+        other[2] = 9;
+        assert_eq!(decode(&other), Err(CodecError::UnknownTemplate(9)));
+        other[6] = 2;
+        assert_eq!(decode(&other), Err(CodecError::UnknownSchema { id: 1, version: 2 }));
+    }
+
+    #[test]
+    fn test_skips_appended_fields() {
+        // A later version with 4 more root bytes and 8 more per level
+        let mut buf = Vec::new();
+        write_header(&mut buf, BOOK_BLOCK_LEN + 4, BOOK_UPDATE);
+        buf.extend_from_slice(&[0; BOOK_BLOCK_LEN + 4]);
+        buf.extend_from_slice(&[24, 0, 1, 0]);
+        buf.extend_from_slice(&5i64.to_le_bytes());
+        buf.extend_from_slice(&6i64.to_le_bytes());
+        buf.extend_from_slice(&[0xff; 8]);
+        buf.extend_from_slice(&[16, 0, 0, 0]);
+
+        let (Message::Book(update), len) = decode(&buf).unwrap() else {
+            panic!("expected a book update");
+        };
+        assert_eq!(len, buf.len());
+        assert_eq!(update.bids.collect::<Vec<_>>(), [Level { price: 5, qty: 6 }]);
+    }
+}
@@ -1,17 +1,381 @@
-use crate::broker::{Exchange, SymbolKey};
+pub mod auth;
+mod deflate;
+mod event_loop;
+pub mod failover;
+pub mod keepalive;
+pub mod multicast;
+pub mod proxy;
+pub mod rate_limit;
+pub mod tap;
+pub mod tls;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+#[cfg(all(feature = "af-xdp", target_os = "linux"))]
+pub mod xdp;
+
+use crate::broker::{instruments, Exchange, Feed, SymbolKey};
+use crate::driver::{self, DriverError, ExchangeDriver, Transport};
+use crate::model::{FullDepthBook, L1FriendlyBook, Level, SharedBook, Side, UpdateCause};
+use crate::wait::{Park, WaitStrategy};
 use core_affinity::CoreId;
-use crossbeam_channel::{unbounded, Sender};
-use std::thread;
+use parking_lot::{Mutex, RwLock};
+use mio::{Poll, Waker};
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender, TryRecvError, TrySendError};
+use std::collections::{HashMap, VecDeque};
+use std::hint::spin_loop;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use deflate::Inflate;
+use failover::Rotation;
+use keepalive::{Action, Keepalive, Ping, Tracker};
+use multicast::Receiver as MulticastReceiver;
+use proxy::Proxy;
+use rate_limit::{RateLimit, RateLimiter};
+use tap::FrameTap;
+use tls::TlsConfig;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::error::{TlsError, UrlError};
+use tungstenite::http::HeaderValue;
+use tungstenite::stream::{MaybeTlsStream, Mode};
+use tungstenite::{HandshakeError, Message, WebSocket};
+
+/// Delay before retrying a failed or dropped connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Minimum spacing of subscribe batches on a shared connection.
+const SUBSCRIBE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Size of the read buffer for raw TCP and UDP streams.
+const READ_BUFFER: usize = 64 * 1024;
+
+/// Source of [Connection] ids.
+static NEXT_CONNECTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The TCP stream under websockets and raw TCP feeds.
+#[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+type Tcp = TcpStream;
+/// The TCP stream under websockets and raw TCP feeds, read through io_uring.
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+type Tcp = uring::UringStream;
+
+type Socket = WebSocket<Inflate<MaybeTlsStream<Tcp>>>;
+
+/// A connected stream in whichever [Transport] its driver asked for.
+///
+/// Kept unboxed so the hot read path does not chase an extra pointer.
+#[allow(clippy::large_enum_variant)]
+enum Stream {
+    WebSocket(Socket),
+    Tcp(RawStream),
+    /// The joined multicast feeds and their lines.
+    Udp(MulticastReceiver),
+}
+
+/// A non-blocking TCP stream with a buffer for partially written frames.
+struct RawStream {
+    stream: Tcp,
+    outbox: Vec<u8>,
+}
 
 /// Commands sent from the Broker to the pinned Exchange Connector.
 pub enum ConnectorCmd {
-    Subscribe(SymbolKey),
+    Subscribe(SymbolKey, Arc<SharedBook>),
     Unsubscribe(SymbolKey),
+    /// Unsubscribes and closes every stream, then stops the worker.
+    /// Commands still queued behind a rate limit are dropped.
+    Shutdown,
+}
+
+/// The outcome of a [ConnectorCmd], reported on [ExchangeConnector::results].
+///
+/// Each subscribe and unsubscribe gets one result, unless the worker is shut
+/// down first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CmdResult {
+    /// The stream is open and the key's subscribe frame has been sent.
+    Subscribed(SymbolKey),
+    /// The first attempt to open or subscribe the stream failed. The worker
+    /// keeps retrying, and the book fills once a retry succeeds.
+    Failed(SymbolKey),
+    /// No driver serves the key, or it was not subscribed.
+    Rejected(SymbolKey),
+    /// Discarded by the [Backpressure] policy before reaching the worker.
+    Dropped(SymbolKey),
+    /// The key's unsubscribe frame has been sent, or its stream closed.
+    Unsubscribed(SymbolKey),
+}
+
+/// An event of a [Feed::Private] stream, reported on
+/// [ExchangeConnector::private_events].
+///
+/// `data` is the frame as the venue sent it, e.g. a Binance
+/// `executionReport` or an OKX `orders` push; decoding it is left to the
+/// execution side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrivateEvent {
+    /// The private key, naming the exchange, market and channel.
+    pub key: SymbolKey,
+    pub received: SystemTime,
+    pub data: Vec<u8>,
+}
+
+/// How a connector reaches the venues, queues commands for them, waits
+/// for work and handles crossed books.
+#[derive(Clone, Debug, Default)]
+pub struct TransportConfig {
+    pub tls: TlsConfig,
+    /// Proxy for websockets, TCP streams and REST calls. Multicast feeds
+    /// are always joined directly.
+    pub proxy: Option<Proxy>,
+    /// Bound on commands waiting for the worker.
+    pub commands: CommandQueue,
+    /// How the spinning worker waits while idle; the event loop always
+    /// sleeps in `epoll_wait`.
+    pub wait: WaitStrategy,
+    /// Copies every raw frame before it is parsed; see [tap].
+    pub tap: Option<FrameTap>,
+    /// What to do when a frame leaves a book crossed or locked.
+    pub crossed: CrossedPolicy,
+    /// AF_XDP socket for multicast feeds; see [xdp].
+    #[cfg(all(feature = "af-xdp", target_os = "linux"))]
+    pub xdp: Option<xdp::XdpConfig>,
+}
+
+/// A change in a connection's transport state, reported on
+/// [ExchangeConnector::status].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusEvent {
+    /// Identifies the connection across its reconnects.
+    pub connection: usize,
+    pub exchange: Exchange,
+    pub status: ConnectionStatus,
+}
+
+/// Transport states of a connection.
+///
+/// A connection goes `Connecting` then `Connected`; after each later drop
+/// it goes `Connecting` then `Resubscribed` once it is back. Racing
+/// connections go `Connecting` once per endpoint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    /// Opening a stream to `endpoint`, the primary or one of its backups.
+    Connecting { endpoint: String },
+    /// The first stream is open and its first key subscribed.
+    Connected,
+    /// A later stream is open and its keys are being subscribed again.
+    Resubscribed,
+    /// The stream is gone, or could not be opened. Books are stale until
+    /// it is back.
+    Disconnected { reason: DisconnectReason },
+    /// A sequence gap, checksum mismatch or crossed book is being repaired
+    /// for `key`.
+    Resyncing { key: SymbolKey },
+    /// A frame left `key`'s best bid at or above its best ask; what
+    /// follows depends on the [CrossedPolicy].
+    Crossed { key: SymbolKey },
+    /// `key`'s book disagreed with the venue's checksum and was kept, as
+    /// its driver was set to log rather than resync.
+    ChecksumMismatch { key: SymbolKey, expected: u32, computed: u32 },
+}
+
+/// Why a connection was dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The stream could not be opened.
+    ConnectFailed,
+    /// A driver's handshake, such as a snapshot request, failed.
+    HandshakeFailed,
+    /// Reading, writing or parsing the stream failed, or the venue closed it.
+    StreamError,
+    /// Nothing arrived within the keepalive timeout.
+    KeepaliveExpired,
+    /// Reopened to rebuild a book after a gap.
+    Resync,
+    /// Moved to an endpoint that won a [race](failover::race).
+    FasterEndpoint,
+    /// Closed on unsubscribe or shutdown.
+    Closed,
+}
+
+/// Bounds the commands a connector holds, whether still in its channel or
+/// waiting on a [rate_limit].
+///
+/// The default holds 4096 commands and blocks senders beyond that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommandQueue {
+    /// Most commands held at once, at least 1.
+    pub capacity: usize,
+    pub policy: Backpressure,
+}
+
+impl Default for CommandQueue {
+    fn default() -> Self {
+        Self {
+            capacity: 4096,
+            policy: Backpressure::Block,
+        }
+    }
+}
+
+/// What [ExchangeConnector::send_cmd] does when the command queue is full.
+///
+/// Commands discarded by a policy are reported as [CmdResult::Dropped]. A
+/// dropped unsubscribe leaves the venue stream open until shutdown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Waits for room.
+    #[default]
+    Block,
+    /// Discards the oldest command still in the channel to make room.
+    DropOldest,
+    /// Hands the command back.
+    Error,
+}
+
+/// What a connector does with a book a frame left crossed or locked, such
+/// as after packets arrive out of order.
+///
+/// Every policy reports [ConnectionStatus::Crossed] first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CrossedPolicy {
+    /// Marks the book stale and rebuilds it like after a sequence gap.
+    #[default]
+    Resync,
+    /// Drops the crossed levels of the side the frame left alone, trusting
+    /// the side whose best level it moved. Falls back to
+    /// [CrossedPolicy::Resync] when it moved both or neither.
+    DropStale,
+    /// Leaves the book as it is.
+    Report,
+}
+
+/// Where the worker reports command results, status changes and private
+/// events.
+#[derive(Clone)]
+struct Reports {
+    results: Sender<CmdResult>,
+    status: Sender<StatusEvent>,
+    private: Sender<PrivateEvent>,
+}
+
+/// Opens sockets as set by a connector's [TransportConfig].
+struct Dialer {
+    tls: Arc<ClientConfig>,
+    proxy: Option<Proxy>,
+}
+
+impl Dialer {
+    /// Opens a TCP connection to `host:port`, through the proxy if any.
+    fn tcp(&self, host: &str, port: u16) -> std::io::Result<Tcp> {
+        let stream = match &self.proxy {
+            Some(proxy) => proxy.connect(host, port)?,
+            None => TcpStream::connect((host, port))?,
+        };
+        stream.set_nodelay(true)?;
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        let stream = Tcp::from(stream);
+        Ok(stream)
+    }
 }
 
 /// Manages pinned worker threads for exchange connectivity.
 pub struct ExchangeConnector {
     cmd_tx: Sender<ConnectorCmd>,
+    /// Kept under [Backpressure::DropOldest] to evict the oldest command.
+    cmd_rx: Option<Receiver<ConnectorCmd>>,
+    policy: Backpressure,
+    results: Receiver<CmdResult>,
+    status: Receiver<StatusEvent>,
+    private: Receiver<PrivateEvent>,
+    /// Reports commands the policy discards.
+    results_tx: Sender<CmdResult>,
+    /// Set by [ExchangeConnector::shutdown] so a full queue cannot delay it.
+    stopping: Arc<AtomicBool>,
+    /// The worker thread, until it has been joined.
+    worker: Mutex<Option<JoinHandle<()>>>,
+    /// Wakes an [event loop](ExchangeConnector::event_loop) worker for new
+    /// commands.
+    waker: Option<Arc<Waker>>,
+}
+
+/// One key's book and driver on a [Connection].
+struct Subscription {
+    key: SymbolKey,
+    book: Arc<SharedBook>,
+    driver: Box<dyn ExchangeDriver>,
+    crossed: CrossedPolicy,
+    /// The book's [overflow](SharedBook::overflow) tail as of the last
+    /// handshake.
+    overflow: Option<Arc<RwLock<FullDepthBook>>>,
+    /// The book's [full depth](SharedBook::full_depth) as of the last
+    /// handshake, when the connector keeps it rather than the driver.
+    depth: Option<Arc<RwLock<FullDepthBook>>>,
+    /// Whether its [CmdResult] has been sent.
+    reported: bool,
+}
+
+impl Subscription {
+    /// Runs the driver's handshake, having it look the instrument's
+    /// [precision](instruments) up first.
+    ///
+    /// A venue that fails to describe the instrument leaves the driver on
+    /// its default scales rather than failing the subscription.
+    fn handshake(&mut self) -> Result<(), DriverError> {
+        if !matches!(self.key.feed, Feed::Private(_)) {
+            let _ = instruments::detect(&self.key, &*self.driver);
+        }
+        self.driver.handshake(&self.key)?;
+        self.depth = match self.driver.full_depth() {
+            Some(depth) => {
+                self.book.set_full_depth(Some(depth));
+                None
+            }
+            None => self.book.full_depth(),
+        };
+        self.overflow = self.book.overflow();
+        Ok(())
+    }
+}
+
+/// A live exchange stream feeding one or more shared books.
+///
+/// Most connections carry a single key. Keys whose driver allows more than
+/// one [stream per connection](ExchangeDriver::streams_per_connection) are
+/// packed onto a shared socket with the same endpoint until it is full, and
+/// each frame is routed to its key by [ExchangeDriver::frame_stream].
+struct Connection {
+    /// Unique per process, used as the event loop's token.
+    id: usize,
+    /// The first key's endpoint, which later keys must share to join.
+    endpoint: String,
+    subscriptions: Vec<Subscription>,
+    /// Index into `subscriptions` by [ExchangeDriver::stream_name].
+    routes: HashMap<Box<[u8]>, usize>,
+    /// Keys waiting to be subscribed on the open socket.
+    pending: Vec<SymbolKey>,
+    next_subscribe: Instant,
+    socket: Option<Stream>,
+    /// Successful connects so far, so a replaced socket can be told apart.
+    connects: u64,
+    /// The connector's TLS and proxy settings.
+    dialer: Arc<Dialer>,
+    reports: Reports,
+    next_connect: Instant,
+    /// Position among the primary endpoint and its [failover] backups.
+    rotation: Rotation,
+    /// When the endpoints are next raced, if the exchange races them.
+    next_race: Instant,
+    keepalive: Tracker,
+    /// Whether a frame arrived since the keepalive was last checked.
+    received: bool,
+    /// Read buffer for [Transport::Tcp] and [Transport::Udp] streams.
+    read_buf: Vec<u8>,
 }
 
 impl ExchangeConnector {
@@ -19,40 +383,1630 @@ impl ExchangeConnector {
     ///
     /// # Performance
     /// * **Core Pinning**: Uses `core_affinity` to prevent OS context switching.
-    /// * **Busy-Waiting**: In a production hot-path, the receiver would loop
-    ///   with `spin_loop` to minimize wake-up latency.
-    pub fn new(core_id: CoreId) -> Self {
-        let (tx, rx) = unbounded::<ConnectorCmd>();
+    /// * **Busy-Waiting**: By default the worker spins with `spin_loop`
+    ///   while any stream is live, draining commands and sockets without
+    ///   blocking, and parks on the command channel when it has nothing to
+    ///   poll. [TransportConfig::wait] trades this for always spinning or
+    ///   for parking after a quiet spell.
+    /// * **Rate Limiting**: Commands are queued and handled as their
+    ///   exchange's [rate_limit] bucket allows, so bursts of subscriptions
+    ///   never reach the venue faster than it accepts them.
+    /// * **io_uring**: With the `io-uring` feature on Linux, TCP reads are
+    ///   served from completed ring buffers and re-armed in one syscall per
+    ///   pass instead of one `recv` per socket.
+    /// * **AF_XDP**: With the `af-xdp` feature on Linux and
+    ///   [TransportConfig::xdp] set, multicast packets are read in place
+    ///   from a UMEM shared with the NIC queue.
+    ///
+    /// Sockets and REST calls are opened as set by `transport`.
+    pub fn new(core_id: CoreId, transport: &TransportConfig) -> Self {
+        Self::spawn(core_id, transport, None)
+    }
+
+    /// Spawns a pinned worker that sleeps in an epoll event loop instead of
+    /// spinning.
+    ///
+    /// The worker only touches sockets that have data, so one core can
+    /// carry every exchange when cores are scarce and symbols are many, at
+    /// the cost of a wake-up per burst of frames. Timers such as keepalives
+    /// and reconnects run on a 100ms tick.
+    pub fn event_loop(core_id: CoreId, transport: &TransportConfig) -> std::io::Result<Self> {
+        let poll = Poll::new()?;
+        let waker = Arc::new(Waker::new(poll.registry(), event_loop::WAKE)?);
+        Ok(Self::spawn(core_id, transport, Some((poll, waker))))
+    }
+
+    fn spawn(core_id: CoreId, transport: &TransportConfig, events: Option<(Poll, Arc<Waker>)>) -> Self {
+        let capacity = transport.commands.capacity.max(1);
+        let policy = transport.commands.policy;
+        let (tx, rx) = bounded::<ConnectorCmd>(capacity);
+        let cmd_rx = (policy == Backpressure::DropOldest).then(|| rx.clone());
+        let (results_tx, results) = unbounded::<CmdResult>();
+        let stopping = Arc::new(AtomicBool::new(false));
+        let (status_tx, status) = unbounded::<StatusEvent>();
+        let (private_tx, private) = unbounded::<PrivateEvent>();
+        let reports = Reports {
+            results: results_tx.clone(),
+            status: status_tx,
+            private: private_tx,
+        };
+        let worker_stopping = Arc::clone(&stopping);
+        let wait = transport.wait;
+        let frame_tap = transport.tap.clone();
+        let crossed = transport.crossed;
+        let dialer = Arc::new(Dialer {
+            tls: transport.tls.client_config(),
+            proxy: transport.proxy.clone(),
+        });
 
-        thread::spawn(move || {
+        let (poll, waker) = events.unzip();
+        #[cfg(all(feature = "af-xdp", target_os = "linux"))]
+        let xdp_config = transport.xdp.clone();
+        let worker = thread::spawn(move || {
             // Pin this thread to the specified core
             core_affinity::set_for_current(core_id);
+            if let Some(proxy) = &dialer.proxy {
+                driver::set_rest_agent(proxy.rest_agent());
+            }
+            if let Some(frame_tap) = &frame_tap {
+                let _ = tap::install(frame_tap);
+            }
+            // Feeds fall back to kernel sockets if the queue cannot be bound
+            #[cfg(all(feature = "af-xdp", target_os = "linux"))]
+            if let Some(config) = &xdp_config {
+                let _ = xdp::install(config);
+            }
+            match poll {
+                Some(poll) => event_loop::run(rx, capacity, &worker_stopping, dialer, reports, crossed, poll),
+                None => Self::run(rx, capacity, wait, &worker_stopping, dialer, reports, crossed),
+            }
+        });
+
+        Self {
+            cmd_tx: tx,
+            cmd_rx,
+            policy,
+            results,
+            status,
+            private,
+            results_tx,
+            stopping,
+            worker: Mutex::new(Some(worker)),
+            waker,
+        }
+    }
+
+    /// Sends a subscription command to the pinned worker.
+    ///
+    /// A full queue is handled by the connector's [Backpressure] policy;
+    /// under [Backpressure::Error] the command is handed back. Commands
+    /// sent after the worker has stopped are ignored.
+    pub fn send_cmd(&self, cmd: ConnectorCmd) -> Result<(), ConnectorCmd> {
+        let sent = match self.policy {
+            Backpressure::Block => {
+                let _ = self.cmd_tx.send(cmd);
+                Ok(())
+            }
+            Backpressure::DropOldest => {
+                self.send_evicting(cmd);
+                Ok(())
+            }
+            Backpressure::Error => match self.cmd_tx.try_send(cmd) {
+                Err(TrySendError::Full(cmd)) => {
+                    self.report_dropped(&cmd);
+                    Err(cmd)
+                }
+                Ok(()) | Err(TrySendError::Disconnected(_)) => Ok(()),
+            },
+        };
+        self.wake();
+        sent
+    }
+
+    /// Sends `cmd`, discarding the oldest queued commands until it fits.
+    fn send_evicting(&self, mut cmd: ConnectorCmd) {
+        let Some(rx) = &self.cmd_rx else {
+            return;
+        };
+        loop {
+            match self.cmd_tx.try_send(cmd) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => return,
+                Err(TrySendError::Full(back)) => cmd = back,
+            }
+            match rx.try_recv() {
+                Ok(ConnectorCmd::Shutdown) => {
+                    // The worker is stopping anyway; keep its shutdown instead
+                    let _ = self.cmd_tx.try_send(ConnectorCmd::Shutdown);
+                    self.report_dropped(&cmd);
+                    return;
+                }
+                Ok(oldest) => self.report_dropped(&oldest),
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn report_dropped(&self, cmd: &ConnectorCmd) {
+        if let ConnectorCmd::Subscribe(key, _) | ConnectorCmd::Unsubscribe(key) = cmd {
+            let _ = self.results_tx.send(CmdResult::Dropped(key.clone()));
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = &self.waker {
+            let _ = waker.wake();
+        }
+    }
+
+    /// Results of the commands sent so far, in the order they were settled.
+    ///
+    /// Results are kept until read, so a caller that sends commands without
+    /// reading them should drain this now and then.
+    pub fn results(&self) -> &Receiver<CmdResult> {
+        &self.results
+    }
+
+    /// Transport state changes of the worker's connections, oldest first.
+    ///
+    /// Like [ExchangeConnector::results], events are kept until read.
+    pub fn status(&self) -> &Receiver<StatusEvent> {
+        &self.status
+    }
+
+    /// Events of the worker's [Feed::Private] streams, oldest first.
+    ///
+    /// Like [ExchangeConnector::results], events are kept until read.
+    pub fn private_events(&self) -> &Receiver<PrivateEvent> {
+        &self.private
+    }
+
+    /// Stops the worker and waits for it to exit.
+    ///
+    /// Every stream is unsubscribed and closed first, and its books are
+    /// marked stale. The worker's core is free once this returns.
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        // Only needed to wake an idle worker, whose queue has room
+        let _ = self.cmd_tx.try_send(ConnectorCmd::Shutdown);
+        self.wake();
+        self.join();
+    }
+
+    /// Waits for the worker to exit, e.g. after a [ConnectorCmd::Shutdown].
+    ///
+    /// Returns straight away if the worker was already joined.
+    pub fn join(&self) {
+        if let Some(worker) = self.worker.lock().take() {
+            let _ = worker.join();
+        }
+    }
+
+    /// The worker event loop: run-to-completion over commands and sockets.
+    ///
+    /// At most `capacity` commands wait on rate limits; the rest stay in the
+    /// channel so senders feel the backpressure.
+    fn run(
+        rx: Receiver<ConnectorCmd>,
+        capacity: usize,
+        wait: WaitStrategy,
+        stopping: &AtomicBool,
+        dialer: Arc<Dialer>,
+        reports: Reports,
+        crossed: CrossedPolicy,
+    ) {
+        let mut connections: Vec<Connection> = Vec::new();
+        let mut queued: VecDeque<ConnectorCmd> = VecDeque::new();
+        let mut limiter = RateLimiter::default();
+        // Rounds in a row that received nothing
+        let mut idle = 0u32;
+
+        while !stopping.load(Ordering::Relaxed) {
+            // Only commands can wake the worker, so it may only park
+            // indefinitely while it has nothing else to do
+            let wakeable = connections.is_empty() && queued.is_empty();
+            let cmd = if queued.len() >= capacity {
+                if let Park::For(timeout) = wait.park(idle, false) {
+                    thread::sleep(timeout);
+                }
+                None
+            } else {
+                match wait.park(idle, wakeable) {
+                    Park::No => match rx.try_recv() {
+                        Ok(cmd) => Some(cmd),
+                        Err(TryRecvError::Empty) => None,
+                        Err(TryRecvError::Disconnected) => break,
+                    },
+                    Park::For(timeout) => match rx.recv_timeout(timeout) {
+                        Ok(cmd) => Some(cmd),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    },
+                    Park::UntilWoken => match rx.recv() {
+                        Ok(cmd) => Some(cmd),
+                        Err(_) => break,
+                    },
+                }
+            };
+            let mut busy = cmd.is_some();
+
+            match cmd {
+                Some(ConnectorCmd::Shutdown) => break,
+                Some(cmd) => queued.push_back(cmd),
+                None => {}
+            }
+            if !queued.is_empty() {
+                Self::handle_queued(&mut connections, &mut queued, &mut limiter, &dialer, &reports, crossed);
+            }
+
+            for connection in connections.iter_mut() {
+                busy |= connection.poll(&mut limiter);
+            }
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            uring::submit();
+            idle = if busy { 0 } else { idle.saturating_add(1) };
+            spin_loop();
+        }
+
+        // Shutting down, or every sender is gone
+        close_all(connections);
+    }
+
+    /// Handles every queued command whose exchange has a token to spare.
+    ///
+    /// Commands for throttled exchanges stay queued in their original order.
+    fn handle_queued(
+        connections: &mut Vec<Connection>,
+        queued: &mut VecDeque<ConnectorCmd>,
+        limiter: &mut RateLimiter,
+        dialer: &Arc<Dialer>,
+        reports: &Reports,
+        crossed: CrossedPolicy,
+    ) {
+        let now = Instant::now();
+        for _ in 0..queued.len() {
+            let Some(cmd) = queued.pop_front() else {
+                break;
+            };
+            // `run` never queues a shutdown
+            let (ConnectorCmd::Subscribe(key, _) | ConnectorCmd::Unsubscribe(key)) = &cmd else {
+                continue;
+            };
+            if !limiter.try_acquire(key.exchange, now, || rate_limit_for(key)) {
+                queued.push_back(cmd);
+                continue;
+            }
+
+            match cmd {
+                ConnectorCmd::Subscribe(key, book) => {
+                    Self::handle_physical_subscribe(connections, key, book, dialer, reports, crossed)
+                }
+                ConnectorCmd::Unsubscribe(key) => Self::handle_physical_unsubscribe(connections, key, reports),
+                ConnectorCmd::Shutdown => {}
+            }
+        }
+    }
+
+    /// Adds `key` to a shared connection with room for it, or opens a new one.
+    ///
+    /// Private keys always open their own, logged in as their exchange's
+    /// [auth] credentials.
+    fn handle_physical_subscribe(
+        connections: &mut Vec<Connection>,
+        key: SymbolKey,
+        book: Arc<SharedBook>,
+        dialer: &Arc<Dialer>,
+        reports: &Reports,
+        crossed: CrossedPolicy,
+    ) {
+        let Some(driver) = driver::driver_for(&key) else {
+            let _ = reports.results.send(CmdResult::Rejected(key));
+            return;
+        };
+        let private = matches!(key.feed, Feed::Private(_));
+        if private && !(driver.private_streams() && auth::credentials_for(key.exchange).is_some()) {
+            let _ = reports.results.send(CmdResult::Rejected(key));
+            return;
+        }
+
+        let limit = driver.streams_per_connection();
+        let endpoint = driver.endpoint(&key);
+        let subscription = Subscription {
+            key,
+            book,
+            driver,
+            crossed,
+            overflow: None,
+            depth: None,
+            reported: false,
+        };
+        if limit > 1
+            && !private
+            && let Some(connection) = connections
+                .iter_mut()
+                .find(|c| c.endpoint == endpoint && c.subscriptions.len() < limit && !c.is_private())
+        {
+            connection.add(subscription);
+            return;
+        }
+
+        let mut connection = Connection {
+            id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            endpoint,
+            subscriptions: Vec::new(),
+            routes: HashMap::new(),
+            pending: Vec::new(),
+            next_subscribe: Instant::now(),
+            socket: None,
+            connects: 0,
+            dialer: Arc::clone(dialer),
+            reports: reports.clone(),
+            next_connect: Instant::now(),
+            rotation: Rotation::default(),
+            next_race: Instant::now(),
+            keepalive: Tracker::new(Keepalive::NONE, Instant::now()),
+            received: false,
+            read_buf: Vec::new(),
+        };
+        connection.add(subscription);
+        connection.connect();
+        connections.push(connection);
+    }
+
+    /// Removes `key` from its connection, closing the socket with the last key.
+    fn handle_physical_unsubscribe(connections: &mut Vec<Connection>, key: SymbolKey, reports: &Reports) {
+        let Some(pos) = connections
+            .iter()
+            .position(|c| c.subscriptions.iter().any(|s| s.key == key))
+        else {
+            let _ = reports.results.send(CmdResult::Rejected(key));
+            return;
+        };
+
+        if connections[pos].subscriptions.len() == 1 {
+            connections.swap_remove(pos).close();
+        } else {
+            connections[pos].remove(&key);
+        }
+        let _ = reports.results.send(CmdResult::Unsubscribed(key));
+    }
+}
+
+impl Connection {
+    /// Opens the stream and subscribes every key.
+    ///
+    /// The first key's subscribe frame goes out with the connection; the
+    /// rest follow as one batch on the first idle poll.
+    ///
+    /// Exchanges set to [race](failover::race) open every endpoint once the
+    /// race is due and keep whichever stream was ready first.
+    fn connect(&mut self) {
+        if !self.subscriptions.iter_mut().all(|s| s.handshake().is_ok()) {
+            self.next_connect = Instant::now() + RECONNECT_DELAY;
+            self.report(|_| true, CmdResult::Failed);
+            self.emit(ConnectionStatus::Disconnected {
+                reason: DisconnectReason::HandshakeFailed,
+            });
+            return;
+        }
+
+        let first = &self.subscriptions[0];
+        let subscribe = first.driver.subscribe_msg(&first.key);
+        let policy = keepalive::policy_for(first.key.exchange, first.driver.keepalive());
+        let endpoints = self.endpoints();
+        let socket = match self.race_interval(&endpoints) {
+            Some(interval) if Instant::now() >= self.next_race => {
+                self.next_race = Instant::now() + interval;
+                let (latencies, mut streams): (Vec<_>, Vec<_>) = endpoints
+                    .iter()
+                    .map(|endpoint| {
+                        self.emit(ConnectionStatus::Connecting {
+                            endpoint: endpoint.clone(),
+                        });
+                        self.timed_open(endpoint, subscribe.clone())
+                    })
+                    .unzip();
+                failover::fastest(&latencies, None).and_then(|index| {
+                    self.rotation.settle(index);
+                    streams[index].take()
+                })
+            }
+            _ => {
+                let endpoint = self.rotation.pick(&endpoints);
+                self.emit(ConnectionStatus::Connecting {
+                    endpoint: endpoint.to_string(),
+                });
+                self.open(endpoint, subscribe)
+            }
+        };
+
+        match socket {
+            Some(socket) => {
+                self.keepalive = Tracker::new(policy, Instant::now());
+                self.received = false;
+                self.pending = self.subscriptions[1..].iter().map(|s| s.key.clone()).collect();
+                self.socket = Some(socket);
+                self.connects += 1;
+                let first = self.subscriptions[0].key.clone();
+                self.report(|key| *key == first, CmdResult::Subscribed);
+                self.emit(if self.connects == 1 {
+                    ConnectionStatus::Connected
+                } else {
+                    ConnectionStatus::Resubscribed
+                });
+            }
+            None => {
+                self.rotation.failed();
+                self.next_connect = Instant::now() + RECONNECT_DELAY;
+                self.report(|_| true, CmdResult::Failed);
+                self.emit(ConnectionStatus::Disconnected {
+                    reason: DisconnectReason::ConnectFailed,
+                });
+            }
+        }
+    }
+
+    /// The primary endpoint and its backups, in failover order.
+    fn endpoints(&self) -> Vec<String> {
+        let first = &self.subscriptions[0];
+        let primary = first.driver.endpoint(&first.key);
+        let backups = failover::backups_for(first.key.exchange, &primary)
+            .unwrap_or_else(|| first.driver.backup_endpoints(&first.key));
+        std::iter::once(primary).chain(backups).collect()
+    }
+
+    /// Opens a stream to `endpoint`, sending `subscribe` with it.
+    fn open(&mut self, endpoint: &str, subscribe: Option<String>) -> Option<Stream> {
+        let first = &self.subscriptions[0];
+        match first.driver.transport() {
+            Transport::WebSocket => {
+                connect_websocket(endpoint, subscribe, first.driver.permessage_deflate(), &self.dialer).ok()
+            }
+            Transport::Tcp => {
+                self.read_buf.resize(READ_BUFFER, 0);
+                connect_tcp(endpoint, subscribe, &self.dialer).ok()
+            }
+            Transport::Udp => {
+                self.read_buf.resize(READ_BUFFER, 0);
+                MulticastReceiver::join(endpoint).map(Stream::Udp).ok()
+            }
+        }
+    }
+
+    /// How often this connection re-measures its endpoints, if it races them.
+    ///
+    /// Multicast feeds and connections with a single endpoint never race.
+    fn race_interval(&self, endpoints: &[String]) -> Option<Duration> {
+        let first = &self.subscriptions[0];
+        if endpoints.len() < 2 || first.driver.transport() == Transport::Udp {
+            return None;
+        }
+        failover::race_interval(first.key.exchange)
+    }
+
+    /// Opens a stream like [Connection::open], also returning how long the
+    /// handshake took if it succeeded.
+    fn timed_open(&mut self, endpoint: &str, subscribe: Option<String>) -> (Option<Duration>, Option<Stream>) {
+        let start = Instant::now();
+        let stream = self.open(endpoint, subscribe);
+        (stream.as_ref().map(|_| start.elapsed()), stream)
+    }
+
+    /// Re-measures the endpoints once the race is due, moving to a clearly
+    /// faster one.
+    ///
+    /// Probes only handshake and are closed straight away. Moving is a
+    /// reconnect, so the books are stale until the new stream has rebuilt
+    /// them.
+    fn rerace(&mut self) {
+        let endpoints = self.endpoints();
+        let Some(interval) = self.race_interval(&endpoints) else {
+            return;
+        };
+        if Instant::now() < self.next_race {
+            return;
+        }
+        self.next_race = Instant::now() + interval;
+
+        let latencies: Vec<_> = endpoints.iter().map(|endpoint| self.timed_open(endpoint, None).0).collect();
+        let current = self.rotation.index(endpoints.len());
+        if let Some(index) = failover::fastest(&latencies, Some(current))
+            && index != current
+        {
+            self.rotation.settle(index);
+            self.disconnect(DisconnectReason::FasterEndpoint);
+            self.next_connect = Instant::now();
+        }
+    }
+
+    /// Adds a key; on an open socket it is subscribed with the next batch.
+    fn add(&mut self, mut subscription: Subscription) {
+        let mut handshaken = true;
+        if self.socket.is_some() {
+            handshaken = subscription.handshake().is_ok();
+            self.pending.push(subscription.key.clone());
+        }
+        self.subscriptions.push(subscription);
+        self.reroute();
+
+        // Reconnecting retries the handshake along with everyone else's
+        if !handshaken {
+            self.report(|_| true, CmdResult::Failed);
+            self.disconnect(DisconnectReason::HandshakeFailed);
+        }
+    }
+
+    /// Unsubscribes one key from a connection that keeps others.
+    fn remove(&mut self, key: &SymbolKey) {
+        let Some(index) = self.subscriptions.iter().position(|s| s.key == *key) else {
+            return;
+        };
+        let subscription = self.subscriptions.remove(index);
+        self.pending.retain(|pending| pending != key);
+        self.reroute();
 
-            for cmd in rx {
-                match cmd {
-                    ConnectorCmd::Subscribe(key) => {
-                        Self::handle_physical_subscribe(key);
+        let sent = match (subscription.driver.unsubscribe_msg(key), self.socket.as_mut()) {
+            (Some(msg), Some(socket)) => socket.send(msg),
+            _ => Ok(()),
+        };
+        if sent.is_err() {
+            self.disconnect(DisconnectReason::StreamError);
+        }
+    }
+
+    /// Whether this is a logged-in [Feed::Private] connection.
+    fn is_private(&self) -> bool {
+        matches!(self.subscriptions[0].key.feed, Feed::Private(_))
+    }
+
+    fn reroute(&mut self) {
+        self.routes = self
+            .subscriptions
+            .iter()
+            .enumerate()
+            .map(|(index, s)| (s.driver.stream_name(&s.key).into_bytes().into_boxed_slice(), index))
+            .collect();
+    }
+
+    /// Drains every frame currently buffered on the socket.
+    ///
+    /// Reconnects are due once `next_connect` has passed and the exchange's
+    /// rate limit allows another connection.
+    ///
+    /// Returns whether anything was received or the connection changed.
+    fn poll(&mut self, limiter: &mut RateLimiter) -> bool {
+        if self.socket.is_none() {
+            let now = Instant::now();
+            let first = &self.subscriptions[0];
+            if now >= self.next_connect && limiter.try_acquire(first.key.exchange, now, || first.driver.rate_limit()) {
+                self.connect();
+                return true;
+            }
+            return false;
+        }
+
+        let mut busy = false;
+        loop {
+            let frame = self.read_frame();
+
+            let replied = match &frame {
+                Some((index, _)) => self.send_reply(*index),
+                None => (0..self.subscriptions.len()).try_for_each(|index| self.send_reply(index)),
+            };
+
+            match frame {
+                Some((_, Ok(()))) if replied.is_ok() => {
+                    self.received = true;
+                    busy = true;
+                }
+                None if replied.is_ok() => {
+                    if self.subscribe_pending().is_err() {
+                        self.disconnect(DisconnectReason::StreamError);
+                    } else {
+                        self.keep_alive();
                     }
-                    ConnectorCmd::Unsubscribe(key) => {
-                        Self::handle_physical_unsubscribe(key);
+                    if self.socket.is_some() {
+                        self.rerace();
                     }
+                    return busy;
+                }
+                Some((
+                    index,
+                    Err(DriverError::SequenceGap { .. } | DriverError::ChecksumMismatch { .. } | DriverError::Crossed),
+                )) => {
+                    self.resync(index);
+                    return true;
+                }
+                _ => {
+                    self.rotation.failed();
+                    self.disconnect(DisconnectReason::StreamError);
+                    return true;
+                }
+            }
+        }
+    }
+
+    /// Reads and applies one frame, or returns `None` if nothing is buffered.
+    ///
+    /// Also returns the index of the subscription the frame was routed to.
+    fn read_frame(&mut self) -> Option<(usize, Result<(), DriverError>)> {
+        let subscriptions = &mut self.subscriptions;
+        let routes = &self.routes;
+        let reports = &self.reports;
+        let id = self.id;
+        match self.socket.as_mut()? {
+            Stream::WebSocket(socket) => match socket.read() {
+                Ok(Message::Text(text)) => Some(dispatch(id, subscriptions, routes, reports, text.as_bytes())),
+                Ok(Message::Binary(bytes)) => Some(dispatch(id, subscriptions, routes, reports, &bytes)),
+                Ok(_) => Some((0, Ok(()))), // Pings are answered by tungstenite
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => None,
+                Err(_) => Some((0, Err(DriverError::Malformed))),
+            },
+            Stream::Tcp(raw) => {
+                if raw.flush().is_err() {
+                    return Some((0, Err(DriverError::Malformed)));
+                }
+                match raw.stream.read(&mut self.read_buf) {
+                    Ok(0) => Some((0, Err(DriverError::Malformed))), // Closed by the venue
+                    Ok(n) => Some(dispatch(id, subscriptions, routes, reports, &self.read_buf[..n])),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => None,
+                    Err(_) => Some((0, Err(DriverError::Malformed))),
                 }
             }
+            Stream::Udp(receiver) => {
+                let driver = &subscriptions[0].driver;
+                match receiver.next(&mut self.read_buf, |packet| driver.packet_sequence(packet), Instant::now())? {
+                    Ok(packet) => Some(dispatch(id, subscriptions, routes, reports, packet)),
+                    Err(err) => Some((0, Err(err))),
+                }
+            }
+        }
+    }
+
+    /// Sends the frame a subscription's driver wants sent back, if any.
+    fn send_reply(&mut self, index: usize) -> Result<(), DriverError> {
+        match (self.subscriptions[index].driver.pending_reply(), self.socket.as_mut()) {
+            (Some(reply), Some(socket)) => socket.send(reply),
+            _ => Ok(()),
+        }
+    }
+
+    /// Subscribes the queued keys, at most one batch per [SUBSCRIBE_INTERVAL].
+    ///
+    /// Venues cap incoming messages per second, so keys added in a burst
+    /// are combined with [ExchangeDriver::subscribe_batch_msg] where the
+    /// driver supports it.
+    fn subscribe_pending(&mut self) -> Result<(), DriverError> {
+        if self.pending.is_empty() || Instant::now() < self.next_subscribe {
+            return Ok(());
+        }
+        let Some(socket) = self.socket.as_mut() else {
+            return Ok(());
+        };
+
+        let driver = &self.subscriptions[0].driver;
+        let keys: Vec<&SymbolKey> = self.pending.iter().collect();
+        match driver.subscribe_batch_msg(&keys) {
+            Some(msg) => socket.send(msg)?,
+            None => {
+                for key in keys {
+                    if let Some(msg) = driver.subscribe_msg(key) {
+                        socket.send(msg)?;
+                    }
+                }
+            }
+        }
+        let sent = std::mem::take(&mut self.pending);
+        self.next_subscribe = Instant::now() + SUBSCRIBE_INTERVAL;
+        self.report(|key| sent.contains(key), CmdResult::Subscribed);
+        Ok(())
+    }
+
+    fn emit(&self, status: ConnectionStatus) {
+        let _ = self.reports.status.send(StatusEvent {
+            connection: self.id,
+            exchange: self.subscriptions[0].key.exchange,
+            status,
         });
+    }
 
-        Self { cmd_tx: tx }
+    /// Sends `result` for each subscription matching `filter` that has not
+    /// had one yet.
+    fn report(&mut self, filter: impl Fn(&SymbolKey) -> bool, result: fn(SymbolKey) -> CmdResult) {
+        for subscription in &mut self.subscriptions {
+            if !subscription.reported && filter(&subscription.key) {
+                subscription.reported = true;
+                let _ = self.reports.results.send(result(subscription.key.clone()));
+            }
+        }
     }
 
-    /// Sends a subscription command to the pinned worker.
-    pub fn send_cmd(&self, cmd: ConnectorCmd) {
-        let _ = self.cmd_tx.send(cmd);
+    /// Sends a due keepalive ping, or drops a stream that has gone silent.
+    ///
+    /// Only called once the socket is drained, so the clock is read while
+    /// idle rather than once per frame. Anything received also shows the
+    /// endpoint is working, which resets its failover count.
+    fn keep_alive(&mut self) {
+        let received = std::mem::take(&mut self.received);
+        if received {
+            self.rotation.succeeded();
+        }
+        let dead = match (self.keepalive.poll(Instant::now(), received), self.socket.as_mut()) {
+            (Action::Ping(ping), Some(socket)) => socket.ping(ping).err().map(|_| DisconnectReason::StreamError),
+            (Action::Expired, _) => Some(DisconnectReason::KeepaliveExpired),
+            _ => None,
+        };
+        if let Some(reason) = dead {
+            self.rotation.failed();
+            self.disconnect(reason);
+        }
+    }
+
+    /// Drops the socket and clears every book so readers never see stale
+    /// levels.
+    ///
+    /// The books stay flagged stale until their next packet is applied.
+    fn disconnect(&mut self, reason: DisconnectReason) {
+        self.emit(ConnectionStatus::Disconnected { reason });
+        self.socket = None;
+        self.pending.clear();
+        self.next_connect = Instant::now() + RECONNECT_DELAY;
+        for subscription in &self.subscriptions {
+            invalidate(&subscription.book);
+        }
+    }
+
+    /// Counts a sequence gap, checksum mismatch or crossed book and rebuilds
+    /// the book.
+    ///
+    /// A connection of its own is reopened straight away: reconnecting
+    /// reruns the driver's handshake and subscription, which is where every
+    /// driver fetches or requests its snapshot. On a shared connection only
+    /// the affected key is reset and subscribed again, leaving the other
+    /// books untouched.
+    fn resync(&mut self, index: usize) {
+        self.subscriptions[index].book.gap_count.fetch_add(1, Ordering::Relaxed);
+        self.emit(ConnectionStatus::Resyncing {
+            key: self.subscriptions[index].key.clone(),
+        });
+        if self.subscriptions.len() == 1 {
+            self.disconnect(DisconnectReason::Resync);
+            self.next_connect = Instant::now();
+            return;
+        }
+
+        let subscription = &mut self.subscriptions[index];
+        invalidate(&subscription.book);
+        if subscription.handshake().is_err() {
+            self.disconnect(DisconnectReason::HandshakeFailed);
+            return;
+        }
+        self.pending.push(subscription.key.clone());
+    }
+
+    /// Sends every key's unsubscribe frame and closes the socket.
+    fn close(&mut self) {
+        let messages = self
+            .subscriptions
+            .iter()
+            .filter_map(|s| s.driver.unsubscribe_msg(&s.key));
+        if self.socket.is_some() {
+            self.emit(ConnectionStatus::Disconnected {
+                reason: DisconnectReason::Closed,
+            });
+        }
+        match self.socket.take() {
+            Some(Stream::WebSocket(mut socket)) => {
+                for msg in messages {
+                    let _ = socket.send(Message::text(msg));
+                }
+                let _ = socket.close(None);
+                let _ = socket.flush();
+            }
+            Some(Stream::Tcp(mut raw)) => {
+                for msg in messages {
+                    raw.outbox.extend_from_slice(msg.as_bytes());
+                }
+                // Blocking for the last write so the goodbye is not lost
+                let _ = raw.stream.set_nonblocking(false);
+                let _ = raw.stream.write_all(&raw.outbox);
+                let _ = raw.stream.shutdown(std::net::Shutdown::Both);
+            }
+            // Dropping the sockets leaves the groups
+            Some(Stream::Udp(_)) | None => {}
+        }
+    }
+}
+
+impl Stream {
+    /// Queues a frame without blocking.
+    fn send(&mut self, msg: String) -> Result<(), DriverError> {
+        match self {
+            Stream::WebSocket(socket) => send(socket, Message::text(msg)).map_err(|_| DriverError::Malformed),
+            Stream::Tcp(raw) => {
+                raw.outbox.extend_from_slice(msg.as_bytes());
+                raw.flush().map_err(|_| DriverError::Malformed)
+            }
+            Stream::Udp(_) => Ok(()),
+        }
+    }
+
+    /// Queues a keepalive ping; ping frames only exist on websockets.
+    fn ping(&mut self, ping: Ping) -> Result<(), DriverError> {
+        match (ping, self) {
+            (Ping::Frame, Stream::WebSocket(socket)) => {
+                send(socket, Message::Ping(Default::default())).map_err(|_| DriverError::Malformed)
+            }
+            (Ping::Frame, _) => Ok(()),
+            (Ping::Text(text), stream) => stream.send(text.to_string()),
+        }
+    }
+}
+
+impl RawStream {
+    /// Writes as much of the outbox as the socket accepts right now.
+    fn flush(&mut self) -> std::io::Result<()> {
+        while !self.outbox.is_empty() {
+            match self.stream.write(&self.outbox) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.outbox.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
     }
+}
+
+/// Opens a websocket to `url` and sends `subscribe`, if any.
+///
+/// With `deflate`, permessage-deflate is offered during the upgrade and
+/// compressed messages are inflated before tungstenite parses them.
+fn connect_websocket(
+    url: &str,
+    subscribe: Option<String>,
+    deflate: bool,
+    dialer: &Dialer,
+) -> tungstenite::Result<Stream> {
+    let mut request = url.into_client_request()?;
+    if deflate {
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Extensions", HeaderValue::from_static(deflate::OFFER));
+    }
+
+    let uri = request.uri();
+    let host = uri.host().ok_or(tungstenite::Error::Url(UrlError::NoHostName))?.to_string();
+    let mode = tungstenite::client::uri_mode(uri)?;
+    let port = uri.port_u16().unwrap_or(match mode {
+        Mode::Plain => 80,
+        Mode::Tls => 443,
+    });
+
+    let tcp = dialer.tcp(&host, port)?;
+    let stream = match mode {
+        Mode::Plain => MaybeTlsStream::Plain(tcp),
+        Mode::Tls => {
+            let name = ServerName::try_from(host).map_err(|_| TlsError::InvalidDnsName)?;
+            let tls = ClientConnection::new(Arc::clone(&dialer.tls), name).map_err(TlsError::from)?;
+            MaybeTlsStream::Rustls(StreamOwned::new(tls, tcp))
+        }
+    };
+
+    let (mut socket, response) =
+        tungstenite::client(request, Inflate::new(stream, deflate)).map_err(|err| match err {
+            HandshakeError::Failure(err) => err,
+            HandshakeError::Interrupted(_) => tungstenite::Error::Io(ErrorKind::WouldBlock.into()),
+        })?;
+    let accepted = response
+        .headers()
+        .get("Sec-WebSocket-Extensions")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains(deflate::OFFER));
+    if !accepted {
+        socket.get_mut().disable();
+    }
+
+    if let Some(msg) = subscribe {
+        socket.send(Message::text(msg))?;
+    }
+    set_nonblocking(&socket)?;
+    Ok(Stream::WebSocket(socket))
+}
+
+/// Opens a TCP connection to `host:port` and sends `subscribe`, if any.
+fn connect_tcp(addr: &str, subscribe: Option<String>, dialer: &Dialer) -> std::io::Result<Stream> {
+    let (host, port) = addr.rsplit_once(':').ok_or(ErrorKind::InvalidInput)?;
+    let port = port.parse().map_err(|_| std::io::Error::from(ErrorKind::InvalidInput))?;
+    let mut stream = dialer.tcp(host, port)?;
+    if let Some(msg) = subscribe {
+        stream.write_all(msg.as_bytes())?;
+    }
+    stream.set_nonblocking(true)?;
+    Ok(Stream::Tcp(RawStream {
+        stream,
+        outbox: Vec::new(),
+    }))
+}
+
+/// Returns the default rate limit of `key`'s driver.
+///
+/// Only called the first time a connector sees an exchange.
+fn rate_limit_for(key: &SymbolKey) -> Option<RateLimit> {
+    driver::driver_for(key).and_then(|driver| driver.rate_limit())
+}
 
-    fn handle_physical_subscribe(key: SymbolKey) {
-        // Logic for opening WebSocket/FIX session based on Exchange enum
+/// Routes a frame to its subscription and applies it there.
+///
+/// Frames that name no known stream, such as acks and pings, go to the
+/// first subscription. Every frame is copied to the worker's [tap] first,
+/// and private events and crossed books are reported on `reports`.
+fn dispatch(
+    connection: usize,
+    subscriptions: &mut [Subscription],
+    routes: &HashMap<Box<[u8]>, usize>,
+    reports: &Reports,
+    frame: &[u8],
+) -> (usize, Result<(), DriverError>) {
+    let received = unix_nanos();
+    tap::record(connection, frame);
+    let index = match subscriptions {
+        [_] => 0,
+        [first, ..] => first
+            .driver
+            .frame_stream(frame)
+            .and_then(|name| routes.get(name).copied())
+            .unwrap_or(0),
+        [] => return (0, Ok(())),
+    };
+    let subscription = &mut subscriptions[index];
+    let applied = match subscription.key.feed {
+        Feed::Private(_) => forward_private(subscription, frame, &reports.private),
+        _ => {
+            let applied = apply_frame(
+                subscription.driver.as_mut(),
+                &subscription.book,
+                frame,
+                received,
+                subscription.crossed,
+                subscription.overflow.as_deref(),
+                subscription.depth.as_deref(),
+            );
+            if let Some((expected, computed)) = subscription.driver.take_mismatch() {
+                let _ = reports.status.send(StatusEvent {
+                    connection,
+                    exchange: subscription.key.exchange,
+                    status: ConnectionStatus::ChecksumMismatch {
+                        key: subscription.key.clone(),
+                        expected,
+                        computed,
+                    },
+                });
+            }
+            if matches!(applied, Ok(true) | Err(DriverError::Crossed)) {
+                let _ = reports.status.send(StatusEvent {
+                    connection,
+                    exchange: subscription.key.exchange,
+                    status: ConnectionStatus::Crossed {
+                        key: subscription.key.clone(),
+                    },
+                });
+            }
+            applied.map(|_| ())
+        }
+    };
+    (index, applied)
+}
+
+/// Reports a private stream's frame if its driver finds an event in it.
+fn forward_private(
+    subscription: &mut Subscription,
+    frame: &[u8],
+    private: &Sender<PrivateEvent>,
+) -> Result<(), DriverError> {
+    if subscription.driver.parse_private(frame)? {
+        let _ = private.send(PrivateEvent {
+            key: subscription.key.clone(),
+            received: SystemTime::now(),
+            data: frame.to_vec(),
+        });
     }
+    Ok(())
+}
 
-    fn handle_physical_unsubscribe(key: SymbolKey) {
-        // Logic for sending 'unsubscribe' message or closing connection
+/// Applies one frame and finalizes the packet: compact, then even out the
+/// version.
+///
+/// The version is odd for the whole frame, since drivers write levels as
+/// they parse. A control frame, for which the driver returns `Ok(false)`
+/// having written nothing, rolls it back to where it was, so readers see no
+/// new version. A frame that changed the book stamps it with the venue's
+/// update id and time, `received` and the time it was finalized.
+///
+/// Returns whether the frame left the book crossed, having handled it as
+/// `crossed` asks; [DriverError::Crossed] if it has to be rebuilt.
+fn apply_frame(
+    driver: &mut dyn ExchangeDriver,
+    shared: &SharedBook,
+    frame: &[u8],
+    received: u64,
+    crossed: CrossedPolicy,
+    overflow: Option<&RwLock<FullDepthBook>>,
+    depth: Option<&RwLock<FullDepthBook>>,
+) -> Result<bool, DriverError> {
+    // SAFETY: The connection's connector thread is the book's only writer.
+    let book = unsafe { shared.writer() };
+    let tops = (book.bids[0], book.asks[0]);
+    let resynced = book.stale.load(Ordering::Relaxed);
+    driver::keep_overflow(overflow.is_some());
+    driver::keep_changes(depth.is_some());
+    book.begin_write();
+    let applied = driver.parse_message(frame, book);
+    let mut crossing = false;
+    let mut rebuild = false;
+    if applied == Ok(true) {
+        book.compact_sides();
+        debug_assert_eq!(book.validate(), Ok(()), "driver left an invalid book");
+        crossing = book.is_crossed();
+        rebuild = crossing && !uncross(book, tops, crossed);
+        if rebuild {
+            book.stale.store(true, Ordering::Relaxed);
+        } else if resynced {
+            book.stale.store(false, Ordering::Relaxed);
+        }
+        // The first frame after a resync jumps the book however it is sent
+        let cause = if rebuild || resynced {
+            UpdateCause::Resync
+        } else if crossing && crossed == CrossedPolicy::DropStale {
+            UpdateCause::TradeThrough
+        } else {
+            driver.update_cause(frame)
+        };
+        book.set_update_cause(cause);
+        let truncated = match overflow {
+            Some(tail) => spill_into(&mut tail.write(), book, cause),
+            None => driver::take_overflow(|_| {}),
+        };
+        book.truncated.fetch_add(truncated, Ordering::Relaxed);
+        match depth {
+            Some(depth) => record_into(&mut depth.write(), book, cause),
+            None => driver::take_changes(|_| {}),
+        }
+        book.update_id.store(driver.update_id().unwrap_or(0), Ordering::Relaxed);
+        book.exchange_ts.store(driver.exchange_ts(frame).unwrap_or(0), Ordering::Relaxed);
+        book.recv_ts.store(received, Ordering::Relaxed);
+        book.publish_ts.store(unix_nanos(), Ordering::Relaxed);
+    } else {
+        // Whatever a failed frame dropped goes with the book it half built
+        driver::take_overflow(|_| {});
+        driver::take_changes(|_| {});
+    }
+    if applied == Ok(false) {
+        book.abort_write();
+    } else {
+        book.end_write();
     }
-}
\ No newline at end of file
+    if applied? {
+        shared.wake_readers();
+    }
+    if rebuild {
+        return Err(DriverError::Crossed);
+    }
+    Ok(crossing)
+}
+
+/// Moves the changes the driver made past `book` into its `tail`, which a
+/// snapshot or resync starts afresh, and returns how many levels it
+/// dropped.
+fn spill_into(tail: &mut FullDepthBook, book: &L1FriendlyBook, cause: UpdateCause) -> u64 {
+    if matches!(cause, UpdateCause::Snapshot | UpdateCause::Resync) {
+        tail.clear();
+    }
+    let dropped = driver::take_overflow(|change| tail.apply(change.side, change.price, change.qty.unwrap_or(0)));
+    // Levels the book has since taken in are no longer past it
+    for (side, levels, count) in [(Side::Bid, &book.bids, book.bid_count), (Side::Ask, &book.asks, book.ask_count)] {
+        if let Some(worst) = levels[..usize::from(count)].last() {
+            tail.trim(side, worst.price);
+        }
+    }
+    dropped
+}
+
+/// Applies the updates the driver made to `book` to its full `depth`, which
+/// a snapshot or resync starts afresh.
+///
+/// A frame that did more than update levels may have written the book
+/// directly, so after one the top of `depth` is made to match the book.
+fn record_into(depth: &mut FullDepthBook, book: &L1FriendlyBook, cause: UpdateCause) {
+    if matches!(cause, UpdateCause::Snapshot | UpdateCause::Resync) {
+        depth.clear();
+    }
+    driver::take_changes(|change| depth.apply(change.side, change.price, change.qty.unwrap_or(0)));
+    depth.price_exponent = book.price_exponent;
+    depth.qty_exponent = book.qty_exponent;
+    if cause == UpdateCause::Delta {
+        return;
+    }
+    for (side, levels, count) in [(Side::Bid, &book.bids, book.bid_count), (Side::Ask, &book.asks, book.ask_count)] {
+        let levels = &levels[..usize::from(count)];
+        if let Some(worst) = levels.last() {
+            depth.trim(side, worst.price);
+        }
+        for level in levels {
+            depth.apply(side, level.price, level.qty);
+        }
+    }
+}
+
+/// Handles a book left crossed as `policy` asks, given the best levels
+/// before the frame.
+///
+/// Returns `false` if the book has to be rebuilt instead.
+fn uncross(book: &mut L1FriendlyBook, tops: (Level, Level), policy: CrossedPolicy) -> bool {
+    match policy {
+        CrossedPolicy::Resync => false,
+        CrossedPolicy::Report => true,
+        CrossedPolicy::DropStale => {
+            let stale = match (book.bids[0] != tops.0, book.asks[0] != tops.1) {
+                (true, false) => Side::Ask,
+                (false, true) => Side::Bid,
+                _ => return false,
+            };
+            book.drop_crossed(stale);
+            true
+        }
+    }
+}
+
+/// Returns the wall-clock time in nanoseconds since the Unix epoch.
+fn unix_nanos() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64)
+}
+
+/// Clears a book and flags it stale until its next packet is applied.
+fn invalidate(shared: &SharedBook) {
+    // SAFETY: The connection's connector thread is the book's only writer.
+    let book = unsafe { shared.writer() };
+    book.begin_write();
+    book.clear_sides();
+    book.stale.store(true, Ordering::Relaxed);
+    book.set_update_cause(UpdateCause::Resync);
+    book.end_write();
+    for depth in [shared.overflow(), shared.full_depth()].into_iter().flatten() {
+        depth.write().clear();
+    }
+    shared.wake_readers();
+}
+
+/// Closes every connection and marks its books stale.
+fn close_all(connections: Vec<Connection>) {
+    for mut connection in connections {
+        connection.close();
+        for subscription in &connection.subscriptions {
+            invalidate(&subscription.book);
+        }
+    }
+    tap::flush();
+}
+
+/// Queues a frame on a non-blocking socket.
+///
+/// A `WouldBlock` on flush is not an error: the frame stays buffered and is
+/// written out by a later read or write.
+fn send(socket: &mut Socket, msg: Message) -> tungstenite::Result<()> {
+    match socket.send(msg) {
+        Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+        result => result,
+    }
+}
+
+/// Switches the underlying TCP stream to non-blocking mode for polling.
+fn set_nonblocking(socket: &Socket) -> std::io::Result<()> {
+    match socket.get_ref().get_ref() {
+        MaybeTlsStream::Plain(stream) => stream.set_nonblocking(true),
+        MaybeTlsStream::Rustls(stream) => stream.get_ref().set_nonblocking(true),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed, ProductType};
+    use crate::model::BOOK_DEPTH;
+    use std::net::TcpListener;
+
+    /// A raw TCP feed that only subscribes and unsubscribes.
+    struct LineDriver(String);
+
+    impl ExchangeDriver for LineDriver {
+        fn transport(&self) -> Transport {
+            Transport::Tcp
+        }
+
+        fn endpoint(&self, _key: &SymbolKey) -> String {
+            self.0.clone()
+        }
+
+        fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+            Some(format!("sub {}\n", key.symbol))
+        }
+
+        fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+            Some(format!("unsub {}\n", key.symbol))
+        }
+
+        fn parse_message(&mut self, _msg: &[u8], _book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+            Ok(false)
+        }
+    }
+
+    /// Applies frames such as `b 100 1` or `a 99 0` as single levels, the
+    /// way venue drivers do.
+    struct LevelDriver;
+
+    impl ExchangeDriver for LevelDriver {
+        fn endpoint(&self, _key: &SymbolKey) -> String {
+            String::new()
+        }
+
+        fn subscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+            None
+        }
+
+        fn unsubscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+            None
+        }
+
+        fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+            let msg = std::str::from_utf8(msg).map_err(|_| DriverError::Malformed)?;
+            let fields: Vec<&str> = msg.split(' ').collect();
+            let [side, price, qty] = fields[..] else {
+                return Err(DriverError::Malformed);
+            };
+            let (price, qty) = (price.parse().unwrap(), qty.parse().unwrap());
+            match side {
+                "b" => driver::apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true),
+                _ => driver::apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false),
+            }
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn test_crossed_policies() {
+        let apply = |book: &SharedBook, frame: &str, policy| apply_frame(&mut LevelDriver, book, frame.as_bytes(), 0, policy, None, None);
+
+        let book = SharedBook::new();
+        assert_eq!(apply(&book, "b 99 1", CrossedPolicy::Resync), Ok(false));
+        assert_eq!(apply(&book, "a 100 1", CrossedPolicy::Resync), Ok(false));
+        assert_eq!(apply(&book, "a 99 1", CrossedPolicy::Resync), Err(DriverError::Crossed));
+        assert!(book.is_stale());
+        assert_eq!(book.version.load(Ordering::Acquire), 6);
+        assert_eq!(book.update_cause(), UpdateCause::Resync);
+
+        // The bid moved, so the asks it crossed are dropped
+        let book = SharedBook::new();
+        apply(&book, "b 99 1", CrossedPolicy::DropStale).unwrap();
+        apply(&book, "a 100 1", CrossedPolicy::DropStale).unwrap();
+        apply(&book, "a 101 1", CrossedPolicy::DropStale).unwrap();
+        assert_eq!(apply(&book, "b 100 2", CrossedPolicy::DropStale), Ok(true));
+        assert_eq!(book.best_bid(), Some(Level { price: 100, qty: 2 }));
+        assert_eq!(book.best_ask(), Some(Level { price: 101, qty: 1 }));
+        assert!(!book.is_stale());
+        assert_eq!(book.update_cause(), UpdateCause::TradeThrough);
+
+        let book = SharedBook::new();
+        apply(&book, "b 99 1", CrossedPolicy::Report).unwrap();
+        assert_eq!(apply(&book, "a 98 1", CrossedPolicy::Report), Ok(true));
+        assert!(book.is_crossed());
+        assert_eq!(book.update_cause(), UpdateCause::Delta);
+    }
+
+    #[test]
+    fn test_control_frames_keep_the_version() {
+        let book = SharedBook::new();
+        let policy = CrossedPolicy::Resync;
+        apply_frame(&mut LevelDriver, &book, b"b 99 1", 0, policy, None, None).unwrap();
+        assert_eq!(book.version.load(Ordering::Acquire), 2);
+
+        let mut control = LineDriver(String::new());
+        assert_eq!(apply_frame(&mut control, &book, b"pong", 0, policy, None, None), Ok(false));
+        assert_eq!(book.version.load(Ordering::Acquire), 2);
+
+        // A failed frame may have written levels, so it still moves the version on
+        assert!(apply_frame(&mut LevelDriver, &book, b"junk", 0, policy, None, None).is_err());
+        assert_eq!(book.version.load(Ordering::Acquire), 4);
+    }
+
+    #[test]
+    fn test_update_cause_after_resync() {
+        let apply = |book: &SharedBook, frame: &str| apply_frame(&mut LevelDriver, book, frame.as_bytes(), 0, CrossedPolicy::Resync, None, None);
+
+        let book = SharedBook::new();
+        apply(&book, "b 99 1").unwrap();
+        assert_eq!(book.update_cause(), UpdateCause::Delta);
+
+        invalidate(&book);
+        assert_eq!(book.update_cause(), UpdateCause::Resync);
+        // The frame rebuilding the book is part of the resync, the next is not
+        apply(&book, "b 98 1").unwrap();
+        assert_eq!(book.update_cause(), UpdateCause::Resync);
+        assert!(!book.is_stale());
+        apply(&book, "a 100 1").unwrap();
+        assert_eq!(book.update_cause(), UpdateCause::Delta);
+    }
+
+    #[test]
+    fn test_truncated_levels_spill_into_tail() {
+        let book = SharedBook::new();
+        let tail = Arc::new(RwLock::new(FullDepthBook::new()));
+        let apply = |frame: String| {
+            apply_frame(&mut LevelDriver, &book, frame.as_bytes(), 0, CrossedPolicy::Resync, Some(&tail), None).unwrap()
+        };
+        for i in 0..BOOK_DEPTH as i64 + 2 {
+            apply(format!("b {} 1", 1_000 - i));
+        }
+        assert_eq!(book.truncated(), 2);
+        let spilled = |tail: &RwLock<FullDepthBook>| tail.read().bids().map(|level| level.price).collect::<Vec<_>>();
+        let worst = 1_000 - BOOK_DEPTH as i64;
+        assert_eq!(spilled(&tail), [worst, worst - 1]);
+
+        // A better bid pushes the worst off the end, a removal past the book
+        // reaches the tail, and neither counts twice
+        apply("b 1001 1".to_string());
+        apply(format!("b {} 0", worst - 1));
+        assert_eq!(book.truncated(), 3);
+        assert_eq!(spilled(&tail), [worst + 1, worst]);
+
+        // Room freed in the book is refilled by the next update there, which
+        // takes the level back from the tail
+        apply("b 1001 0".to_string());
+        apply(format!("b {} 2", worst + 1));
+        assert_eq!(book.bids[BOOK_DEPTH - 1], Level { price: worst + 1, qty: 2 });
+        assert_eq!(spilled(&tail), [worst]);
+        assert_eq!(book.truncated(), 3);
+
+        book.set_overflow(Some(Arc::clone(&tail)));
+        invalidate(&book);
+        assert!(tail.read().is_empty());
+    }
+
+    #[test]
+    fn test_full_depth_follows_level_updates() {
+        let book = SharedBook::new();
+        let depth = Arc::new(RwLock::new(FullDepthBook::new()));
+        let apply = |frame: String, policy| apply_frame(&mut LevelDriver, &book, frame.as_bytes(), 0, policy, None, Some(&depth));
+        for i in 0..BOOK_DEPTH as i64 + 2 {
+            apply(format!("b {} 1", 1_000 - i), CrossedPolicy::Resync).unwrap();
+        }
+        let bids = |depth: &RwLock<FullDepthBook>| depth.read().bids().map(|level| level.price).collect::<Vec<_>>();
+        assert_eq!(bids(&depth), (1_000 - BOOK_DEPTH as i64 - 1..=1_000).rev().collect::<Vec<_>>());
+
+        // A removal at the top leaves the levels past the book in place,
+        // unlike the tail, which loses what moves back up
+        apply("b 1000 0".to_string(), CrossedPolicy::Resync).unwrap();
+        apply(format!("b {} 3", 1_000 - BOOK_DEPTH as i64 - 1), CrossedPolicy::Resync).unwrap();
+        assert_eq!(depth.read().len(Side::Bid), BOOK_DEPTH + 1);
+        assert_eq!(depth.read().bids().last(), Some(Level { price: 1_000 - BOOK_DEPTH as i64 - 1, qty: 3 }));
+
+        // Levels the connector drops to uncross the book leave it too
+        apply("a 1001 1".to_string(), CrossedPolicy::DropStale).unwrap();
+        assert_eq!(apply("a 998 1".to_string(), CrossedPolicy::DropStale), Ok(true));
+        assert_eq!(book.update_cause(), UpdateCause::TradeThrough);
+        assert_eq!(depth.read().bids().next(), book.bids.first().copied());
+
+        book.set_full_depth(Some(Arc::clone(&depth)));
+        invalidate(&book);
+        assert!(depth.read().is_empty());
+    }
+
+    /// A raw TCP account stream whose frames are all events.
+    struct AccountDriver(String);
+
+    impl ExchangeDriver for AccountDriver {
+        fn transport(&self) -> Transport {
+            Transport::Tcp
+        }
+
+        fn endpoint(&self, _key: &SymbolKey) -> String {
+            self.0.clone()
+        }
+
+        fn subscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+            None
+        }
+
+        fn unsubscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+            None
+        }
+
+        fn parse_message(&mut self, _msg: &[u8], _book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+            Ok(false)
+        }
+
+        fn private_streams(&self) -> bool {
+            true
+        }
+
+        fn parse_private(&mut self, _msg: &[u8]) -> Result<bool, DriverError> {
+            Ok(true)
+        }
+    }
+
+    /// Subscribes a key through `connector`, then checks that shutting down
+    /// unsubscribes it, closes the stream and marks the book stale.
+    fn check_shutdown(connector: ExchangeConnector, name: &str) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        driver::register_custom(name, move || Box::new(LineDriver(addr.clone())));
+
+        let key = SymbolKey {
+            exchange: Exchange::custom(name),
+            symbol: "ABC".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let book = Arc::new(SharedBook::new());
+        let _ = connector.send_cmd(ConnectorCmd::Subscribe(key, Arc::clone(&book)));
+
+        let (mut stream, _) = listener.accept().unwrap();
+        connector.shutdown();
+        connector.join();
+
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "sub ABC\nunsub ABC\n");
+        assert!(book.is_stale());
+        driver::unregister_custom(name);
+    }
+
+    #[test]
+    fn test_reports_results() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        driver::register_custom("results-test", move || Box::new(LineDriver(addr.clone())));
+        let key = |exchange| SymbolKey {
+            exchange,
+            symbol: "ABC".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let served = key(Exchange::custom("results-test"));
+        let unknown = key(Exchange::custom("results-unknown"));
+
+        let connector = ExchangeConnector::new(CoreId { id: 0 }, &TransportConfig::default());
+        let next = || connector.results().recv_timeout(Duration::from_secs(5)).unwrap();
+        let _ = connector.send_cmd(ConnectorCmd::Subscribe(served.clone(), Arc::new(SharedBook::new())));
+        assert_eq!(next(), CmdResult::Subscribed(served.clone()));
+        let _ = connector.send_cmd(ConnectorCmd::Subscribe(unknown.clone(), Arc::new(SharedBook::new())));
+        assert_eq!(next(), CmdResult::Rejected(unknown.clone()));
+        let _ = connector.send_cmd(ConnectorCmd::Unsubscribe(served.clone()));
+        assert_eq!(next(), CmdResult::Unsubscribed(served));
+        let _ = connector.send_cmd(ConnectorCmd::Unsubscribe(unknown.clone()));
+        assert_eq!(next(), CmdResult::Rejected(unknown));
+
+        connector.shutdown();
+        driver::unregister_custom("results-test");
+    }
+
+    #[test]
+    fn test_reports_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let endpoint = addr.clone();
+        driver::register_custom("status-test", move || Box::new(LineDriver(addr.clone())));
+        let exchange = Exchange::custom("status-test");
+        let key = SymbolKey {
+            exchange,
+            symbol: "ABC".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+
+        let connector = ExchangeConnector::new(CoreId { id: 0 }, &TransportConfig::default());
+        let next = || connector.status().recv_timeout(Duration::from_secs(5)).unwrap().status;
+        let _ = connector.send_cmd(ConnectorCmd::Subscribe(key.clone(), Arc::new(SharedBook::new())));
+        let connecting = ConnectionStatus::Connecting { endpoint };
+        assert_eq!(next(), connecting);
+        assert_eq!(next(), ConnectionStatus::Connected);
+
+        // The venue hangs up; the connection comes back after the reconnect delay
+        drop(listener.accept().unwrap());
+        let disconnected = ConnectionStatus::Disconnected {
+            reason: DisconnectReason::StreamError,
+        };
+        assert_eq!(next(), disconnected);
+        assert_eq!(next(), connecting);
+        assert_eq!(next(), ConnectionStatus::Resubscribed);
+
+        let _ = connector.send_cmd(ConnectorCmd::Unsubscribe(key));
+        let closed = ConnectionStatus::Disconnected {
+            reason: DisconnectReason::Closed,
+        };
+        assert_eq!(next(), closed);
+        connector.shutdown();
+        driver::unregister_custom("status-test");
+    }
+
+    #[test]
+    fn test_private_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        driver::register_custom("private-test", move || Box::new(AccountDriver(addr.clone())));
+        let exchange = Exchange::custom("private-test");
+        let key = SymbolKey {
+            exchange,
+            symbol: String::new(),
+            product: ProductType::Spot,
+            feed: Feed::Private(crate::broker::PrivateChannel::Orders),
+        };
+
+        let connector = ExchangeConnector::new(CoreId { id: 0 }, &TransportConfig::default());
+        let next = || connector.results().recv_timeout(Duration::from_secs(5)).unwrap();
+        let _ = connector.send_cmd(ConnectorCmd::Subscribe(key.clone(), Arc::new(SharedBook::new())));
+        assert_eq!(next(), CmdResult::Rejected(key.clone()));
+
+        auth::configure(exchange, auth::Credentials::new("key", "secret"));
+        let _ = connector.send_cmd(ConnectorCmd::Subscribe(key.clone(), Arc::new(SharedBook::new())));
+        assert_eq!(next(), CmdResult::Subscribed(key.clone()));
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(b"fill").unwrap();
+        let event = connector.private_events().recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((event.key, event.data), (key, b"fill".to_vec()));
+
+        connector.shutdown();
+        auth::reset(exchange);
+        driver::unregister_custom("private-test");
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let exchange = Exchange::custom("backpressure-test");
+        rate_limit::configure(exchange, RateLimit { per_second: 0, burst: 1 });
+        let key = |symbol: &str| SymbolKey {
+            exchange,
+            symbol: symbol.to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let subscribe = |symbol| ConnectorCmd::Subscribe(key(symbol), Arc::new(SharedBook::new()));
+
+        for (policy, dropped) in [(Backpressure::Error, "D"), (Backpressure::DropOldest, "C")] {
+            let transport = TransportConfig {
+                commands: CommandQueue { capacity: 1, policy },
+                ..Default::default()
+            };
+            let connector = ExchangeConnector::new(CoreId { id: 0 }, &transport);
+            let next = || connector.results().recv_timeout(Duration::from_secs(5)).unwrap();
+
+            // A spends the only token, B waits on the rate limit and C fills the channel
+            assert!(connector.send_cmd(subscribe("A")).is_ok());
+            assert_eq!(next(), CmdResult::Rejected(key("A")));
+            assert!(connector.send_cmd(subscribe("B")).is_ok());
+            while !connector.cmd_tx.is_empty() {
+                thread::yield_now();
+            }
+            assert!(connector.send_cmd(subscribe("C")).is_ok());
+
+            let sent = connector.send_cmd(subscribe("D"));
+            assert_eq!(sent.is_err(), policy == Backpressure::Error);
+            assert_eq!(next(), CmdResult::Dropped(key(dropped)));
+            connector.shutdown();
+        }
+        rate_limit::reset(exchange);
+    }
+
+    #[test]
+    fn test_shutdown_closes_streams() {
+        let connector = ExchangeConnector::new(CoreId { id: 0 }, &TransportConfig::default());
+        check_shutdown(connector, "shutdown-test");
+    }
+
+    #[test]
+    fn test_event_loop_shutdown() {
+        let connector = ExchangeConnector::event_loop(CoreId { id: 0 }, &TransportConfig::default()).unwrap();
+        check_shutdown(connector, "event-loop-test");
+    }
+}
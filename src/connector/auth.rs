@@ -0,0 +1,111 @@
+//! API keys for authenticated user-data streams.
+//!
+//! Keys subscribed with [Feed::Private](crate::broker::Feed::Private) log in
+//! with the credentials configured here for their exchange. Drivers sign
+//! their logins with [Credentials::sign], an HMAC-SHA256 keyed by the
+//! secret, and keep the key itself off the worker's other connections.
+
+use crate::broker::Exchange;
+use parking_lot::RwLock;
+use ring::hmac;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+
+/// Credentials set with [configure], by exchange.
+static CREDENTIALS: LazyLock<RwLock<HashMap<Exchange, Credentials>>> = LazyLock::new(Default::default);
+
+/// An API key and the secret its requests are signed with.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub api_key: String,
+    pub secret: String,
+    /// The extra login factor some venues issue with a key, e.g. OKX.
+    pub passphrase: Option<String>,
+}
+
+impl Credentials {
+    pub fn new(api_key: &str, secret: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            secret: secret.to_string(),
+            passphrase: None,
+        }
+    }
+
+    pub fn with_passphrase(mut self, passphrase: &str) -> Self {
+        self.passphrase = Some(passphrase.to_string());
+        self
+    }
+
+    /// Returns the HMAC-SHA256 of `payload` keyed by the secret.
+    pub fn sign(&self, payload: &str) -> [u8; 32] {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, self.secret.as_bytes());
+        let mut signature = [0; 32];
+        signature.copy_from_slice(hmac::sign(&key, payload.as_bytes()).as_ref());
+        signature
+    }
+}
+
+/// Shows the key only, so credentials can be logged with a config.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.api_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sets the credentials private streams on `exchange` log in with.
+///
+/// Streams already open keep their session until they reconnect.
+pub fn configure(exchange: Exchange, credentials: Credentials) {
+    CREDENTIALS.write().insert(exchange, credentials);
+}
+
+/// Forgets the credentials of `exchange`; its private keys are rejected from
+/// now on.
+pub fn reset(exchange: Exchange) {
+    CREDENTIALS.write().remove(&exchange);
+}
+
+/// Returns the credentials configured for `exchange`, if any.
+pub(crate) fn credentials_for(exchange: Exchange) -> Option<Credentials> {
+    CREDENTIALS.read().get(&exchange).cloned()
+}
+
+/// Encodes `bytes` as padded standard base64.
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = chunk.iter().enumerate().fold(0u32, |word, (i, &b)| word | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(word >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        let signature = Credentials::new("key", "Jefe").sign("what do ya want for nothing?");
+        assert_eq!(
+            signature[..8],
+            [0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e]
+        );
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert!(!format!("{:?}", Credentials::new("key", "Jefe")).contains("Jefe"));
+    }
+}
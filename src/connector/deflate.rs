@@ -0,0 +1,310 @@
+//! Client-side permessage-deflate (RFC 7692) for websocket streams.
+//!
+//! tungstenite fails the connection on any frame with RSV1 set, so
+//! [Inflate] sits between the TLS stream and the websocket and rewrites each
+//! compressed message into a single uncompressed frame before tungstenite
+//! reads it. Outgoing frames are never compressed, which the extension
+//! allows per message.
+
+use flate2::{Decompress, FlushDecompress};
+use std::io::{self, ErrorKind, Read, Write};
+
+/// Extension offer sent in `Sec-WebSocket-Extensions`.
+pub(super) const OFFER: &str = "permessage-deflate";
+
+/// Tail the sender strips from every compressed message (RFC 7692 §7.2.1).
+const TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+const FIN: u8 = 0x80;
+const RSV1: u8 = 0x40;
+const OPCODE: u8 = 0x0f;
+const CONTINUATION: u8 = 0x0;
+
+/// A stream that inflates compressed websocket messages on read.
+///
+/// Disabled streams are a plain pass-through. Enabled streams pass the HTTP
+/// upgrade response through untouched and then parse every frame: control
+/// frames and uncompressed messages are forwarded as they are, compressed
+/// messages are reassembled, inflated and re-emitted as one final frame.
+/// The LZ77 window is kept across messages, which is also correct when the
+/// server resets its own context after each one.
+pub(super) struct Inflate<S> {
+    inner: S,
+    enabled: bool,
+    /// Whether the upgrade response has been passed through.
+    upgraded: bool,
+    /// Bytes read from `inner` that do not yet form a complete frame.
+    raw: Vec<u8>,
+    /// Rewritten bytes waiting to be read, starting at `out_pos`.
+    out: Vec<u8>,
+    out_pos: usize,
+    /// Opcode of the compressed message being reassembled, if any.
+    opcode: Option<u8>,
+    /// Compressed payload of that message.
+    message: Vec<u8>,
+    inflated: Vec<u8>,
+    decompress: Decompress,
+}
+
+impl<S> Inflate<S> {
+    pub(super) fn new(inner: S, enabled: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            upgraded: false,
+            raw: Vec::new(),
+            out: Vec::new(),
+            out_pos: 0,
+            opcode: None,
+            message: Vec::new(),
+            inflated: Vec::new(),
+            decompress: Decompress::new(false),
+        }
+    }
+
+    pub(super) fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Stops parsing frames, for when the server declined the extension.
+    ///
+    /// Bytes already buffered are handed out before reads go straight to
+    /// the inner stream.
+    pub(super) fn disable(&mut self) {
+        self.out.append(&mut self.raw);
+        self.enabled = false;
+    }
+
+    /// Moves every complete unit in `raw` into `out`.
+    fn transcode(&mut self) -> io::Result<()> {
+        let mut pos = 0;
+        if !self.upgraded {
+            let Some(end) = self.raw.windows(4).position(|w| w == b"\r\n\r\n") else {
+                return Ok(());
+            };
+            self.out.extend_from_slice(&self.raw[..end + 4]);
+            self.upgraded = true;
+            pos = end + 4;
+        }
+
+        while let Some((header_len, payload_len)) = frame_len(&self.raw[pos..]) {
+            let frame = pos..pos + header_len + payload_len;
+            let payload = pos + header_len..frame.end;
+            let first = self.raw[pos];
+            let opcode = first & OPCODE;
+
+            let compressed = match opcode {
+                CONTINUATION => self.opcode.is_some(),
+                // Control frames may interleave with a fragmented message
+                0x8.. => false,
+                _ => first & RSV1 != 0,
+            };
+            if !compressed {
+                self.out.extend_from_slice(&self.raw[frame.clone()]);
+            } else {
+                if opcode != CONTINUATION {
+                    self.opcode = Some(opcode);
+                    self.message.clear();
+                }
+                self.message.extend_from_slice(&self.raw[payload]);
+                if first & FIN != 0 {
+                    self.finish_message()?;
+                }
+            }
+            pos = frame.end;
+        }
+
+        self.raw.drain(..pos);
+        Ok(())
+    }
+
+    /// Inflates the reassembled message and queues it as one final frame.
+    fn finish_message(&mut self) -> io::Result<()> {
+        let opcode = self.opcode.take().unwrap_or_default();
+        self.message.extend_from_slice(&TRAILER);
+        self.inflated.clear();
+
+        let mut input = &self.message[..];
+        loop {
+            self.inflated.reserve(input.len() * 4 + 256);
+            let total_in = self.decompress.total_in();
+            let produced = self.inflated.len();
+            self.decompress
+                .decompress_vec(input, &mut self.inflated, FlushDecompress::Sync)
+                .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+            let consumed = (self.decompress.total_in() - total_in) as usize;
+            input = &input[consumed..];
+
+            if input.is_empty() && self.inflated.len() < self.inflated.capacity() {
+                break;
+            }
+            if consumed == 0 && self.inflated.len() == produced {
+                return Err(ErrorKind::InvalidData.into());
+            }
+        }
+
+        let len = self.inflated.len();
+        self.out.push(FIN | opcode);
+        match len {
+            0..=125 => self.out.push(len as u8),
+            126..=0xffff => {
+                self.out.push(126);
+                self.out.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                self.out.push(127);
+                self.out.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        self.out.extend_from_slice(&self.inflated);
+        Ok(())
+    }
+}
+
+/// Returns the header and payload length of the frame at the start of
+/// `bytes`, or `None` if it is not complete yet.
+fn frame_len(bytes: &[u8]) -> Option<(usize, usize)> {
+    let second = *bytes.get(1)?;
+    let (mut header_len, payload_len) = match second & 0x7f {
+        126 => (4, u16::from_be_bytes(bytes.get(2..4)?.try_into().ok()?) as usize),
+        127 => (10, usize::try_from(u64::from_be_bytes(bytes.get(2..10)?.try_into().ok()?)).ok()?),
+        len => (2, len as usize),
+    };
+    if second & 0x80 != 0 {
+        header_len += 4;
+    }
+    (bytes.len() >= header_len + payload_len).then_some((header_len, payload_len))
+}
+
+impl<S: Read> Read for Inflate<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.enabled && self.out_pos == self.out.len() {
+            return self.inner.read(buf);
+        }
+
+        loop {
+            let pending = &self.out[self.out_pos..];
+            if !pending.is_empty() {
+                let n = pending.len().min(buf.len());
+                buf[..n].copy_from_slice(&pending[..n]);
+                self.out_pos += n;
+                if self.out_pos == self.out.len() {
+                    self.out.clear();
+                    self.out_pos = 0;
+                }
+                return Ok(n);
+            }
+
+            if !self.enabled {
+                return self.inner.read(buf);
+            }
+
+            // `buf` doubles as scratch space for the raw read
+            let n = self.inner.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.raw.extend_from_slice(&buf[..n]);
+            self.transcode()?;
+        }
+    }
+}
+
+impl<S: Write> Write for Inflate<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compress, Compression, FlushCompress};
+    use std::io::Cursor;
+
+    /// Compresses `text` as one permessage-deflate payload.
+    fn compress(compressor: &mut Compress, text: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(text.len() + 64);
+        compressor.compress_vec(text, &mut out, FlushCompress::Sync).unwrap();
+        assert!(out.ends_with(&TRAILER));
+        out.truncate(out.len() - TRAILER.len());
+        out
+    }
+
+    fn frame(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![first, payload.len() as u8];
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn read_all(stream: &mut Inflate<Cursor<Vec<u8>>>) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 7];
+        loop {
+            match stream.read(&mut buf).unwrap() {
+                0 => return out,
+                n => out.extend_from_slice(&buf[..n]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_inflates_compressed_messages() {
+        let mut compressor = Compress::new(Compression::default(), false);
+        let first = br#"{"arg":{"channel":"books"},"data":[]}"#;
+        let second = br#"{"arg":{"channel":"books"},"data":[1]}"#;
+        // The second message may back-reference the first
+        let first_payload = compress(&mut compressor, first);
+        let second_payload = compress(&mut compressor, second);
+        let (head, tail) = second_payload.split_at(4);
+
+        let mut wire = b"HTTP/1.1 101 Switching Protocols\r\n\r\n".to_vec();
+        wire.extend(frame(FIN | RSV1 | 0x1, &first_payload));
+        // A fragmented compressed message with a ping in between
+        wire.extend(frame(RSV1 | 0x1, head));
+        wire.extend(frame(FIN | 0x9, b"hi"));
+        wire.extend(frame(FIN | CONTINUATION, tail));
+        wire.extend(frame(FIN | 0x1, b"plain"));
+
+        let mut stream = Inflate::new(Cursor::new(wire), true);
+        let mut expected = b"HTTP/1.1 101 Switching Protocols\r\n\r\n".to_vec();
+        expected.extend(frame(FIN | 0x1, first));
+        expected.extend(frame(FIN | 0x9, b"hi"));
+        expected.extend(frame(FIN | 0x1, second));
+        expected.extend(frame(FIN | 0x1, b"plain"));
+        assert_eq!(read_all(&mut stream), expected);
+    }
+
+    #[test]
+    fn test_disabled_is_passthrough() {
+        let wire = frame(FIN | RSV1 | 0x1, b"not deflate");
+        let mut stream = Inflate::new(Cursor::new(wire.clone()), false);
+        assert_eq!(read_all(&mut stream), wire);
+    }
+
+    #[test]
+    fn test_disable_flushes_buffered_bytes() {
+        let mut wire = b"HTTP/1.1 101 Switching Protocols\r\n\r\n".to_vec();
+        wire.extend(frame(FIN | 0x1, b"first"));
+        let mut stream = Inflate::new(Cursor::new(wire.clone()), true);
+        // Leave the first frame half-parsed in `raw`
+        stream.raw.extend_from_slice(&[FIN | 0x1, 3, b'a']);
+        stream.disable();
+        let mut expected = vec![FIN | 0x1, 3, b'a'];
+        expected.extend(wire);
+        assert_eq!(read_all(&mut stream), expected);
+    }
+
+    #[test]
+    fn test_frame_len() {
+        assert_eq!(frame_len(&[0x81]), None);
+        assert_eq!(frame_len(&[0x81, 2, b'o']), None);
+        assert_eq!(frame_len(&[0x81, 2, b'o', b'k']), Some((2, 2)));
+        assert_eq!(frame_len(&[0x81, 126, 0x01, 0x00]), None);
+        assert_eq!(frame_len(&[0x81, 0x82, 0, 0, 0, 0, b'o', b'k']), Some((6, 2)));
+    }
+}
@@ -0,0 +1,120 @@
+//! The epoll-driven worker behind [ExchangeConnector::event_loop].
+//!
+//! Instead of spinning over every connection, the worker sleeps in
+//! `epoll_wait` until a socket is readable or a command arrives, then polls
+//! only the connections that woke it. Every [TICK] all connections are
+//! polled regardless, which drives reconnects, subscribe batches,
+//! keepalives and rate-limited commands.
+
+use super::{Connection, ConnectorCmd, CrossedPolicy, Dialer, Reports, ExchangeConnector, RateLimiter, Stream};
+use crossbeam_channel::{Receiver, TryRecvError};
+use mio::unix::SourceFd;
+use mio::{Events, Interest, Poll, Token};
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tungstenite::stream::MaybeTlsStream;
+
+/// Token of the waker that signals new commands.
+pub(super) const WAKE: Token = Token(usize::MAX);
+
+/// Interval of the housekeeping pass over every connection.
+pub(super) const TICK: Duration = Duration::from_millis(100);
+
+/// Runs the worker until shut down or every sender is gone.
+///
+/// Like the spinning worker, it holds at most `capacity` queued commands.
+pub(super) fn run(
+    rx: Receiver<ConnectorCmd>,
+    capacity: usize,
+    stopping: &AtomicBool,
+    dialer: Arc<Dialer>,
+    reports: Reports,
+    crossed: CrossedPolicy,
+    mut poll: Poll,
+) {
+    let mut connections: Vec<Connection> = Vec::new();
+    let mut queued: VecDeque<ConnectorCmd> = VecDeque::new();
+    let mut limiter = RateLimiter::default();
+    let mut events = Events::with_capacity(1024);
+    // The connect count each connection's socket was registered at
+    let mut registered: HashMap<usize, u64> = HashMap::new();
+    let mut next_tick = Instant::now();
+
+    'run: loop {
+        // Ticking while idle too notices a dropped connector
+        let timeout = next_tick.saturating_duration_since(Instant::now());
+        if let Err(err) = poll.poll(&mut events, Some(timeout))
+            && err.kind() != ErrorKind::Interrupted
+        {
+            break;
+        }
+
+        if stopping.load(Ordering::Relaxed) {
+            break;
+        }
+        while queued.len() < capacity {
+            match rx.try_recv() {
+                Ok(ConnectorCmd::Shutdown) | Err(TryRecvError::Disconnected) => break 'run,
+                Ok(cmd) => queued.push_back(cmd),
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        let now = Instant::now();
+        let tick = now >= next_tick;
+        if tick {
+            next_tick = now + TICK;
+        }
+        if !queued.is_empty() && (tick || events.iter().any(|event| event.token() == WAKE)) {
+            ExchangeConnector::handle_queued(&mut connections, &mut queued, &mut limiter, &dialer, &reports, crossed);
+            registered.retain(|id, _| connections.iter().any(|c| c.id == *id));
+        }
+
+        for connection in connections.iter_mut() {
+            if tick || events.iter().any(|event| event.token() == Token(connection.id)) {
+                connection.poll(&mut limiter);
+            }
+            if connection.socket.is_some() && registered.get(&connection.id) != Some(&connection.connects) {
+                register(&poll, connection);
+                registered.insert(connection.id, connection.connects);
+                // Frames read ahead during the handshake raise no event
+                connection.poll(&mut limiter);
+            }
+        }
+    }
+
+    super::close_all(connections);
+}
+
+/// Registers a connection's new socket for read events.
+///
+/// Closing a socket removes it from epoll, so replaced sockets need no
+/// deregistration.
+fn register(poll: &Poll, connection: &Connection) {
+    let Some(socket) = &connection.socket else {
+        return;
+    };
+    for fd in raw_fds(socket) {
+        // A socket that cannot be registered is still polled every tick
+        let _ = poll
+            .registry()
+            .register(&mut SourceFd(&fd), Token(connection.id), Interest::READABLE);
+    }
+}
+
+/// Returns the file descriptors a stream reads from.
+fn raw_fds(stream: &Stream) -> Vec<RawFd> {
+    match stream {
+        Stream::WebSocket(socket) => match socket.get_ref().get_ref() {
+            MaybeTlsStream::Plain(stream) => vec![stream.as_raw_fd()],
+            MaybeTlsStream::Rustls(stream) => vec![stream.get_ref().as_raw_fd()],
+            _ => Vec::new(),
+        },
+        Stream::Tcp(raw) => vec![raw.stream.as_raw_fd()],
+        Stream::Udp(receiver) => receiver.sockets().map(|socket| socket.as_raw_fd()).collect(),
+    }
+}
@@ -0,0 +1,174 @@
+//! Backup endpoints for venues with mirrors or regional gateways.
+//!
+//! A connection starts on its driver's endpoint. After [FAILOVER_AFTER]
+//! consecutive failures, whether the connect itself failed or the stream
+//! dropped before delivering anything, it moves on to the next backup, and
+//! wraps around to the primary after the last one.
+//!
+//! Exchanges set to [race] instead connect to whichever endpoint completes
+//! its handshake fastest, and re-measure them every so often in case the
+//! network has changed.
+
+use crate::broker::Exchange;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Backup endpoints by primary endpoint.
+type Backups = HashMap<String, Vec<String>>;
+
+/// Backups set with [configure], by exchange.
+static BACKUPS: LazyLock<RwLock<HashMap<Exchange, Backups>>> = LazyLock::new(Default::default);
+
+/// Race intervals set with [race], by exchange.
+static RACING: LazyLock<RwLock<HashMap<Exchange, Duration>>> = LazyLock::new(Default::default);
+
+/// Consecutive failures on one endpoint before trying the next.
+pub const FAILOVER_AFTER: u32 = 3;
+
+/// A challenger must handshake in under this share of the current
+/// endpoint's time to replace it, so near ties do not cause reconnects.
+pub const SWITCH_RATIO: f64 = 0.8;
+
+/// Registers `backups` to try, in order, when `primary` keeps failing.
+///
+/// Replaces the backups the driver suggests for `primary`, if any. An
+/// exchange can have backups for each of its endpoints, e.g. one list for
+/// spot and one for futures.
+pub fn configure(exchange: Exchange, primary: &str, backups: &[&str]) {
+    BACKUPS
+        .write()
+        .entry(exchange)
+        .or_default()
+        .insert(primary.to_string(), backups.iter().map(|b| b.to_string()).collect());
+}
+
+/// Connects `exchange` to whichever of its endpoints handshakes fastest,
+/// re-measuring every `interval`.
+///
+/// Each measurement opens one connection per endpoint from the worker
+/// thread, which stalls its other streams for as long, so `interval` should
+/// be minutes rather than seconds.
+pub fn race(exchange: Exchange, interval: Duration) {
+    RACING.write().insert(exchange, interval);
+}
+
+/// Returns every `exchange` endpoint to its driver's backups, and stops
+/// racing them.
+pub fn reset(exchange: Exchange) {
+    BACKUPS.write().remove(&exchange);
+    RACING.write().remove(&exchange);
+}
+
+/// Returns how often `exchange` re-measures its endpoints, if it races them.
+pub(super) fn race_interval(exchange: Exchange) -> Option<Duration> {
+    RACING.read().get(&exchange).copied()
+}
+
+/// Picks the endpoint to use given each one's handshake time, `None` where
+/// it failed.
+///
+/// The fastest wins outright, unless `current` still works and the fastest
+/// is not [SWITCH_RATIO] faster than it.
+pub(super) fn fastest(latencies: &[Option<Duration>], current: Option<usize>) -> Option<usize> {
+    let (index, best) = latencies
+        .iter()
+        .enumerate()
+        .filter_map(|(index, latency)| Some((index, (*latency)?)))
+        .min_by_key(|(_, latency)| *latency)?;
+    match current.and_then(|current| Some((current, latencies.get(current).copied()??))) {
+        Some((current, latency)) if best >= latency.mul_f64(SWITCH_RATIO) => Some(current),
+        _ => Some(index),
+    }
+}
+
+/// Returns the backups configured for `primary`, if any.
+pub(super) fn backups_for(exchange: Exchange, primary: &str) -> Option<Vec<String>> {
+    BACKUPS.read().get(&exchange)?.get(primary).cloned()
+}
+
+/// Which of a connection's endpoints is in use, and how it has been doing.
+#[derive(Default)]
+pub(super) struct Rotation {
+    index: usize,
+    failures: u32,
+}
+
+impl Rotation {
+    /// Returns the endpoint to connect to out of the primary and its backups.
+    pub(super) fn pick<'a>(&self, endpoints: &'a [String]) -> &'a str {
+        &endpoints[self.index % endpoints.len()]
+    }
+
+    /// Returns the position of the endpoint in use out of `len`.
+    pub(super) fn index(&self, len: usize) -> usize {
+        self.index % len
+    }
+
+    /// Moves to the endpoint at `index`, as picked by a race.
+    pub(super) fn settle(&mut self, index: usize) {
+        self.index = index;
+        self.failures = 0;
+    }
+
+    /// Records a failure, moving to the next endpoint once there are enough.
+    pub(super) fn failed(&mut self) {
+        self.failures += 1;
+        if self.failures >= FAILOVER_AFTER {
+            self.index = self.index.wrapping_add(1);
+            self.failures = 0;
+        }
+    }
+
+    /// Records that the current endpoint delivered data.
+    pub(super) fn succeeded(&mut self) {
+        self.failures = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotates_after_repeated_failures() {
+        let endpoints = ["wss://primary".to_string(), "wss://backup".to_string()];
+        let mut rotation = Rotation::default();
+
+        rotation.failed();
+        rotation.failed();
+        rotation.succeeded();
+        rotation.failed();
+        rotation.failed();
+        assert_eq!(rotation.pick(&endpoints), "wss://primary");
+
+        rotation.failed();
+        assert_eq!(rotation.pick(&endpoints), "wss://backup");
+
+        for _ in 0..FAILOVER_AFTER {
+            rotation.failed();
+        }
+        assert_eq!(rotation.pick(&endpoints), "wss://primary");
+    }
+
+    #[test]
+    fn test_fastest() {
+        let ms = |ms| Some(Duration::from_millis(ms));
+        assert_eq!(fastest(&[ms(30), None, ms(10)], None), Some(2));
+        assert_eq!(fastest(&[None, None], None), None);
+        // Near ties keep the current endpoint; clear wins and failures move
+        assert_eq!(fastest(&[ms(10), ms(9)], Some(0)), Some(0));
+        assert_eq!(fastest(&[ms(10), ms(5)], Some(0)), Some(1));
+        assert_eq!(fastest(&[None, ms(50)], Some(0)), Some(1));
+    }
+
+    #[test]
+    fn test_configured_backups() {
+        configure(Exchange::Dydx, "wss://primary", &["wss://mirror"]);
+        assert_eq!(backups_for(Exchange::Dydx, "wss://primary"), Some(vec!["wss://mirror".to_string()]));
+        assert_eq!(backups_for(Exchange::Dydx, "wss://other"), None);
+        reset(Exchange::Dydx);
+        assert_eq!(backups_for(Exchange::Dydx, "wss://primary"), None);
+    }
+}
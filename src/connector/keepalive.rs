@@ -0,0 +1,149 @@
+//! Per-venue keepalive rules and the tracker the connector drives with them.
+//!
+//! Each driver describes its venue's rules as a [Keepalive]: whether the
+//! client has to ping, with what, and how long the stream may stay silent
+//! before it is considered dead. The connector checks the policy whenever a
+//! connection has nothing to read, sends the pings, and drops expired streams,
+//! which marks their books stale until the reconnect has rebuilt them.
+
+use crate::broker::Exchange;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// Policies set with [configure], overriding the drivers' own.
+static OVERRIDES: LazyLock<RwLock<HashMap<Exchange, Keepalive>>> = LazyLock::new(Default::default);
+
+/// How a stream is kept alive and when it is considered dead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keepalive {
+    /// How often to send `ping`, or `None` if the venue pings the client or
+    /// the driver answers its heartbeats itself.
+    pub ping_interval: Option<Duration>,
+    pub ping: Ping,
+    /// How long the stream may go without receiving anything, pongs
+    /// included, before it is dropped; `None` never expires it.
+    pub timeout: Option<Duration>,
+}
+
+/// What the connector sends as a keepalive ping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ping {
+    /// A websocket ping control frame.
+    Frame,
+    /// An application-level text frame, for venues that ignore control frames.
+    Text(&'static str),
+}
+
+impl Keepalive {
+    /// Websocket ping frames every 30 seconds, dead after 90 seconds of silence.
+    pub const WEBSOCKET: Keepalive = Keepalive {
+        ping_interval: Some(Duration::from_secs(30)),
+        ping: Ping::Frame,
+        timeout: Some(Duration::from_secs(90)),
+    };
+
+    /// No pings and no timeout.
+    pub const NONE: Keepalive = Keepalive {
+        ping_interval: None,
+        ping: Ping::Frame,
+        timeout: None,
+    };
+}
+
+/// Overrides the keepalive policy of every future `exchange` connection.
+pub fn configure(exchange: Exchange, keepalive: Keepalive) {
+    OVERRIDES.write().insert(exchange, keepalive);
+}
+
+/// Returns `exchange` connections to their driver's keepalive policy.
+pub fn reset(exchange: Exchange) {
+    OVERRIDES.write().remove(&exchange);
+}
+
+/// Returns the policy for an `exchange` connection whose driver asks for `default`.
+pub(super) fn policy_for(exchange: Exchange, default: Keepalive) -> Keepalive {
+    OVERRIDES.read().get(&exchange).copied().unwrap_or(default)
+}
+
+/// What the connector should do for a connection right now.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Action {
+    Idle,
+    Ping(Ping),
+    /// Nothing was received within the timeout.
+    Expired,
+}
+
+/// Tracks one connection's traffic against its [Keepalive].
+pub(super) struct Tracker {
+    policy: Keepalive,
+    last_received: Instant,
+    last_ping: Instant,
+}
+
+impl Tracker {
+    pub(super) fn new(policy: Keepalive, now: Instant) -> Self {
+        Self {
+            policy,
+            last_received: now,
+            last_ping: now,
+        }
+    }
+
+    /// Decides the next action at `now`.
+    ///
+    /// `received` reports whether anything arrived since the last call, so
+    /// the clock is only read when the connection is idle, not per frame.
+    pub(super) fn poll(&mut self, now: Instant, received: bool) -> Action {
+        if received {
+            self.last_received = now;
+        }
+        if self
+            .policy
+            .timeout
+            .is_some_and(|timeout| now.duration_since(self.last_received) >= timeout)
+        {
+            return Action::Expired;
+        }
+        match self.policy.ping_interval {
+            Some(interval) if now.duration_since(self.last_ping) >= interval => {
+                self.last_ping = now;
+                Action::Ping(self.policy.ping)
+            }
+            _ => Action::Idle,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pings_then_expires() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(Keepalive::WEBSOCKET, start);
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(tracker.poll(at(10), true), Action::Idle);
+        assert_eq!(tracker.poll(at(30), false), Action::Ping(Ping::Frame));
+        assert_eq!(tracker.poll(at(31), false), Action::Idle);
+        // Silence is measured from the last received frame
+        assert_eq!(tracker.poll(at(99), false), Action::Ping(Ping::Frame));
+        assert_eq!(tracker.poll(at(100), false), Action::Expired);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let quiet = Keepalive {
+            timeout: Some(Duration::from_secs(600)),
+            ..Keepalive::WEBSOCKET
+        };
+        configure(Exchange::Gate, quiet);
+        assert_eq!(policy_for(Exchange::Gate, Keepalive::NONE), quiet);
+        reset(Exchange::Gate);
+        assert_eq!(policy_for(Exchange::Gate, Keepalive::NONE), Keepalive::NONE);
+    }
+}
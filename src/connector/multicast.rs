@@ -0,0 +1,302 @@
+//! Multicast feeds with A/B line arbitration.
+//!
+//! A multicast endpoint lists its feeds separated by commas, each feed
+//! being one or more redundant lines separated by `|`, followed by an
+//! optional `@interface`:
+//!
+//! ```text
+//! 224.0.31.1:14310|224.0.32.1:15310,224.0.31.43:6310@10.0.0.5
+//! ```
+//!
+//! Lines of a feed carry the same packets. The receiver delivers each
+//! sequence number once, from whichever line has it first, using
+//! [ExchangeDriver::packet_sequence](crate::driver::ExchangeDriver::packet_sequence).
+//! A packet that skips ahead is held for up to [GAP_TIMEOUT] in case the
+//! other line fills the hole; if it does not, the gap is reported as
+//! [DriverError::SequenceGap] and the connector resyncs. Single-line feeds
+//! are passed through untouched, leaving sequencing to the driver.
+//!
+//! With the `af-xdp` feature, lines are read from the worker's
+//! [XDP socket](super::xdp) when it has one; the kernel sockets then only
+//! keep the group membership.
+
+use crate::driver::DriverError;
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// How long packets past a hole wait for another line to fill it.
+pub const GAP_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// Packets held past a hole before it is declared a gap regardless.
+const MAX_HELD: usize = 1024;
+
+/// The joined feeds of one connection.
+pub(super) struct Receiver {
+    feeds: Vec<Feed>,
+    /// Holds the packet last released by an arbiter while it is dispatched.
+    released: Vec<u8>,
+}
+
+struct Feed {
+    lines: Vec<Line>,
+    arbiter: Arbiter,
+}
+
+/// One joined multicast group.
+struct Line {
+    socket: UdpSocket,
+    #[cfg(all(feature = "af-xdp", target_os = "linux"))]
+    tap: Option<super::xdp::Tap>,
+}
+
+impl Line {
+    fn join(group: SocketAddrV4, interface: Ipv4Addr) -> io::Result<Self> {
+        Ok(Self {
+            socket: join_group(group, interface)?,
+            #[cfg(all(feature = "af-xdp", target_os = "linux"))]
+            tap: super::xdp::tap(group),
+        })
+    }
+
+    /// Receives the next packet, into `buf` unless read in place.
+    fn recv<'a>(&'a mut self, buf: &'a mut [u8]) -> io::Result<&'a [u8]> {
+        #[cfg(all(feature = "af-xdp", target_os = "linux"))]
+        if let Some(tap) = &mut self.tap {
+            return tap.recv().ok_or_else(|| ErrorKind::WouldBlock.into());
+        }
+        let len = self.socket.recv(buf)?;
+        Ok(&buf[..len])
+    }
+
+    /// The packet last received, of length `len` if it went into `buf`.
+    fn last<'a>(&'a self, buf: &'a [u8], len: usize) -> &'a [u8] {
+        #[cfg(all(feature = "af-xdp", target_os = "linux"))]
+        if let Some(tap) = &self.tap {
+            return tap.last();
+        }
+        &buf[..len]
+    }
+}
+
+impl Receiver {
+    /// Joins every line of every feed in `endpoint`.
+    ///
+    /// Sockets share their port so several connections can listen to one feed.
+    pub(super) fn join(endpoint: &str) -> io::Result<Self> {
+        let invalid = || io::Error::from(ErrorKind::InvalidInput);
+        let (feeds, interface) = match endpoint.split_once('@') {
+            Some((feeds, interface)) => (feeds, interface.parse().map_err(|_| invalid())?),
+            None => (endpoint, Ipv4Addr::UNSPECIFIED),
+        };
+
+        let mut joined = Vec::new();
+        for feed in feeds.split(',') {
+            let mut lines = Vec::new();
+            for line in feed.split('|') {
+                let group: SocketAddrV4 = line.parse().map_err(|_| invalid())?;
+                lines.push(Line::join(group, interface)?);
+            }
+            joined.push(Feed {
+                lines,
+                arbiter: Arbiter::default(),
+            });
+        }
+        Ok(Self {
+            feeds: joined,
+            released: Vec::new(),
+        })
+    }
+
+    /// Every socket of every line.
+    pub(super) fn sockets(&self) -> impl Iterator<Item = &UdpSocket> {
+        self.feeds.iter().flat_map(|feed| feed.lines.iter().map(|line| &line.socket))
+    }
+
+    /// Returns the next packet to apply, or `None` if nothing is pending.
+    ///
+    /// Packets are received into `buf`. `sequence` extracts a packet's first
+    /// sequence number and how many it spans.
+    pub(super) fn next<'a>(
+        &'a mut self,
+        buf: &'a mut [u8],
+        sequence: impl Fn(&[u8]) -> Option<(u64, u64)>,
+        now: Instant,
+    ) -> Option<Result<&'a [u8], DriverError>> {
+        for feed in &mut self.feeds {
+            match feed.arbiter.release(now, &mut self.released) {
+                Some(Ok(())) => return Some(Ok(&self.released)),
+                Some(Err(gap)) => return Some(Err(gap)),
+                None => {}
+            }
+        }
+
+        // Found by position, as the packet may live in the line itself
+        let mut received = None;
+        'feeds: for (f, feed) in self.feeds.iter_mut().enumerate() {
+            let redundant = feed.lines.len() > 1;
+            for (l, line) in feed.lines.iter_mut().enumerate() {
+                loop {
+                    let packet = match line.recv(buf) {
+                        Ok(packet) => packet,
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                        Err(_) => return Some(Err(DriverError::Malformed)),
+                    };
+                    let verdict = match sequence(packet) {
+                        Some((seq, count)) if redundant => feed.arbiter.offer(seq, count, packet, now),
+                        _ => Verdict::Deliver,
+                    };
+                    if verdict == Verdict::Deliver {
+                        received = Some((f, l, packet.len()));
+                        break 'feeds;
+                    }
+                }
+            }
+        }
+        let (f, l, len) = received?;
+        Some(Ok(self.feeds[f].lines[l].last(buf, len)))
+    }
+}
+
+/// Opens a non-blocking socket joined to `group` on `interface`.
+fn join_group(group: SocketAddrV4, interface: Ipv4Addr) -> io::Result<UdpSocket> {
+    let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::DGRAM, Some(socket2::Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, group.port()).into())?;
+    socket.join_multicast_v4(group.ip(), &interface)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+/// What to do with a packet offered to an [Arbiter].
+#[derive(Debug, PartialEq, Eq)]
+enum Verdict {
+    /// Next in sequence: apply it now.
+    Deliver,
+    /// Already delivered from another line.
+    Duplicate,
+    /// Ahead of the sequence; kept until the hole is filled or times out.
+    Held,
+}
+
+/// Orders the packets of one feed's lines by sequence number.
+#[derive(Default)]
+struct Arbiter {
+    /// The next sequence number to deliver, once the first packet is seen.
+    next: Option<u64>,
+    /// Packets ahead of `next` by first sequence number, with their span.
+    held: BTreeMap<u64, (u64, Vec<u8>)>,
+    /// When the oldest held packet arrived.
+    waiting_since: Option<Instant>,
+}
+
+impl Arbiter {
+    /// Decides what to do with a packet spanning `count` numbers from `seq`.
+    ///
+    /// Held packets are copied; delivered ones are left to the caller.
+    fn offer(&mut self, seq: u64, count: u64, packet: &[u8], now: Instant) -> Verdict {
+        let Some(next) = self.next else {
+            self.next = Some(seq + count);
+            return Verdict::Deliver;
+        };
+        if seq < next || (seq == next && count == 0) {
+            // Heartbeats repeat the next number without advancing it
+            return Verdict::Duplicate;
+        }
+        if seq == next {
+            self.next = Some(seq + count);
+            return Verdict::Deliver;
+        }
+        if self.held.len() < MAX_HELD {
+            self.held.entry(seq).or_insert_with(|| (count, packet.to_vec()));
+        }
+        self.waiting_since.get_or_insert(now);
+        Verdict::Held
+    }
+
+    /// Moves the next held packet into `out` once the sequence reaches it.
+    ///
+    /// Reports a gap once packets have waited [GAP_TIMEOUT] for a hole no
+    /// line filled, and starts over from the next packet received.
+    fn release(&mut self, now: Instant, out: &mut Vec<u8>) -> Option<Result<(), DriverError>> {
+        let next = self.next?;
+        while let Some(entry) = self.held.first_entry() {
+            let seq = *entry.key();
+            if seq > next {
+                let waited = self.waiting_since.is_some_and(|since| now - since >= GAP_TIMEOUT);
+                if !waited && self.held.len() < MAX_HELD {
+                    return None;
+                }
+                self.reset();
+                return Some(Err(DriverError::SequenceGap {
+                    expected: next,
+                    received: seq,
+                }));
+            }
+
+            let (count, packet) = entry.remove();
+            if seq == next && count > 0 {
+                self.next = Some(seq + count);
+                *out = packet;
+                if self.held.is_empty() {
+                    self.waiting_since = None;
+                }
+                return Some(Ok(()));
+            }
+        }
+        self.waiting_since = None;
+        None
+    }
+
+    fn reset(&mut self) {
+        self.next = None;
+        self.held.clear();
+        self.waiting_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arbitrates_lines() {
+        let mut arbiter = Arbiter::default();
+        let mut out = Vec::new();
+        let now = Instant::now();
+
+        assert_eq!(arbiter.offer(10, 2, b"A10", now), Verdict::Deliver);
+        assert_eq!(arbiter.offer(10, 2, b"B10", now), Verdict::Duplicate);
+        assert_eq!(arbiter.offer(12, 0, b"heartbeat", now), Verdict::Duplicate);
+
+        // Line A loses 12; line B fills the hole before the timeout
+        assert_eq!(arbiter.offer(13, 1, b"A13", now), Verdict::Held);
+        assert!(arbiter.release(now, &mut out).is_none());
+        assert_eq!(arbiter.offer(12, 1, b"B12", now), Verdict::Deliver);
+        assert_eq!(arbiter.release(now, &mut out), Some(Ok(())));
+        assert_eq!(out, b"A13");
+        assert_eq!(arbiter.offer(13, 1, b"B13", now), Verdict::Duplicate);
+        assert!(arbiter.release(now, &mut out).is_none());
+    }
+
+    #[test]
+    fn test_reports_unfilled_gaps() {
+        let mut arbiter = Arbiter::default();
+        let mut out = Vec::new();
+        let now = Instant::now();
+
+        arbiter.offer(1, 1, b"1", now);
+        assert_eq!(arbiter.offer(5, 1, b"5", now), Verdict::Held);
+        assert!(arbiter.release(now + GAP_TIMEOUT / 2, &mut out).is_none());
+        assert_eq!(
+            arbiter.release(now + GAP_TIMEOUT, &mut out),
+            Some(Err(DriverError::SequenceGap { expected: 2, received: 5 }))
+        );
+
+        // The next packet starts a fresh sequence
+        assert_eq!(arbiter.offer(7, 1, b"7", now), Verdict::Deliver);
+    }
+}
@@ -0,0 +1,349 @@
+//! SOCKS5 and HTTP `CONNECT` proxies for venue traffic.
+//!
+//! A connector configured with a [Proxy] opens its websockets, raw TCP
+//! streams and REST snapshot calls through it. Multicast feeds are joined
+//! directly. Host names are always resolved by the proxy.
+
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use ureq::unversioned::resolver::DefaultResolver;
+use ureq::unversioned::transport::{
+    Buffers, ConnectionDetails, Connector, LazyBuffers, NextTimeout, RustlsConnector, TcpConnector, Transport,
+};
+use ureq::{Agent, ProxyProtocol};
+
+/// Longest HTTP `CONNECT` response header accepted.
+const MAX_RESPONSE: usize = 8 * 1024;
+
+/// A proxy between the connector and the venues.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    /// `host:port` of the proxy.
+    pub addr: String,
+    /// Username and password, if the proxy requires them.
+    pub credentials: Option<(String, String)>,
+}
+
+/// The protocol a [Proxy] speaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyKind {
+    Socks5,
+    /// An HTTP proxy tunnelling with `CONNECT`.
+    HttpConnect,
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("kind", &self.kind)
+            .field("addr", &self.addr)
+            .field("user", &self.credentials.as_ref().map(|(user, _)| user))
+            .finish()
+    }
+}
+
+impl Proxy {
+    /// Opens a TCP tunnel to `host:port` through the proxy.
+    pub fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.addr)?;
+        stream.set_nodelay(true)?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        match self.kind {
+            ProxyKind::Socks5 => self.socks5(&mut stream, host, port)?,
+            ProxyKind::HttpConnect => self.http_connect(&mut stream, host, port)?,
+        }
+        Ok(stream)
+    }
+
+    /// Runs the SOCKS5 greeting, optional username/password authentication
+    /// (RFC 1929) and `CONNECT` request.
+    fn socks5(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        let greeting: &[u8] = match self.credentials {
+            Some(_) => &[5, 2, 0x00, 0x02],
+            None => &[5, 1, 0x00],
+        };
+        stream.write_all(greeting)?;
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice)?;
+        match (choice, &self.credentials) {
+            ([5, 0x00], _) => {}
+            ([5, 0x02], Some((user, password))) => {
+                let (user, password) = (user.as_bytes(), password.as_bytes());
+                let (Ok(user_len), Ok(password_len)) = (u8::try_from(user.len()), u8::try_from(password.len()))
+                else {
+                    return Err(io::Error::new(ErrorKind::InvalidInput, "SOCKS5 credentials too long"));
+                };
+                let mut auth = vec![1, user_len];
+                auth.extend_from_slice(user);
+                auth.push(password_len);
+                auth.extend_from_slice(password);
+                stream.write_all(&auth)?;
+                let mut status = [0u8; 2];
+                stream.read_exact(&mut status)?;
+                if status[1] != 0 {
+                    return Err(io::Error::new(ErrorKind::PermissionDenied, "SOCKS5 authentication failed"));
+                }
+            }
+            _ => return Err(io::Error::new(ErrorKind::PermissionDenied, "no acceptable SOCKS5 auth method")),
+        }
+
+        let mut request = vec![5, 1, 0];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(1);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                let len = u8::try_from(host.len())
+                    .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "host name too long for SOCKS5"))?;
+                request.extend_from_slice(&[3, len]);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request)?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply)?;
+        if reply[1] != 0 {
+            return Err(io::Error::new(
+                ErrorKind::ConnectionRefused,
+                format!("SOCKS5 connect failed with reply {}", reply[1]),
+            ));
+        }
+        // Skip the bound address and port
+        let bound = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len)?;
+                len[0] as usize
+            }
+            _ => return Err(ErrorKind::InvalidData.into()),
+        };
+        let mut skip = vec![0u8; bound + 2];
+        stream.read_exact(&mut skip)
+    }
+
+    /// Sends `CONNECT` and checks for a 2xx response.
+    ///
+    /// The response is read a byte at a time so nothing the venue sends
+    /// after the header is consumed.
+    fn http_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        let authority = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => format!("[{host}]:{port}"),
+            _ => format!("{host}:{port}"),
+        };
+        let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+        if let Some((user, password)) = &self.credentials {
+            let token = base64(format!("{user}:{password}").as_bytes());
+            request.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() == MAX_RESPONSE {
+                return Err(io::Error::new(ErrorKind::InvalidData, "CONNECT response too long"));
+            }
+            stream.read_exact(&mut byte)?;
+            response.push(byte[0]);
+        }
+
+        let status = response.split(|&b| b == b' ').nth(1).unwrap_or_default();
+        if !status.starts_with(b"2") {
+            let line = response.split(|&b| b == b'\r').next().unwrap_or_default();
+            return Err(io::Error::new(
+                ErrorKind::ConnectionRefused,
+                format!("proxy refused CONNECT: {}", String::from_utf8_lossy(line)),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Builds a REST agent whose connections are tunnelled through the proxy.
+    pub(crate) fn rest_agent(&self) -> Agent {
+        // Only marks the agent as proxied, so ureq leaves name resolution to
+        // the proxy; the tunnel itself is opened by `Tunnel`.
+        let marker = ureq::Proxy::builder(ProxyProtocol::Socks5h)
+            .host("proxy")
+            .resolve_target(false)
+            .build()
+            .ok();
+        let config = Agent::config_builder().proxy(marker).build();
+        let connector = Tunnel(self.clone())
+            .chain(TcpConnector::default())
+            .chain(RustlsConnector::default());
+        Agent::with_parts(config, connector, DefaultResolver::default())
+    }
+}
+
+/// ureq connector that opens every connection through a [Proxy].
+#[derive(Debug)]
+struct Tunnel(Proxy);
+
+impl Connector<()> for Tunnel {
+    type Out = TunnelTransport;
+
+    fn connect(&self, details: &ConnectionDetails, _chained: Option<()>) -> Result<Option<TunnelTransport>, ureq::Error> {
+        let uri = details.uri;
+        let host = uri.host().ok_or(ureq::Error::HostNotFound)?;
+        let port = uri
+            .port_u16()
+            .unwrap_or(if uri.scheme_str() == Some("http") { 80 } else { 443 });
+        let stream = self.0.connect(host, port)?;
+        let config = details.config;
+        let buffers = LazyBuffers::new(config.input_buffer_size(), config.output_buffer_size());
+        Ok(Some(TunnelTransport { stream, buffers }))
+    }
+}
+
+/// A tunnelled stream carrying ureq's plain or TLS traffic.
+#[derive(Debug)]
+struct TunnelTransport {
+    stream: TcpStream,
+    buffers: LazyBuffers,
+}
+
+impl TunnelTransport {
+    /// Maps a socket timeout onto the ureq timeout that was due.
+    fn timed_out(err: io::Error, timeout: &NextTimeout) -> ureq::Error {
+        match err.kind() {
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => ureq::Error::Timeout(timeout.reason),
+            _ => err.into(),
+        }
+    }
+}
+
+impl Transport for TunnelTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        &mut self.buffers
+    }
+
+    fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), ureq::Error> {
+        self.stream.set_write_timeout(timeout.not_zero().map(|t| *t))?;
+        self.stream
+            .write_all(&self.buffers.output()[..amount])
+            .map_err(|err| Self::timed_out(err, &timeout))
+    }
+
+    fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, ureq::Error> {
+        self.stream.set_read_timeout(timeout.not_zero().map(|t| *t))?;
+        let amount = self
+            .stream
+            .read(self.buffers.input_append_buf())
+            .map_err(|err| Self::timed_out(err, &timeout))?;
+        self.buffers.input_appended(amount);
+        Ok(amount > 0)
+    }
+
+    fn is_open(&mut self) -> bool {
+        // A reused connection must have nothing left to read
+        let mut byte = [0u8; 1];
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let idle = matches!(self.stream.read(&mut byte), Err(err) if err.kind() == ErrorKind::WouldBlock);
+        self.stream.set_nonblocking(false).is_ok() && idle
+    }
+}
+
+/// Standard base64 with padding, for `Proxy-Authorization`.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"user:pass"), "dXNlcjpwYXNz");
+    }
+
+    /// Accepts one connection, checks what the client sends and replies.
+    fn fake_proxy(exchanges: Vec<(Vec<u8>, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for (expected, reply) in exchanges {
+                let mut received = vec![0u8; expected.len()];
+                stream.read_exact(&mut received).unwrap();
+                assert_eq!(received, expected);
+                stream.write_all(&reply).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_socks5_with_credentials() {
+        let addr = fake_proxy(vec![
+            (vec![5, 2, 0, 2], vec![5, 2]),
+            (b"\x01\x04user\x04pass".to_vec(), vec![1, 0]),
+            (
+                b"\x05\x01\x00\x03\x0bexample.com\x01\xbb".to_vec(),
+                vec![5, 0, 0, 1, 10, 0, 0, 1, 0x1f, 0x90, b'!'],
+            ),
+        ]);
+        let proxy = Proxy {
+            kind: ProxyKind::Socks5,
+            addr,
+            credentials: Some(("user".to_string(), "pass".to_string())),
+        };
+        let mut stream = proxy.connect("example.com", 443).unwrap();
+        let mut first = [0u8; 1];
+        stream.read_exact(&mut first).unwrap();
+        assert_eq!(&first, b"!");
+    }
+
+    #[test]
+    fn test_http_connect() {
+        let request = b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n".to_vec();
+        let addr = fake_proxy(vec![(request, b"HTTP/1.1 200 Connection established\r\n\r\n!".to_vec())]);
+        let proxy = Proxy {
+            kind: ProxyKind::HttpConnect,
+            addr,
+            credentials: None,
+        };
+        let mut stream = proxy.connect("example.com", 443).unwrap();
+        let mut first = [0u8; 1];
+        stream.read_exact(&mut first).unwrap();
+        assert_eq!(&first, b"!");
+
+        let request = b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n".to_vec();
+        let addr = fake_proxy(vec![(request, b"HTTP/1.1 403 Forbidden\r\n\r\n".to_vec())]);
+        let proxy = Proxy { addr, ..proxy };
+        let err = proxy.connect("example.com", 443).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionRefused);
+    }
+}
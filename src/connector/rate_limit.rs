@@ -0,0 +1,122 @@
+//! Per-exchange token buckets for subscription traffic.
+//!
+//! Venues ban clients that send too many subscribe requests or open too
+//! many connections in a short time. Every subscribe or unsubscribe command
+//! and every reconnect spends one token from its exchange's bucket; commands
+//! that find the bucket empty wait in the connector's queue until it refills.
+
+use crate::broker::Exchange;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Instant;
+
+/// Limits set with [configure], overriding the drivers' own.
+static OVERRIDES: LazyLock<RwLock<HashMap<Exchange, RateLimit>>> = LazyLock::new(Default::default);
+
+/// A token bucket: up to `burst` frames at once, refilled at `per_second`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub per_second: u32,
+    pub burst: u32,
+}
+
+/// Overrides the rate limit of `exchange`.
+///
+/// Each connector reads an exchange's limit the first time it subscribes to
+/// it, so this should be called before subscribing.
+pub fn configure(exchange: Exchange, limit: RateLimit) {
+    OVERRIDES.write().insert(exchange, limit);
+}
+
+/// Returns `exchange` to its driver's rate limit.
+pub fn reset(exchange: Exchange) {
+    OVERRIDES.write().remove(&exchange);
+}
+
+/// The buckets of one connector, created lazily per exchange.
+#[derive(Default)]
+pub(super) struct RateLimiter {
+    buckets: HashMap<Exchange, Option<Bucket>>,
+}
+
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    /// Spends one of `exchange`'s tokens, returning `false` if none is left.
+    ///
+    /// `default` supplies the driver's limit the first time the exchange is
+    /// seen; exchanges without a limit always succeed.
+    pub(super) fn try_acquire(
+        &mut self,
+        exchange: Exchange,
+        now: Instant,
+        default: impl FnOnce() -> Option<RateLimit>,
+    ) -> bool {
+        let bucket = self.buckets.entry(exchange).or_insert_with(|| {
+            let limit = OVERRIDES.read().get(&exchange).copied().or_else(default)?;
+            Some(Bucket {
+                limit,
+                tokens: limit.burst as f64,
+                refilled: now,
+            })
+        });
+        let Some(bucket) = bucket else {
+            return true;
+        };
+
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * bucket.limit.per_second as f64).min(bucket.limit.burst as f64);
+        bucket.refilled = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_refills() {
+        let mut limiter = RateLimiter::default();
+        let limit = || Some(RateLimit { per_second: 2, burst: 3 });
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.try_acquire(Exchange::Binance, start, limit));
+        }
+        assert!(!limiter.try_acquire(Exchange::Binance, start, limit));
+        assert!(!limiter.try_acquire(Exchange::Binance, start + Duration::from_millis(400), limit));
+        assert!(limiter.try_acquire(Exchange::Binance, start + Duration::from_millis(500), limit));
+
+        // Refills never exceed the burst
+        let later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.try_acquire(Exchange::Binance, later, limit));
+        }
+        assert!(!limiter.try_acquire(Exchange::Binance, later, limit));
+    }
+
+    #[test]
+    fn test_unlimited_and_overrides() {
+        let mut limiter = RateLimiter::default();
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(limiter.try_acquire(Exchange::Bitstamp, now, || None));
+        }
+
+        configure(Exchange::Bitfinex, RateLimit { per_second: 1, burst: 1 });
+        assert!(limiter.try_acquire(Exchange::Bitfinex, now, || None));
+        assert!(!limiter.try_acquire(Exchange::Bitfinex, now, || None));
+        reset(Exchange::Bitfinex);
+    }
+}
@@ -0,0 +1,150 @@
+//! Raw frame capture for debugging and replay.
+//!
+//! A connector configured with a [FrameTap] copies every frame it reads,
+//! websocket messages, TCP reads and multicast packets alike, before the
+//! driver parses it. Captured traffic reproduces parser bugs offline and
+//! feeds replay tests through [read_capture].
+//!
+//! Capture files are a sequence of records, each a little-endian `u64`
+//! receive time in nanoseconds since the Unix epoch, a `u64` connection id,
+//! a `u32` length and that many bytes of frame.
+
+use crossbeam_channel::Sender;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where a connector copies its raw frames.
+#[derive(Clone, Debug)]
+pub enum FrameTap {
+    /// Sends each frame as a [TappedFrame]. Frames are dropped rather than
+    /// stalling the worker when a bounded channel is full.
+    Channel(Sender<TappedFrame>),
+    /// Appends each frame to a capture file, created or truncated when the
+    /// worker starts. Records are written in 64 KiB blocks and in full on
+    /// shutdown; if the file cannot be created nothing is captured.
+    File(PathBuf),
+}
+
+/// One frame as read from the wire.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TappedFrame {
+    /// The [StatusEvent](super::StatusEvent) id of the connection.
+    pub connection: usize,
+    pub received: SystemTime,
+    pub data: Vec<u8>,
+}
+
+enum Sink {
+    Channel(Sender<TappedFrame>),
+    File(BufWriter<File>),
+}
+
+thread_local! {
+    /// Where this worker thread's frames go, if it taps them.
+    static TAP: RefCell<Option<Sink>> = const { RefCell::new(None) };
+}
+
+/// Starts tapping the frames read on this worker thread.
+pub(super) fn install(tap: &FrameTap) -> io::Result<()> {
+    let sink = match tap {
+        FrameTap::Channel(tx) => Sink::Channel(tx.clone()),
+        FrameTap::File(path) => Sink::File(BufWriter::with_capacity(64 * 1024, File::create(path)?)),
+    };
+    TAP.set(Some(sink));
+    Ok(())
+}
+
+/// Copies a frame to this thread's tap, if any.
+pub(super) fn record(connection: usize, frame: &[u8]) {
+    TAP.with_borrow_mut(|tap| {
+        let Some(sink) = tap else {
+            return;
+        };
+        let received = SystemTime::now();
+        match sink {
+            Sink::Channel(tx) => {
+                let _ = tx.try_send(TappedFrame {
+                    connection,
+                    received,
+                    data: frame.to_vec(),
+                });
+            }
+            Sink::File(file) => {
+                let nanos = received.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+                let written = file
+                    .write_all(&nanos.to_le_bytes())
+                    .and_then(|_| file.write_all(&(connection as u64).to_le_bytes()))
+                    .and_then(|_| file.write_all(&(frame.len() as u32).to_le_bytes()))
+                    .and_then(|_| file.write_all(frame));
+                if written.is_err() {
+                    // A full disk should not cost a write per frame forever
+                    *tap = None;
+                }
+            }
+        }
+    });
+}
+
+/// Writes out any buffered records.
+pub(super) fn flush() {
+    TAP.with_borrow_mut(|tap| {
+        if let Some(Sink::File(file)) = tap {
+            let _ = file.flush();
+        }
+    });
+}
+
+/// Reads every frame of a capture file written by [FrameTap::File].
+///
+/// A record cut short, as when the process died mid-write, ends the capture.
+pub fn read_capture(path: impl AsRef<Path>) -> io::Result<Vec<TappedFrame>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut frames = Vec::new();
+    let mut header = [0u8; 20];
+    loop {
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(frames),
+            Err(err) => return Err(err),
+        }
+        let nanos = u64::from_le_bytes(header[0..8].try_into().unwrap());
+        let connection = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+        let len = u32::from_le_bytes(header[16..20].try_into().unwrap()) as usize;
+        let mut data = vec![0; len];
+        match file.read_exact(&mut data) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(frames),
+            Err(err) => return Err(err),
+        }
+        frames.push(TappedFrame {
+            connection,
+            received: UNIX_EPOCH + Duration::from_nanos(nanos),
+            data,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_round_trip() {
+        let path = std::env::temp_dir().join(format!("tap-test-{}.bin", std::process::id()));
+        install(&FrameTap::File(path.clone())).unwrap();
+        record(3, b"{\"bids\":[]}");
+        record(4, &[0, 1, 2]);
+        flush();
+        TAP.set(None);
+
+        let frames = read_capture(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].connection, frames[0].data.as_slice()), (3, &b"{\"bids\":[]}"[..]));
+        assert_eq!((frames[1].connection, frames[1].data.as_slice()), (4, &[0u8, 1, 2][..]));
+        assert!(frames[0].received <= frames[1].received);
+    }
+}
@@ -0,0 +1,111 @@
+//! TLS settings for a connector's websockets.
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use rustls::{ClientConfig, RootCertStore};
+use std::io::{self, ErrorKind};
+use std::sync::Arc;
+
+/// How a connector's websockets negotiate TLS.
+///
+/// The default trusts the bundled webpki roots, allows TLS 1.2 and 1.3,
+/// sends SNI and resumes sessions on reconnect. Early data (0-RTT) is never
+/// used: the websocket upgrade is only sent once the handshake completes.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    /// Certificates trusted in addition to the webpki roots, e.g. the root
+    /// of a TLS-intercepting gateway. Added with [TlsConfig::add_ca_pem].
+    pub extra_roots: Vec<CertificateDer<'static>>,
+    /// Trust only `extra_roots`, dropping the webpki roots.
+    pub extra_roots_only: bool,
+    /// Refuse servers that cannot speak TLS 1.3.
+    pub tls13_only: bool,
+    /// Send the host name in the handshake. Some gateways route on it,
+    /// others reject it for IP endpoints.
+    pub sni: bool,
+    /// Cache sessions so reconnects use an abbreviated handshake. The cache
+    /// is shared by every connection of the connector.
+    pub session_resumption: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            extra_roots: Vec::new(),
+            extra_roots_only: false,
+            tls13_only: false,
+            sni: true,
+            session_resumption: true,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Trusts every certificate in a PEM bundle.
+    ///
+    /// Fails without adding anything if the bundle holds no certificate or
+    /// one that cannot be used as a trust anchor.
+    pub fn add_ca_pem(&mut self, pem: &[u8]) -> io::Result<()> {
+        let certs = CertificateDer::pem_slice_iter(pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        if certs.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "no certificates in PEM bundle"));
+        }
+
+        let mut roots = RootCertStore::empty();
+        for cert in &certs {
+            roots.add(cert.clone()).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        }
+        self.extra_roots.extend(certs);
+        Ok(())
+    }
+
+    /// Builds the rustls configuration shared by the connector's sockets.
+    pub(super) fn client_config(&self) -> Arc<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        if !self.extra_roots_only {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+        // Every certificate was checked by `add_ca_pem`
+        roots.add_parsable_certificates(self.extra_roots.iter().cloned());
+
+        let builder = if self.tls13_only {
+            ClientConfig::builder_with_protocol_versions(&[&rustls::version::TLS13])
+        } else {
+            ClientConfig::builder()
+        };
+        let mut config = builder.with_root_certificates(roots).with_no_client_auth();
+        config.enable_sni = self.sni;
+        if !self.session_resumption {
+            config.resumption = rustls::client::Resumption::disabled();
+        }
+        Arc::new(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_bad_bundles() {
+        let mut config = TlsConfig::default();
+        assert!(config.add_ca_pem(b"").is_err());
+        assert!(config
+            .add_ca_pem(b"-----BEGIN CERTIFICATE-----\nbm90IGEgY2VydA==\n-----END CERTIFICATE-----\n")
+            .is_err());
+        assert!(config.extra_roots.is_empty());
+    }
+
+    #[test]
+    fn test_client_config() {
+        let config = TlsConfig {
+            tls13_only: true,
+            sni: false,
+            ..TlsConfig::default()
+        }
+        .client_config();
+        assert!(!config.enable_sni);
+    }
+}
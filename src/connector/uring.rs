@@ -0,0 +1,521 @@
+//! io_uring reads for the connector's TCP streams.
+//!
+//! Enabled with the `io-uring` feature on Linux. Each worker thread owns one
+//! ring. A polled [UringStream] keeps a `recv` in flight on the ring instead
+//! of calling `recv(2)` itself: reading copies out of a completed buffer, and
+//! [submit] hands every re-armed `recv` to the kernel in a single
+//! `io_uring_enter(2)` per pass of the event loop. Completions are reaped
+//! from shared memory without a syscall.
+//!
+//! If the ring cannot be created, e.g. on an old kernel or under a seccomp
+//! filter, or every slot is taken, streams fall back to plain non-blocking
+//! reads.
+
+use std::cell::{Cell, RefCell};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Submission queue entries, which also caps the streams on the ring.
+const ENTRIES: u32 = 256;
+
+/// Bytes received per completed `recv`.
+const SLOT_BUFFER: usize = 16 * 1024;
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OP_RECV: u8 = 27;
+
+thread_local! {
+    /// This thread's ring, created on first use.
+    static RING: Option<Rc<RefCell<Ring>>> = Ring::new().ok().map(|ring| Rc::new(RefCell::new(ring)));
+}
+
+/// Submits every `recv` queued on this thread's ring since the last call.
+pub(super) fn submit() {
+    RING.with(|ring| {
+        if let Some(ring) = ring {
+            // A failed submit leaves the entries queued for the next pass
+            let _ = ring.borrow_mut().submit();
+        }
+    });
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    msg_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// A memory-mapped region of the ring.
+struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: &OwnedFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        // SAFETY: a fresh shared mapping of the ring fd; the kernel checks the
+        // offset and length
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd.as_raw_fd(),
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: NonNull::new(ptr.cast()).ok_or(ErrorKind::Other)?,
+            len,
+        })
+    }
+
+    /// Returns a pointer `offset` bytes into the mapping.
+    fn at<T>(&self, offset: u32) -> *mut T {
+        debug_assert!(offset as usize + size_of::<T>() <= self.len);
+        // SAFETY: the kernel-reported offsets lie within the mapping
+        unsafe { self.ptr.as_ptr().add(offset as usize).cast() }
+    }
+
+    /// Returns the ring index at `offset`, shared with the kernel.
+    fn atomic(&self, offset: u32) -> &AtomicU32 {
+        // SAFETY: ring indices are aligned u32s the kernel only accesses atomically
+        unsafe { &*self.at::<AtomicU32>(offset) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly what `new` mapped
+        unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+/// The receive state of one stream's buffer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Nothing in flight and nothing buffered.
+    Idle,
+    /// A `recv` is queued or in flight.
+    Armed,
+    /// A `recv` completed with `res`, of which `pos` bytes were read.
+    Ready { res: i32, pos: usize },
+    /// The stream was dropped with a `recv` in flight; the buffer is freed
+    /// once it completes.
+    Orphaned,
+    /// Not in use by any stream.
+    Free,
+}
+
+struct Slot {
+    buf: Box<[u8]>,
+    state: State,
+}
+
+struct Ring {
+    // Field order matters: the maps must go before the fd is closed
+    sq_ring: Mapping,
+    cq_ring: Mapping,
+    sqes: Mapping,
+    fd: OwnedFd,
+    params: Params,
+    slots: Vec<Slot>,
+    /// Entries written to the submission queue but not yet submitted.
+    unsubmitted: u32,
+}
+
+impl Ring {
+    fn new() -> io::Result<Self> {
+        let mut params = Params::default();
+        // SAFETY: io_uring_setup only writes to `params`
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, ENTRIES, &mut params as *mut Params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: a new fd owned by nothing else
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * size_of::<u32>();
+        let cq_len = params.cq_off.cqes as usize + params.cq_entries as usize * size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * size_of::<Sqe>();
+        Ok(Self {
+            sq_ring: Mapping::new(&fd, sq_len, IORING_OFF_SQ_RING)?,
+            cq_ring: Mapping::new(&fd, cq_len, IORING_OFF_CQ_RING)?,
+            sqes: Mapping::new(&fd, sqes_len, IORING_OFF_SQES)?,
+            fd,
+            params,
+            slots: Vec::new(),
+            unsubmitted: 0,
+        })
+    }
+
+    /// Reserves a buffer for a new stream, if the ring has room.
+    fn allocate(&mut self) -> Option<usize> {
+        if let Some(index) = self.slots.iter().position(|slot| slot.state == State::Free) {
+            self.slots[index].state = State::Idle;
+            return Some(index);
+        }
+        if self.slots.len() >= self.params.sq_entries as usize {
+            return None;
+        }
+        self.slots.push(Slot {
+            buf: vec![0; SLOT_BUFFER].into_boxed_slice(),
+            state: State::Idle,
+        });
+        Some(self.slots.len() - 1)
+    }
+
+    /// Releases a stream's buffer, deferring while a `recv` is in flight.
+    fn release(&mut self, index: usize) {
+        let slot = &mut self.slots[index];
+        slot.state = match slot.state {
+            State::Armed => State::Orphaned,
+            _ => State::Free,
+        };
+    }
+
+    /// Queues a `recv` into a slot's buffer.
+    fn arm(&mut self, index: usize, fd: i32) {
+        let sq = &self.params.sq_off;
+        let head = self.sq_ring.atomic(sq.head).load(Ordering::Acquire);
+        let tail = self.sq_ring.atomic(sq.tail).load(Ordering::Relaxed);
+        // One slot per entry means the queue never fills
+        debug_assert!(tail.wrapping_sub(head) < self.params.sq_entries);
+
+        let mask = unsafe { *self.sq_ring.at::<u32>(sq.ring_mask) };
+        let entry = tail & mask;
+        let slot = &mut self.slots[index];
+        let sqe = Sqe {
+            opcode: IORING_OP_RECV,
+            fd,
+            addr: slot.buf.as_mut_ptr() as u64,
+            len: slot.buf.len() as u32,
+            user_data: index as u64,
+            ..Sqe::default()
+        };
+        // SAFETY: `entry` is masked into the submission queue, which the
+        // kernel does not read past `tail`
+        unsafe {
+            self.sqes.at::<Sqe>(0).add(entry as usize).write(sqe);
+            self.sq_ring.at::<u32>(sq.array).add(entry as usize).write(entry);
+        }
+        self.sq_ring.atomic(sq.tail).store(tail.wrapping_add(1), Ordering::Release);
+        slot.state = State::Armed;
+        self.unsubmitted += 1;
+    }
+
+    /// Hands every queued entry to the kernel.
+    fn submit(&mut self) -> io::Result<()> {
+        if self.unsubmitted == 0 {
+            return Ok(());
+        }
+        self.enter(self.unsubmitted, 0, 0)?;
+        self.unsubmitted = 0;
+        Ok(())
+    }
+
+    fn enter(&self, to_submit: u32, min_complete: u32, flags: u32) -> io::Result<()> {
+        // SAFETY: no signal mask is passed
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd.as_raw_fd(),
+                to_submit,
+                min_complete,
+                flags,
+                ptr::null::<libc::sigset_t>(),
+                0usize,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Moves every completed `recv` into its slot.
+    fn reap(&mut self) {
+        let cq = &self.params.cq_off;
+        let mut head = self.cq_ring.atomic(cq.head).load(Ordering::Relaxed);
+        let tail = self.cq_ring.atomic(cq.tail).load(Ordering::Acquire);
+        if head == tail {
+            return;
+        }
+        let mask = unsafe { *self.cq_ring.at::<u32>(cq.ring_mask) };
+        while head != tail {
+            // SAFETY: entries between head and tail were written by the kernel
+            let cqe = unsafe { self.cq_ring.at::<Cqe>(cq.cqes).add((head & mask) as usize).read() };
+            let slot = &mut self.slots[cqe.user_data as usize];
+            slot.state = match slot.state {
+                State::Orphaned => State::Free,
+                _ => State::Ready { res: cqe.res, pos: 0 },
+            };
+            head = head.wrapping_add(1);
+        }
+        self.cq_ring.atomic(cq.head).store(head, Ordering::Release);
+    }
+
+    /// Copies buffered bytes of a slot into `buf`, re-arming it once empty.
+    fn read(&mut self, index: usize, fd: i32, buf: &mut [u8]) -> io::Result<usize> {
+        self.reap();
+        let slot = &mut self.slots[index];
+        match slot.state {
+            State::Idle => {
+                self.arm(index, fd);
+                Err(ErrorKind::WouldBlock.into())
+            }
+            State::Ready { res, .. } if res == -libc::EAGAIN || res == -libc::EINTR => {
+                self.arm(index, fd);
+                Err(ErrorKind::WouldBlock.into())
+            }
+            State::Ready { res, .. } if res < 0 => {
+                slot.state = State::Idle;
+                Err(io::Error::from_raw_os_error(-res))
+            }
+            // End of stream, reported on every read from now on
+            State::Ready { res: 0, .. } => Ok(0),
+            State::Ready { res, pos } => {
+                let available = &slot.buf[pos..res as usize];
+                let n = available.len().min(buf.len());
+                buf[..n].copy_from_slice(&available[..n]);
+                if pos + n == res as usize {
+                    self.arm(index, fd);
+                } else {
+                    slot.state = State::Ready { res, pos: pos + n };
+                }
+                Ok(n)
+            }
+            State::Armed => Err(ErrorKind::WouldBlock.into()),
+            State::Orphaned | State::Free => unreachable!("slot read after release"),
+        }
+    }
+}
+
+impl Drop for Ring {
+    /// Waits for orphaned `recv`s so the kernel never writes to a freed buffer.
+    fn drop(&mut self) {
+        let _ = self.submit();
+        while self.slots.iter().any(|slot| slot.state == State::Orphaned) {
+            if self.enter(0, 1, IORING_ENTER_GETEVENTS).is_err() {
+                // Leak the buffers rather than risk a late write into them
+                std::mem::forget(std::mem::take(&mut self.slots));
+                return;
+            }
+            self.reap();
+        }
+    }
+}
+
+/// A TCP stream read through this thread's ring once it is non-blocking.
+///
+/// Blocking reads, used for the TLS and websocket handshakes, go straight
+/// to the socket. The socket itself stays in blocking mode so the ring can
+/// park `recv`s on it; writes never block regardless.
+pub(super) struct UringStream {
+    stream: TcpStream,
+    /// This thread's ring and the stream's slot on it.
+    ring: Option<(Rc<RefCell<Ring>>, usize)>,
+    nonblocking: Cell<bool>,
+}
+
+impl From<TcpStream> for UringStream {
+    fn from(stream: TcpStream) -> Self {
+        let ring = RING.with(|ring| {
+            let ring = ring.as_ref()?;
+            let slot = ring.borrow_mut().allocate()?;
+            Some((Rc::clone(ring), slot))
+        });
+        Self {
+            stream,
+            ring,
+            nonblocking: Cell::new(false),
+        }
+    }
+}
+
+impl UringStream {
+    pub(super) fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.nonblocking.set(nonblocking);
+        if self.ring.is_none() {
+            return self.stream.set_nonblocking(nonblocking);
+        }
+        Ok(())
+    }
+
+    pub(super) fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.stream.shutdown(how)
+    }
+}
+
+impl AsRawFd for UringStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl Read for UringStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &self.ring {
+            Some((ring, slot)) if self.nonblocking.get() => {
+                ring.borrow_mut().read(*slot, self.stream.as_raw_fd(), buf)
+            }
+            _ => self.stream.read(buf),
+        }
+    }
+}
+
+impl Write for UringStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.ring.is_none() || !self.nonblocking.get() {
+            return self.stream.write(buf);
+        }
+        // SAFETY: sends from a valid buffer on an open socket
+        let sent = unsafe {
+            libc::send(
+                self.stream.as_raw_fd(),
+                buf.as_ptr().cast(),
+                buf.len(),
+                libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(sent as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Drop for UringStream {
+    fn drop(&mut self) {
+        if let Some((ring, slot)) = &self.ring {
+            // Completes any `recv` still parked on the socket
+            let _ = self.stream.shutdown(Shutdown::Both);
+            ring.borrow_mut().release(*slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_reads_through_ring() {
+        if RING.with(|ring| ring.is_none()) {
+            // io_uring is unavailable here; streams use plain reads
+            return;
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut stream = UringStream::from(TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+        let (mut peer, _) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(stream.read(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+        submit();
+        peer.write_all(b"hello").unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < 5 {
+            match stream.read(&mut buf) {
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => submit(),
+                Err(err) => panic!("{err}"),
+            }
+        }
+        assert_eq!(received, b"hello");
+
+        stream.write_all(b"bye").unwrap();
+        let mut bye = [0u8; 3];
+        peer.read_exact(&mut bye).unwrap();
+        assert_eq!(&bye, b"bye");
+        drop(peer);
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => panic!("unexpected data"),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => submit(),
+                Err(err) => panic!("{err}"),
+            }
+        }
+    }
+}
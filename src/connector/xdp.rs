@@ -0,0 +1,581 @@
+//! AF_XDP receive path for multicast feeds.
+//!
+//! Enabled with the `af-xdp` feature on Linux. A connector configured with
+//! an [XdpConfig] binds one XDP socket to a NIC queue at startup and
+//! receives multicast packets straight into a UMEM shared with the driver,
+//! skipping the kernel network stack. Drivers parse payloads in place; a
+//! frame goes back to the NIC once every line listening to its group has
+//! read past it.
+//!
+//! Steering is left to an XDP program loaded by the operator, which must
+//! redirect the feeds' packets on the queue into an `XSKMAP` pinned at
+//! [XdpConfig::xsks_map]; the connector inserts its socket at the queue's
+//! index. Group membership is still joined with ordinary sockets so IGMP
+//! keeps the traffic flowing. Only one connector can own a queue: others,
+//! and every connector when the socket cannot be bound, fall back to
+//! kernel sockets.
+//!
+//! The XDP socket is drained by polling, so this path suits the spinning
+//! worker rather than the [event loop](super::ExchangeConnector::event_loop).
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::io::{self, ErrorKind};
+use std::mem::size_of;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Where a connector's XDP socket receives from.
+#[derive(Clone, Debug)]
+pub struct XdpConfig {
+    /// Network interface, e.g. `ens1f0`.
+    pub interface: String,
+    /// Receive queue the feeds are steered to.
+    pub queue: u32,
+    /// Path of the pinned `XSKMAP` the XDP program redirects into.
+    pub xsks_map: PathBuf,
+    /// Require zero-copy mode instead of letting the kernel fall back to
+    /// copying into the UMEM.
+    pub zero_copy: bool,
+    /// UMEM frames; a power of two.
+    pub frames: u32,
+}
+
+impl XdpConfig {
+    /// Listens on `queue` of `interface` through the map at `xsks_map`, with
+    /// 4096 frames and no zero-copy requirement.
+    pub fn new(interface: &str, queue: u32, xsks_map: impl Into<PathBuf>) -> Self {
+        Self {
+            interface: interface.to_string(),
+            queue,
+            xsks_map: xsks_map.into(),
+            zero_copy: false,
+            frames: 4096,
+        }
+    }
+}
+
+/// Bytes per UMEM frame.
+const FRAME_SIZE: u32 = 2048;
+
+/// Frames one line may have queued before the oldest is dropped.
+const MAX_QUEUED: usize = 256;
+
+const BPF_MAP_UPDATE_ELEM: libc::c_long = 2;
+const BPF_OBJ_GET: libc::c_long = 7;
+
+thread_local! {
+    /// The XDP socket of this worker thread, if [install]ed.
+    static SOCKET: RefCell<Option<Rc<RefCell<Socket>>>> = const { RefCell::new(None) };
+}
+
+/// Binds this worker thread's XDP socket.
+///
+/// On failure the thread keeps receiving through kernel sockets.
+pub(super) fn install(config: &XdpConfig) -> io::Result<()> {
+    let socket = Socket::bind(config)?;
+    SOCKET.set(Some(Rc::new(RefCell::new(socket))));
+    Ok(())
+}
+
+/// Starts receiving `group` through this thread's XDP socket, if it has one.
+pub(super) fn tap(group: SocketAddrV4) -> Option<Tap> {
+    let socket = SOCKET.with_borrow(|socket| socket.clone())?;
+    let id = socket.borrow_mut().open_tap(group);
+    Some(Tap {
+        socket,
+        id,
+        current: None,
+    })
+}
+
+#[repr(C)]
+struct BpfObjGet {
+    pathname: u64,
+    bpf_fd: u32,
+    file_flags: u32,
+}
+
+#[repr(C)]
+struct BpfMapUpdate {
+    map_fd: u32,
+    pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+/// Calls `bpf(2)` with `attr`.
+fn bpf<T>(cmd: libc::c_long, attr: &T) -> io::Result<i32> {
+    // SAFETY: `attr` matches the layout the command expects
+    let res = unsafe { libc::syscall(libc::SYS_bpf, cmd, attr as *const T, size_of::<T>()) };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res as i32)
+}
+
+/// A memory mapping released on drop.
+struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: Option<&OwnedFd>, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        let (flags, fd) = match fd {
+            Some(fd) => (libc::MAP_SHARED | libc::MAP_POPULATE, fd.as_raw_fd()),
+            None => (libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_POPULATE, -1),
+        };
+        // SAFETY: a fresh mapping; the kernel validates the fd and offset
+        let ptr = unsafe { libc::mmap(ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, flags, fd, offset) };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: NonNull::new(ptr.cast()).ok_or(ErrorKind::Other)?,
+            len,
+        })
+    }
+
+    fn at<T>(&self, offset: u64) -> *mut T {
+        debug_assert!(offset as usize + size_of::<T>() <= self.len);
+        // SAFETY: the kernel-reported offsets lie within the mapping
+        unsafe { self.ptr.as_ptr().add(offset as usize).cast() }
+    }
+
+    fn atomic(&self, offset: u64) -> &AtomicU32 {
+        // SAFETY: ring producer and consumer indices are aligned u32s
+        unsafe { &*self.at::<AtomicU32>(offset) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly what `new` mapped
+        unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+/// A single-producer or single-consumer ring shared with the kernel.
+struct Ring {
+    map: Mapping,
+    offsets: libc::xdp_ring_offset,
+    mask: u32,
+}
+
+impl Ring {
+    fn map(fd: &OwnedFd, offsets: libc::xdp_ring_offset, entries: u32, entry: usize, pgoff: libc::off_t) -> io::Result<Self> {
+        let len = offsets.desc as usize + entries as usize * entry;
+        Ok(Self {
+            map: Mapping::new(Some(fd), len, pgoff)?,
+            offsets,
+            mask: entries - 1,
+        })
+    }
+
+    fn producer(&self) -> &AtomicU32 {
+        self.map.atomic(self.offsets.producer)
+    }
+
+    fn consumer(&self) -> &AtomicU32 {
+        self.map.atomic(self.offsets.consumer)
+    }
+
+    fn needs_wakeup(&self) -> bool {
+        self.map.atomic(self.offsets.flags).load(Ordering::Relaxed) & libc::XDP_RING_NEED_WAKEUP != 0
+    }
+
+    fn entry<T>(&self, index: u32) -> *mut T {
+        // SAFETY: masked into the descriptor array
+        unsafe { self.map.at::<T>(self.offsets.desc).add((index & self.mask) as usize) }
+    }
+}
+
+/// One line's queue of frames to read.
+#[derive(Default)]
+struct TapQueue {
+    /// UMEM offset and length of each payload, oldest first.
+    payloads: VecDeque<(u64, u32)>,
+}
+
+/// This thread's XDP socket with its UMEM and rings.
+struct Socket {
+    // Rings go before the fd is closed, and the UMEM last of all
+    rx: Ring,
+    fill: Ring,
+    fd: OwnedFd,
+    umem: Mapping,
+    /// Taps by destination group.
+    groups: HashMap<SocketAddrV4, Vec<usize>>,
+    taps: Vec<Option<TapQueue>>,
+    /// Taps still to read each frame, by frame number.
+    refs: Vec<u16>,
+    /// Frames ready to hand back through the fill ring.
+    free: Vec<u64>,
+}
+
+impl Socket {
+    fn bind(config: &XdpConfig) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(ErrorKind::InvalidInput, msg.to_string());
+        if !config.frames.is_power_of_two() {
+            return Err(invalid("XDP frame count must be a power of two"));
+        }
+        let name = CString::new(config.interface.as_str()).map_err(|_| invalid("bad interface name"))?;
+        // SAFETY: `name` is NUL-terminated
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: plain socket creation
+        let fd = unsafe { libc::socket(libc::AF_XDP, libc::SOCK_RAW | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: a new fd owned by nothing else
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let frames = config.frames;
+        let umem = Mapping::new(None, (frames * FRAME_SIZE) as usize, 0)?;
+        let reg = libc::xdp_umem_reg {
+            addr: umem.ptr.as_ptr() as u64,
+            len: umem.len as u64,
+            chunk_size: FRAME_SIZE,
+            headroom: 0,
+            flags: 0,
+            tx_metadata_len: 0,
+        };
+        setsockopt(&fd, libc::XDP_UMEM_REG, &reg)?;
+        setsockopt(&fd, libc::XDP_UMEM_FILL_RING, &frames)?;
+        setsockopt(&fd, libc::XDP_UMEM_COMPLETION_RING, &frames)?;
+        setsockopt(&fd, libc::XDP_RX_RING, &frames)?;
+
+        // SAFETY: an all-zero value is valid for these plain structs
+        let mut offsets: libc::xdp_mmap_offsets = unsafe { std::mem::zeroed() };
+        let mut len = size_of::<libc::xdp_mmap_offsets>() as libc::socklen_t;
+        // SAFETY: the kernel writes at most `len` bytes
+        let res = unsafe {
+            libc::getsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_XDP,
+                libc::XDP_MMAP_OFFSETS,
+                (&mut offsets as *mut libc::xdp_mmap_offsets).cast(),
+                &mut len,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if len as usize != size_of::<libc::xdp_mmap_offsets>() {
+            return Err(io::Error::new(ErrorKind::Unsupported, "kernel too old for XDP ring flags"));
+        }
+
+        let rx = Ring::map(&fd, offsets.rx, frames, size_of::<libc::xdp_desc>(), libc::XDP_PGOFF_RX_RING)?;
+        let fill = Ring::map(
+            &fd,
+            offsets.fr,
+            frames,
+            size_of::<u64>(),
+            libc::XDP_UMEM_PGOFF_FILL_RING as libc::off_t,
+        )?;
+
+        let mode = if config.zero_copy { libc::XDP_ZEROCOPY } else { 0 };
+        let addr = libc::sockaddr_xdp {
+            sxdp_family: libc::AF_XDP as u16,
+            sxdp_flags: mode | libc::XDP_USE_NEED_WAKEUP,
+            sxdp_ifindex: ifindex,
+            sxdp_queue_id: config.queue,
+            sxdp_shared_umem_fd: 0,
+        };
+        // SAFETY: `addr` is a valid sockaddr_xdp
+        let res = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                (&addr as *const libc::sockaddr_xdp).cast(),
+                size_of::<libc::sockaddr_xdp>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let path = CString::new(config.xsks_map.as_os_str().as_encoded_bytes())
+            .map_err(|_| invalid("bad XSKMAP path"))?;
+        let map_fd = bpf(BPF_OBJ_GET, &BpfObjGet {
+            pathname: path.as_ptr() as u64,
+            bpf_fd: 0,
+            file_flags: 0,
+        })?;
+        // SAFETY: a new fd owned by nothing else
+        let map_fd = unsafe { OwnedFd::from_raw_fd(map_fd) };
+        let socket_fd = fd.as_raw_fd() as u32;
+        bpf(BPF_MAP_UPDATE_ELEM, &BpfMapUpdate {
+            map_fd: map_fd.as_raw_fd() as u32,
+            pad: 0,
+            key: &config.queue as *const u32 as u64,
+            value: &socket_fd as *const u32 as u64,
+            flags: 0,
+        })?;
+
+        let mut socket = Self {
+            rx,
+            fill,
+            fd,
+            umem,
+            groups: HashMap::new(),
+            taps: Vec::new(),
+            refs: vec![0; frames as usize],
+            free: (0..u64::from(frames)).map(|frame| frame * u64::from(FRAME_SIZE)).collect(),
+        };
+        socket.refill();
+        Ok(socket)
+    }
+
+    fn open_tap(&mut self, group: SocketAddrV4) -> usize {
+        let id = match self.taps.iter().position(Option::is_none) {
+            Some(id) => id,
+            None => {
+                self.taps.push(None);
+                self.taps.len() - 1
+            }
+        };
+        self.taps[id] = Some(TapQueue::default());
+        self.groups.entry(group).or_default().push(id);
+        id
+    }
+
+    fn close_tap(&mut self, id: usize) {
+        if let Some(queue) = self.taps[id].take() {
+            for (offset, _) in queue.payloads {
+                self.release(offset);
+            }
+        }
+        for taps in self.groups.values_mut() {
+            taps.retain(|&tap| tap != id);
+        }
+        self.groups.retain(|_, taps| !taps.is_empty());
+    }
+
+    /// Takes the next payload queued for a tap, receiving first if it has none.
+    fn next(&mut self, id: usize) -> Option<(u64, u32)> {
+        if self.taps[id].as_ref()?.payloads.is_empty() {
+            self.receive();
+        }
+        self.taps[id].as_mut()?.payloads.pop_front()
+    }
+
+    /// Moves every received frame to the taps of its group.
+    fn receive(&mut self) {
+        let consumer = self.rx.consumer().load(Ordering::Relaxed);
+        let producer = self.rx.producer().load(Ordering::Acquire);
+        let mut index = consumer;
+        while index != producer {
+            // SAFETY: descriptors between consumer and producer were written by the kernel
+            let desc = unsafe { self.rx.entry::<libc::xdp_desc>(index).read() };
+            index = index.wrapping_add(1);
+
+            let frame = desc.addr & !u64::from(FRAME_SIZE - 1);
+            // SAFETY: the kernel only hands out descriptors inside the UMEM
+            let packet = unsafe { std::slice::from_raw_parts(self.umem.at::<u8>(desc.addr), desc.len as usize) };
+            let taps = udp_payload(packet).and_then(|(group, start, len)| Some((self.groups.get(&group)?, start, len)));
+            let Some((taps, start, len)) = taps else {
+                self.free.push(frame);
+                continue;
+            };
+
+            let payload = (desc.addr + start as u64, len as u32);
+            self.refs[(frame / u64::from(FRAME_SIZE)) as usize] = taps.len() as u16;
+            let mut dropped = Vec::new();
+            for &tap in taps {
+                let Some(queue) = self.taps[tap].as_mut() else {
+                    continue;
+                };
+                if queue.payloads.len() == MAX_QUEUED {
+                    // A line that stops reading must not starve the others
+                    dropped.extend(queue.payloads.pop_front().map(|(offset, _)| offset));
+                }
+                queue.payloads.push_back(payload);
+            }
+            for offset in dropped {
+                self.release(offset);
+            }
+        }
+        self.rx.consumer().store(index, Ordering::Release);
+        self.refill();
+    }
+
+    /// Drops one tap's reference to the frame holding `offset`.
+    fn release(&mut self, offset: u64) {
+        let frame = offset / u64::from(FRAME_SIZE);
+        let refs = &mut self.refs[frame as usize];
+        *refs = refs.saturating_sub(1);
+        if *refs == 0 {
+            self.free.push(frame * u64::from(FRAME_SIZE));
+        }
+    }
+
+    /// Hands free frames back to the kernel.
+    fn refill(&mut self) {
+        if self.free.is_empty() {
+            return;
+        }
+        let producer = self.fill.producer().load(Ordering::Relaxed);
+        let consumer = self.fill.consumer().load(Ordering::Acquire);
+        let room = (self.fill.mask + 1) - producer.wrapping_sub(consumer);
+        let count = (room as usize).min(self.free.len());
+        for (i, addr) in self.free.drain(..count).enumerate() {
+            // SAFETY: `room` slots past the producer belong to us
+            unsafe { self.fill.entry::<u64>(producer.wrapping_add(i as u32)).write(addr) };
+        }
+        self.fill.producer().store(producer.wrapping_add(count as u32), Ordering::Release);
+
+        if self.fill.needs_wakeup() {
+            // SAFETY: a zero-length non-blocking receive only kicks the driver
+            unsafe {
+                libc::recvfrom(self.fd.as_raw_fd(), ptr::null_mut(), 0, libc::MSG_DONTWAIT, ptr::null_mut(), ptr::null_mut())
+            };
+        }
+    }
+}
+
+fn setsockopt<T>(fd: &OwnedFd, name: libc::c_int, value: &T) -> io::Result<()> {
+    // SAFETY: `value` is a valid option of its own size
+    let res = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_XDP,
+            name,
+            (value as *const T).cast(),
+            size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns the destination, payload offset and payload length of an
+/// unfragmented IPv4 UDP datagram in an Ethernet frame.
+fn udp_payload(frame: &[u8]) -> Option<(SocketAddrV4, usize, usize)> {
+    let mut ip = 14;
+    let mut ether_type = u16::from_be_bytes(frame.get(12..14)?.try_into().ok()?);
+    if ether_type == 0x8100 {
+        // 802.1Q tag
+        ether_type = u16::from_be_bytes(frame.get(16..18)?.try_into().ok()?);
+        ip += 4;
+    }
+    if ether_type != 0x0800 {
+        return None;
+    }
+
+    let header = frame.get(ip..ip + 20)?;
+    let ihl = usize::from(header[0] & 0x0f) * 4;
+    let fragmented = u16::from_be_bytes([header[6], header[7]]) & 0x3fff != 0;
+    if header[0] >> 4 != 4 || header[9] != 17 || ihl < 20 || fragmented {
+        return None;
+    }
+    let dst = Ipv4Addr::new(header[16], header[17], header[18], header[19]);
+
+    let udp = ip + ihl;
+    let header = frame.get(udp..udp + 8)?;
+    let port = u16::from_be_bytes([header[2], header[3]]);
+    let len = usize::from(u16::from_be_bytes([header[4], header[5]])).checked_sub(8)?;
+    frame.get(udp + 8..udp + 8 + len)?;
+    Some((SocketAddrV4::new(dst, port), udp + 8, len))
+}
+
+/// One multicast line's view of the XDP socket.
+///
+/// Each payload stays in the UMEM until the next read, so drivers parse it
+/// in place.
+pub(super) struct Tap {
+    socket: Rc<RefCell<Socket>>,
+    id: usize,
+    /// The payload last handed out.
+    current: Option<(u64, u32)>,
+}
+
+impl Tap {
+    /// Returns the next payload for this line, if any has arrived.
+    pub(super) fn recv(&mut self) -> Option<&[u8]> {
+        let mut socket = self.socket.borrow_mut();
+        if let Some((offset, _)) = self.current.take() {
+            socket.release(offset);
+        }
+        self.current = socket.next(self.id);
+        drop(socket);
+        self.current?;
+        Some(self.last())
+    }
+
+    /// The payload last returned by [Tap::recv], or an empty slice.
+    pub(super) fn last(&self) -> &[u8] {
+        let Some((offset, len)) = self.current else {
+            return &[];
+        };
+        let data = self.socket.borrow().umem.at::<u8>(offset);
+        // SAFETY: the frame is not recycled until this tap releases it on
+        // its next read or drop, and the UMEM outlives the tap's `Rc`
+        unsafe { std::slice::from_raw_parts(data, len as usize) }
+    }
+}
+
+impl Drop for Tap {
+    fn drop(&mut self) {
+        let mut socket = self.socket.borrow_mut();
+        if let Some((offset, _)) = self.current.take() {
+            socket.release(offset);
+        }
+        socket.close_tap(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An Ethernet frame carrying `payload` to `group`, optionally VLAN-tagged.
+    fn frame(group: SocketAddrV4, payload: &[u8], vlan: bool) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        if vlan {
+            frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x64]);
+        }
+        frame.extend_from_slice(&[0x08, 0x00]);
+        let total = (20 + 8 + payload.len()) as u16;
+        frame.extend_from_slice(&[0x45, 0, (total >> 8) as u8, total as u8, 0, 0, 0x40, 0, 64, 17, 0, 0]);
+        frame.extend_from_slice(&[10, 0, 0, 1]);
+        frame.extend_from_slice(&group.ip().octets());
+        frame.extend_from_slice(&1234u16.to_be_bytes());
+        frame.extend_from_slice(&group.port().to_be_bytes());
+        frame.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_udp_payload() {
+        let group = SocketAddrV4::new(Ipv4Addr::new(224, 0, 31, 1), 14310);
+        for vlan in [false, true] {
+            let frame = frame(group, b"packet", vlan);
+            let (dst, start, len) = udp_payload(&frame).unwrap();
+            assert_eq!(dst, group);
+            assert_eq!(&frame[start..start + len], b"packet");
+        }
+
+        let mut fragment = frame(group, b"packet", false);
+        fragment[14 + 6] = 0x20; // More fragments
+        assert_eq!(udp_payload(&fragment), None);
+
+        let mut truncated = frame(group, b"packet", false);
+        truncated.truncate(truncated.len() - 1);
+        assert_eq!(udp_payload(&truncated), None);
+    }
+}
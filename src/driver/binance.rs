@@ -0,0 +1,1070 @@
+//! Binance spot, USDT-margined and COIN-margined futures diff-depth streams (`<symbol>@depth`),
+//! partial-depth streams (`<symbol>@depth20`), top-of-book streams (`<symbol>@bookTicker`)
+//! and user-data streams (`/ws/<listenKey>`).
+
+use crate::broker::instruments::Precision;
+use crate::broker::{Feed, PrivateChannel, ProductType, SymbolKey};
+use crate::connector::auth;
+use crate::connector::rate_limit::RateLimit;
+use crate::driver::{
+    DriverError, ExchangeDriver, Scales, apply_level, find, find_str, find_u64, for_each_level, parse_qty, rest_get,
+    rest_post_with_header, rest_put_with_header, schema,
+};
+use crate::json;
+use crate::model::{L1FriendlyBook, Level, UpdateCause};
+#[cfg(feature = "simd-json")]
+use crate::driver::parse_i64;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+const WS_URL: &str = "wss://stream.binance.com:9443/stream";
+/// The same spot streams on port 443, then the market-data-only mirror.
+const WS_BACKUP_URLS: [&str; 2] = [
+    "wss://stream.binance.com:443/stream",
+    "wss://data-stream.binance.vision/stream",
+];
+const REST_URL: &str = "https://api.binance.com/api/v3/depth";
+const FUTURES_WS_URL: &str = "wss://fstream.binance.com/stream";
+const FUTURES_REST_URL: &str = "https://fapi.binance.com/fapi/v1/depth";
+const COIN_FUTURES_WS_URL: &str = "wss://dstream.binance.com/stream";
+const COIN_FUTURES_REST_URL: &str = "https://dapi.binance.com/dapi/v1/depth";
+const INFO_URL: &str = "https://api.binance.com/api/v3/exchangeInfo";
+const FUTURES_INFO_URL: &str = "https://fapi.binance.com/fapi/v1/exchangeInfo";
+const COIN_FUTURES_INFO_URL: &str = "https://dapi.binance.com/dapi/v1/exchangeInfo";
+const LISTEN_KEY_URL: &str = "https://api.binance.com/api/v3/userDataStream";
+const FUTURES_LISTEN_KEY_URL: &str = "https://fapi.binance.com/fapi/v1/listenKey";
+const COIN_FUTURES_LISTEN_KEY_URL: &str = "https://dapi.binance.com/dapi/v1/listenKey";
+
+/// Listen keys expire an hour after their last keepalive.
+const LISTEN_KEY_KEEPALIVE: Duration = Duration::from_secs(30 * 60);
+
+/// Fixed-point scale applied to Binance prices, unless the symbol's
+/// `tickSize` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to Binance quantities, unless the symbol's
+/// `stepSize` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Keys packed onto one combined-stream connection.
+pub const STREAMS_PER_CONNECTION: usize = 200;
+
+/// Update speeds of spot depth streams in milliseconds, the default first.
+const SPOT_SPEEDS: [u16; 2] = [1000, 100];
+
+/// Update speeds of futures depth streams in milliseconds, the default first.
+const FUTURES_SPEEDS: [u16; 3] = [250, 500, 100];
+
+/// Sizes of the partial-depth streams.
+const PARTIAL_LEVELS: [u8; 3] = [5, 10, 20];
+
+/// Driver for the Binance spot and futures depth streams.
+///
+/// Keys connect to the combined-stream endpoint of their market and are
+/// added and removed with `SUBSCRIBE` and `UNSUBSCRIBE` requests, so up to
+/// [STREAMS_PER_CONNECTION] of them share one socket. Every event arrives
+/// wrapped as `{"stream":...,"data":...}` and is routed by its stream name.
+///
+/// USD-quoted futures keys (`BTC-USD`, `BTCUSD_240628`) connect to the
+/// COIN-margined `dstream.binance.com`; other perpetual keys connect to the
+/// USDT-margined `fstream.binance.com`.
+///
+/// Every depth book is synchronised against a REST snapshot. On the first
+/// update the driver fetches the market's depth endpoint; updates that
+/// arrive meanwhile wait in the socket. Updates already covered by the
+/// snapshot's `lastUpdateId` are dropped and the first applied update must
+/// straddle it. From then on spot updates must start at the previous `u`
+/// plus one, while futures updates must carry the previous `u` as `pu`.
+///
+/// COIN-margined quantities are contract counts. The driver looks up the
+/// contract's `contractSize` before connecting and stores `qty` as the USD
+/// notional (`contracts × contractSize`) instead.
+///
+/// [Feed::DepthEvery] keys pick the diff-depth stream of the nearest speed
+/// and sync the same way.
+///
+/// [Feed::Top] keys connect to the smallest partial-depth stream holding
+/// the requested levels, `depth5`, `depth10` or `depth20`. Each event
+/// replaces the whole book and no snapshot is fetched.
+///
+/// [Feed::Bbo] keys connect to `bookTicker` instead. Each event overwrites
+/// `bids[0]` and `asks[0]` directly; no snapshot is fetched.
+///
+/// Partial-depth and `bookTicker` events whose update id is not newer than
+/// the last applied one are dropped.
+///
+/// [Feed::Private] keys create a listen key with the exchange's API key
+/// before connecting, then read the raw `/ws/<listenKey>` stream of their
+/// market, which carries every account event; only those of the key's
+/// channel are reported. The listen key is kept alive every 30 minutes
+/// while idle, and a `listenKeyExpired` event reconnects with a new one.
+pub struct BinanceDriver {
+    market: Market,
+    feed: Feed,
+    symbol: String,
+    /// The API key and listen key of a private stream.
+    api_key: String,
+    listen_key: String,
+    next_keepalive: Instant,
+    /// USD value of one contract; 1 outside COIN-margined markets.
+    contract_size: i64,
+    scales: Scales,
+    /// `u` of the last applied update, or the snapshot's `lastUpdateId`.
+    last_update_id: Option<u64>,
+    /// Whether an update has been applied on top of the snapshot yet.
+    synced: bool,
+    /// Whether the last frame replaced the book, with the REST snapshot
+    /// or a partial-depth event.
+    replaced: bool,
+    /// Full parser for snapshots and replies.
+    #[cfg(feature = "simd-json")]
+    parser: json::Parser,
+}
+
+impl BinanceDriver {
+    pub fn new() -> Self {
+        Self {
+            market: Market::Spot,
+            feed: Feed::Depth,
+            symbol: String::new(),
+            api_key: String::new(),
+            listen_key: String::new(),
+            next_keepalive: Instant::now(),
+            contract_size: 1,
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            last_update_id: None,
+            synced: false,
+            replaced: false,
+            #[cfg(feature = "simd-json")]
+            parser: json::Parser::new(),
+        }
+    }
+
+    /// Replaces the book with a REST `depth` response.
+    fn apply_snapshot(&mut self, body: &[u8], book: &mut L1FriendlyBook) -> Result<(), DriverError> {
+        book.clear_sides();
+
+        #[cfg(feature = "simd-json")]
+        let id = {
+            let (contract_size, scales) = (self.contract_size, self.scales);
+            self.parser
+                .parse(body, |snapshot| read_snapshot(snapshot, book, contract_size, scales))
+                .map_err(|_| DriverError::Malformed)??
+        };
+        #[cfg(not(feature = "simd-json"))]
+        let id = {
+            let fields = DepthFields::scan(body);
+            fields.apply_sides(body, book, self.contract_size, self.scales)?;
+            fields.id(body, &fields.last)?
+        };
+
+        self.last_update_id = Some(id);
+        self.synced = false;
+        self.replaced = true;
+        Ok(())
+    }
+
+    /// Applies a `depthUpdate` on top of the snapshot, fetching it first if needed.
+    fn apply_update(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let snapshotted = self.last_update_id.is_none();
+        if snapshotted {
+            let url = match self.market {
+                Market::Spot => format!("{REST_URL}?symbol={}&limit=5000", self.symbol),
+                Market::UsdFutures => format!("{FUTURES_REST_URL}?symbol={}&limit=1000", self.symbol),
+                Market::CoinFutures => format!("{COIN_FUTURES_REST_URL}?symbol={}&limit=1000", self.symbol),
+            };
+            let body = rest_get(&url)?;
+            self.apply_snapshot(body.as_bytes(), book)?;
+        }
+        self.apply_delta(msg, book, snapshotted)
+    }
+
+    /// Applies a `depthUpdate` on top of the snapshot in `book`.
+    ///
+    /// An update the snapshot already covers changes nothing, but the book
+    /// still changed if `snapshotted`, as the snapshot was written for it.
+    fn apply_delta(&mut self, msg: &[u8], book: &mut L1FriendlyBook, snapshotted: bool) -> Result<bool, DriverError> {
+        let last = self.last_update_id.unwrap_or(0);
+
+        let fields = DepthFields::scan(msg);
+        let first = fields.id(msg, &fields.first)?;
+        let final_id = fields.id(msg, &fields.last)?;
+
+        if self.synced {
+            // Spot ids are contiguous; futures link each update to the last via `pu`
+            let (expected, received) = match self.market {
+                Market::Spot => (last + 1, first),
+                _ => (last, fields.id(msg, &fields.previous)?),
+            };
+            if received != expected {
+                return Err(DriverError::SequenceGap { expected, received });
+            }
+        } else {
+            // The first update must contain `lastUpdateId + 1` on spot and
+            // `lastUpdateId` itself on futures
+            let straddle = match self.market {
+                Market::Spot => last + 1,
+                _ => last,
+            };
+            if final_id < straddle {
+                return Ok(snapshotted);
+            }
+            if first > straddle {
+                return Err(DriverError::SequenceGap {
+                    expected: straddle,
+                    received: first,
+                });
+            }
+        }
+
+        fields.apply_sides(msg, book, self.contract_size, self.scales)?;
+        self.last_update_id = Some(final_id);
+        self.synced = true;
+        Ok(true)
+    }
+
+    /// Writes a `bookTicker` event over the top level of each side.
+    fn apply_book_ticker(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let event = event(msg);
+        let id = json::field(msg, event, b"u").ok_or(DriverError::Malformed)?;
+        let id = u64::try_from(json::number(msg, id, 0)?).map_err(|_| DriverError::Malformed)?;
+        if self.last_update_id.is_some_and(|last| id <= last) {
+            return Ok(false);
+        }
+
+        book.bids[0] = ticker_level(msg, event, (b"b", b"B"), self.contract_size, self.scales)?;
+        book.asks[0] = ticker_level(msg, event, (b"a", b"A"), self.contract_size, self.scales)?;
+        self.last_update_id = Some(id);
+        Ok(true)
+    }
+
+    /// Creates the listen key of a private stream.
+    fn open_user_stream(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        let credentials = auth::credentials_for(key.exchange)
+            .ok_or_else(|| DriverError::Rejected("no credentials".to_string()))?;
+        let body = rest_post_with_header(listen_key_url(self.market), "X-MBX-APIKEY", &credentials.api_key)?;
+        self.listen_key = schema::decode::<schema::binance::ListenKey>(body.as_bytes())?
+            .listen_key
+            .into_owned();
+        self.api_key = credentials.api_key;
+        self.next_keepalive = Instant::now() + LISTEN_KEY_KEEPALIVE;
+        Ok(())
+    }
+
+    /// Returns the reason given by an error reply.
+    ///
+    /// ```json
+    /// {"error":{"code":2,"msg":"Invalid request: unknown variant"},"id":1}
+    /// ```
+    fn rejection(&mut self, msg: &[u8]) -> String {
+        #[cfg(feature = "simd-json")]
+        let reason = {
+            use simd_json::prelude::*;
+            self.parser
+                .parse(msg, |reply| Some(reply.get("error")?.get("msg")?.as_str()?.to_string()))
+                .ok()
+                .flatten()
+        };
+        #[cfg(not(feature = "simd-json"))]
+        let reason = schema::decode::<schema::binance::SubscribeAck>(msg)
+            .ok()
+            .and_then(|ack| ack.error)
+            .map(|error| error.msg.into_owned());
+        reason.unwrap_or_else(|| "request failed".to_string())
+    }
+
+    /// Replaces the book with a partial-depth event.
+    ///
+    /// Spot events carry `lastUpdateId` and `bids`/`asks`; futures events
+    /// look like a `depthUpdate` whose `b`/`a` hold the top levels.
+    fn apply_partial(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if find(msg, br#""result":"#).is_some() {
+            return Ok(false);
+        }
+        let fields = DepthFields::scan(msg);
+        let id = fields.id(msg, &fields.last)?;
+        if self.last_update_id.is_some_and(|last| id <= last) {
+            return Ok(false);
+        }
+
+        book.clear_sides();
+        fields.apply_sides(msg, book, self.contract_size, self.scales)?;
+        self.last_update_id = Some(id);
+        self.replaced = true;
+        Ok(true)
+    }
+}
+
+/// Reads a REST `depth` response into `book`, returning its `lastUpdateId`.
+#[cfg(feature = "simd-json")]
+fn read_snapshot(
+    snapshot: simd_json::tape::Value,
+    book: &mut L1FriendlyBook,
+    contract_size: i64,
+    scales: Scales,
+) -> Result<u64, DriverError> {
+    use simd_json::prelude::*;
+
+    for (side, tombstones, key, descending) in [
+        (&mut book.bids, &mut book.bid_tombstones, "bids", true),
+        (&mut book.asks, &mut book.ask_tombstones, "asks", false),
+    ] {
+        let levels = snapshot.get(key).and_then(|levels| levels.as_array()).ok_or(DriverError::Malformed)?;
+        for level in levels.iter() {
+            let (Some(price), Some(qty)) = (level.get_idx(0), level.get_idx(1)) else {
+                return Err(DriverError::Malformed);
+            };
+            let price = price.as_str().ok_or(DriverError::Malformed)?;
+            let qty = qty.as_str().ok_or(DriverError::Malformed)?;
+            let (price, _) = parse_i64(price.as_bytes(), 0, scales.price)?;
+            let (qty, _) = parse_qty(qty.as_bytes(), 0, scales.qty)?;
+            apply_level(side, tombstones, price, qty * contract_size, descending);
+        }
+    }
+    snapshot.get("lastUpdateId").and_then(|id| id.as_u64()).ok_or(DriverError::Malformed)
+}
+
+/// Returns where the fields of an event start: inside `data` on combined
+/// streams, or at the message itself.
+fn event(msg: &[u8]) -> usize {
+    json::field(msg, 0, b"data").map_or(0, |data| data.start)
+}
+
+/// Where the fields of a depth event or snapshot sit, found in one pass.
+#[derive(Default)]
+struct DepthFields {
+    /// `U`.
+    first: Option<Range<usize>>,
+    /// `u`, or `lastUpdateId` in snapshots and spot partial depth.
+    last: Option<Range<usize>>,
+    /// `pu`, on futures.
+    previous: Option<Range<usize>>,
+    /// `b` or `bids`.
+    bids: Option<Range<usize>>,
+    /// `a` or `asks`.
+    asks: Option<Range<usize>>,
+}
+
+impl DepthFields {
+    fn scan(msg: &[u8]) -> Self {
+        let mut fields = Self::default();
+        for field in json::fields(msg, event(msg)) {
+            let slot = match field.key {
+                b"U" => &mut fields.first,
+                b"u" | b"lastUpdateId" => &mut fields.last,
+                b"pu" => &mut fields.previous,
+                b"b" | b"bids" => &mut fields.bids,
+                b"a" | b"asks" => &mut fields.asks,
+                _ => continue,
+            };
+            *slot = Some(field.value);
+        }
+        fields
+    }
+
+    /// Reads the update id at `value`.
+    fn id(&self, msg: &[u8], value: &Option<Range<usize>>) -> Result<u64, DriverError> {
+        let value = value.clone().ok_or(DriverError::Malformed)?;
+        u64::try_from(json::number(msg, value, 0)?).map_err(|_| DriverError::Malformed)
+    }
+
+    /// Applies the bid and ask arrays.
+    ///
+    /// Quantities are multiplied by `contract_size`.
+    fn apply_sides(&self, msg: &[u8], book: &mut L1FriendlyBook, contract_size: i64, scales: Scales) -> Result<(), DriverError> {
+        let bids = self.bids.as_ref().ok_or(DriverError::Malformed)?;
+        for_each_level(msg, bids.start, scales.price, scales.qty, |price, qty| {
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty * contract_size, true);
+        })?;
+
+        let asks = self.asks.as_ref().ok_or(DriverError::Malformed)?;
+        for_each_level(msg, asks.start, scales.price, scales.qty, |price, qty| {
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty * contract_size, false);
+        })?;
+        Ok(())
+    }
+}
+
+/// Reads one side of a `bookTicker` event.
+fn ticker_level(
+    msg: &[u8],
+    event: usize,
+    keys: (&[u8], &[u8]),
+    contract_size: i64,
+    scales: Scales,
+) -> Result<Level, DriverError> {
+    let price = json::field(msg, event, keys.0).ok_or(DriverError::Malformed)?;
+    let qty = json::field(msg, event, keys.1).ok_or(DriverError::Malformed)?;
+    let price = json::number(msg, price, scales.price)?;
+    // `B` and `A` are always quoted
+    let (qty, _) = parse_qty(msg, qty.start + 1, scales.qty)?;
+    Ok(Level {
+        price,
+        qty: qty * contract_size,
+    })
+}
+
+impl Default for BinanceDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `BTC-USDT` into Binance's `btcusdt` form.
+pub fn stream_symbol(symbol: &str) -> String {
+    symbol
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Returns the user-data events reported for `channel`, spot then futures.
+fn private_events(channel: PrivateChannel) -> &'static [&'static str] {
+    match channel {
+        PrivateChannel::Orders => &["executionReport", "ORDER_TRADE_UPDATE"],
+        PrivateChannel::Balances => &["outboundAccountPosition", "balanceUpdate", "ACCOUNT_UPDATE"],
+        PrivateChannel::Positions => &["ACCOUNT_UPDATE"],
+    }
+}
+
+fn listen_key_url(market: Market) -> &'static str {
+    match market {
+        Market::Spot => LISTEN_KEY_URL,
+        Market::UsdFutures => FUTURES_LISTEN_KEY_URL,
+        Market::CoinFutures => COIN_FUTURES_LISTEN_KEY_URL,
+    }
+}
+
+/// Returns the stream carrying `key`, e.g. `btcusdt@depth` or `btcusd_perp@bookTicker`.
+pub fn stream_name(key: &SymbolKey) -> String {
+    let symbol = match market(key) {
+        Market::CoinFutures => coin_contract_symbol(key).to_ascii_lowercase(),
+        _ => stream_symbol(&key.symbol),
+    };
+    let speeds: &[u16] = match market(key) {
+        Market::Spot => &SPOT_SPEEDS,
+        Market::UsdFutures | Market::CoinFutures => &FUTURES_SPEEDS,
+    };
+    // The default speed is the one without a suffix
+    let speed = |interval_ms: u16| match speeds.iter().min_by_key(|speed| speed.abs_diff(interval_ms)) {
+        Some(&speed) if speed != speeds[0] => format!("@{speed}ms"),
+        _ => String::new(),
+    };
+    match key.feed {
+        Feed::Depth => format!("{symbol}@depth"),
+        Feed::DepthEvery { interval_ms } => format!("{symbol}@depth{}", speed(interval_ms)),
+        Feed::Top { levels, interval_ms } => {
+            let levels = PARTIAL_LEVELS.iter().find(|&&size| size >= levels).unwrap_or(&20);
+            format!("{symbol}@depth{levels}{}", speed(interval_ms))
+        }
+        Feed::Bbo => format!("{symbol}@bookTicker"),
+        // A listen key's stream carries nothing else
+        Feed::Private(_) => String::new(),
+    }
+}
+
+/// Which Binance product family a key trades on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Market {
+    Spot,
+    UsdFutures,
+    CoinFutures,
+}
+
+fn market(key: &SymbolKey) -> Market {
+    match key.product {
+        ProductType::Spot | ProductType::VanillaOption => Market::Spot,
+        ProductType::Perpetual | ProductType::Future => {
+            if key.symbol.contains('_') || stream_symbol(&key.symbol).ends_with("usd") {
+                Market::CoinFutures
+            } else {
+                Market::UsdFutures
+            }
+        }
+    }
+}
+
+/// Converts a COIN-margined key into its contract symbol, e.g. `BTC-USD` → `BTCUSD_PERP`.
+///
+/// Delivery contracts are expected to carry their expiry already
+/// (`BTCUSD_240628`) and are only upper-cased.
+pub fn coin_contract_symbol(key: &SymbolKey) -> String {
+    if key.symbol.contains('_') {
+        return key.symbol.to_ascii_uppercase();
+    }
+    format!("{}_PERP", stream_symbol(&key.symbol).to_ascii_uppercase())
+}
+
+/// Finds the `contractSize` of `symbol` in a `dapi/v1/exchangeInfo` response.
+fn contract_size(info: &[u8], symbol: &str) -> Option<i64> {
+    let pattern = format!(r#""symbol":"{symbol}""#);
+    let at = find(info, pattern.as_bytes())?;
+    find_u64(&info[at..], "contractSize").and_then(|size| i64::try_from(size).ok())
+}
+
+/// Reads the `tickSize` and `stepSize` of `symbol` off an `exchangeInfo`
+/// response.
+///
+/// ```json
+/// {"symbols":[{"symbol":"BTCUSDT","filters":[{"filterType":"PRICE_FILTER","tickSize":"0.01000000"},{"filterType":"LOT_SIZE","stepSize":"0.00001000"}]}]}
+/// ```
+fn precision(info: &[u8], symbol: &str) -> Result<Option<Precision>, DriverError> {
+    let info: schema::binance::ExchangeInfo = schema::decode(info)?;
+    let Some(entry) = info.symbols.iter().find(|entry| entry.symbol == symbol) else {
+        return Ok(None);
+    };
+    let size = |filter_type: &str| entry.filters.iter().find(|filter| filter.filter_type == filter_type);
+    let tick = size("PRICE_FILTER").and_then(|filter| filter.tick_size.as_deref());
+    let step = size("LOT_SIZE").and_then(|filter| filter.step_size.as_deref());
+    match (tick, step) {
+        (Some(tick), Some(step)) => Precision::from_sizes(tick.as_bytes(), step.as_bytes()).map(Some).ok_or(DriverError::Malformed),
+        _ => Ok(None),
+    }
+}
+
+impl ExchangeDriver for BinanceDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.market = market(key);
+        self.feed = key.feed;
+        self.contract_size = 1;
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        if self.market == Market::CoinFutures {
+            self.symbol = coin_contract_symbol(key);
+            let info = rest_get(COIN_FUTURES_INFO_URL)?;
+            self.contract_size = contract_size(info.as_bytes(), &self.symbol).ok_or(DriverError::Malformed)?;
+        } else {
+            self.symbol = stream_symbol(&key.symbol).to_ascii_uppercase();
+        }
+        self.last_update_id = None;
+        self.synced = false;
+        if let Feed::Private(_) = key.feed {
+            self.open_user_stream(key)?;
+        }
+        Ok(())
+    }
+
+    /// Looks the symbol up in its market's `exchangeInfo`. Spot can be asked
+    /// for one symbol; futures markets always list every one.
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        let (info, symbol) = match market(key) {
+            Market::Spot => {
+                let symbol = stream_symbol(&key.symbol).to_ascii_uppercase();
+                (rest_get(&format!("{INFO_URL}?symbol={symbol}"))?, symbol)
+            }
+            Market::UsdFutures => (rest_get(FUTURES_INFO_URL)?, stream_symbol(&key.symbol).to_ascii_uppercase()),
+            Market::CoinFutures => (rest_get(COIN_FUTURES_INFO_URL)?, coin_contract_symbol(key)),
+        };
+        precision(info.as_bytes(), &symbol)
+    }
+
+    /// Private keys connect to the raw stream of their listen key, which
+    /// is only known once the handshake has run.
+    fn endpoint(&self, key: &SymbolKey) -> String {
+        let url = match market(key) {
+            Market::Spot => WS_URL,
+            Market::UsdFutures => FUTURES_WS_URL,
+            Market::CoinFutures => COIN_FUTURES_WS_URL,
+        };
+        match key.feed {
+            Feed::Private(_) => format!("{}/ws/{}", url.trim_end_matches("/stream"), self.listen_key),
+            _ => url.to_string(),
+        }
+    }
+
+    fn backup_endpoints(&self, key: &SymbolKey) -> Vec<String> {
+        match (market(key), key.feed) {
+            (Market::Spot, Feed::Private(_)) | (Market::UsdFutures | Market::CoinFutures, _) => Vec::new(),
+            (Market::Spot, _) => WS_BACKUP_URLS.iter().map(|url| url.to_string()).collect(),
+        }
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        match key.feed {
+            Feed::Private(_) => None,
+            _ => self.subscribe_batch_msg(&[key]),
+        }
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        match key.feed {
+            Feed::Private(_) => None,
+            _ => Some(request("UNSUBSCRIBE", &[key])),
+        }
+    }
+
+    fn streams_per_connection(&self) -> usize {
+        STREAMS_PER_CONNECTION
+    }
+
+    /// Binance disconnects clients sending more than five messages a second.
+    fn rate_limit(&self) -> Option<RateLimit> {
+        Some(RateLimit { per_second: 5, burst: 5 })
+    }
+
+    fn stream_name(&self, key: &SymbolKey) -> String {
+        stream_name(key)
+    }
+
+    fn frame_stream<'a>(&self, msg: &'a [u8]) -> Option<&'a [u8]> {
+        const KEY: &[u8] = br#"{"stream":""#;
+        let rest = msg.strip_prefix(KEY)?;
+        let len = rest.iter().position(|&b| b == b'"')?;
+        Some(&rest[..len])
+    }
+
+    fn subscribe_batch_msg(&self, keys: &[&SymbolKey]) -> Option<String> {
+        Some(request("SUBSCRIBE", keys))
+    }
+
+    /// Reads the event time, in milliseconds; spot `bookTicker` events
+    /// carry none.
+    fn exchange_ts(&self, msg: &[u8]) -> Option<u64> {
+        find_u64(msg, "E")?.checked_mul(1_000_000)
+    }
+
+    /// Returns the final update id (`u`) of the last event applied, or the snapshot's `lastUpdateId`.
+    fn update_id(&self) -> Option<u64> {
+        self.last_update_id
+    }
+
+    /// Partial-depth events, and updates the REST snapshot was fetched
+    /// for, replace the book.
+    fn update_cause(&self, _msg: &[u8]) -> UpdateCause {
+        if self.replaced { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    /// Applies a `depthUpdate` event.
+    ///
+    /// ```json
+    /// {"e":"depthUpdate","E":1,"s":"BTCUSDT","U":157,"u":160,"b":[["0.0024","10"]],"a":[["0.0026","100"]]}
+    /// {"e":"depthUpdate","E":1,"T":1,"s":"BTCUSDT","U":157,"u":160,"pu":149,"b":[["0.0024","10"]],"a":[]}
+    /// ```
+    ///
+    /// or, for [Feed::Bbo], a `bookTicker` event.
+    ///
+    /// ```json
+    /// {"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        // Replies to SUBSCRIBE and UNSUBSCRIBE
+        self.replaced = false;
+        if find(msg, br#""error":"#).is_some() {
+            return Err(DriverError::Rejected(self.rejection(msg)));
+        }
+        if find(msg, br#""result":"#).is_some() {
+            return Ok(false);
+        }
+
+        self.scales.stamp(book);
+        match self.feed {
+            Feed::Bbo => return self.apply_book_ticker(msg, book),
+            Feed::Top { .. } => return self.apply_partial(msg, book),
+            Feed::Private(_) => return Ok(false),
+            Feed::Depth | Feed::DepthEvery { .. } => {}
+        }
+
+        if find(msg, br#""e":"depthUpdate""#).is_none() {
+            return Ok(false);
+        }
+
+        self.apply_update(msg, book)
+    }
+
+    fn private_streams(&self) -> bool {
+        true
+    }
+
+    /// Picks the key's channel out of the user-data events.
+    ///
+    /// ```json
+    /// {"e":"executionReport","E":1499405658658,"s":"ETHBTC","c":"mUvoqJxFIILMdfAW5iGSOW","S":"BUY","o":"LIMIT","x":"NEW","X":"NEW"}
+    /// ```
+    fn parse_private(&mut self, msg: &[u8]) -> Result<bool, DriverError> {
+        let Feed::Private(channel) = self.feed else {
+            return Ok(false);
+        };
+        match find_str(msg, "e") {
+            Some("listenKeyExpired") => Err(DriverError::Rejected("listen key expired".to_string())),
+            Some(event) => Ok(private_events(channel).contains(&event)),
+            None => Ok(false),
+        }
+    }
+
+    /// Keeps a private stream's listen key alive; nothing is sent on the
+    /// socket.
+    fn pending_reply(&mut self) -> Option<String> {
+        if let Feed::Private(_) = self.feed
+            && Instant::now() >= self.next_keepalive
+        {
+            self.next_keepalive = Instant::now() + LISTEN_KEY_KEEPALIVE;
+            let url = match self.market {
+                Market::Spot => format!("{LISTEN_KEY_URL}?listenKey={}", self.listen_key),
+                market => listen_key_url(market).to_string(),
+            };
+            // A key that lapses anyway ends with `listenKeyExpired`
+            let _ = rest_put_with_header(&url, "X-MBX-APIKEY", &self.api_key);
+        }
+        None
+    }
+}
+
+/// Builds a `SUBSCRIBE` or `UNSUBSCRIBE` request for the streams of `keys`.
+fn request(method: &str, keys: &[&SymbolKey]) -> String {
+    let params: Vec<String> = keys.iter().map(|key| format!(r#""{}""#, stream_name(key))).collect();
+    format!(r#"{{"method":"{method}","params":[{}],"id":1}}"#, params.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed, ProductType};
+
+    #[test]
+    fn test_endpoint() {
+        let key = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "BTC-USDT".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let driver = BinanceDriver::new();
+        assert_eq!(driver.endpoint(&key), "wss://stream.binance.com:9443/stream");
+        assert_eq!(driver.backup_endpoints(&key)[1], "wss://data-stream.binance.vision/stream");
+        assert_eq!(
+            driver.subscribe_msg(&key).unwrap(),
+            r#"{"method":"SUBSCRIBE","params":["btcusdt@depth"],"id":1}"#
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let info = br#"{"symbols":[{"symbol":"ETHUSDT","filters":[]},{"symbol":"BTCUSDT","filters":[{"filterType":"PRICE_FILTER","minPrice":"0.01000000","tickSize":"0.01000000"},{"filterType":"LOT_SIZE","stepSize":"0.00001000"}]}]}"#;
+        assert_eq!(precision(info, "BTCUSDT"), Ok(Precision::from_decimals(2, 5)));
+        assert_eq!(precision(info, "ETHUSDT"), Ok(None));
+        assert_eq!(precision(info, "XRPUSDT"), Ok(None));
+
+        // Symbols with a known precision are parsed at its scales
+        let key = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "PRECISION-USDT".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Bbo,
+        };
+        crate::broker::instruments::register(&key, Precision::from_decimals(2, 5).unwrap());
+        let mut driver = BinanceDriver::new();
+        driver.handshake(&key).unwrap();
+        crate::broker::instruments::remove(&key);
+        let mut book = L1FriendlyBook::new();
+        let msg = br#"{"u":1,"s":"PRECISIONUSDT","b":"25.35000000","B":"31.21000000","a":"25.36000000","A":"40.66000000"}"#;
+        assert_eq!(driver.parse_message(msg, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 2535, qty: 3_121_000 });
+        assert_eq!((book.price_exponent, book.qty_exponent), (-2, -5));
+    }
+
+    #[test]
+    fn test_combined_streams() {
+        let depth = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "BTC-USDT".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let ticker = SymbolKey {
+            symbol: "BNB-USDT".to_string(),
+            feed: Feed::Bbo,
+            ..depth.clone()
+        };
+        let mut driver = BinanceDriver::new();
+        assert_eq!(
+            driver.subscribe_batch_msg(&[&depth, &ticker]).unwrap(),
+            r#"{"method":"SUBSCRIBE","params":["btcusdt@depth","bnbusdt@bookTicker"],"id":1}"#
+        );
+
+        let event = br#"{"stream":"bnbusdt@bookTicker","data":{"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}}"#;
+        assert_eq!(driver.frame_stream(event), Some(&b"bnbusdt@bookTicker"[..]));
+
+        let mut book = L1FriendlyBook::new();
+        let ack = br#"{"result":null,"id":1}"#;
+        assert_eq!(driver.frame_stream(ack), None);
+        assert_eq!(driver.parse_message(ack, &mut book), Ok(false));
+        let error = br#"{"error":{"code":2,"msg":"Invalid request: unknown variant"},"id":1}"#;
+        assert_eq!(
+            driver.parse_message(error, &mut book),
+            Err(DriverError::Rejected("Invalid request: unknown variant".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_depth_update() {
+        let mut driver = BinanceDriver::new();
+        let mut book = L1FriendlyBook::new();
+        driver.apply_snapshot(br#"{"lastUpdateId":156,"bids":[],"asks":[]}"#, &mut book).unwrap();
+        let msg = br#"{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":157,"u":160,"b":[["0.0024","10"],["0.0025","1"]],"a":[["0.0026","100"]]}"#;
+
+        assert_eq!(driver.parse_message(msg, &mut book), Ok(true));
+        assert_eq!(book.bids[0].price, 250_000);
+        assert_eq!(book.bids[1].price, 240_000);
+        assert_eq!(book.bids[1].qty, 1_000_000_000);
+        assert_eq!(book.asks[0].price, 260_000);
+        assert_eq!(driver.update_id(), Some(160));
+        assert_eq!(driver.exchange_ts(msg), Some(1_000_000));
+
+        let removal = br#"{"e":"depthUpdate","E":2,"s":"BTCUSDT","U":161,"u":161,"b":[["0.0025","0.00000000"]],"a":[]}"#;
+        assert_eq!(driver.parse_message(removal, &mut book), Ok(true));
+        L1FriendlyBook::compact(&mut book.bids, &mut book.bid_tombstones);
+        assert_eq!(book.bids[0].price, 240_000);
+    }
+
+    #[test]
+    fn test_book_ticker() {
+        let key = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "BNB-USDT".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Bbo,
+        };
+        let mut driver = BinanceDriver::new();
+        let mut book = L1FriendlyBook::new();
+        driver.handshake(&key).unwrap();
+        assert_eq!(driver.stream_name(&key), "bnbusdt@bookTicker");
+
+        let msg = br#"{"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}"#;
+        assert_eq!(driver.parse_message(msg, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 2_535_190_000, qty: 3_121_000_000 });
+        assert_eq!(book.asks[0], Level { price: 2_536_520_000, qty: 4_066_000_000 });
+        assert_eq!(book.bids[1], Level::default());
+
+        let stale = br#"{"u":400900216,"s":"BNBUSDT","b":"25.00000000","B":"1.00000000","a":"26.00000000","A":"1.00000000"}"#;
+        assert_eq!(driver.parse_message(stale, &mut book), Ok(false));
+        assert_eq!(book.bids[0].price, 2_535_190_000);
+
+        // Combined streams wrap the event in `data`
+        let wrapped = br#"{"stream":"bnbusdt@bookTicker","data":{"u":400900218,"s":"BNBUSDT","b":"25.36","B":"1","a":"25.37","A":"2"}}"#;
+        assert_eq!(driver.parse_message(wrapped, &mut book), Ok(true));
+        assert_eq!(book.asks[0], Level { price: 2_537_000_000, qty: 200_000_000 });
+    }
+
+    #[test]
+    fn test_stream_flavors() {
+        let spot = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "BTC-USDT".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::DepthEvery { interval_ms: 0 },
+        };
+        let futures = SymbolKey {
+            product: ProductType::Perpetual,
+            ..spot.clone()
+        };
+        let with_feed = |key: &SymbolKey, feed| stream_name(&SymbolKey { feed, ..key.clone() });
+
+        assert_eq!(stream_name(&spot), "btcusdt@depth@100ms");
+        assert_eq!(with_feed(&spot, Feed::DepthEvery { interval_ms: 1000 }), "btcusdt@depth");
+        assert_eq!(with_feed(&futures, Feed::DepthEvery { interval_ms: 400 }), "btcusdt@depth@500ms");
+        assert_eq!(with_feed(&spot, Feed::Top { levels: 20, interval_ms: 100 }), "btcusdt@depth20@100ms");
+        assert_eq!(with_feed(&spot, Feed::Top { levels: 7, interval_ms: 1000 }), "btcusdt@depth10");
+        assert_eq!(with_feed(&futures, Feed::Top { levels: 50, interval_ms: 250 }), "btcusdt@depth20");
+    }
+
+    #[test]
+    fn test_user_data_stream() {
+        let key = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: String::new(),
+            product: ProductType::Spot,
+            feed: Feed::Private(PrivateChannel::Orders),
+        };
+        let mut driver = BinanceDriver::new();
+        driver.feed = key.feed;
+        driver.listen_key = "pqia91ma19a5s61cv6a81va65sdf19v8a65a1a5s61cv6a81va65sdf19v8a65a1".to_string();
+        assert_eq!(
+            driver.endpoint(&key),
+            "wss://stream.binance.com:9443/ws/pqia91ma19a5s61cv6a81va65sdf19v8a65a1a5s61cv6a81va65sdf19v8a65a1"
+        );
+        assert_eq!(driver.subscribe_msg(&key), None);
+
+        let order = br#"{"e":"executionReport","E":1499405658658,"s":"ETHBTC","x":"NEW"}"#;
+        let balance = br#"{"e":"outboundAccountPosition","E":1564034571105,"u":1564034571073,"B":[]}"#;
+        assert_eq!(driver.parse_private(order), Ok(true));
+        assert_eq!(driver.parse_private(balance), Ok(false));
+        assert!(matches!(
+            driver.parse_private(br#"{"e":"listenKeyExpired","E":1576653824250}"#),
+            Err(DriverError::Rejected(_))
+        ));
+    }
+
+    #[test]
+    fn test_partial_depth() {
+        let key = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "BNB-BTC".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Top { levels: 5, interval_ms: 100 },
+        };
+        let mut driver = BinanceDriver::new();
+        let mut book = L1FriendlyBook::new();
+        driver.handshake(&key).unwrap();
+
+        let first = br#"{"lastUpdateId":160,"bids":[["0.0024","10"],["0.0023","5"]],"asks":[["0.0026","100"]]}"#;
+        assert_eq!(driver.parse_message(first, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(first), UpdateCause::Snapshot);
+        assert_eq!(book.bids[1], Level { price: 230_000, qty: 500_000_000 });
+
+        // Levels missing from the next event are gone
+        let next = br#"{"lastUpdateId":161,"bids":[["0.0024","9"]],"asks":[["0.0026","90"]]}"#;
+        assert_eq!(driver.parse_message(next, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 240_000, qty: 900_000_000 });
+        assert_eq!(book.bids[1], Level::default());
+
+        assert_eq!(driver.parse_message(first, &mut book), Ok(false));
+        assert_eq!(book.bids[0].qty, 900_000_000);
+    }
+
+    #[test]
+    fn test_spot_sync_against_snapshot() {
+        let mut driver = BinanceDriver::new();
+        let mut book = L1FriendlyBook::new();
+        let snapshot = br#"{"lastUpdateId":160,"bids":[["0.0024","14.7"]],"asks":[["0.0026","3.6"]]}"#;
+        driver.apply_snapshot(snapshot, &mut book).unwrap();
+
+        // Fully covered by the snapshot
+        let stale = br#"{"e":"depthUpdate","E":1,"s":"BNBBTC","U":157,"u":160,"b":[["0.0024","10"]],"a":[]}"#;
+        assert_eq!(driver.parse_message(stale, &mut book), Ok(false));
+        assert_eq!(book.bids[0].qty, 1_470_000_000);
+
+        let first = br#"{"e":"depthUpdate","E":2,"s":"BNBBTC","U":158,"u":162,"b":[["0.0024","10"]],"a":[]}"#;
+        assert_eq!(driver.parse_message(first, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(first), UpdateCause::Delta);
+        assert_eq!(book.bids[0].qty, 1_000_000_000);
+
+        let gap = br#"{"e":"depthUpdate","E":3,"s":"BNBBTC","U":164,"u":165,"b":[],"a":[]}"#;
+        assert_eq!(
+            driver.parse_message(gap, &mut book),
+            Err(DriverError::SequenceGap { expected: 163, received: 164 })
+        );
+    }
+
+    #[test]
+    fn test_snapshot_fetched_for_a_stale_update_is_applied() {
+        let mut driver = BinanceDriver::new();
+        let mut book = L1FriendlyBook::new();
+        let snapshot = br#"{"lastUpdateId":160,"bids":[["0.0024","14.7"]],"asks":[["0.0026","3.6"]]}"#;
+        driver.apply_snapshot(snapshot, &mut book).unwrap();
+
+        // The update that had the snapshot fetched predates it
+        let stale = br#"{"e":"depthUpdate","E":1,"s":"BNBBTC","U":157,"u":160,"b":[["0.0024","10"]],"a":[]}"#;
+        assert_eq!(driver.apply_delta(stale, &mut book, true), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 240_000, qty: 1_470_000_000 });
+        assert_eq!(book.asks[0], Level { price: 260_000, qty: 360_000_000 });
+        assert_eq!(driver.apply_delta(stale, &mut book, false), Ok(false));
+    }
+
+    #[test]
+    fn test_spot_snapshot_must_be_straddled() {
+        let mut driver = BinanceDriver::new();
+        let mut book = L1FriendlyBook::new();
+        driver.apply_snapshot(br#"{"lastUpdateId":160,"bids":[],"asks":[]}"#, &mut book).unwrap();
+
+        let late = br#"{"e":"depthUpdate","E":1,"s":"BNBBTC","U":162,"u":165,"b":[],"a":[]}"#;
+        assert_eq!(
+            driver.parse_message(late, &mut book),
+            Err(DriverError::SequenceGap { expected: 161, received: 162 })
+        );
+    }
+
+    #[test]
+    fn test_futures_endpoint() {
+        let key = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "BTC-USDT".to_string(),
+            product: ProductType::Perpetual,
+            feed: Feed::Depth,
+        };
+        assert_eq!(BinanceDriver::new().endpoint(&key), "wss://fstream.binance.com/stream");
+    }
+
+    #[test]
+    fn test_futures_sync_against_snapshot() {
+        let key = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "BTC-USDT".to_string(),
+            product: ProductType::Perpetual,
+            feed: Feed::Depth,
+        };
+        let mut driver = BinanceDriver::new();
+        let mut book = L1FriendlyBook::new();
+        driver.handshake(&key).unwrap();
+
+        let snapshot = br#"{"lastUpdateId":1027024,"E":1,"T":1,"bids":[["4.00000000","431.00000000"]],"asks":[["4.00000200","12.00000000"]]}"#;
+        driver.apply_snapshot(snapshot, &mut book).unwrap();
+        assert_eq!(book.bids[0], Level { price: 400_000_000, qty: 43_100_000_000 });
+
+        // Entirely older than the snapshot
+        let stale = br#"{"e":"depthUpdate","E":2,"T":2,"s":"BTCUSDT","U":1027000,"u":1027020,"pu":1026990,"b":[["4.00000000","0"]],"a":[]}"#;
+        assert_eq!(driver.parse_message(stale, &mut book), Ok(false));
+        assert_eq!(book.bids[0].qty, 43_100_000_000);
+
+        let first = br#"{"e":"depthUpdate","E":3,"T":3,"s":"BTCUSDT","U":1027020,"u":1027030,"pu":1027019,"b":[["4.00000000","1"]],"a":[]}"#;
+        assert_eq!(driver.parse_message(first, &mut book), Ok(true));
+        assert_eq!(book.bids[0].qty, 100_000_000);
+
+        let next = br#"{"e":"depthUpdate","E":4,"T":4,"s":"BTCUSDT","U":1027031,"u":1027035,"pu":1027030,"b":[],"a":[["4.00000200","0"]]}"#;
+        assert_eq!(driver.parse_message(next, &mut book), Ok(true));
+        assert_eq!(book.ask_tombstones, 1);
+
+        let gap = br#"{"e":"depthUpdate","E":5,"T":5,"s":"BTCUSDT","U":1027040,"u":1027045,"pu":1027039,"b":[],"a":[]}"#;
+        assert_eq!(
+            driver.parse_message(gap, &mut book),
+            Err(DriverError::SequenceGap { expected: 1027035, received: 1027039 })
+        );
+    }
+
+    #[test]
+    fn test_coin_futures_routing() {
+        let perp = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "BTC-USD".to_string(),
+            product: ProductType::Perpetual,
+            feed: Feed::Depth,
+        };
+        assert_eq!(coin_contract_symbol(&perp), "BTCUSD_PERP");
+        assert_eq!(BinanceDriver::new().endpoint(&perp), "wss://dstream.binance.com/stream");
+        assert_eq!(stream_name(&perp), "btcusd_perp@depth");
+
+        let delivery = SymbolKey {
+            exchange: Exchange::Binance,
+            symbol: "btcusd_240628".to_string(),
+            product: ProductType::Future,
+            feed: Feed::Depth,
+        };
+        assert_eq!(stream_name(&delivery), "btcusd_240628@depth");
+    }
+
+    #[test]
+    fn test_coin_futures_qty_is_usd_notional() {
+        let info = br#"{"timezone":"UTC","symbols":[{"filters":[{"filterType":"PRICE_FILTER"}],"symbol":"ETHUSD_PERP","pair":"ETHUSD","contractType":"PERPETUAL","contractSize":10},{"symbol":"BTCUSD_PERP","pair":"BTCUSD","contractType":"PERPETUAL","contractStatus":"TRADING","contractSize":100,"marginAsset":"BTC"}]}"#;
+        assert_eq!(contract_size(info, "BTCUSD_PERP"), Some(100));
+        assert_eq!(contract_size(info, "ETHUSD_PERP"), Some(10));
+        assert_eq!(contract_size(info, "SOLUSD_PERP"), None);
+
+        let mut driver = BinanceDriver::new();
+        driver.market = Market::CoinFutures;
+        driver.contract_size = 100;
+        let mut book = L1FriendlyBook::new();
+
+        let snapshot = br#"{"lastUpdateId":16769853,"symbol":"BTCUSD_PERP","pair":"BTCUSD","E":1,"T":1,"bids":[["30000.0","12"]],"asks":[["30000.1","3"]]}"#;
+        driver.apply_snapshot(snapshot, &mut book).unwrap();
+        assert_eq!(book.bids[0], Level { price: 3_000_000_000_000, qty: 120_000_000_000 });
+
+        let update = br#"{"e":"depthUpdate","E":2,"T":2,"s":"BTCUSD_PERP","ps":"BTCUSD","U":16769850,"u":16769860,"pu":16769849,"b":[],"a":[["30000.1","5"]]}"#;
+        assert_eq!(driver.parse_message(update, &mut book), Ok(true));
+        assert_eq!(book.asks[0].qty, 50_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_ignores_control_frames() {
+        let mut book = L1FriendlyBook::new();
+        assert_eq!(BinanceDriver::new().parse_message(br#"{"result":null,"id":1}"#, &mut book), Ok(false));
+    }
+}
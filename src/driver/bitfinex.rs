@@ -0,0 +1,224 @@
+//! Bitfinex v2 public `book` channel.
+
+use crate::broker::SymbolKey;
+use crate::driver::schema::{self, bitfinex::Event};
+use crate::driver::{DriverError, ExchangeDriver, Scales, apply_counted_level, expect, find, parse_i64};
+use crate::model::{L1FriendlyBook, UpdateCause};
+
+const WS_URL: &str = "wss://api-pub.bitfinex.com/ws/2";
+
+/// Fixed-point scale applied to Bitfinex prices, unless the pair's
+/// precision was [registered](crate::broker::instruments::register).
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to Bitfinex amounts, unless the pair's
+/// precision was registered.
+pub const QTY_SCALE: u32 = 8;
+
+/// Driver for the Bitfinex aggregated (`P0`) book.
+///
+/// Book frames are positional arrays keyed by the channel id Bitfinex
+/// assigns in its `subscribed` event:
+///
+/// * snapshot: `[CHAN_ID,[[PRICE,COUNT,AMOUNT],...]]`
+/// * update: `[CHAN_ID,[PRICE,COUNT,AMOUNT]]`
+/// * heartbeat: `[CHAN_ID,"hb"]`
+///
+/// A positive `AMOUNT` is a bid and a negative one an ask. `COUNT` is the
+/// number of orders at the level, recorded in the book's order counts, and
+/// `COUNT == 0` deletes the level, which is applied as a lazy removal.
+///
+/// Bitfinex prices have five significant digits rather than a tick size,
+/// so there is no precision to look up.
+///
+/// Book frames only carry sequence numbers on connections that opt in
+/// with the `SEQ_ALL` configuration flag, which this driver does not send,
+/// so there is no gap to detect; a lost frame shows up as a stale or
+/// crossed book instead.
+pub struct BitfinexDriver {
+    chan_id: Option<u64>,
+    scales: Scales,
+}
+
+impl BitfinexDriver {
+    pub fn new() -> Self {
+        Self {
+            chan_id: None,
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+        }
+    }
+}
+
+impl Default for BitfinexDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `BTC-USD` into Bitfinex's `tBTCUSD` form.
+///
+/// Assets with tickers longer than three characters are joined with a
+/// colon, e.g. `TESTBTC-TESTUSD` becomes `tTESTBTC:TESTUSD`.
+pub fn trading_symbol(symbol: &str) -> String {
+    let upper = symbol.to_ascii_uppercase();
+    match upper.split_once(['-', '/', '_']) {
+        Some((base, quote)) if base.len() > 3 || quote.len() > 3 => format!("t{base}:{quote}"),
+        Some((base, quote)) => format!("t{base}{quote}"),
+        None => format!("t{upper}"),
+    }
+}
+
+impl ExchangeDriver for BitfinexDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        Ok(())
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"event":"subscribe","channel":"book","symbol":"{}","prec":"P0","freq":"F0","len":"25"}}"#,
+            trading_symbol(&key.symbol)
+        ))
+    }
+
+    fn unsubscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+        self.chan_id
+            .map(|chan_id| format!(r#"{{"event":"unsubscribe","chanId":{chan_id}}}"#))
+    }
+
+    /// Snapshots, the only frames nesting arrays of levels, replace the book.
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if find(msg, b",[[").is_some() { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if msg.first() == Some(&b'{') {
+            // Event frames: remember the channel id once subscribed
+            match schema::decode(msg)? {
+                Event::Subscribed(ack) => self.chan_id = Some(ack.chan_id),
+                Event::Error(error) => return Err(DriverError::Rejected(error.msg.into_owned())),
+                Event::Other => {}
+            }
+            return Ok(false);
+        }
+
+        self.scales.stamp(book);
+        // Skip "[CHAN_ID,"
+        let mut idx = expect(msg, 0, b'[')?;
+        let (_, next) = parse_i64(msg, idx, 0)?;
+        idx = expect(msg, next, b',')?;
+
+        match msg.get(idx..idx + 2) {
+            Some(b"[[") => {
+                book.clear_sides();
+
+                idx += 1;
+                loop {
+                    idx = apply_entry(msg, idx, self.scales, book)?;
+                    match msg.get(idx) {
+                        Some(b',') => idx += 1,
+                        Some(b']') => break,
+                        _ => return Err(DriverError::Malformed),
+                    }
+                }
+                Ok(true)
+            }
+            Some([b'[', _]) => {
+                apply_entry(msg, idx, self.scales, book)?;
+                Ok(true)
+            }
+            // Heartbeats ("hb") and checksums ("cs")
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Parses one `[PRICE,COUNT,AMOUNT]` entry at `idx` and applies it.
+///
+/// Returns the index just past the entry's closing `]`.
+fn apply_entry(bytes: &[u8], idx: usize, scales: Scales, book: &mut L1FriendlyBook) -> Result<usize, DriverError> {
+    let idx = expect(bytes, idx, b'[')?;
+    let (price, next) = parse_i64(bytes, idx, scales.price)?;
+    let idx = expect(bytes, next, b',')?;
+    let (count, next) = parse_i64(bytes, idx, 0)?;
+    let idx = expect(bytes, next, b',')?;
+    let (amount, next) = parse_i64(bytes, idx, scales.qty)?;
+    let idx = expect(bytes, next, b']')?;
+
+    let qty = if count == 0 { 0 } else { amount.abs() };
+    let count = u32::try_from(count).map_err(|_| DriverError::Malformed)?;
+    if amount > 0 {
+        apply_counted_level(&mut book.bids, &mut book.bid_tombstones, &mut book.bid_orders, price, qty, count, true);
+    } else {
+        apply_counted_level(&mut book.asks, &mut book.ask_tombstones, &mut book.ask_orders, price, qty, count, false);
+    }
+    Ok(idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed, ProductType};
+    use crate::model::Level;
+
+    #[test]
+    fn test_trading_symbol() {
+        assert_eq!(trading_symbol("BTC-USD"), "tBTCUSD");
+        assert_eq!(trading_symbol("testbtc/testusd"), "tTESTBTC:TESTUSD");
+        assert_eq!(trading_symbol("ETHUSD"), "tETHUSD");
+    }
+
+    #[test]
+    fn test_unsubscribe_uses_channel_id() {
+        let key = SymbolKey {
+            exchange: Exchange::Bitfinex,
+            symbol: "BTC-USD".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let mut driver = BitfinexDriver::new();
+        let mut book = L1FriendlyBook::new();
+        assert_eq!(driver.unsubscribe_msg(&key), None);
+
+        let subscribed = br#"{"event":"subscribed","channel":"book","chanId":17082,"symbol":"tBTCUSD","prec":"P0","freq":"F0","len":"25","pair":"BTCUSD"}"#;
+        assert_eq!(driver.parse_message(subscribed, &mut book), Ok(false));
+        assert_eq!(driver.unsubscribe_msg(&key).unwrap(), r#"{"event":"unsubscribe","chanId":17082}"#);
+
+        let error = br#"{"event":"error","msg":"symbol: invalid","code":10300}"#;
+        assert_eq!(driver.parse_message(error, &mut book), Err(DriverError::Rejected("symbol: invalid".to_string())));
+        assert_eq!(driver.parse_message(br#"{"event":"info","version":2}"#, &mut book), Ok(false));
+    }
+
+    #[test]
+    fn test_snapshot_then_updates() {
+        let mut driver = BitfinexDriver::new();
+        let mut book = L1FriendlyBook::new();
+
+        let snapshot = br#"[17082,[[7254.7,3,3.3],[7254.6,1,0.5],[7255.1,2,-1.25]]]"#;
+        assert_eq!(driver.parse_message(snapshot, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 725_470_000_000, qty: 330_000_000 });
+        assert_eq!(book.bids[1].price, 725_460_000_000);
+        assert_eq!(book.asks[0], Level { price: 725_510_000_000, qty: 125_000_000 });
+        assert_eq!((&book.bid_orders[..2], book.ask_orders[0]), (&[3, 1][..], 2));
+
+        // Count zero deletes the bid at 7254.7
+        assert_eq!(driver.parse_message(b"[17082,[7254.7,0,1]]", &mut book), Ok(true));
+        book.compact_sides();
+        assert_eq!(book.bids[0].price, 725_460_000_000);
+        assert_eq!(book.bid_orders[..2], [1, 0]);
+
+        assert_eq!(driver.parse_message(b"[17082,[7255.0,1,-2]]", &mut book), Ok(true));
+        assert_eq!(book.asks[0].price, 725_500_000_000);
+        assert_eq!(book.ask_orders[..3], [1, 2, 0]);
+    }
+
+    #[test]
+    fn test_ignores_heartbeat() {
+        let mut book = L1FriendlyBook::new();
+        assert_eq!(BitfinexDriver::new().parse_message(br#"[17082,"hb"]"#, &mut book), Ok(false));
+    }
+}
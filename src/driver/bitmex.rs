@@ -0,0 +1,256 @@
+//! BitMEX `orderBookL2` table.
+
+use crate::broker::SymbolKey;
+use crate::broker::instruments::Precision;
+use crate::driver::{DriverError, ExchangeDriver, Scales, apply_level, find, find_str, find_u64, parse_i64, rest_get, schema};
+use crate::json;
+use crate::model::{L1FriendlyBook, UpdateCause};
+use std::collections::HashMap;
+
+const WS_URL: &str = "wss://ws.bitmex.com/realtime";
+const INSTRUMENT_URL: &str = "https://www.bitmex.com/api/v1/instrument";
+
+/// Fixed-point scale applied to BitMEX prices, unless the instrument's
+/// `tickSize` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to BitMEX sizes, unless the instrument's
+/// `lotSize` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Driver for the BitMEX full-depth L2 table.
+///
+/// BitMEX keys every level by an opaque `id`. Only `partial` and `insert`
+/// rows are guaranteed to carry a price, so the driver keeps an id → price
+/// map for the whole book and resolves `update` and `delete` rows through it.
+///
+/// The table carries no sequence numbers, so there is no gap to detect;
+/// a row for an id the map does not hold fails the frame instead, which
+/// has the connector rebuild the book from a fresh `partial`.
+pub struct BitmexDriver {
+    scales: Scales,
+    prices: HashMap<u64, i64>,
+}
+
+impl BitmexDriver {
+    pub fn new() -> Self {
+        Self {
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Applies a single row of the `data` array.
+    fn apply_row(&mut self, row: &[u8], action: Action, book: &mut L1FriendlyBook) -> Result<(), DriverError> {
+        let id = find_u64(row, "id").ok_or(DriverError::Malformed)?;
+        let bid = match find_str(row, "side") {
+            Some("Buy") => true,
+            Some("Sell") => false,
+            _ => return Err(DriverError::Malformed),
+        };
+
+        let price = match (find_number(row, "price", self.scales.price)?, action) {
+            (Some(price), Action::Insert) => {
+                self.prices.insert(id, price);
+                price
+            }
+            (Some(price), _) => price,
+            (None, _) => *self.prices.get(&id).ok_or(DriverError::Malformed)?,
+        };
+
+        let qty = match action {
+            Action::Delete => {
+                self.prices.remove(&id);
+                0
+            }
+            _ => find_number(row, "size", self.scales.qty)?.ok_or(DriverError::Malformed)?,
+        };
+
+        if bid {
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+        } else {
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+        }
+        Ok(())
+    }
+}
+
+impl Default for BitmexDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Converts a symbol such as `XBT-USD` into BitMEX's `XBTUSD` form.
+pub fn instrument_symbol(symbol: &str) -> String {
+    symbol
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Reads the sizes off an `instrument?symbol=` response, which is empty
+/// for unknown symbols.
+///
+/// ```json
+/// [{"symbol":"XBTUSD","rootSymbol":"XBT","state":"Open","tickSize":0.5,"lotSize":100}]
+/// ```
+fn precision(body: &[u8]) -> Result<Option<Precision>, DriverError> {
+    let instruments: Vec<schema::bitmex::Instrument> = schema::decode(body)?;
+    match instruments.first() {
+        Some(inst) => Precision::from_sizes(inst.tick_size.text(), inst.lot_size.text())
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+impl ExchangeDriver for BitmexDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.prices.clear();
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        precision(rest_get(&format!("{INSTRUMENT_URL}?symbol={}", instrument_symbol(&key.symbol)))?.as_bytes())
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"op":"subscribe","args":["orderBookL2:{}"]}}"#,
+            instrument_symbol(&key.symbol)
+        ))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"op":"unsubscribe","args":["orderBookL2:{}"]}}"#,
+            instrument_symbol(&key.symbol)
+        ))
+    }
+
+    /// `partial` actions replace the book.
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if find_str(msg, "action") == Some("partial") { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    /// Applies a `partial`, `insert`, `update` or `delete` action.
+    ///
+    /// ```json
+    /// {"table":"orderBookL2","action":"update","data":[{"symbol":"XBTUSD","id":8799000000,"side":"Sell","size":50}]}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if find(msg, br#""table":"orderBookL2""#).is_none() {
+            // Subscribe replies and the welcome banner, unless refused
+            if json::field(msg, 0, b"error").is_some() {
+                let error: schema::bitmex::Error = schema::decode(msg)?;
+                return Err(DriverError::Rejected(error.error.into_owned()));
+            }
+            return Ok(false);
+        }
+
+        self.scales.stamp(book);
+        let action = match find_str(msg, "action") {
+            Some("partial") => {
+                self.prices.clear();
+                book.clear_sides();
+                Action::Insert
+            }
+            Some("insert") => Action::Insert,
+            Some("update") => Action::Update,
+            Some("delete") => Action::Delete,
+            _ => return Err(DriverError::Malformed),
+        };
+
+        let mut idx = find(msg, br#""data":["#).ok_or(DriverError::Malformed)? + 8;
+        while let Some(&b) = msg.get(idx) {
+            match b {
+                b'{' => {
+                    let len = msg[idx..].iter().position(|&b| b == b'}').ok_or(DriverError::Malformed)?;
+                    self.apply_row(&msg[idx..=idx + len], action, book)?;
+                    idx += len + 1;
+                }
+                b']' => return Ok(true),
+                _ => idx += 1,
+            }
+        }
+        Err(DriverError::Malformed)
+    }
+}
+
+/// Returns the fixed-point value of the `"key":number` pair in `row`, if present.
+fn find_number(row: &[u8], key: &str, scale: u32) -> Result<Option<i64>, DriverError> {
+    let pattern = format!(r#""{key}":"#);
+    match find(row, pattern.as_bytes()) {
+        Some(idx) => Ok(Some(parse_i64(row, idx + pattern.len(), scale)?.0)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Level;
+
+    #[test]
+    fn test_instrument_symbol() {
+        assert_eq!(instrument_symbol("xbt-usd"), "XBTUSD");
+    }
+
+    #[test]
+    fn test_partial_update_delete() {
+        let mut driver = BitmexDriver::new();
+        let mut book = L1FriendlyBook::new();
+
+        let partial = br#"{"table":"orderBookL2","action":"partial","keys":["symbol","id","side"],"types":{"id":"long"},"filter":{"symbol":"XBTUSD"},"data":[{"symbol":"XBTUSD","id":8799000000,"side":"Sell","size":100,"price":10000.5},{"symbol":"XBTUSD","id":8799000100,"side":"Buy","size":200,"price":9999}]}"#;
+        assert_eq!(driver.parse_message(partial, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(partial), UpdateCause::Snapshot);
+        assert_eq!(book.asks[0], Level { price: 1_000_050_000_000, qty: 10_000_000_000 });
+        assert_eq!(book.bids[0], Level { price: 999_900_000_000, qty: 20_000_000_000 });
+
+        // Update rows carry no price; it is resolved from the id
+        let update = br#"{"table":"orderBookL2","action":"update","data":[{"symbol":"XBTUSD","id":8799000000,"side":"Sell","size":50}]}"#;
+        assert_eq!(driver.parse_message(update, &mut book), Ok(true));
+        assert_eq!(book.asks[0].qty, 5_000_000_000);
+        assert_eq!(driver.update_cause(update), UpdateCause::Delta);
+
+        let delete = br#"{"table":"orderBookL2","action":"delete","data":[{"symbol":"XBTUSD","id":8799000100,"side":"Buy"}]}"#;
+        assert_eq!(driver.parse_message(delete, &mut book), Ok(true));
+        assert_eq!(book.bid_tombstones, 1);
+        assert!(!driver.prices.contains_key(&8799000100));
+    }
+
+    #[test]
+    fn test_unknown_id_is_malformed() {
+        let mut book = L1FriendlyBook::new();
+        let update = br#"{"table":"orderBookL2","action":"update","data":[{"symbol":"XBTUSD","id":1,"side":"Sell","size":50}]}"#;
+        assert_eq!(BitmexDriver::new().parse_message(update, &mut book), Err(DriverError::Malformed));
+    }
+
+    #[test]
+    fn test_ignores_subscribe_ack() {
+        let mut book = L1FriendlyBook::new();
+        let ack = br#"{"success":true,"subscribe":"orderBookL2:XBTUSD","request":{"op":"subscribe"}}"#;
+        assert_eq!(BitmexDriver::new().parse_message(ack, &mut book), Ok(false));
+    }
+
+    #[test]
+    fn test_precision() {
+        let body = br#"[{"symbol":"XBTUSD","rootSymbol":"XBT","state":"Open","tickSize":0.5,"lotSize":100}]"#;
+        assert_eq!(precision(body).unwrap().map(|p| (p.price_scale, p.tick, p.qty_scale, p.lot)), Some((1, 5, 0, 100)));
+        assert_eq!(precision(b"[]"), Ok(None));
+    }
+}
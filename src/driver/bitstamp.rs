@@ -0,0 +1,247 @@
+//! Bitstamp `diff_order_book_{pair}` channel with REST snapshot bootstrap.
+
+use crate::broker::SymbolKey;
+use crate::broker::instruments::Precision;
+use crate::driver::schema::{self, bitstamp::Event, bitstamp::Snapshot};
+use crate::driver::{DriverError, ExchangeDriver, Scales, apply_level, find, find_u64, for_each_level, rest_get};
+use crate::model::{L1FriendlyBook, UpdateCause};
+
+const WS_URL: &str = "wss://ws.bitstamp.net";
+const REST_URL: &str = "https://www.bitstamp.net/api/v2/order_book";
+const PAIRS_URL: &str = "https://www.bitstamp.net/api/v2/trading-pairs-info/";
+
+/// Fixed-point scale applied to Bitstamp prices, unless the pair's
+/// `counter_decimals` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to Bitstamp quantities, unless the pair's
+/// `base_decimals` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Driver for the Bitstamp spot diff book.
+///
+/// Once the subscription is confirmed the driver fetches a full REST order
+/// book. Diffs queue up on the socket while the request is in flight and
+/// are applied afterwards, skipping any whose `microtimestamp` is not newer
+/// than the snapshot's.
+pub struct BitstampDriver {
+    pair: String,
+    scales: Scales,
+    /// `microtimestamp` of the snapshot the book was built from.
+    snapshot_ts: Option<u64>,
+}
+
+impl BitstampDriver {
+    pub fn new() -> Self {
+        Self {
+            pair: String::new(),
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            snapshot_ts: None,
+        }
+    }
+
+    /// Replaces the book with a REST `order_book` response.
+    fn apply_snapshot(&mut self, body: &[u8], book: &mut L1FriendlyBook) -> Result<(), DriverError> {
+        let snapshot: Snapshot = schema::decode(body)?;
+        let ts = snapshot.microtimestamp.parse().map_err(|_| DriverError::Malformed)?;
+
+        book.clear_sides();
+        for level in &snapshot.bids {
+            let (price, qty) = level.parse(self.scales.price, self.scales.qty)?;
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+        }
+        for level in &snapshot.asks {
+            let (price, qty) = level.parse(self.scales.price, self.scales.qty)?;
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+        }
+        self.snapshot_ts = Some(ts);
+        Ok(())
+    }
+}
+
+impl Default for BitstampDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `BTC-USD` into Bitstamp's `btcusd` form.
+pub fn market_pair(symbol: &str) -> String {
+    symbol
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Finds the decimals of `pair` in a `trading-pairs-info` response.
+///
+/// ```json
+/// [{"name":"BTC/USD","url_symbol":"btcusd","base_decimals":8,"counter_decimals":0,"trading":"Enabled"}]
+/// ```
+fn precision(info: &[u8], pair: &str) -> Result<Option<Precision>, DriverError> {
+    let pairs: Vec<schema::bitstamp::PairInfo> = schema::decode(info)?;
+    match pairs.iter().find(|info| info.url_symbol == pair) {
+        Some(info) => Precision::from_decimals(info.counter_decimals, info.base_decimals)
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+impl ExchangeDriver for BitstampDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.pair = market_pair(&key.symbol);
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.snapshot_ts = None;
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        precision(rest_get(PAIRS_URL)?.as_bytes(), &market_pair(&key.symbol))
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"event":"bts:subscribe","data":{{"channel":"diff_order_book_{}"}}}}"#,
+            market_pair(&key.symbol)
+        ))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"event":"bts:unsubscribe","data":{{"channel":"diff_order_book_{}"}}}}"#,
+            market_pair(&key.symbol)
+        ))
+    }
+
+    /// The REST snapshot is applied on the subscription ack, the only frame
+    /// other than a diff to change the book.
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if find(msg, br#""event":"data""#).is_none() { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    /// Applies a diff, bootstrapping from REST once subscribed.
+    ///
+    /// ```json
+    /// {"data":{"timestamp":"1643643522","microtimestamp":"1643643522123456","bids":[["36797.17","0.00000000"]],"asks":[]},"channel":"diff_order_book_btcusd","event":"data"}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        self.scales.stamp(book);
+        if find(msg, br#""event":"data""#).is_none() {
+            return match schema::decode(msg)? {
+                Event::Subscribed(_) => {
+                    let body = rest_get(&format!("{REST_URL}/{}/", self.pair))?;
+                    self.apply_snapshot(body.as_bytes(), book)?;
+                    Ok(true)
+                }
+                Event::Error(error) => Err(DriverError::Rejected(error.data.message.into_owned())),
+                Event::Other => Ok(false),
+            };
+        }
+
+        let Some(snapshot_ts) = self.snapshot_ts else {
+            return Ok(false);
+        };
+        let ts = find_u64(msg, "microtimestamp").ok_or(DriverError::Malformed)?;
+        if ts <= snapshot_ts {
+            return Ok(false);
+        }
+
+        apply_sides(msg, book, self.scales)?;
+        Ok(true)
+    }
+}
+
+/// Applies the `bids` and `asks` arrays of a diff.
+fn apply_sides(msg: &[u8], book: &mut L1FriendlyBook, scales: Scales) -> Result<(), DriverError> {
+    let bids = find(msg, br#""bids":"#).ok_or(DriverError::Malformed)? + 7;
+    for_each_level(msg, bids, scales.price, scales.qty, |price, qty| {
+        apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+    })?;
+
+    let asks = find(msg, br#""asks":"#).ok_or(DriverError::Malformed)? + 7;
+    for_each_level(msg, asks, scales.price, scales.qty, |price, qty| {
+        apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed, ProductType};
+    use crate::model::Level;
+
+    fn diff(ts: u64, bids: &str) -> String {
+        format!(
+            r#"{{"data":{{"timestamp":"1","microtimestamp":"{ts}","bids":[{bids}],"asks":[]}},"channel":"diff_order_book_btcusd","event":"data"}}"#
+        )
+    }
+
+    #[test]
+    fn test_subscribe_msg() {
+        let key = SymbolKey {
+            exchange: Exchange::Bitstamp,
+            symbol: "BTC-USD".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        assert_eq!(
+            BitstampDriver::new().subscribe_msg(&key).unwrap(),
+            r#"{"event":"bts:subscribe","data":{"channel":"diff_order_book_btcusd"}}"#
+        );
+    }
+
+    #[test]
+    fn test_diffs_ignored_before_snapshot() {
+        let mut book = L1FriendlyBook::new();
+        let msg = diff(5, r#"["100","1"]"#);
+        assert_eq!(BitstampDriver::new().parse_message(msg.as_bytes(), &mut book), Ok(false));
+    }
+
+    #[test]
+    fn test_splices_diffs_onto_snapshot() {
+        let mut driver = BitstampDriver::new();
+        let mut book = L1FriendlyBook::new();
+
+        let snapshot = br#"{"timestamp":"1","microtimestamp":"1000","bids":[["100","1"],["99","2"]],"asks":[["101","3"]]}"#;
+        driver.apply_snapshot(snapshot, &mut book).unwrap();
+        assert_eq!(book.bids[0], Level { price: 10_000_000_000, qty: 100_000_000 });
+        assert_eq!(book.asks[0].price, 10_100_000_000);
+
+        // Already reflected in the snapshot
+        let stale = diff(1000, r#"["100","0"]"#);
+        assert_eq!(driver.parse_message(stale.as_bytes(), &mut book), Ok(false));
+        assert_eq!(book.bids[0].qty, 100_000_000);
+
+        let fresh = diff(1001, r#"["100","0"]"#);
+        assert_eq!(driver.parse_message(fresh.as_bytes(), &mut book), Ok(true));
+        assert_eq!(driver.update_cause(fresh.as_bytes()), UpdateCause::Delta);
+        assert_eq!(book.bid_tombstones, 1);
+
+        let subscribed = br#"{"event":"bts:subscription_succeeded","channel":"diff_order_book_btcusd","data":{}}"#;
+        assert_eq!(driver.update_cause(subscribed), UpdateCause::Snapshot);
+    }
+
+    #[test]
+    fn test_error_is_rejected() {
+        let mut book = L1FriendlyBook::new();
+        let error = br#"{"event":"bts:error","channel":"","data":{"code":null,"message":"Bad subscription string."}}"#;
+        assert_eq!(
+            BitstampDriver::new().parse_message(error, &mut book),
+            Err(DriverError::Rejected("Bad subscription string.".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let info = br#"[{"name":"BTC/USD","url_symbol":"btcusd","base_decimals":8,"counter_decimals":0,"trading":"Enabled"},{"name":"XRP/EUR","url_symbol":"xrpeur","base_decimals":8,"counter_decimals":5,"trading":"Enabled"}]"#;
+        assert_eq!(precision(info, "xrpeur"), Ok(Precision::from_decimals(5, 8)));
+        assert_eq!(precision(info, "ethusd"), Ok(None));
+    }
+}
@@ -0,0 +1,465 @@
+//! CME MDP 3.0 SBE multicast feed (incremental plus snapshot recovery).
+
+use crate::broker::SymbolKey;
+use crate::driver::{DriverError, ExchangeDriver, NativeScale, Transport, apply_level};
+use crate::model::{BOOK_DEPTH, L1FriendlyBook, Level, UpdateCause};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::LazyLock;
+
+/// Decimal exponent of MDP `PRICE9` fields.
+pub const PRICE_EXPONENT: i8 = -9;
+
+/// MDIncrementalRefreshBook46.
+const INCREMENTAL_BOOK: u16 = 46;
+
+/// SnapshotFullRefresh52.
+const SNAPSHOT_FULL_REFRESH: u16 = 52;
+
+/// Binary packet header: MsgSeqNum (u32) + SendingTime (u64).
+const PACKET_HEADER_LEN: usize = 12;
+
+/// Message size (u16) + SBE header (block length, template, schema, version).
+const MESSAGE_HEADER_LEN: usize = 10;
+
+/// Null value of an optional `PRICE9`.
+const PRICE_NULL: i64 = i64::MAX;
+
+/// Incremental entries kept while waiting for a snapshot before giving up on them.
+const MAX_PENDING: usize = 65_536;
+
+/// Market-data channels and the instruments they carry.
+static CHANNELS: LazyLock<RwLock<Vec<CmeChannel>>> = LazyLock::new(Default::default);
+
+/// Multicast groups of one MDP channel and the instruments it carries.
+#[derive(Debug, Clone)]
+pub struct CmeChannel {
+    /// Incremental feed (A or B).
+    pub incremental: SocketAddrV4,
+    /// The other incremental feed, arbitrated against `incremental` by
+    /// `MsgSeqNum`.
+    pub incremental_b: Option<SocketAddrV4>,
+    /// Market recovery (snapshot loop) feed.
+    pub snapshot: SocketAddrV4,
+    /// Local interface to join the groups on.
+    pub interface: Ipv4Addr,
+    /// SecurityID of each instrument symbol, e.g. `ESZ4` → `118`.
+    pub security_ids: HashMap<String, i32>,
+}
+
+/// Makes the instruments on `channel` available to [Exchange::Cme](crate::broker::Exchange::Cme) subscriptions.
+pub fn register_channel(channel: CmeChannel) {
+    CHANNELS.write().push(channel);
+}
+
+/// Returns the channel carrying `symbol` and the symbol's SecurityID.
+fn channel_for(symbol: &str) -> Option<(CmeChannel, i32)> {
+    CHANNELS.read().iter().find_map(|channel| {
+        let id = *channel.security_ids.get(symbol)?;
+        Some((channel.clone(), id))
+    })
+}
+
+/// One book entry of an incremental or snapshot message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BookEntry {
+    rpt_seq: u32,
+    price: i64,
+    size: i64,
+    /// MDPriceLevel, 1-based.
+    level: u8,
+    /// MDUpdateAction: 0 New, 1 Change, 2 Delete, 3 DeleteThru, 4 DeleteFrom, 5 Overlay.
+    action: u8,
+    /// MDEntryType: `0` bid, `1` offer.
+    entry_type: u8,
+}
+
+/// Driver for a single instrument on a CME MDP 3.0 channel.
+///
+/// The connector joins the channel's incremental and snapshot groups; every
+/// datagram is one MDP packet. The driver starts out recovering: incremental
+/// entries for its SecurityID are buffered until a SnapshotFullRefresh for
+/// the instrument arrives, after which buffered entries newer than the
+/// snapshot's `RptSeq` are replayed and snapshots are ignored. Live entries
+/// must then carry consecutive `RptSeq` values; a gap is reported as
+/// [DriverError::SequenceGap] so the connector starts over.
+///
+/// Prices are published with the feed's native `10^-9` exponent and sizes
+/// as whole contracts, unless
+/// [set_book_exponents](crate::driver::set_book_exponents) asks for finer
+/// ones. Implied entries are ignored.
+pub struct CmeDriver {
+    security_id: i32,
+    scale: NativeScale,
+    feeds: String,
+    rpt_seq: Option<u32>,
+    pending: Vec<BookEntry>,
+    /// Whether the last packet recovered the book from a snapshot.
+    recovered: bool,
+}
+
+impl CmeDriver {
+    pub fn new() -> Self {
+        Self {
+            security_id: 0,
+            scale: NativeScale::native(PRICE_EXPONENT, 0),
+            feeds: String::new(),
+            rpt_seq: None,
+            pending: Vec::new(),
+            recovered: false,
+        }
+    }
+
+    fn apply_incremental(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let mut modified = false;
+        for entry in entries(msg, |entry| {
+            Some((i32_at(entry, 12)?, BookEntry {
+                rpt_seq: u32_at(entry, 16)?,
+                price: i64_at(entry, 0)?,
+                size: i64::from(i32_at(entry, 8)?),
+                level: *entry.get(24)?,
+                action: *entry.get(25)?,
+                entry_type: *entry.get(26)?,
+            }))
+        })? {
+            let (security_id, entry) = entry;
+            if security_id != self.security_id {
+                continue;
+            }
+            if self.rpt_seq.is_none() {
+                if self.pending.len() == MAX_PENDING {
+                    self.pending.clear();
+                }
+                self.pending.push(entry);
+                continue;
+            }
+            modified |= self.apply_live(entry, book)?;
+        }
+        Ok(modified)
+    }
+
+    fn apply_snapshot(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if self.rpt_seq.is_some() {
+            return Ok(false);
+        }
+        let root = msg.get(MESSAGE_HEADER_LEN..).ok_or(DriverError::Malformed)?;
+        if i32_at(root, 8) != Some(self.security_id) {
+            return Ok(false);
+        }
+        let rpt_seq = u32_at(root, 12).ok_or(DriverError::Malformed)?;
+
+        book.clear_sides();
+        self.scale.stamp(book);
+
+        for entry in entries(msg, |entry| {
+            Some(BookEntry {
+                rpt_seq,
+                price: i64_at(entry, 0)?,
+                size: i64::from(i32_at(entry, 8)?),
+                level: *entry.get(16)?,
+                action: 0,
+                entry_type: *entry.get(21)?,
+            })
+        })? {
+            apply_entry(&entry, &self.scale, book)?;
+        }
+
+        self.rpt_seq = Some(rpt_seq);
+        self.recovered = true;
+        for entry in std::mem::take(&mut self.pending) {
+            self.apply_live(entry, book)?;
+        }
+        Ok(true)
+    }
+
+    /// Applies an entry on top of the recovered book, checking `RptSeq` continuity.
+    fn apply_live(&mut self, entry: BookEntry, book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let last = self.rpt_seq.unwrap_or(0);
+        if entry.rpt_seq <= last {
+            return Ok(false);
+        }
+        if entry.rpt_seq != last + 1 {
+            return Err(DriverError::SequenceGap {
+                expected: u64::from(last) + 1,
+                received: u64::from(entry.rpt_seq),
+            });
+        }
+        apply_entry(&entry, &self.scale, book)?;
+        self.rpt_seq = Some(entry.rpt_seq);
+        Ok(true)
+    }
+}
+
+impl Default for CmeDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExchangeDriver for CmeDriver {
+    fn transport(&self) -> Transport {
+        Transport::Udp
+    }
+
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        let (channel, security_id) = channel_for(&key.symbol)
+            .ok_or_else(|| DriverError::Rejected(format!("no CME channel carries {}", key.symbol)))?;
+        self.security_id = security_id;
+        self.scale = NativeScale::for_key(key, PRICE_EXPONENT, 0)?;
+        self.feeds = match channel.incremental_b {
+            Some(b) => format!("{}|{},{}@{}", channel.incremental, b, channel.snapshot, channel.interface),
+            None => format!("{},{}@{}", channel.incremental, channel.snapshot, channel.interface),
+        };
+        self.rpt_seq = None;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        self.feeds.clone()
+    }
+
+    fn subscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+        None
+    }
+
+    fn unsubscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+        None
+    }
+
+    /// One `MsgSeqNum` per packet.
+    fn packet_sequence(&self, packet: &[u8]) -> Option<(u64, u64)> {
+        Some((u64::from(u32_at(packet, 0)?), 1))
+    }
+
+    /// Applies every message in an MDP packet.
+    fn parse_message(&mut self, packet: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let mut idx = PACKET_HEADER_LEN;
+        let mut modified = false;
+        self.recovered = false;
+
+        while idx < packet.len() {
+            let size = usize::from(u16_at(packet, idx).ok_or(DriverError::Malformed)?);
+            if size < MESSAGE_HEADER_LEN || idx + size > packet.len() {
+                return Err(DriverError::Malformed);
+            }
+            let msg = &packet[idx..idx + size];
+            modified |= match u16_at(msg, 4) {
+                Some(INCREMENTAL_BOOK) => self.apply_incremental(msg, book)?,
+                Some(SNAPSHOT_FULL_REFRESH) => self.apply_snapshot(msg, book)?,
+                _ => false,
+            };
+            idx += size;
+        }
+        Ok(modified)
+    }
+
+    /// A packet that recovered the book from a SnapshotFullRefresh replaced it.
+    fn update_cause(&self, _msg: &[u8]) -> UpdateCause {
+        if self.recovered { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+}
+
+/// Applies a bid or offer entry to the book.
+fn apply_entry(entry: &BookEntry, scale: &NativeScale, book: &mut L1FriendlyBook) -> Result<(), DriverError> {
+    let (side, tombstones, descending) = match entry.entry_type {
+        b'0' => (&mut book.bids, &mut book.bid_tombstones, true),
+        b'1' => (&mut book.asks, &mut book.ask_tombstones, false),
+        _ => return Ok(()),
+    };
+
+    match entry.action {
+        // DeleteThru: clear the side
+        3 => {
+            *side = [Level::default(); BOOK_DEPTH];
+            *tombstones = 0;
+        }
+        // DeleteFrom: drop the top `level` levels
+        4 => {
+            let marked = *tombstones;
+            let live = (0..BOOK_DEPTH).filter(|&idx| side[idx] != Level::default() && marked & 1 << idx == 0);
+            for idx in live.take(usize::from(entry.level)) {
+                L1FriendlyBook::mark_removal(tombstones, idx);
+            }
+        }
+        _ if entry.price == PRICE_NULL => {}
+        action => {
+            // Delete
+            let size = if action == 2 { 0 } else { entry.size };
+            let level = scale.level(entry.price, size)?;
+            apply_level(side, tombstones, level.price, level.qty, descending);
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the first repeating group of an SBE message with `decode`.
+fn entries<T>(msg: &[u8], decode: impl Fn(&[u8]) -> Option<T>) -> Result<Vec<T>, DriverError> {
+    let block_len = usize::from(u16_at(msg, 2).ok_or(DriverError::Malformed)?);
+    let group = MESSAGE_HEADER_LEN + block_len;
+    let entry_len = usize::from(u16_at(msg, group).ok_or(DriverError::Malformed)?);
+    let count = usize::from(*msg.get(group + 2).ok_or(DriverError::Malformed)?);
+
+    let start = group + 3;
+    (0..count)
+        .map(|i| {
+            let offset = start + i * entry_len;
+            msg.get(offset..offset + entry_len).and_then(&decode).ok_or(DriverError::Malformed)
+        })
+        .collect()
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn i32_at(bytes: &[u8], offset: usize) -> Option<i32> {
+    Some(i32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn i64_at(bytes: &[u8], offset: usize) -> Option<i64> {
+    Some(i64::from_le_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ES: i32 = 118;
+
+    /// Wraps SBE messages in an MDP packet header.
+    fn packet(messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut packet = vec![0; PACKET_HEADER_LEN];
+        for msg in messages {
+            packet.extend_from_slice(msg);
+        }
+        packet
+    }
+
+    fn message(template: u16, root: &[u8], entry_len: u16, entries: &[Vec<u8>]) -> Vec<u8> {
+        let body_len = root.len() + 3 + entries.iter().map(Vec::len).sum::<usize>();
+        let mut msg = Vec::new();
+        msg.extend_from_slice(&((MESSAGE_HEADER_LEN + body_len) as u16).to_le_bytes());
+        msg.extend_from_slice(&(root.len() as u16).to_le_bytes());
+        msg.extend_from_slice(&template.to_le_bytes());
+        msg.extend_from_slice(&1u16.to_le_bytes());
+        msg.extend_from_slice(&9u16.to_le_bytes());
+        msg.extend_from_slice(root);
+        msg.extend_from_slice(&entry_len.to_le_bytes());
+        msg.push(entries.len() as u8);
+        for entry in entries {
+            msg.extend_from_slice(entry);
+        }
+        msg
+    }
+
+    fn incremental(entries: &[(i32, u32, i64, i32, u8, u8, u8)]) -> Vec<u8> {
+        let entries: Vec<Vec<u8>> = entries
+            .iter()
+            .map(|&(security_id, rpt_seq, price, size, level, action, entry_type)| {
+                let mut entry = Vec::new();
+                entry.extend_from_slice(&price.to_le_bytes());
+                entry.extend_from_slice(&size.to_le_bytes());
+                entry.extend_from_slice(&security_id.to_le_bytes());
+                entry.extend_from_slice(&rpt_seq.to_le_bytes());
+                entry.extend_from_slice(&1i32.to_le_bytes());
+                entry.extend_from_slice(&[level, action, entry_type, 0, 0, 0, 0, 0]);
+                entry
+            })
+            .collect();
+        message(INCREMENTAL_BOOK, &[0; 11], 32, &entries)
+    }
+
+    fn snapshot(security_id: i32, rpt_seq: u32, levels: &[(i64, i32, u8, u8)]) -> Vec<u8> {
+        let mut root = vec![0; 59];
+        root[8..12].copy_from_slice(&security_id.to_le_bytes());
+        root[12..16].copy_from_slice(&rpt_seq.to_le_bytes());
+        let entries: Vec<Vec<u8>> = levels
+            .iter()
+            .map(|&(price, size, level, entry_type)| {
+                let mut entry = Vec::new();
+                entry.extend_from_slice(&price.to_le_bytes());
+                entry.extend_from_slice(&size.to_le_bytes());
+                entry.extend_from_slice(&1i32.to_le_bytes());
+                entry.extend_from_slice(&[level, 0, 0, 0, 0, entry_type]);
+                entry
+            })
+            .collect();
+        message(SNAPSHOT_FULL_REFRESH, &root, 22, &entries)
+    }
+
+    fn driver() -> CmeDriver {
+        CmeDriver {
+            security_id: ES,
+            ..CmeDriver::new()
+        }
+    }
+
+    #[test]
+    fn test_recovers_from_snapshot() {
+        let mut driver = driver();
+        let mut book = L1FriendlyBook::new();
+
+        // Buffered until the snapshot arrives; the first is already in it
+        let early = incremental(&[
+            (ES, 10, 5_000_250_000_000, 7, 1, 1, b'0'),
+            (ES, 11, 5_000_500_000_000, 4, 1, 0, b'1'),
+            (999, 3, 1, 1, 1, 0, b'0'),
+        ]);
+        assert_eq!(driver.parse_message(&packet(&[early]), &mut book), Ok(false));
+
+        let snap = snapshot(ES, 10, &[
+            (5_000_250_000_000, 7, 1, b'0'),
+            (5_000_000_000_000, 3, 2, b'0'),
+            (5_000_750_000_000, 2, 1, b'1'),
+        ]);
+        assert_eq!(driver.parse_message(&packet(&[snap]), &mut book), Ok(true));
+        assert_eq!(driver.update_cause(&[]), UpdateCause::Snapshot);
+        assert_eq!((book.price_exponent, book.qty_exponent), (PRICE_EXPONENT, 0));
+        assert_eq!(book.bids[0], Level { price: 5_000_250_000_000, qty: 7 });
+        assert_eq!(book.bids[1].price, 5_000_000_000_000);
+        assert_eq!(book.asks[0], Level { price: 5_000_500_000_000, qty: 4 });
+        assert_eq!(book.asks[1].price, 5_000_750_000_000);
+
+        let live = incremental(&[(ES, 12, 5_000_250_000_000, 0, 1, 2, b'0')]);
+        assert_eq!(driver.parse_message(&packet(&[live]), &mut book), Ok(true));
+        assert_eq!(driver.update_cause(&[]), UpdateCause::Delta);
+        assert_eq!(book.bid_tombstones, 1);
+
+        let gap = incremental(&[(ES, 14, 5_000_000_000_000, 1, 1, 1, b'0')]);
+        assert_eq!(
+            driver.parse_message(&packet(&[gap]), &mut book),
+            Err(DriverError::SequenceGap { expected: 13, received: 14 })
+        );
+    }
+
+    #[test]
+    fn test_delete_from_and_thru() {
+        let mut driver = driver();
+        let mut book = L1FriendlyBook::new();
+        let snap = snapshot(ES, 1, &[(300, 1, 1, b'1'), (400, 1, 2, b'1'), (500, 1, 3, b'1'), (200, 1, 1, b'0')]);
+        driver.parse_message(&packet(&[snap]), &mut book).unwrap();
+
+        let delete_from = incremental(&[(ES, 2, PRICE_NULL, 0, 2, 4, b'1')]);
+        assert_eq!(driver.parse_message(&packet(&[delete_from]), &mut book), Ok(true));
+        L1FriendlyBook::compact(&mut book.asks, &mut book.ask_tombstones);
+        assert_eq!(book.asks[0].price, 500);
+
+        let delete_thru = incremental(&[(ES, 3, PRICE_NULL, 0, 0, 3, b'0')]);
+        assert_eq!(driver.parse_message(&packet(&[delete_thru]), &mut book), Ok(true));
+        assert!(book.bids_empty());
+    }
+
+    #[test]
+    fn test_truncated_packet_is_malformed() {
+        let mut msg = incremental(&[(ES, 1, 1, 1, 1, 0, b'0')]);
+        msg.truncate(msg.len() - 4);
+        let mut book = L1FriendlyBook::new();
+        assert_eq!(driver().parse_message(&packet(&[msg]), &mut book), Err(DriverError::Malformed));
+    }
+}
@@ -0,0 +1,330 @@
+//! Coinbase Exchange `full` channel (market-by-order).
+
+use crate::broker::SymbolKey;
+use crate::broker::instruments::Precision;
+use crate::driver::schema::{self, coinbase::Event, coinbase::Snapshot};
+use crate::driver::{DriverError, ExchangeDriver, Scales, find_str, find_u64, parse_i64, parse_qty, rest_get};
+use crate::model::{FullDepthBook, L1FriendlyBook, OrderBookL3, Side, UpdateCause};
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+const WS_URL: &str = "wss://ws-feed.exchange.coinbase.com";
+const REST_URL: &str = "https://api.exchange.coinbase.com/products";
+
+/// Fixed-point scale applied to Coinbase prices, unless the product's
+/// `quote_increment` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to Coinbase sizes, unless the product's
+/// `base_increment` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Driver for the Coinbase `full` channel.
+///
+/// The feed reports every order individually, so the driver keeps an
+/// [OrderBookL3] aggregated into a [FullDepthBook], and projects its best
+/// levels into the shared book after every event.
+///
+/// Once the subscription is confirmed the driver fetches a level 3 REST
+/// snapshot; events queue up on the socket while it is in flight. Events
+/// not newer than the snapshot's `sequence` are skipped, and afterwards
+/// every event must carry the next sequence number.
+pub struct CoinbaseDriver {
+    product_id: String,
+    scales: Scales,
+    orders: OrderBookL3,
+    depth: Arc<RwLock<FullDepthBook>>,
+    last_seq: Option<u64>,
+}
+
+impl CoinbaseDriver {
+    pub fn new() -> Self {
+        Self {
+            product_id: String::new(),
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            orders: OrderBookL3::new(),
+            depth: Arc::new(RwLock::new(FullDepthBook::new())),
+            last_seq: None,
+        }
+    }
+
+    /// Replaces the order book with a REST `book?level=3` response.
+    fn apply_snapshot(&mut self, body: &[u8], book: &mut L1FriendlyBook) -> Result<(), DriverError> {
+        let snapshot: Snapshot = schema::decode(body)?;
+
+        self.orders.clear(&mut self.depth.write());
+
+        for (orders, side) in [(&snapshot.bids, Side::Bid), (&snapshot.asks, Side::Ask)] {
+            for order in orders {
+                self.open(&order.2, side, order.0.parse(self.scales.price)?, order.1.parse(self.scales.qty)?);
+            }
+        }
+
+        self.last_seq = Some(snapshot.sequence);
+        self.project(book);
+        Ok(())
+    }
+
+    /// Applies a single `full` channel event.
+    ///
+    /// Returns whether a resting order was added, resized or removed.
+    fn apply_event(&mut self, msg: &[u8]) -> Result<bool, DriverError> {
+        match find_str(msg, "type") {
+            Some("open") => {
+                let id = find_str(msg, "order_id").ok_or(DriverError::Malformed)?;
+                let side = side(msg)?;
+                let price = field(msg, "price", self.scales.price)?;
+                let size = field(msg, "remaining_size", self.scales.qty)?;
+                self.open(id, side, price, size);
+                Ok(true)
+            }
+            Some("done") => {
+                let id = find_str(msg, "order_id").ok_or(DriverError::Malformed)?;
+                Ok(self.orders.resize(id, |_| 0, &mut self.depth.write()))
+            }
+            Some("match") => {
+                let id = find_str(msg, "maker_order_id").ok_or(DriverError::Malformed)?;
+                let size = field(msg, "size", self.scales.qty)?;
+                Ok(self.orders.resize(id, |remaining| remaining - size, &mut self.depth.write()))
+            }
+            Some("change") => {
+                let id = find_str(msg, "order_id").ok_or(DriverError::Malformed)?;
+                let Some(new_size) = find_str(msg, "new_size") else {
+                    // Market orders change funds and never rest on the book
+                    return Ok(false);
+                };
+                let size = parse_qty(new_size.as_bytes(), 0, self.scales.qty)?.0;
+                Ok(self.orders.resize(id, |_| size, &mut self.depth.write()))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Adds a resting order and its size to the price aggregate.
+    fn open(&mut self, id: &str, side: Side, price: i64, size: i64) {
+        self.orders.open(id.to_string(), side, price, size, &mut self.depth.write());
+    }
+
+    /// Writes the best aggregated levels of each side into `book`.
+    fn project(&self, book: &mut L1FriendlyBook) {
+        self.depth.read().project(book);
+    }
+}
+
+impl Default for CoinbaseDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `btc/usd` into Coinbase's `BTC-USD` product id.
+pub fn product_id(symbol: &str) -> String {
+    symbol.replace(['/', '_'], "-").to_ascii_uppercase()
+}
+
+/// Reads the increments off a REST `products/<id>` response.
+///
+/// ```json
+/// {"id":"BTC-USD","base_currency":"BTC","quote_currency":"USD","quote_increment":"0.01","base_increment":"0.00000001"}
+/// ```
+fn precision(body: &[u8]) -> Result<Precision, DriverError> {
+    let product: schema::coinbase::Product = schema::decode(body)?;
+    Precision::from_sizes(product.quote_increment.as_bytes(), product.base_increment.as_bytes()).ok_or(DriverError::Malformed)
+}
+
+impl ExchangeDriver for CoinbaseDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.product_id = product_id(&key.symbol);
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        let mut depth = self.depth.write();
+        self.orders.clear(&mut depth);
+        depth.price_exponent = -(self.scales.price as i8);
+        depth.qty_exponent = -(self.scales.qty as i8);
+        drop(depth);
+        self.last_seq = None;
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        precision(rest_get(&format!("{REST_URL}/{}", product_id(&key.symbol)))?.as_bytes()).map(Some)
+    }
+
+    fn full_depth(&self) -> Option<Arc<RwLock<FullDepthBook>>> {
+        Some(Arc::clone(&self.depth))
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"type":"subscribe","product_ids":["{}"],"channels":["full"]}}"#,
+            product_id(&key.symbol)
+        ))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"type":"unsubscribe","product_ids":["{}"],"channels":["full"]}}"#,
+            product_id(&key.symbol)
+        ))
+    }
+
+    /// Returns the `sequence` of the last event applied.
+    fn update_id(&self) -> Option<u64> {
+        self.last_seq
+    }
+
+    /// The REST snapshot is applied on the `subscriptions` reply, the only
+    /// frame without a `sequence` to change the book.
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if find_u64(msg, "sequence").is_none() { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    /// Applies a `full` channel event, bootstrapping from REST once subscribed.
+    ///
+    /// ```json
+    /// {"type":"open","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":10,"order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","price":"200.2","remaining_size":"1.00","side":"sell"}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        self.scales.stamp(book);
+        let Some(seq) = find_u64(msg, "sequence") else {
+            // Subscription replies and errors carry no sequence
+            return match schema::decode(msg)? {
+                Event::Subscriptions(_) => {
+                    let body = rest_get(&format!("{REST_URL}/{}/book?level=3", self.product_id))?;
+                    self.apply_snapshot(body.as_bytes(), book)?;
+                    Ok(true)
+                }
+                Event::Error(error) => Err(DriverError::Rejected(match error.reason {
+                    Some(reason) => format!("{}: {reason}", error.message),
+                    None => error.message.into_owned(),
+                })),
+                Event::Other => Ok(false),
+            };
+        };
+        let Some(last) = self.last_seq else {
+            return Ok(false);
+        };
+        if seq <= last {
+            return Ok(false);
+        }
+        if seq != last + 1 {
+            return Err(DriverError::SequenceGap {
+                expected: last + 1,
+                received: seq,
+            });
+        }
+        self.last_seq = Some(seq);
+
+        if !self.apply_event(msg)? {
+            return Ok(false);
+        }
+        self.project(book);
+        Ok(true)
+    }
+}
+
+fn side(msg: &[u8]) -> Result<Side, DriverError> {
+    match find_str(msg, "side") {
+        Some("buy") => Ok(Side::Bid),
+        Some("sell") => Ok(Side::Ask),
+        _ => Err(DriverError::Malformed),
+    }
+}
+
+/// Returns the fixed-point value of the `"key":"value"` pair in `msg`.
+fn field(msg: &[u8], key: &str, scale: u32) -> Result<i64, DriverError> {
+    let value = find_str(msg, key).ok_or(DriverError::Malformed)?;
+    Ok(parse_i64(value.as_bytes(), 0, scale)?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Level;
+
+    fn snapshot_driver(book: &mut L1FriendlyBook) -> CoinbaseDriver {
+        let mut driver = CoinbaseDriver::new();
+        let snapshot = br#"{"bids":[["295.96","0.5","b1"],["295.96","1.5","b2"],["295.95","1","b3"]],"asks":[["296.00","2","a1"]],"sequence":10}"#;
+        driver.apply_snapshot(snapshot, book).unwrap();
+        driver
+    }
+
+    #[test]
+    fn test_snapshot_aggregates_orders() {
+        let mut book = L1FriendlyBook::new();
+        let driver = snapshot_driver(&mut book);
+        assert_eq!(driver.full_depth().unwrap().read().len(Side::Bid), 2);
+        assert_eq!(book.bids[0], Level { price: 29_596_000_000, qty: 200_000_000 });
+        assert_eq!(book.bids[1], Level { price: 29_595_000_000, qty: 100_000_000 });
+        assert_eq!(book.bids[2], Level::default());
+        assert_eq!(book.asks[0], Level { price: 29_600_000_000, qty: 200_000_000 });
+    }
+
+    #[test]
+    fn test_order_lifecycle() {
+        let mut book = L1FriendlyBook::new();
+        let mut driver = snapshot_driver(&mut book);
+
+        let stale = br#"{"type":"done","side":"buy","order_id":"b1","reason":"canceled","product_id":"BTC-USD","sequence":10}"#;
+        assert_eq!(driver.parse_message(stale, &mut book), Ok(false));
+        assert_eq!(book.bids[0].qty, 200_000_000);
+
+        let received = br#"{"type":"received","side":"sell","order_id":"a2","order_type":"limit","size":"1","price":"295.99","product_id":"BTC-USD","sequence":11}"#;
+        assert_eq!(driver.parse_message(received, &mut book), Ok(false));
+
+        let open = br#"{"type":"open","side":"sell","price":"295.99","order_id":"a2","remaining_size":"1","product_id":"BTC-USD","sequence":12}"#;
+        assert_eq!(driver.parse_message(open, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(open), UpdateCause::Delta);
+        let subscriptions = br#"{"type":"subscriptions","channels":[{"name":"full","product_ids":["BTC-USD"]}]}"#;
+        assert_eq!(driver.update_cause(subscriptions), UpdateCause::Snapshot);
+        assert_eq!(book.asks[0], Level { price: 29_599_000_000, qty: 100_000_000 });
+        assert_eq!(book.asks[1].price, 29_600_000_000);
+
+        let matched = br#"{"type":"match","trade_id":1,"maker_order_id":"b2","taker_order_id":"t","side":"buy","size":"0.5","price":"295.96","product_id":"BTC-USD","sequence":13}"#;
+        assert_eq!(driver.parse_message(matched, &mut book), Ok(true));
+        assert_eq!(book.bids[0].qty, 150_000_000);
+
+        let change = br#"{"type":"change","side":"buy","order_id":"b2","new_size":"0.25","old_size":"1","price":"295.96","product_id":"BTC-USD","sequence":14}"#;
+        assert_eq!(driver.parse_message(change, &mut book), Ok(true));
+        assert_eq!(book.bids[0].qty, 75_000_000);
+
+        let done = br#"{"type":"done","side":"buy","order_id":"b1","reason":"canceled","price":"295.96","remaining_size":"0.5","product_id":"BTC-USD","sequence":15}"#;
+        assert_eq!(driver.parse_message(done, &mut book), Ok(true));
+        let done = br#"{"type":"done","side":"buy","order_id":"b2","reason":"canceled","price":"295.96","remaining_size":"0.25","product_id":"BTC-USD","sequence":16}"#;
+        assert_eq!(driver.parse_message(done, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 29_595_000_000, qty: 100_000_000 });
+        assert_eq!(book.bids[1], Level::default());
+        assert!(driver.orders.get("b2").is_none());
+    }
+
+    #[test]
+    fn test_sequence_gap() {
+        let mut book = L1FriendlyBook::new();
+        let mut driver = snapshot_driver(&mut book);
+        let open = br#"{"type":"open","side":"sell","price":"295.99","order_id":"a2","remaining_size":"1","product_id":"BTC-USD","sequence":13}"#;
+        assert_eq!(
+            driver.parse_message(open, &mut book),
+            Err(DriverError::SequenceGap { expected: 11, received: 13 })
+        );
+    }
+
+    #[test]
+    fn test_error_is_rejected() {
+        let mut book = L1FriendlyBook::new();
+        let error = br#"{"type":"error","message":"Failed to subscribe","reason":"ETH-BTC is delisted"}"#;
+        assert_eq!(
+            CoinbaseDriver::new().parse_message(error, &mut book),
+            Err(DriverError::Rejected("Failed to subscribe: ETH-BTC is delisted".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let product = br#"{"id":"ETH-BTC","base_currency":"ETH","quote_currency":"BTC","quote_increment":"0.00001","base_increment":"0.00000001"}"#;
+        assert_eq!(precision(product), Ok(Precision::from_decimals(5, 8).unwrap()));
+        assert_eq!(precision(br#"{"message":"NotFound"}"#), Err(DriverError::Malformed));
+    }
+}
@@ -0,0 +1,384 @@
+//! Deribit v2 `book.{instrument_name}.{interval}` channels.
+
+use crate::broker::instruments::Precision;
+use crate::broker::{Exchange, Feed, ProductType, SymbolKey};
+use crate::connector::keepalive::Keepalive;
+use crate::driver::{
+    DriverError, ExchangeDriver, Scales, apply_level, expect_token, find, find_u64, parse_i64, parse_qty, rest_get,
+    schema,
+};
+use crate::model::{L1FriendlyBook, UpdateCause};
+use std::time::Duration;
+
+const WS_URL: &str = "wss://www.deribit.com/ws/api/v2";
+const INSTRUMENT_URL: &str = "https://www.deribit.com/api/v2/public/get_instrument";
+
+/// Fixed-point scale applied to Deribit prices, unless the instrument's
+/// `tick_size` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to Deribit amounts, unless the instrument's
+/// `min_trade_amount` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Heartbeat interval requested with `public/set_heartbeat`, in seconds.
+const HEARTBEAT_SECS: u64 = 30;
+
+/// Notification interval of the book channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeribitInterval {
+    /// Aggregated changes every 100 milliseconds.
+    Ms100,
+    /// Every change as it happens. Deribit only serves this to authorized
+    /// connections.
+    Raw,
+}
+
+impl DeribitInterval {
+    fn name(self) -> &'static str {
+        match self {
+            DeribitInterval::Ms100 => "100ms",
+            DeribitInterval::Raw => "raw",
+        }
+    }
+}
+
+/// Driver for Deribit perpetuals, futures, options and spot books.
+///
+/// Each subscription starts with a `snapshot` notification; later `change`
+/// notifications carry `new`/`change`/`delete` entries per level. Each
+/// `change` names the `change_id` it follows as `prev_change_id`; one that
+/// does not follow on from the last is reported as
+/// [DriverError::SequenceGap].
+///
+/// After subscribing the driver enables Deribit's heartbeats and answers
+/// each `test_request` with `public/test`; Deribit closes connections that
+/// leave one unanswered.
+pub struct DeribitDriver {
+    interval: DeribitInterval,
+    scales: Scales,
+    /// Frame queued for [ExchangeDriver::pending_reply].
+    reply: Option<String>,
+    /// `change_id` of the last notification applied.
+    change_id: Option<u64>,
+}
+
+impl DeribitDriver {
+    pub fn new() -> Self {
+        Self::with_interval(DeribitInterval::Ms100)
+    }
+
+    pub fn with_interval(interval: DeribitInterval) -> Self {
+        Self {
+            interval,
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            reply: None,
+            change_id: None,
+        }
+    }
+
+    fn request(&self, method: &str, key: &SymbolKey) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"public/{method}","params":{{"channels":["book.{}.{}"]}}}}"#,
+            instrument_name(key),
+            self.interval.name()
+        )
+    }
+}
+
+impl Default for DeribitDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps a [SymbolKey] onto a Deribit instrument name.
+///
+/// * Perpetual: `BTC` → `BTC-PERPETUAL`
+/// * Spot: `BTC-USDC` → `BTC_USDC`
+/// * Future / Option: passed through (`BTC-27JUN25`, `BTC-27JUN25-100000-C`)
+pub fn instrument_name(key: &SymbolKey) -> String {
+    let symbol = key.symbol.to_ascii_uppercase();
+    match key.product {
+        ProductType::Perpetual if !symbol.ends_with("-PERPETUAL") => format!("{symbol}-PERPETUAL"),
+        ProductType::Spot => symbol.replace(['-', '/'], "_"),
+        _ => symbol,
+    }
+}
+
+/// Maps a Deribit instrument name back onto a [SymbolKey].
+///
+/// Returns `None` if the name does not follow a known Deribit pattern.
+pub fn symbol_key(instrument: &str) -> Option<SymbolKey> {
+    let parts: Vec<&str> = instrument.split('-').collect();
+    let product = match parts.as_slice() {
+        [pair] if pair.contains('_') => ProductType::Spot,
+        [_, "PERPETUAL"] => ProductType::Perpetual,
+        [_, _expiry] => ProductType::Future,
+        [_, _expiry, _strike, "C" | "P"] => ProductType::VanillaOption,
+        _ => return None,
+    };
+
+    Some(SymbolKey {
+        exchange: Exchange::Deribit,
+        symbol: instrument.to_string(),
+        product,
+        feed: Feed::Depth,
+    })
+}
+
+/// Reads the sizes off a `public/get_instrument` response.
+fn precision(body: &[u8]) -> Result<Option<Precision>, DriverError> {
+    let response: schema::deribit::GetInstrument = schema::decode(body)?;
+    match response.result {
+        Some(inst) => Precision::from_sizes(inst.tick_size.text(), inst.min_trade_amount.text())
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+impl ExchangeDriver for DeribitDriver {
+    /// Queues `public/set_heartbeat`, which goes out after the subscription.
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.change_id = None;
+        self.reply = Some(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"public/set_heartbeat","params":{{"interval":{HEARTBEAT_SECS}}}}}"#
+        ));
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        precision(rest_get(&format!("{INSTRUMENT_URL}?instrument_name={}", instrument_name(key)))?.as_bytes())
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(self.request("subscribe", key))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(self.request("unsubscribe", key))
+    }
+
+    /// `snapshot` notifications replace the book.
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if find(msg, br#""type":"snapshot""#).is_some() { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    /// Applies a book notification.
+    ///
+    /// ```json
+    /// {"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"change","bids":[["delete",5042.34,0]],"asks":[["new",5042.64,40]]}}}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if find(msg, br#""method":"subscription""#).is_none() {
+            let control: schema::deribit::Control = schema::decode(msg)?;
+            if let Some(error) = control.error {
+                return Err(DriverError::Rejected(error.message.into_owned()));
+            }
+            if control.params.is_some_and(|params| params.kind == "test_request") {
+                self.reply = Some(r#"{"jsonrpc":"2.0","id":3,"method":"public/test","params":{}}"#.to_string());
+            }
+            return Ok(false);
+        }
+
+        let snapshot = find(msg, br#""type":"snapshot""#).is_some();
+        let change_id = find_u64(msg, "change_id").ok_or(DriverError::Malformed)?;
+        if !snapshot && let Some(last) = self.change_id {
+            let previous = find_u64(msg, "prev_change_id").ok_or(DriverError::Malformed)?;
+            if previous != last {
+                return Err(DriverError::SequenceGap {
+                    expected: last,
+                    received: previous,
+                });
+            }
+        }
+        self.change_id = Some(change_id);
+
+        self.scales.stamp(book);
+        if snapshot {
+            book.clear_sides();
+        }
+
+        let bids = find(msg, br#""bids":"#).ok_or(DriverError::Malformed)? + 7;
+        for_each_entry(msg, bids, self.scales, |price, qty| {
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+        })?;
+
+        let asks = find(msg, br#""asks":"#).ok_or(DriverError::Malformed)? + 7;
+        for_each_entry(msg, asks, self.scales, |price, qty| {
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+        })?;
+
+        Ok(true)
+    }
+
+    fn pending_reply(&mut self) -> Option<String> {
+        self.reply.take()
+    }
+
+    /// Deribit's heartbeats keep the stream busy, so no pings are sent; the
+    /// stream is dropped after three silent intervals.
+    fn keepalive(&self) -> Keepalive {
+        Keepalive {
+            timeout: Some(Duration::from_secs(HEARTBEAT_SECS * 3)),
+            ..Keepalive::NONE
+        }
+    }
+}
+
+/// Walks a JSON array of `["action",price,amount]` entries starting at `start`.
+///
+/// `delete` entries are reported with a zero quantity.
+fn for_each_entry(
+    bytes: &[u8],
+    start: usize,
+    scales: Scales,
+    mut on_level: impl FnMut(i64, i64),
+) -> Result<usize, DriverError> {
+    let mut idx = expect_token(bytes, start, b"[")?;
+    if bytes.get(idx) == Some(&b']') {
+        return Ok(idx + 1);
+    }
+
+    loop {
+        idx = expect_token(bytes, idx, b"[\"")?;
+        let delete = bytes[idx..].starts_with(b"delete\"");
+        idx = bytes[idx..]
+            .iter()
+            .position(|&b| b == b'"')
+            .map(|offset| idx + offset + 1)
+            .ok_or(DriverError::Malformed)?;
+        idx = expect_token(bytes, idx, b",")?;
+        let (price, next) = parse_i64(bytes, idx, scales.price)?;
+        idx = expect_token(bytes, next, b",")?;
+        let (qty, next) = parse_qty(bytes, idx, scales.qty)?;
+        idx = expect_token(bytes, next, b"]")?;
+
+        on_level(price, if delete { 0 } else { qty });
+
+        match bytes.get(idx) {
+            Some(b',') => idx += 1,
+            Some(b']') => return Ok(idx + 1),
+            _ => return Err(DriverError::Malformed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Level;
+
+    fn key(symbol: &str, product: ProductType) -> SymbolKey {
+        SymbolKey {
+            exchange: Exchange::Deribit,
+            symbol: symbol.to_string(),
+            product,
+            feed: Feed::Depth,
+        }
+    }
+
+    #[test]
+    fn test_instrument_name_mapping() {
+        assert_eq!(instrument_name(&key("btc", ProductType::Perpetual)), "BTC-PERPETUAL");
+        assert_eq!(instrument_name(&key("BTC-PERPETUAL", ProductType::Perpetual)), "BTC-PERPETUAL");
+        assert_eq!(instrument_name(&key("BTC-USDC", ProductType::Spot)), "BTC_USDC");
+        assert_eq!(instrument_name(&key("BTC-27JUN25-100000-C", ProductType::VanillaOption)), "BTC-27JUN25-100000-C");
+    }
+
+    #[test]
+    fn test_symbol_key_mapping() {
+        assert_eq!(symbol_key("BTC-PERPETUAL"), Some(key("BTC-PERPETUAL", ProductType::Perpetual)));
+        assert_eq!(symbol_key("BTC_USDC"), Some(key("BTC_USDC", ProductType::Spot)));
+        assert_eq!(symbol_key("BTC-27JUN25"), Some(key("BTC-27JUN25", ProductType::Future)));
+        assert_eq!(
+            symbol_key("ETH-27JUN25-4000-P"),
+            Some(key("ETH-27JUN25-4000-P", ProductType::VanillaOption))
+        );
+        assert_eq!(symbol_key("BTC-27JUN25-4000-X"), None);
+    }
+
+    #[test]
+    fn test_subscribe_msg() {
+        let driver = DeribitDriver::with_interval(DeribitInterval::Raw);
+        assert_eq!(
+            driver.subscribe_msg(&key("BTC-27JUN25-100000-C", ProductType::VanillaOption)).unwrap(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"public/subscribe","params":{"channels":["book.BTC-27JUN25-100000-C.raw"]}}"#
+        );
+    }
+
+    #[test]
+    fn test_snapshot_then_change() {
+        let mut driver = DeribitDriver::new();
+        let mut book = L1FriendlyBook::new();
+
+        let snapshot = br#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"snapshot","timestamp":1,"instrument_name":"BTC-PERPETUAL","change_id":1,"bids":[["new",5042.34,30],["new",5041.94,20]],"asks":[["new",5042.64,40]]}}}"#;
+        assert_eq!(driver.parse_message(snapshot, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 504_234_000_000, qty: 3_000_000_000 });
+        assert_eq!(book.asks[0].price, 504_264_000_000);
+
+        let change = br#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"change","timestamp":2,"instrument_name":"BTC-PERPETUAL","prev_change_id":1,"change_id":2,"bids":[["delete",5042.34,0.0]],"asks":[["change",5042.64,10]]}}}"#;
+        assert_eq!(driver.parse_message(change, &mut book), Ok(true));
+        L1FriendlyBook::compact(&mut book.bids, &mut book.bid_tombstones);
+        assert_eq!(book.bids[0].price, 504_194_000_000);
+        assert_eq!(book.asks[0].qty, 1_000_000_000);
+
+        // A change that does not follow the last one
+        let gap = br#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"change","timestamp":3,"instrument_name":"BTC-PERPETUAL","prev_change_id":4,"change_id":5,"bids":[],"asks":[]}}}"#;
+        assert_eq!(
+            driver.parse_message(gap, &mut book),
+            Err(DriverError::SequenceGap { expected: 2, received: 4 })
+        );
+    }
+
+    #[test]
+    fn test_ignores_rpc_responses() {
+        let mut book = L1FriendlyBook::new();
+        let ack = br#"{"jsonrpc":"2.0","id":1,"result":["book.BTC-PERPETUAL.100ms"]}"#;
+        assert_eq!(DeribitDriver::new().parse_message(ack, &mut book), Ok(false));
+
+        let error = br#"{"jsonrpc":"2.0","id":1,"error":{"code":11050,"message":"bad_request"}}"#;
+        assert_eq!(
+            DeribitDriver::new().parse_message(error, &mut book),
+            Err(DriverError::Rejected("bad_request".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_heartbeats() {
+        let mut driver = DeribitDriver::new();
+        let mut book = L1FriendlyBook::new();
+        driver.handshake(&key("BTC", ProductType::Perpetual)).unwrap();
+        assert_eq!(
+            driver.pending_reply().unwrap(),
+            r#"{"jsonrpc":"2.0","id":2,"method":"public/set_heartbeat","params":{"interval":30}}"#
+        );
+
+        let heartbeat = br#"{"jsonrpc":"2.0","method":"heartbeat","params":{"type":"heartbeat"}}"#;
+        assert_eq!(driver.parse_message(heartbeat, &mut book), Ok(false));
+        assert_eq!(driver.pending_reply(), None);
+
+        let test_request = br#"{"jsonrpc":"2.0","method":"heartbeat","params":{"type":"test_request"}}"#;
+        assert_eq!(driver.parse_message(test_request, &mut book), Ok(false));
+        assert_eq!(
+            driver.pending_reply().unwrap(),
+            r#"{"jsonrpc":"2.0","id":3,"method":"public/test","params":{}}"#
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let body = br#"{"jsonrpc":"2.0","id":1,"result":{"instrument_name":"BTC-27JUN25-100000-C","tick_size":0.0005,"min_trade_amount":0.1,"contract_size":1.0}}"#;
+        assert_eq!(precision(body).unwrap().map(|p| (p.price_scale, p.tick, p.qty_scale)), Some((4, 5, 1)));
+        let perpetual = br#"{"jsonrpc":"2.0","id":1,"result":{"instrument_name":"BTC-PERPETUAL","tick_size":0.5,"min_trade_amount":10,"contract_size":10}}"#;
+        assert_eq!(precision(perpetual).unwrap().map(|p| (p.price_scale, p.qty_scale, p.lot)), Some((1, 0, 10)));
+        let error = br#"{"jsonrpc":"2.0","id":1,"error":{"message":"instrument_not_found","code":13020}}"#;
+        assert_eq!(precision(error), Ok(None));
+    }
+}
@@ -0,0 +1,225 @@
+//! dYdX v4 indexer `v4_orderbook` channel.
+
+use crate::broker::SymbolKey;
+use crate::broker::instruments::Precision;
+use crate::driver::schema::{self, dydx::Event, dydx::Snapshot};
+use crate::driver::{DriverError, ExchangeDriver, Scales, apply_level, find, find_u64, for_each_level, rest_get};
+use crate::model::{L1FriendlyBook, UpdateCause};
+
+const WS_URL: &str = "wss://indexer.dydx.trade/v4/ws";
+const MARKETS_URL: &str = "https://indexer.dydx.trade/v4/perpetualMarkets";
+
+/// Fixed-point scale applied to dYdX prices, unless the market's `tickSize`
+/// is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to dYdX sizes, unless the market's `stepSize`
+/// is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Driver for dYdX v4 perpetual books.
+///
+/// The `subscribed` reply carries the initial book as `{"price","size"}`
+/// objects; later `channel_data` messages carry `["price","size"]` pairs
+/// and may omit a side entirely.
+///
+/// Every message on the connection carries the next `message_id`, so one
+/// that skips an id is reported as [DriverError::SequenceGap].
+pub struct DydxDriver {
+    scales: Scales,
+    /// `message_id` of the last message received.
+    message_id: Option<u64>,
+}
+
+impl DydxDriver {
+    pub fn new() -> Self {
+        Self {
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            message_id: None,
+        }
+    }
+}
+
+impl Default for DydxDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `btc/usd` into dYdX's `BTC-USD` market id.
+pub fn market_id(symbol: &str) -> String {
+    symbol.replace(['/', '_'], "-").to_ascii_uppercase()
+}
+
+/// Finds the sizes of `market` in a `perpetualMarkets` response.
+///
+/// ```json
+/// {"markets":{"BTC-USD":{"ticker":"BTC-USD","status":"ACTIVE","tickSize":"1","stepSize":"0.0001"}}}
+/// ```
+fn precision(body: &[u8], market: &str) -> Result<Option<Precision>, DriverError> {
+    let markets: schema::dydx::PerpetualMarkets = schema::decode(body)?;
+    match markets.markets.get(market) {
+        Some(info) => Precision::from_sizes(info.tick_size.as_bytes(), info.step_size.as_bytes())
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+impl ExchangeDriver for DydxDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.message_id = None;
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        let market = market_id(&key.symbol);
+        precision(rest_get(&format!("{MARKETS_URL}?ticker={market}"))?.as_bytes(), &market)
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"type":"subscribe","channel":"v4_orderbook","id":"{}"}}"#,
+            market_id(&key.symbol)
+        ))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"type":"unsubscribe","channel":"v4_orderbook","id":"{}"}}"#,
+            market_id(&key.symbol)
+        ))
+    }
+
+    /// The `subscribed` frame carries the initial book.
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if find(msg, br#""type":"subscribed""#).is_some() { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    /// Applies the initial book or an incremental update.
+    ///
+    /// ```json
+    /// {"type":"channel_data","connection_id":"c","message_id":3,"id":"BTC-USD","channel":"v4_orderbook","version":"1.0.0","contents":{"bids":[["65000","0"]]}}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if let Some(id) = find_u64(msg, "message_id") {
+            if let Some(last) = self.message_id
+                && id != last.wrapping_add(1)
+            {
+                return Err(DriverError::SequenceGap {
+                    expected: last.wrapping_add(1),
+                    received: id,
+                });
+            }
+            self.message_id = Some(id);
+        }
+
+        if find(msg, br#""channel":"v4_orderbook""#).is_none() {
+            return match schema::decode(msg)? {
+                Event::Error(error) => Err(DriverError::Rejected(error.message.into_owned())),
+                _ => Ok(false),
+            };
+        }
+
+        self.scales.stamp(book);
+        if find(msg, br#""type":"subscribed""#).is_some() {
+            let snapshot: Snapshot = schema::decode(msg)?;
+            book.clear_sides();
+
+            for level in &snapshot.contents.bids {
+                apply_level(&mut book.bids, &mut book.bid_tombstones, level.price.parse(self.scales.price)?, level.size.parse(self.scales.qty)?, true);
+            }
+            for level in &snapshot.contents.asks {
+                apply_level(&mut book.asks, &mut book.ask_tombstones, level.price.parse(self.scales.price)?, level.size.parse(self.scales.qty)?, false);
+            }
+            return Ok(true);
+        }
+
+        if find(msg, br#""type":"channel_data""#).is_none() {
+            return Ok(false);
+        }
+
+        if let Some(bids) = find(msg, br#""bids":"#) {
+            for_each_level(msg, bids + 7, self.scales.price, self.scales.qty, |price, qty| {
+                apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+            })?;
+        }
+        if let Some(asks) = find(msg, br#""asks":"#) {
+            for_each_level(msg, asks + 7, self.scales.price, self.scales.qty, |price, qty| {
+                apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+            })?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed, ProductType};
+    use crate::model::Level;
+
+    #[test]
+    fn test_subscribe_msg() {
+        let key = SymbolKey {
+            exchange: Exchange::Dydx,
+            symbol: "btc/usd".to_string(),
+            product: ProductType::Perpetual,
+            feed: Feed::Depth,
+        };
+        assert_eq!(
+            DydxDriver::new().subscribe_msg(&key).unwrap(),
+            r#"{"type":"subscribe","channel":"v4_orderbook","id":"BTC-USD"}"#
+        );
+    }
+
+    #[test]
+    fn test_snapshot_then_partial_update() {
+        let mut driver = DydxDriver::new();
+        let mut book = L1FriendlyBook::new();
+
+        let subscribed = br#"{"type":"subscribed","connection_id":"c","message_id":1,"channel":"v4_orderbook","id":"BTC-USD","contents":{"bids":[{"price":"65000","size":"1.2"},{"price":"64999","size":"0.5"}],"asks":[{"price":"65001","size":"2"}]}}"#;
+        assert_eq!(driver.parse_message(subscribed, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(subscribed), UpdateCause::Snapshot);
+        assert_eq!(book.bids[0], Level { price: 6_500_000_000_000, qty: 120_000_000 });
+        assert_eq!(book.asks[0].price, 6_500_100_000_000);
+
+        let update = br#"{"type":"channel_data","connection_id":"c","message_id":2,"id":"BTC-USD","channel":"v4_orderbook","version":"1.0.0","contents":{"bids":[["65000","0"]]}}"#;
+        assert_eq!(driver.parse_message(update, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(update), UpdateCause::Delta);
+        L1FriendlyBook::compact(&mut book.bids, &mut book.bid_tombstones);
+        assert_eq!(book.bids[0].price, 6_499_900_000_000);
+        assert_eq!(book.asks[0].qty, 200_000_000);
+
+        let gap = br#"{"type":"channel_data","connection_id":"c","message_id":4,"id":"BTC-USD","channel":"v4_orderbook","version":"1.0.0","contents":{}}"#;
+        assert_eq!(
+            driver.parse_message(gap, &mut book),
+            Err(DriverError::SequenceGap { expected: 3, received: 4 })
+        );
+    }
+
+    #[test]
+    fn test_ignores_connected() {
+        let mut book = L1FriendlyBook::new();
+        let connected = br#"{"type":"connected","connection_id":"c","message_id":0}"#;
+        assert_eq!(DydxDriver::new().parse_message(connected, &mut book), Ok(false));
+
+        let error = br#"{"type":"error","message":"Invalid subscribe message: channel is not valid","connection_id":"c","message_id":1}"#;
+        assert_eq!(
+            DydxDriver::new().parse_message(error, &mut book),
+            Err(DriverError::Rejected("Invalid subscribe message: channel is not valid".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let body = br#"{"markets":{"BTC-USD":{"ticker":"BTC-USD","status":"ACTIVE","tickSize":"1","stepSize":"0.0001"}}}"#;
+        assert_eq!(precision(body, "BTC-USD"), Ok(Precision::from_decimals(0, 4)));
+        assert_eq!(precision(br#"{"markets":{}}"#, "BTC-USD"), Ok(None));
+    }
+}
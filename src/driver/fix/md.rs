@@ -0,0 +1,188 @@
+//! FIX 4.4 market-data application layer.
+
+use crate::driver::fix::session::{field, fields, push_field, text};
+use crate::driver::{DriverError, apply_counted_level};
+use crate::model::{L1FriendlyBook, UpdateCause};
+use crate::util::{ParseOptions, Terminators, parse_i64_with_options};
+
+/// Fixed-point scale applied to FIX prices.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to FIX sizes.
+pub const QTY_SCALE: u32 = 8;
+
+/// Builds the body of a MarketDataRequest (`V`) for bids and offers on `symbol`.
+///
+/// Subscribes to a snapshot followed by incremental refreshes, or cancels
+/// the request with the same `req_id` when `subscribe` is false.
+pub fn request_body(req_id: &str, symbol: &str, depth: u32, subscribe: bool) -> String {
+    let mut body = String::new();
+    push_field(&mut body, 262, req_id);
+    push_field(&mut body, 263, if subscribe { 1 } else { 2 });
+    push_field(&mut body, 264, depth);
+    push_field(&mut body, 265, 1);
+    push_field(&mut body, 267, 2);
+    push_field(&mut body, 269, 0);
+    push_field(&mut body, 269, 1);
+    push_field(&mut body, 146, 1);
+    push_field(&mut body, 55, symbol);
+    body
+}
+
+/// One `NoMDEntries` group entry.
+#[derive(Default)]
+struct Entry {
+    /// MDUpdateAction (`279`); `2` deletes the level.
+    action: u8,
+    /// MDEntryType (`269`): `0` bid, `1` offer.
+    entry_type: u8,
+    price: Option<i64>,
+    size: i64,
+    /// NumberOfOrders (`346`), 0 if absent.
+    orders: u32,
+}
+
+/// Applies an application message to `book`.
+///
+/// Handles MarketDataSnapshotFullRefresh (`W`), which replaces the book,
+/// and MarketDataIncrementalRefresh (`X`), recording each level's
+/// NumberOfOrders where sent. A MarketDataRequestReject (`Y`)
+/// is reported as [DriverError::Rejected]; other types are ignored.
+pub fn apply(msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+    match field(msg, 35) {
+        Some(b"W") => {
+            book.clear_sides();
+            apply_entries(msg, book)?;
+            Ok(true)
+        }
+        Some(b"X") => {
+            apply_entries(msg, book)?;
+            Ok(true)
+        }
+        Some(b"Y") => Err(DriverError::Rejected(text(field(msg, 58).unwrap_or(b"Y")).to_string())),
+        _ => Ok(false),
+    }
+}
+
+/// Returns [UpdateCause::Snapshot] for a MarketDataSnapshotFullRefresh
+/// (`W`), which replaces the book, and [UpdateCause::Delta] otherwise.
+pub fn update_cause(msg: &[u8]) -> UpdateCause {
+    if field(msg, 35) == Some(b"W") { UpdateCause::Snapshot } else { UpdateCause::Delta }
+}
+
+/// Walks the `NoMDEntries` repeating group and applies each bid or offer.
+///
+/// The group delimiter is whichever tag follows `268`; a repeat of it
+/// starts the next entry.
+fn apply_entries(msg: &[u8], book: &mut L1FriendlyBook) -> Result<(), DriverError> {
+    let mut group = fields(msg).skip_while(|&(tag, _)| tag != 268).skip(1).peekable();
+    let Some(&(delimiter, _)) = group.peek() else {
+        return Ok(());
+    };
+
+    let mut entry: Option<Entry> = None;
+    for (tag, value) in group {
+        if tag == delimiter || tag == 10 {
+            if let Some(entry) = entry.take() {
+                apply_entry(&entry, book);
+            }
+            if tag == 10 {
+                break;
+            }
+            entry = Some(Entry::default());
+        }
+
+        let Some(entry) = entry.as_mut() else {
+            continue;
+        };
+        match tag {
+            279 => entry.action = value.first().copied().unwrap_or_default(),
+            269 => entry.entry_type = value.first().copied().unwrap_or_default(),
+            270 => entry.price = Some(number(value, PRICE_SCALE)?),
+            271 => entry.size = number(value, QTY_SCALE)?,
+            346 => entry.orders = u32::try_from(number(value, 0)?).map_err(|_| DriverError::Malformed)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn apply_entry(entry: &Entry, book: &mut L1FriendlyBook) {
+    let Some(price) = entry.price else {
+        return;
+    };
+    let qty = if entry.action == b'2' { 0 } else { entry.size };
+    match entry.entry_type {
+        b'0' => apply_counted_level(&mut book.bids, &mut book.bid_tombstones, &mut book.bid_orders, price, qty, entry.orders, true),
+        b'1' => apply_counted_level(&mut book.asks, &mut book.ask_tombstones, &mut book.ask_orders, price, qty, entry.orders, false),
+        _ => {}
+    }
+}
+
+/// FIX floats may carry a `+`, and some counterparties pad them.
+const FIX_NUMBER: ParseOptions = ParseOptions {
+    allow_plus: true,
+    skip_whitespace: true,
+    strict: false,
+    terminators: Terminators::ANY,
+};
+
+fn number(value: &[u8], scale: u32) -> Result<i64, DriverError> {
+    let (number, end) = parse_i64_with_options(value, 0, scale, FIX_NUMBER)?;
+    if end != value.len() {
+        return Err(DriverError::Malformed);
+    }
+    Ok(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::fix::session::SOH;
+    use crate::model::Level;
+
+    fn msg(fields: &str) -> Vec<u8> {
+        fields.replace('|', "\u{1}").into_bytes()
+    }
+
+    #[test]
+    fn test_request_body() {
+        assert_eq!(
+            request_body("md-1", "BTC/USD", 0, true).replace(SOH, "|"),
+            "262=md-1|263=1|264=0|265=1|267=2|269=0|269=1|146=1|55=BTC/USD|"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_then_incremental() {
+        let mut book = L1FriendlyBook::new();
+
+        let snapshot = msg("8=FIX.4.4|9=0|35=W|34=2|55=BTC/USD|268=3|269=0|270=100.5|271=2|346=4|269=0|270=100|271=1|269=1|270=101|271=3|10=000|");
+        assert_eq!(apply(&snapshot, &mut book), Ok(true));
+        assert_eq!(update_cause(&snapshot), UpdateCause::Snapshot);
+        assert_eq!(book.bids[0], Level { price: 10_050_000_000, qty: 200_000_000 });
+        assert_eq!(book.bids[1].price, 10_000_000_000);
+        assert_eq!(book.asks[0], Level { price: 10_100_000_000, qty: 300_000_000 });
+        assert_eq!(book.bid_orders[..2], [4, 0]);
+
+        let incremental = msg("8=FIX.4.4|9=0|35=X|34=3|268=2|279=2|269=0|55=BTC/USD|270=100.5|279=0|269=1|55=BTC/USD|270=100.75|271=0.5|10=000|");
+        assert_eq!(apply(&incremental, &mut book), Ok(true));
+        assert_eq!(update_cause(&incremental), UpdateCause::Delta);
+        assert_eq!(book.bid_tombstones, 1);
+        assert_eq!(book.asks[0], Level { price: 10_075_000_000, qty: 50_000_000 });
+    }
+
+    #[test]
+    fn test_request_reject() {
+        let mut book = L1FriendlyBook::new();
+        let reject = msg("8=FIX.4.4|9=0|35=Y|34=2|262=md-1|58=Unknown symbol|10=000|");
+        assert_eq!(apply(&reject, &mut book), Err(DriverError::Rejected("Unknown symbol".to_string())));
+    }
+
+    #[test]
+    fn test_signed_and_padded_numbers() {
+        assert_eq!(number(b"+1.5", 2), Ok(150));
+        assert_eq!(number(b" 1.5 ", 2), Ok(150));
+        assert_eq!(number(b"1.5x", 2), Err(DriverError::Malformed));
+    }
+}
@@ -0,0 +1,250 @@
+//! Generic FIX 4.4 market-data connector.
+//!
+//! Venues that only offer FIX are served by a [FixDriver] instead of their
+//! native websocket driver once a [FixConfig] is registered for the
+//! [Exchange] with [register].
+
+pub mod md;
+pub mod session;
+
+use crate::broker::{Exchange, SymbolKey};
+use crate::connector::keepalive::Keepalive;
+use crate::driver::{DriverError, ExchangeDriver, Transport};
+use crate::model::{L1FriendlyBook, UpdateCause};
+use parking_lot::RwLock;
+use session::{FixSession, Inbound};
+use std::collections::HashMap;
+use std::mem;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+
+/// Exchanges routed over FIX, and how to reach them.
+static VENUES: LazyLock<RwLock<HashMap<Exchange, FixConfig>>> = LazyLock::new(Default::default);
+
+/// Connection and identity settings for a FIX venue.
+#[derive(Debug, Clone)]
+pub struct FixConfig {
+    /// `host:port` of the venue's market-data gateway.
+    pub endpoint: String,
+    pub sender_comp_id: String,
+    pub target_comp_id: String,
+    /// Sent as Username (`553`) on logon, if set.
+    pub username: Option<String>,
+    /// Sent as Password (`554`) on logon, if set.
+    pub password: Option<String>,
+    pub heartbeat_interval: Duration,
+    /// MarketDepth (`264`) to request; `0` asks for the full book.
+    pub market_depth: u32,
+}
+
+/// Routes all future subscriptions on `exchange` over FIX.
+///
+/// Streams that are already connected keep their current driver until they
+/// reconnect from scratch.
+pub fn register(exchange: Exchange, config: FixConfig) {
+    VENUES.write().insert(exchange, config);
+}
+
+/// Returns `exchange` to its native driver for future subscriptions.
+pub fn unregister(exchange: Exchange) {
+    VENUES.write().remove(&exchange);
+}
+
+/// Returns the FIX settings registered for `exchange`, if any.
+pub(crate) fn config_for(exchange: Exchange) -> Option<FixConfig> {
+    VENUES.read().get(&exchange).cloned()
+}
+
+/// Driver for a single FIX market-data session.
+///
+/// Each symbol gets its own session: the driver logs on when the connector
+/// connects, sends a MarketDataRequest for the key's symbol once the logon
+/// is acknowledged, and feeds snapshot and incremental refreshes into the
+/// book. The key's symbol is sent as-is in Symbol (`55`).
+pub struct FixDriver {
+    config: FixConfig,
+    session: FixSession,
+    symbol: String,
+    /// Reusable buffer for the message being processed.
+    scratch: Vec<u8>,
+    /// Snapshot if a full refresh was applied from the last frame.
+    cause: UpdateCause,
+}
+
+impl FixDriver {
+    pub fn new(config: FixConfig) -> Self {
+        let session = FixSession::new(&config.sender_comp_id, &config.target_comp_id, config.heartbeat_interval);
+        Self {
+            config,
+            session,
+            symbol: String::new(),
+            scratch: Vec::new(),
+            cause: UpdateCause::Delta,
+        }
+    }
+
+    /// Processes every complete message buffered in the session.
+    fn drain(&mut self, scratch: &mut Vec<u8>, book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let mut modified = false;
+        while self.session.next_message(scratch)? {
+            match self.session.accept(scratch)? {
+                Inbound::Logon => {
+                    let body = md::request_body(&md_req_id(&self.symbol), &self.symbol, self.config.market_depth, true);
+                    self.session.send("V", &body);
+                }
+                Inbound::App if md::apply(scratch, book)? => {
+                    modified = true;
+                    if md::update_cause(scratch) == UpdateCause::Snapshot {
+                        self.cause = UpdateCause::Snapshot;
+                    }
+                }
+                Inbound::App => {}
+                Inbound::Admin => {}
+            }
+        }
+        Ok(modified)
+    }
+}
+
+fn md_req_id(symbol: &str) -> String {
+    format!("md-{symbol}")
+}
+
+impl ExchangeDriver for FixDriver {
+    fn transport(&self) -> Transport {
+        Transport::Tcp
+    }
+
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.symbol = key.symbol.clone();
+        self.session.reset();
+        self.session
+            .logon(self.config.username.as_deref(), self.config.password.as_deref());
+        Ok(())
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        self.config.endpoint.clone()
+    }
+
+    /// The Logon is queued by `handshake` and sent through [ExchangeDriver::pending_reply].
+    fn subscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+        None
+    }
+
+    /// Cancels the MarketDataRequest and logs out.
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        let seq = self.session.next_out_seq();
+        let now = SystemTime::now();
+        let cancel = md::request_body(&md_req_id(&key.symbol), &key.symbol, self.config.market_depth, false);
+        Some(self.session.encode(seq, "V", &cancel, now) + &self.session.encode(seq + 1, "5", "", now))
+    }
+
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        self.session.push(msg);
+        self.cause = UpdateCause::Delta;
+        let mut scratch = mem::take(&mut self.scratch);
+        let result = self.drain(&mut scratch, book);
+        self.scratch = scratch;
+        result
+    }
+
+    fn pending_reply(&mut self) -> Option<String> {
+        self.session.poll_outbox()
+    }
+
+    /// A frame is a snapshot if any of its messages was a full refresh.
+    fn update_cause(&self, _msg: &[u8]) -> UpdateCause {
+        self.cause
+    }
+
+    /// The session sends its own Heartbeats; the venue's are expected at the
+    /// same interval, so the session is dropped after three missed ones.
+    fn keepalive(&self) -> Keepalive {
+        Keepalive {
+            ping_interval: None,
+            timeout: Some(self.config.heartbeat_interval * 3),
+            ..Keepalive::NONE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Feed, ProductType};
+    use crate::model::Level;
+    use session::{SOH, field};
+    use std::time::UNIX_EPOCH;
+
+    fn config() -> FixConfig {
+        FixConfig {
+            endpoint: "127.0.0.1:9878".to_string(),
+            sender_comp_id: "CLIENT".to_string(),
+            target_comp_id: "VENUE".to_string(),
+            username: Some("user".to_string()),
+            password: None,
+            heartbeat_interval: Duration::from_secs(30),
+            market_depth: 0,
+        }
+    }
+
+    fn inbound(seq: u64, msg_type: &str, body: &str) -> String {
+        FixSession::new("VENUE", "CLIENT", Duration::from_secs(30)).encode(seq, msg_type, &body.replace('|', "\u{1}"), UNIX_EPOCH)
+    }
+
+    #[test]
+    fn test_logon_then_market_data() {
+        let key = SymbolKey {
+            exchange: Exchange::Coinbase,
+            symbol: "BTC/USD".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let mut driver = FixDriver::new(config());
+        let mut book = L1FriendlyBook::new();
+        driver.handshake(&key).unwrap();
+
+        let logon = driver.pending_reply().unwrap();
+        assert_eq!(field(logon.as_bytes(), 35), Some(&b"A"[..]));
+        assert_eq!(field(logon.as_bytes(), 553), Some(&b"user"[..]));
+        assert_eq!(driver.pending_reply(), None);
+
+        let ack = inbound(1, "A", "98=0|108=30|141=Y|");
+        assert_eq!(driver.parse_message(ack.as_bytes(), &mut book), Ok(false));
+        let request = driver.pending_reply().unwrap();
+        assert_eq!(field(request.as_bytes(), 35), Some(&b"V"[..]));
+        assert_eq!(field(request.as_bytes(), 34), Some(&b"2"[..]));
+        assert_eq!(field(request.as_bytes(), 55), Some(&b"BTC/USD"[..]));
+
+        let snapshot = inbound(2, "W", "55=BTC/USD|268=2|269=0|270=100|271=1|269=1|270=101|271=2|");
+        assert_eq!(driver.parse_message(snapshot.as_bytes(), &mut book), Ok(true));
+        assert_eq!(driver.update_cause(snapshot.as_bytes()), UpdateCause::Snapshot);
+        assert_eq!(book.bids[0], Level { price: 10_000_000_000, qty: 100_000_000 });
+        assert_eq!(book.asks[0].price, 10_100_000_000);
+
+        let incremental = inbound(3, "X", "268=1|279=1|269=0|55=BTC/USD|270=100|271=3|");
+        assert_eq!(driver.parse_message(incremental.as_bytes(), &mut book), Ok(true));
+        assert_eq!(driver.update_cause(incremental.as_bytes()), UpdateCause::Delta);
+        assert_eq!(book.bids[0].qty, 300_000_000);
+
+        let unsubscribe = driver.unsubscribe_msg(&key).unwrap();
+        assert_eq!(unsubscribe.matches(&format!("{SOH}35=")).count(), 2);
+    }
+
+    #[test]
+    fn test_registry_selects_fix_driver() {
+        let key = SymbolKey {
+            exchange: Exchange::Bitstamp,
+            symbol: "BTC/USD".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        register(Exchange::Bitstamp, config());
+        let driver = crate::driver::driver_for(&key).unwrap();
+        assert_eq!(driver.transport(), Transport::Tcp);
+        unregister(Exchange::Bitstamp);
+        let driver = crate::driver::driver_for(&key).unwrap();
+        assert_eq!(driver.transport(), Transport::WebSocket);
+    }
+}
@@ -0,0 +1,399 @@
+//! FIX 4.4 session layer: framing, sequencing, logon and heartbeats.
+
+use crate::driver::DriverError;
+use std::fmt::Display;
+use std::fmt::Write;
+use std::mem;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Field delimiter.
+pub const SOH: char = '\u{1}';
+
+const BEGIN_STRING: &str = "FIX.4.4";
+
+/// Length of the `10=NNN<SOH>` trailer.
+const TRAILER_LEN: usize = 7;
+
+/// What a validated inbound message means to the application layer.
+#[derive(Debug, PartialEq)]
+pub enum Inbound {
+    /// The counterparty acknowledged our Logon.
+    Logon,
+    /// A session-level message that was handled internally.
+    Admin,
+    /// An application message for the market-data layer.
+    App,
+}
+
+/// Initiator side of a FIX 4.4 session.
+///
+/// The session frames the inbound byte stream into messages, checks
+/// `MsgSeqNum` continuity and answers TestRequest, ResendRequest and
+/// SequenceReset on its own. Outbound messages are queued and collected by
+/// the driver with [FixSession::poll_outbox], which also emits a Heartbeat
+/// whenever nothing has been sent for a full heartbeat interval.
+///
+/// Every logon sets `ResetSeqNumFlag`, so both sides start from 1 on each
+/// connection and an inbound gap is reported as [DriverError::SequenceGap]
+/// for the connector to reconnect, rather than replayed.
+pub struct FixSession {
+    sender_comp_id: String,
+    target_comp_id: String,
+    heartbeat_interval: Duration,
+    next_out_seq: u64,
+    next_in_seq: u64,
+    logged_in: bool,
+    last_sent: Instant,
+    /// Unframed inbound bytes; everything before `consumed` has been handed out.
+    inbound: Vec<u8>,
+    consumed: usize,
+    outbox: String,
+}
+
+impl FixSession {
+    pub fn new(sender_comp_id: &str, target_comp_id: &str, heartbeat_interval: Duration) -> Self {
+        Self {
+            sender_comp_id: sender_comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+            heartbeat_interval,
+            next_out_seq: 1,
+            next_in_seq: 1,
+            logged_in: false,
+            last_sent: Instant::now(),
+            inbound: Vec::with_capacity(64 * 1024),
+            consumed: 0,
+            outbox: String::new(),
+        }
+    }
+
+    /// Returns the session to its pre-logon state.
+    pub fn reset(&mut self) {
+        self.next_out_seq = 1;
+        self.next_in_seq = 1;
+        self.logged_in = false;
+        self.inbound.clear();
+        self.consumed = 0;
+        self.outbox.clear();
+    }
+
+    /// Queues a Logon (`A`) that resets sequence numbers on both sides.
+    pub fn logon(&mut self, username: Option<&str>, password: Option<&str>) {
+        let mut body = String::new();
+        push_field(&mut body, 98, 0);
+        push_field(&mut body, 108, self.heartbeat_interval.as_secs());
+        push_field(&mut body, 141, 'Y');
+        if let Some(username) = username {
+            push_field(&mut body, 553, username);
+        }
+        if let Some(password) = password {
+            push_field(&mut body, 554, password);
+        }
+        self.send("A", &body);
+    }
+
+    /// Queues a message with the next outbound sequence number.
+    ///
+    /// `body` holds the fields after the standard header, each terminated by [SOH].
+    pub fn send(&mut self, msg_type: &str, body: &str) {
+        let msg = self.encode(self.next_out_seq, msg_type, body, SystemTime::now());
+        self.outbox.push_str(&msg);
+        self.next_out_seq += 1;
+        self.last_sent = Instant::now();
+    }
+
+    /// Returns the sequence number the next outbound message will carry.
+    pub fn next_out_seq(&self) -> u64 {
+        self.next_out_seq
+    }
+
+    /// Encodes a complete message, including BodyLength and CheckSum.
+    pub fn encode(&self, seq: u64, msg_type: &str, body: &str, sending_time: SystemTime) -> String {
+        let mut rest = String::with_capacity(64 + body.len());
+        push_field(&mut rest, 35, msg_type);
+        push_field(&mut rest, 49, &self.sender_comp_id);
+        push_field(&mut rest, 56, &self.target_comp_id);
+        push_field(&mut rest, 34, seq);
+        push_field(&mut rest, 52, utc_timestamp(sending_time));
+        rest.push_str(body);
+
+        let mut msg = String::with_capacity(rest.len() + 32);
+        push_field(&mut msg, 8, BEGIN_STRING);
+        push_field(&mut msg, 9, rest.len());
+        msg.push_str(&rest);
+        let checksum = msg.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
+        push_field(&mut msg, 10, format_args!("{checksum:03}"));
+        msg
+    }
+
+    /// Returns everything queued for sending, adding a Heartbeat if one is due.
+    pub fn poll_outbox(&mut self) -> Option<String> {
+        if self.logged_in && self.outbox.is_empty() && self.last_sent.elapsed() >= self.heartbeat_interval {
+            self.send("0", "");
+        }
+        if self.outbox.is_empty() {
+            return None;
+        }
+        Some(mem::take(&mut self.outbox))
+    }
+
+    /// Appends bytes read from the socket.
+    pub fn push(&mut self, bytes: &[u8]) {
+        if self.consumed > 0 {
+            self.inbound.drain(..self.consumed);
+            self.consumed = 0;
+        }
+        self.inbound.extend_from_slice(bytes);
+    }
+
+    /// Moves the next complete message into `out`.
+    ///
+    /// Returns `Ok(false)` once only a partial message (or nothing) is left.
+    pub fn next_message(&mut self, out: &mut Vec<u8>) -> Result<bool, DriverError> {
+        let pending = &self.inbound[self.consumed..];
+        if pending.is_empty() {
+            return Ok(false);
+        }
+        if !pending.starts_with(b"8=") {
+            return Err(DriverError::Malformed);
+        }
+
+        let Some(begin_end) = position(pending, 0) else {
+            return Ok(false);
+        };
+        let length_start = begin_end + 1;
+        let Some(length_end) = position(pending, length_start) else {
+            return Ok(false);
+        };
+        let length = pending[length_start..length_end]
+            .strip_prefix(b"9=")
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| digits.parse::<usize>().ok())
+            .ok_or(DriverError::Malformed)?;
+
+        let body_end = length_end + 1 + length;
+        let end = body_end + TRAILER_LEN;
+        if pending.len() < end {
+            return Ok(false);
+        }
+
+        let trailer = &pending[body_end..end];
+        let checksum = pending[..body_end].iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+        let expected = [b'1', b'0', b'=', b'0' + checksum / 100, b'0' + checksum / 10 % 10, b'0' + checksum % 10, SOH as u8];
+        if *trailer != expected {
+            return Err(DriverError::Malformed);
+        }
+
+        out.clear();
+        out.extend_from_slice(&pending[..end]);
+        self.consumed += end;
+        Ok(true)
+    }
+
+    /// Validates the sequence number of an inbound message and handles
+    /// session-level message types.
+    pub fn accept(&mut self, msg: &[u8]) -> Result<Inbound, DriverError> {
+        let msg_type = field(msg, 35).ok_or(DriverError::Malformed)?;
+        let seq = field_u64(msg, 34).ok_or(DriverError::Malformed)?;
+
+        if msg_type == b"4" {
+            // SequenceReset, in either reset or gap-fill mode
+            self.next_in_seq = field_u64(msg, 36).ok_or(DriverError::Malformed)?;
+            return Ok(Inbound::Admin);
+        }
+
+        if seq < self.next_in_seq {
+            if field(msg, 43) == Some(b"Y") {
+                // PossDupFlag: already processed
+                return Ok(Inbound::Admin);
+            }
+            return Err(DriverError::SequenceGap {
+                expected: self.next_in_seq,
+                received: seq,
+            });
+        }
+        if seq > self.next_in_seq {
+            return Err(DriverError::SequenceGap {
+                expected: self.next_in_seq,
+                received: seq,
+            });
+        }
+        self.next_in_seq += 1;
+
+        match msg_type {
+            b"A" => {
+                self.logged_in = true;
+                Ok(Inbound::Logon)
+            }
+            b"0" => Ok(Inbound::Admin),
+            b"1" => {
+                let mut body = String::new();
+                push_field(&mut body, 112, text(field(msg, 112).unwrap_or_default()));
+                self.send("0", &body);
+                Ok(Inbound::Admin)
+            }
+            b"2" => {
+                // Nothing we send needs replaying; skip the counterparty past it
+                let mut body = String::new();
+                push_field(&mut body, 123, 'N');
+                push_field(&mut body, 36, self.next_out_seq + 1);
+                self.send("4", &body);
+                Ok(Inbound::Admin)
+            }
+            b"3" | b"5" => {
+                self.logged_in = false;
+                Err(DriverError::Rejected(text(field(msg, 58).unwrap_or(msg_type)).to_string()))
+            }
+            _ => Ok(Inbound::App),
+        }
+    }
+}
+
+/// Iterates over the `(tag, value)` pairs of a message.
+pub fn fields(msg: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    msg.split(|&b| b == SOH as u8).filter_map(|pair| {
+        let eq = pair.iter().position(|&b| b == b'=')?;
+        let tag = std::str::from_utf8(&pair[..eq]).ok()?.parse().ok()?;
+        Some((tag, &pair[eq + 1..]))
+    })
+}
+
+/// Returns the value of the first occurrence of `tag`.
+pub fn field(msg: &[u8], tag: u32) -> Option<&[u8]> {
+    fields(msg).find(|&(t, _)| t == tag).map(|(_, value)| value)
+}
+
+fn field_u64(msg: &[u8], tag: u32) -> Option<u64> {
+    std::str::from_utf8(field(msg, tag)?).ok()?.parse().ok()
+}
+
+/// Interprets a field value as text, falling back to an empty string.
+pub fn text(value: &[u8]) -> &str {
+    std::str::from_utf8(value).unwrap_or_default()
+}
+
+/// Appends `tag=value<SOH>` to `buf`.
+pub fn push_field(buf: &mut String, tag: u32, value: impl Display) {
+    let _ = write!(buf, "{tag}={value}{SOH}");
+}
+
+fn position(bytes: &[u8], from: usize) -> Option<usize> {
+    bytes[from..].iter().position(|&b| b == SOH as u8).map(|offset| from + offset)
+}
+
+/// Formats `time` as a FIX UTCTimestamp, e.g. `20231114-22:13:20.000`.
+fn utc_timestamp(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Days since the epoch to a proleptic Gregorian date
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}:{:02}:{:02}.{:03}",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> FixSession {
+        FixSession::new("CLIENT", "VENUE", Duration::from_secs(30))
+    }
+
+    /// Encodes a message as the counterparty would send it.
+    fn inbound(seq: u64, msg_type: &str, body: &str) -> String {
+        FixSession::new("VENUE", "CLIENT", Duration::from_secs(30)).encode(seq, msg_type, &body.replace('|', "\u{1}"), UNIX_EPOCH)
+    }
+
+    #[test]
+    fn test_utc_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        assert_eq!(utc_timestamp(time), "20231114-22:13:20.123");
+        assert_eq!(utc_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "20000229-00:00:00.000");
+    }
+
+    #[test]
+    fn test_encode() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let msg = session().encode(1, "0", "", time).replace(SOH, "|");
+        assert_eq!(msg, "8=FIX.4.4|9=54|35=0|49=CLIENT|56=VENUE|34=1|52=20231114-22:13:20.000|10=255|");
+    }
+
+    #[test]
+    fn test_frames_split_reads() {
+        let mut session = session();
+        let mut out = Vec::new();
+        let stream = inbound(1, "A", "98=0|108=30|") + &inbound(2, "0", "");
+        let (head, tail) = stream.as_bytes().split_at(20);
+
+        session.push(head);
+        assert_eq!(session.next_message(&mut out), Ok(false));
+        session.push(tail);
+        assert_eq!(session.next_message(&mut out), Ok(true));
+        assert_eq!(session.accept(&out), Ok(Inbound::Logon));
+        assert_eq!(session.next_message(&mut out), Ok(true));
+        assert_eq!(session.accept(&out), Ok(Inbound::Admin));
+        assert_eq!(session.next_message(&mut out), Ok(false));
+    }
+
+    #[test]
+    fn test_rejects_bad_checksum() {
+        let mut session = session();
+        let mut msg = inbound(1, "0", "").into_bytes();
+        let len = msg.len();
+        msg[len - 2] = if msg[len - 2] == b'0' { b'1' } else { b'0' };
+        session.push(&msg);
+        assert_eq!(session.next_message(&mut Vec::new()), Err(DriverError::Malformed));
+    }
+
+    #[test]
+    fn test_answers_test_request() {
+        let mut session = session();
+        let msg = inbound(1, "1", "112=ping-1|");
+        assert_eq!(session.accept(msg.as_bytes()), Ok(Inbound::Admin));
+        let reply = session.poll_outbox().unwrap();
+        assert_eq!(field(reply.as_bytes(), 35), Some(&b"0"[..]));
+        assert_eq!(field(reply.as_bytes(), 112), Some(&b"ping-1"[..]));
+        assert_eq!(session.next_out_seq(), 2);
+    }
+
+    #[test]
+    fn test_sequencing() {
+        let mut session = session();
+        assert_eq!(session.accept(inbound(1, "A", "98=0|108=30|").as_bytes()), Ok(Inbound::Logon));
+        assert_eq!(session.accept(inbound(2, "W", "55=BTC/USD|").as_bytes()), Ok(Inbound::App));
+
+        // Gap-fill past 3..=9
+        assert_eq!(session.accept(inbound(3, "4", "123=Y|36=10|").as_bytes()), Ok(Inbound::Admin));
+        assert_eq!(session.accept(inbound(10, "0", "").as_bytes()), Ok(Inbound::Admin));
+        assert_eq!(session.accept(inbound(10, "0", "43=Y|").as_bytes()), Ok(Inbound::Admin));
+
+        assert_eq!(
+            session.accept(inbound(13, "0", "").as_bytes()),
+            Err(DriverError::SequenceGap { expected: 11, received: 13 })
+        );
+    }
+
+    #[test]
+    fn test_logout_is_rejection() {
+        let mut session = session();
+        assert_eq!(
+            session.accept(inbound(1, "5", "58=Invalid password|").as_bytes()),
+            Err(DriverError::Rejected("Invalid password".to_string()))
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! Gate.io v4 `spot.order_book_update` and `futures.order_book_update` channels.
+
+use crate::broker::instruments::Precision;
+use crate::broker::{ProductType, SymbolKey};
+use crate::driver::{
+    DriverError, ExchangeDriver, Scales, apply_level, expect_token, find, find_u64, for_each_level, parse_i64,
+    parse_qty, rest_get, schema,
+};
+use crate::model::L1FriendlyBook;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SPOT_WS_URL: &str = "wss://api.gateio.ws/ws/v4/";
+const FUTURES_WS_URL: &str = "wss://fx-ws.gateio.ws/v4/ws/usdt";
+const PAIRS_URL: &str = "https://api.gateio.ws/api/v4/spot/currency_pairs";
+const CONTRACTS_URL: &str = "https://api.gateio.ws/api/v4/futures/usdt/contracts";
+
+/// Fixed-point scale applied to Gate.io prices, unless the pair's or
+/// contract's precision is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to Gate.io quantities, unless the pair's
+/// precision is known; contract sizes are whole.
+pub const QTY_SCALE: u32 = 8;
+
+/// Driver for Gate.io spot and USDT-settled futures books.
+///
+/// Spot keys use the spot endpoint; perpetual and dated futures keys use the
+/// futures endpoint, whose levels carry integer contract sizes. Both channels
+/// stamp updates with a `U..=u` id range, and a range that does not follow on
+/// from the last applied `u` is reported as [DriverError::SequenceGap].
+pub struct GateDriver {
+    scales: Scales,
+    last_update_id: Option<u64>,
+}
+
+impl GateDriver {
+    pub fn new() -> Self {
+        Self {
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            last_update_id: None,
+        }
+    }
+}
+
+impl Default for GateDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `BTC-USDT` into Gate.io's `BTC_USDT` form.
+pub fn currency_pair(symbol: &str) -> String {
+    symbol.replace(['-', '/'], "_").to_ascii_uppercase()
+}
+
+fn is_futures(key: &SymbolKey) -> bool {
+    matches!(key.product, ProductType::Perpetual | ProductType::Future)
+}
+
+fn request(event: &str, key: &SymbolKey) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let pair = currency_pair(&key.symbol);
+
+    if is_futures(key) {
+        format!(
+            r#"{{"time":{time},"channel":"futures.order_book_update","event":"{event}","payload":["{pair}","100ms","20"]}}"#
+        )
+    } else {
+        format!(
+            r#"{{"time":{time},"channel":"spot.order_book_update","event":"{event}","payload":["{pair}","100ms"]}}"#
+        )
+    }
+}
+
+/// Reads the precision off a spot `currency_pairs/<pair>` response.
+///
+/// ```json
+/// {"id":"BTC_USDT","base":"BTC","quote":"USDT","precision":2,"amount_precision":6,"trade_status":"tradable"}
+/// ```
+fn pair_precision(body: &[u8]) -> Result<Precision, DriverError> {
+    let pair: schema::gate::CurrencyPair = schema::decode(body)?;
+    Precision::from_decimals(pair.precision, pair.amount_precision).ok_or(DriverError::Malformed)
+}
+
+/// Reads the price tick off a futures `contracts/<contract>` response;
+/// sizes are counted in whole contracts.
+///
+/// ```json
+/// {"name":"BTC_USDT","type":"direct","quanto_multiplier":"0.0001","order_price_round":"0.1","order_size_min":1}
+/// ```
+fn contract_precision(body: &[u8]) -> Result<Precision, DriverError> {
+    let contract: schema::gate::Contract = schema::decode(body)?;
+    Precision::from_sizes(contract.order_price_round.as_bytes(), b"1").ok_or(DriverError::Malformed)
+}
+
+impl ExchangeDriver for GateDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.last_update_id = None;
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        let pair = currency_pair(&key.symbol);
+        let precision = if is_futures(key) {
+            contract_precision(rest_get(&format!("{CONTRACTS_URL}/{pair}"))?.as_bytes())
+        } else {
+            pair_precision(rest_get(&format!("{PAIRS_URL}/{pair}"))?.as_bytes())
+        };
+        precision.map(Some)
+    }
+
+    fn endpoint(&self, key: &SymbolKey) -> String {
+        if is_futures(key) {
+            FUTURES_WS_URL.to_string()
+        } else {
+            SPOT_WS_URL.to_string()
+        }
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(request("subscribe", key))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(request("unsubscribe", key))
+    }
+
+    /// Returns the last update id (`u`) applied.
+    fn update_id(&self) -> Option<u64> {
+        self.last_update_id
+    }
+
+    /// Applies an order book update.
+    ///
+    /// ```json
+    /// {"time":1,"channel":"spot.order_book_update","event":"update","result":{"s":"BTC_USDT","U":48791820,"u":48791830,"b":[["19137.74","0.0001"]],"a":[]}}
+    /// {"time":1,"channel":"futures.order_book_update","event":"update","result":{"s":"BTC_USDT","U":10,"u":12,"b":[{"p":"16493.50","s":0}],"a":[]}}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if find(msg, br#""event":"update""#).is_none() {
+            let ack: schema::gate::SubscribeAck = schema::decode(msg)?;
+            return match ack.error {
+                Some(error) => Err(DriverError::Rejected(error.message.into_owned())),
+                None => Ok(false),
+            };
+        }
+
+        let first = find_u64(msg, "U").ok_or(DriverError::Malformed)?;
+        let last = find_u64(msg, "u").ok_or(DriverError::Malformed)?;
+        if let Some(applied) = self.last_update_id
+            && first > applied + 1
+        {
+            return Err(DriverError::SequenceGap {
+                expected: applied + 1,
+                received: first,
+            });
+        }
+        self.last_update_id = Some(last);
+        self.scales.stamp(book);
+
+        let futures = find(msg, br#""channel":"futures."#).is_some();
+        let bids = find(msg, br#""b":"#).ok_or(DriverError::Malformed)? + 4;
+        let asks = find(msg, br#""a":"#).ok_or(DriverError::Malformed)? + 4;
+
+        if futures {
+            for_each_contract_level(msg, bids, self.scales, |price, qty| {
+                apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+            })?;
+            for_each_contract_level(msg, asks, self.scales, |price, qty| {
+                apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+            })?;
+        } else {
+            for_each_level(msg, bids, self.scales.price, self.scales.qty, |price, qty| {
+                apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+            })?;
+            for_each_level(msg, asks, self.scales.price, self.scales.qty, |price, qty| {
+                apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+            })?;
+        }
+
+        Ok(true)
+    }
+}
+
+/// Walks a JSON array of futures `{"p":"price","s":size}` levels starting at `start`.
+fn for_each_contract_level(
+    bytes: &[u8],
+    start: usize,
+    scales: Scales,
+    mut on_level: impl FnMut(i64, i64),
+) -> Result<usize, DriverError> {
+    let mut idx = expect_token(bytes, start, b"[")?;
+    if bytes.get(idx) == Some(&b']') {
+        return Ok(idx + 1);
+    }
+
+    loop {
+        idx = expect_token(bytes, idx, br#"{"p":""#)?;
+        let (price, next) = parse_i64(bytes, idx, scales.price)?;
+        idx = expect_token(bytes, next, br#"","s":"#)?;
+        let (qty, next) = parse_qty(bytes, idx, scales.qty)?;
+        idx = expect_token(bytes, next, b"}")?;
+
+        on_level(price, qty);
+
+        match bytes.get(idx) {
+            Some(b',') => idx += 1,
+            Some(b']') => return Ok(idx + 1),
+            _ => return Err(DriverError::Malformed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed};
+
+    fn key(product: ProductType) -> SymbolKey {
+        SymbolKey {
+            exchange: Exchange::Gate,
+            symbol: "btc-usdt".to_string(),
+            product,
+            feed: Feed::Depth,
+        }
+    }
+
+    #[test]
+    fn test_routing_by_product() {
+        let driver = GateDriver::new();
+        assert_eq!(driver.endpoint(&key(ProductType::Spot)), SPOT_WS_URL);
+        assert_eq!(driver.endpoint(&key(ProductType::Perpetual)), FUTURES_WS_URL);
+
+        let spot = driver.subscribe_msg(&key(ProductType::Spot)).unwrap();
+        assert!(spot.contains(r#""channel":"spot.order_book_update","event":"subscribe","payload":["BTC_USDT","100ms"]"#));
+        let futures = driver.subscribe_msg(&key(ProductType::Perpetual)).unwrap();
+        assert!(futures.contains(r#""channel":"futures.order_book_update""#));
+    }
+
+    #[test]
+    fn test_spot_update() {
+        let mut driver = GateDriver::new();
+        let mut book = L1FriendlyBook::new();
+        let msg = br#"{"time":1,"channel":"spot.order_book_update","event":"update","result":{"t":1,"e":"depthUpdate","E":1,"s":"BTC_USDT","U":48791820,"u":48791830,"b":[["19137.74","0.0001"]],"a":[["19137.75","0.6135"]]}}"#;
+        assert_eq!(driver.parse_message(msg, &mut book), Ok(true));
+        assert_eq!(book.bids[0].price, 1_913_774_000_000);
+        assert_eq!(book.bids[0].qty, 10_000);
+        assert_eq!(book.asks[0].qty, 61_350_000);
+    }
+
+    #[test]
+    fn test_futures_update_and_gap() {
+        let mut driver = GateDriver::new();
+        let mut book = L1FriendlyBook::new();
+        let msg = br#"{"time":1,"channel":"futures.order_book_update","event":"update","result":{"t":1,"s":"BTC_USDT","U":10,"u":12,"b":[{"p":"16493.50","s":3}],"a":[{"p":"16493.60","s":2}]}}"#;
+        assert_eq!(driver.parse_message(msg, &mut book), Ok(true));
+        assert_eq!(book.bids[0].price, 1_649_350_000_000);
+        assert_eq!(book.bids[0].qty, 300_000_000);
+
+        let gap = br#"{"time":2,"channel":"futures.order_book_update","event":"update","result":{"t":2,"s":"BTC_USDT","U":14,"u":15,"b":[],"a":[]}}"#;
+        assert_eq!(
+            driver.parse_message(gap, &mut book),
+            Err(DriverError::SequenceGap { expected: 13, received: 14 })
+        );
+    }
+
+    #[test]
+    fn test_ignores_subscribe_ack() {
+        let mut book = L1FriendlyBook::new();
+        let ack = br#"{"time":1,"channel":"spot.order_book_update","event":"subscribe","result":{"status":"success"}}"#;
+        assert_eq!(GateDriver::new().parse_message(ack, &mut book), Ok(false));
+
+        let error = br#"{"time":1,"channel":"spot.order_book_update","event":"subscribe","error":{"code":2,"message":"unknown currency pair: FOO_USDT"},"result":null}"#;
+        assert_eq!(
+            GateDriver::new().parse_message(error, &mut book),
+            Err(DriverError::Rejected("unknown currency pair: FOO_USDT".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let pair = br#"{"id":"ETH_BTC","base":"ETH","quote":"BTC","precision":6,"amount_precision":4,"trade_status":"tradable"}"#;
+        assert_eq!(pair_precision(pair), Ok(Precision::from_decimals(6, 4).unwrap()));
+        let contract = br#"{"name":"BTC_USDT","type":"direct","quanto_multiplier":"0.0001","order_price_round":"0.1","order_size_min":1}"#;
+        assert_eq!(contract_precision(contract), Ok(Precision::from_decimals(1, 0).unwrap()));
+        assert_eq!(pair_precision(br#"{"label":"INVALID_CURRENCY","message":"Invalid currency"}"#), Err(DriverError::Malformed));
+    }
+}
@@ -0,0 +1,367 @@
+//! HTX (formerly Huobi) spot market-by-price (`mbp`) channel.
+
+use crate::broker::SymbolKey;
+use crate::broker::instruments::Precision;
+use crate::driver::schema::{self, htx::Reply, htx::Snapshot};
+use crate::driver::{
+    DriverError, ExchangeDriver, Scales, apply_level, expect, find, find_u64, parse_i64, parse_qty, rest_get,
+};
+use crate::json;
+use crate::model::{L1FriendlyBook, UpdateCause};
+use flate2::read::GzDecoder;
+use std::io::Read;
+use std::mem;
+
+const WS_URL: &str = "wss://api.huobi.pro/feed";
+const SYMBOLS_URL: &str = "https://api.huobi.pro/v1/common/symbols";
+
+/// Fixed-point scale applied to HTX prices, unless the symbol's
+/// `price-precision` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to HTX quantities, unless the symbol's
+/// `amount-precision` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Number of MBP levels requested from HTX.
+pub const MBP_LEVELS: u32 = 150;
+
+/// Driver for the HTX incremental MBP feed.
+///
+/// Every frame arrives gzip-compressed. HTX pings with `{"ping":ts}` and
+/// drops the connection unless it receives `{"pong":ts}` in return.
+///
+/// The incremental channel carries no snapshot, so once the subscription is
+/// acknowledged the driver requests one with `req` on the same channel.
+/// Updates received before the snapshot are buffered and replayed on top of
+/// it; afterwards each update's `prevSeqNum` must match the last applied
+/// `seqNum`.
+pub struct HtxDriver {
+    /// Reusable buffer for decompressed frames.
+    inflated: Vec<u8>,
+    /// Raw updates received before the snapshot arrived.
+    pending: Vec<Vec<u8>>,
+    /// Channel name, e.g. `market.btcusdt.mbp.150`.
+    channel: String,
+    scales: Scales,
+    last_seq: Option<u64>,
+    reply: Option<String>,
+    /// Whether the last frame was the snapshot reply.
+    replaced: bool,
+}
+
+impl HtxDriver {
+    pub fn new() -> Self {
+        Self {
+            inflated: Vec::with_capacity(64 * 1024),
+            pending: Vec::new(),
+            channel: String::new(),
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            last_seq: None,
+            reply: None,
+            replaced: false,
+        }
+    }
+
+    /// Applies a decompressed frame.
+    fn apply(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if let Some(ts) = find_u64(msg, "ping") {
+            self.reply = Some(format!(r#"{{"pong":{ts}}}"#));
+            return Ok(false);
+        }
+
+        if find(msg, br#""rep":"#).is_some() {
+            return self.apply_snapshot(msg, book);
+        }
+
+        if json::field(msg, 0, b"status").is_some() {
+            match schema::decode(msg)? {
+                Reply::Ok(ack) if ack.subbed.is_some() => {
+                    self.reply = Some(format!(r#"{{"req":"{}","id":"snapshot"}}"#, self.channel));
+                }
+                Reply::Ok(_) => {}
+                Reply::Error(error) => return Err(DriverError::Rejected(error.msg.into_owned())),
+            }
+            return Ok(false);
+        }
+
+        if find(msg, br#""tick":"#).is_none() {
+            return Ok(false);
+        }
+
+        if self.last_seq.is_none() {
+            self.pending.push(msg.to_vec());
+            return Ok(false);
+        }
+        self.apply_update(msg, book)
+    }
+
+    fn apply_snapshot(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let snapshot: Snapshot = schema::decode(msg)?;
+        let seq = snapshot.data.seq_num;
+
+        book.clear_sides();
+        for level in &snapshot.data.bids {
+            let (price, qty) = level.parse(self.scales.price, self.scales.qty)?;
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+        }
+        for level in &snapshot.data.asks {
+            let (price, qty) = level.parse(self.scales.price, self.scales.qty)?;
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+        }
+        self.last_seq = Some(seq);
+        self.replaced = true;
+
+        for update in mem::take(&mut self.pending) {
+            if find_u64(&update, "seqNum").is_some_and(|update_seq| update_seq > seq) {
+                self.apply_update(&update, book)?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn apply_update(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let seq = find_u64(msg, "seqNum").ok_or(DriverError::Malformed)?;
+        let prev = find_u64(msg, "prevSeqNum").ok_or(DriverError::Malformed)?;
+        let last = self.last_seq.unwrap_or(0);
+
+        if seq <= last {
+            return Ok(false);
+        }
+        if prev > last {
+            return Err(DriverError::SequenceGap {
+                expected: last,
+                received: prev,
+            });
+        }
+
+        apply_sides(msg, book, self.scales)?;
+        self.last_seq = Some(seq);
+        Ok(true)
+    }
+}
+
+impl Default for HtxDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `BTC-USDT` into HTX's `btcusdt` form.
+pub fn market_symbol(symbol: &str) -> String {
+    symbol
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Finds the precision of `symbol` in a `common/symbols` response.
+///
+/// ```json
+/// {"status":"ok","data":[{"base-currency":"btc","quote-currency":"usdt","price-precision":2,"amount-precision":6,"symbol":"btcusdt"}]}
+/// ```
+fn precision(body: &[u8], symbol: &str) -> Result<Option<Precision>, DriverError> {
+    let symbols: schema::htx::Symbols = schema::decode(body)?;
+    match symbols.data.iter().find(|info| info.symbol == symbol) {
+        Some(info) => Precision::from_decimals(info.price_precision, info.amount_precision)
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+impl ExchangeDriver for HtxDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.channel = format!("market.{}.mbp.{MBP_LEVELS}", market_symbol(&key.symbol));
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.pending.clear();
+        self.last_seq = None;
+        self.reply = None;
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        precision(rest_get(SYMBOLS_URL)?.as_bytes(), &market_symbol(&key.symbol))
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"sub":"market.{}.mbp.{MBP_LEVELS}","id":"sub"}}"#,
+            market_symbol(&key.symbol)
+        ))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"unsub":"market.{}.mbp.{MBP_LEVELS}","id":"unsub"}}"#,
+            market_symbol(&key.symbol)
+        ))
+    }
+
+    /// Returns the `seqNum` of the last update applied.
+    fn update_id(&self) -> Option<u64> {
+        self.last_seq
+    }
+
+    /// Frames arrive compressed, so whether the last was the snapshot reply
+    /// is remembered while applying it.
+    fn update_cause(&self, _msg: &[u8]) -> UpdateCause {
+        if self.replaced { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        self.scales.stamp(book);
+        self.replaced = false;
+        let mut inflated = mem::take(&mut self.inflated);
+        inflated.clear();
+        let result = match GzDecoder::new(msg).read_to_end(&mut inflated) {
+            Ok(_) => self.apply(&inflated, book),
+            Err(_) => Err(DriverError::Malformed),
+        };
+        self.inflated = inflated;
+        result
+    }
+
+    fn pending_reply(&mut self) -> Option<String> {
+        self.reply.take()
+    }
+}
+
+/// Applies the `bids` and `asks` arrays of an MBP tick.
+fn apply_sides(msg: &[u8], book: &mut L1FriendlyBook, scales: Scales) -> Result<(), DriverError> {
+    let bids = find(msg, br#""bids":"#).ok_or(DriverError::Malformed)? + 7;
+    for_each_level(msg, bids, scales, |price, qty| {
+        apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+    })?;
+
+    let asks = find(msg, br#""asks":"#).ok_or(DriverError::Malformed)? + 7;
+    for_each_level(msg, asks, scales, |price, qty| {
+        apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+    })?;
+    Ok(())
+}
+
+/// Walks a JSON array of `[price,size]` number pairs starting at `start`.
+fn for_each_level(
+    bytes: &[u8],
+    start: usize,
+    scales: Scales,
+    mut on_level: impl FnMut(i64, i64),
+) -> Result<usize, DriverError> {
+    let mut idx = expect(bytes, start, b'[')?;
+    if bytes.get(idx) == Some(&b']') {
+        return Ok(idx + 1);
+    }
+
+    loop {
+        idx = expect(bytes, idx, b'[')?;
+        let (price, next) = parse_i64(bytes, idx, scales.price)?;
+        idx = expect(bytes, next, b',')?;
+        let (qty, next) = parse_qty(bytes, idx, scales.qty)?;
+        idx = expect(bytes, next, b']')?;
+
+        on_level(price, qty);
+
+        match bytes.get(idx) {
+            Some(b',') => idx += 1,
+            Some(b']') => return Ok(idx + 1),
+            _ => return Err(DriverError::Malformed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed, ProductType};
+    use crate::model::Level;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    fn gzip(msg: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(msg.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn subscribed_driver() -> HtxDriver {
+        let key = SymbolKey {
+            exchange: Exchange::Htx,
+            symbol: "BTC-USDT".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        let mut driver = HtxDriver::new();
+        driver.handshake(&key).unwrap();
+        driver
+    }
+
+    #[test]
+    fn test_answers_ping() {
+        let mut driver = subscribed_driver();
+        let mut book = L1FriendlyBook::new();
+        assert_eq!(driver.parse_message(&gzip(r#"{"ping":1492420473027}"#), &mut book), Ok(false));
+        assert_eq!(driver.pending_reply().unwrap(), r#"{"pong":1492420473027}"#);
+        assert_eq!(driver.pending_reply(), None);
+    }
+
+    #[test]
+    fn test_requests_snapshot_after_subscribe() {
+        let mut driver = subscribed_driver();
+        let mut book = L1FriendlyBook::new();
+        let ack = gzip(r#"{"id":"sub","status":"ok","subbed":"market.btcusdt.mbp.150","ts":1}"#);
+        assert_eq!(driver.parse_message(&ack, &mut book), Ok(false));
+        assert_eq!(driver.pending_reply().unwrap(), r#"{"req":"market.btcusdt.mbp.150","id":"snapshot"}"#);
+
+        let error = gzip(r#"{"id":"sub","status":"error","err-code":"bad-request","err-msg":"invalid topic market.foo.mbp.150","ts":1}"#);
+        assert_eq!(
+            driver.parse_message(&error, &mut book),
+            Err(DriverError::Rejected("invalid topic market.foo.mbp.150".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_buffers_updates_until_snapshot() {
+        let mut driver = subscribed_driver();
+        let mut book = L1FriendlyBook::new();
+
+        let early = gzip(r#"{"ch":"market.btcusdt.mbp.150","ts":1,"tick":{"seqNum":11,"prevSeqNum":10,"bids":[[100.5,2]],"asks":[]}}"#);
+        assert_eq!(driver.parse_message(&early, &mut book), Ok(false));
+
+        let snapshot = gzip(r#"{"id":"snapshot","rep":"market.btcusdt.mbp.150","status":"ok","data":{"seqNum":10,"bids":[[100.5,1],[100,3]],"asks":[[101,4]]}}"#);
+        assert_eq!(driver.parse_message(&snapshot, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(&snapshot), UpdateCause::Snapshot);
+        assert_eq!(book.bids[0], Level { price: 10_050_000_000, qty: 200_000_000 });
+        assert_eq!(book.asks[0].price, 10_100_000_000);
+
+        let update = gzip(r#"{"ch":"market.btcusdt.mbp.150","ts":2,"tick":{"seqNum":12,"prevSeqNum":11,"bids":[],"asks":[[101,0]]}}"#);
+        assert_eq!(driver.parse_message(&update, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(&update), UpdateCause::Delta);
+        assert_eq!(book.ask_tombstones, 1);
+
+        let gap = gzip(r#"{"ch":"market.btcusdt.mbp.150","ts":3,"tick":{"seqNum":15,"prevSeqNum":14,"bids":[],"asks":[]}}"#);
+        assert_eq!(
+            driver.parse_message(&gap, &mut book),
+            Err(DriverError::SequenceGap { expected: 12, received: 14 })
+        );
+    }
+
+    #[test]
+    fn test_rejects_uncompressed_frames() {
+        let mut book = L1FriendlyBook::new();
+        assert_eq!(subscribed_driver().parse_message(b"{}", &mut book), Err(DriverError::Malformed));
+    }
+
+    #[test]
+    fn test_precision() {
+        let body = br#"{"status":"ok","data":[{"base-currency":"eth","quote-currency":"btc","price-precision":6,"amount-precision":4,"symbol":"ethbtc"},{"base-currency":"btc","quote-currency":"usdt","price-precision":2,"amount-precision":6,"symbol":"btcusdt"}]}"#;
+        assert_eq!(precision(body, "btcusdt"), Ok(Precision::from_decimals(2, 6)));
+        assert_eq!(precision(body, "solusdt"), Ok(None));
+    }
+}
@@ -0,0 +1,249 @@
+//! Hyperliquid `l2Book` subscription.
+
+use crate::broker::instruments::{self, Precision};
+use crate::broker::{ProductType, SymbolKey};
+use crate::driver::schema::{self, hyperliquid::Event, hyperliquid::Meta};
+use crate::driver::{
+    DriverError, ExchangeDriver, Scales, apply_level, expect_token, find, parse_i64, parse_qty, rest_post_json,
+};
+use crate::model::{L1FriendlyBook, UpdateCause};
+
+const WS_URL: &str = "wss://api.hyperliquid.xyz/ws";
+const INFO_URL: &str = "https://api.hyperliquid.xyz/info";
+
+/// Maximum decimal places of a perpetual price, shared with the size decimals.
+const PERP_MAX_DECIMALS: u32 = 6;
+
+/// Maximum decimal places of a spot price, shared with the size decimals.
+const SPOT_MAX_DECIMALS: u32 = 8;
+
+/// Driver for Hyperliquid perpetual and spot books.
+///
+/// Hyperliquid quotes sizes in coin units with a per-coin number of decimals
+/// (`szDecimals`), and allows `MAX_DECIMALS - szDecimals` decimals on prices.
+/// The driver looks these up from the `info` endpoint before connecting,
+/// unless the connector already has, and parses with exactly that
+/// precision, publishing the resulting exponents on the book. Every `l2Book` push is a full snapshot.
+pub struct HyperliquidDriver {
+    scales: Scales,
+}
+
+impl HyperliquidDriver {
+    pub fn new() -> Self {
+        Self {
+            scales: Scales { price: PERP_MAX_DECIMALS, qty: 0 },
+        }
+    }
+}
+
+impl Default for HyperliquidDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps a [SymbolKey] onto a Hyperliquid coin name.
+///
+/// Perpetuals are keyed by the base asset (`BTC-USD` → `BTC`); spot pairs
+/// keep their `BASE/QUOTE` form (`PURR-USDC` → `PURR/USDC`).
+pub fn coin(key: &SymbolKey) -> String {
+    let symbol = key.symbol.to_ascii_uppercase();
+    match key.product {
+        ProductType::Spot => symbol.replace(['-', '_'], "/"),
+        _ => symbol
+            .split(['-', '/', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Finds the `szDecimals` of the asset or token called `name` in an `info` response.
+fn sz_decimals(info: &[u8], name: &str) -> Option<u32> {
+    let meta: Meta = schema::decode(info).ok()?;
+    meta.universe
+        .iter()
+        .chain(&meta.tokens)
+        .find(|asset| asset.name == name && asset.sz_decimals.is_some())?
+        .sz_decimals
+}
+
+/// Derives the price and size scales of `coin` from an `info` response.
+fn precision(info: &[u8], coin: &str, max_decimals: u32) -> Result<Precision, DriverError> {
+    let sz_decimals = sz_decimals(info, coin).ok_or(DriverError::Malformed)?;
+    Precision::from_decimals(max_decimals.saturating_sub(sz_decimals), sz_decimals).ok_or(DriverError::Malformed)
+}
+
+impl ExchangeDriver for HyperliquidDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        let precision = instruments::detect(key, &*self)?.ok_or(DriverError::Malformed)?;
+        self.scales = Scales::from(precision);
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        let coin = coin(key);
+        let precision = match key.product {
+            ProductType::Spot => {
+                let info = rest_post_json(INFO_URL, r#"{"type":"spotMeta"}"#)?;
+                let base = coin.split('/').next().unwrap_or_default();
+                precision(info.as_bytes(), base, SPOT_MAX_DECIMALS)
+            }
+            _ => {
+                let info = rest_post_json(INFO_URL, r#"{"type":"meta"}"#)?;
+                precision(info.as_bytes(), &coin, PERP_MAX_DECIMALS)
+            }
+        };
+        precision.map(Some)
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"method":"subscribe","subscription":{{"type":"l2Book","coin":"{}"}}}}"#,
+            coin(key)
+        ))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(format!(
+            r#"{{"method":"unsubscribe","subscription":{{"type":"l2Book","coin":"{}"}}}}"#,
+            coin(key)
+        ))
+    }
+
+    /// Every `l2Book` frame is a snapshot.
+    fn update_cause(&self, _msg: &[u8]) -> UpdateCause {
+        UpdateCause::Snapshot
+    }
+
+    /// Replaces the book with an `l2Book` snapshot.
+    ///
+    /// ```json
+    /// {"channel":"l2Book","data":{"coin":"BTC","time":1,"levels":[[{"px":"19900","sz":"1","n":1}],[{"px":"19920","sz":"1","n":1}]]}}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if find(msg, br#""channel":"l2Book""#).is_none() {
+            return match schema::decode(msg)? {
+                Event::Error(error) => Err(DriverError::Rejected(error.data.into_owned())),
+                _ => Ok(false),
+            };
+        }
+
+        book.clear_sides();
+        self.scales.stamp(book);
+
+        // "levels":[[bids...],[asks...]]
+        let idx = find(msg, br#""levels":["#).ok_or(DriverError::Malformed)? + 10;
+        let idx = for_each_level(msg, idx, self.scales.price, self.scales.qty, |price, qty| {
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+        })?;
+        if msg.get(idx) != Some(&b',') {
+            return Err(DriverError::Malformed);
+        }
+        for_each_level(msg, idx + 1, self.scales.price, self.scales.qty, |price, qty| {
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+        })?;
+
+        Ok(true)
+    }
+}
+
+/// Walks a JSON array of `{"px":"p","sz":"q","n":count}` objects starting at `start`.
+fn for_each_level(
+    bytes: &[u8],
+    start: usize,
+    price_scale: u32,
+    qty_scale: u32,
+    mut on_level: impl FnMut(i64, i64),
+) -> Result<usize, DriverError> {
+    let mut idx = expect_token(bytes, start, b"[")?;
+    if bytes.get(idx) == Some(&b']') {
+        return Ok(idx + 1);
+    }
+
+    loop {
+        idx = expect_token(bytes, idx, br#"{"px":""#)?;
+        let (price, next) = parse_i64(bytes, idx, price_scale)?;
+        idx = expect_token(bytes, next, br#"","sz":""#)?;
+        let (qty, next) = parse_qty(bytes, idx, qty_scale)?;
+        idx = expect_token(bytes, next, b"\"")?;
+        idx += bytes[idx..].iter().position(|&b| b == b'}').ok_or(DriverError::Malformed)? + 1;
+
+        on_level(price, qty);
+
+        match bytes.get(idx) {
+            Some(b',') => idx += 1,
+            Some(b']') => return Ok(idx + 1),
+            _ => return Err(DriverError::Malformed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed};
+    use crate::model::Level;
+
+    fn key(symbol: &str, product: ProductType) -> SymbolKey {
+        SymbolKey {
+            exchange: Exchange::Hyperliquid,
+            symbol: symbol.to_string(),
+            product,
+            feed: Feed::Depth,
+        }
+    }
+
+    #[test]
+    fn test_coin_mapping() {
+        assert_eq!(coin(&key("btc-usd", ProductType::Perpetual)), "BTC");
+        assert_eq!(coin(&key("ETH", ProductType::Perpetual)), "ETH");
+        assert_eq!(coin(&key("PURR-USDC", ProductType::Spot)), "PURR/USDC");
+    }
+
+    #[test]
+    fn test_scales_from_meta() {
+        let meta = br#"{"universe":[{"szDecimals":5,"name":"BTC","maxLeverage":50},{"szDecimals":4,"name":"ETH","maxLeverage":50}]}"#;
+        assert_eq!(precision(meta, "ETH", PERP_MAX_DECIMALS), Ok(Precision::from_decimals(2, 4).unwrap()));
+        assert_eq!(precision(meta, "SOL", PERP_MAX_DECIMALS), Err(DriverError::Malformed));
+
+        let spot_meta = br#"{"universe":[{"tokens":[1,0],"name":"PURR/USDC","index":0}],"tokens":[{"name":"USDC","szDecimals":8,"weiDecimals":8,"index":0},{"name":"PURR","szDecimals":0,"weiDecimals":5,"index":1}]}"#;
+        assert_eq!(precision(spot_meta, "PURR", SPOT_MAX_DECIMALS), Ok(Precision::from_decimals(8, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_snapshot_uses_symbol_precision() {
+        // As if the connector had looked BTC up
+        let key = key("PRECISION", ProductType::Perpetual);
+        instruments::register(&key, Precision::from_decimals(1, 5).unwrap());
+        let mut driver = HyperliquidDriver::new();
+        driver.handshake(&key).unwrap();
+        instruments::remove(&key);
+
+        let mut book = L1FriendlyBook::new();
+        let msg = br#"{"channel":"l2Book","data":{"coin":"BTC","time":1,"levels":[[{"px":"19900.5","sz":"1.25","n":1},{"px":"19899","sz":"0.00001","n":2}],[{"px":"19920","sz":"3","n":1}]]}}"#;
+        assert_eq!(driver.parse_message(msg, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(msg), UpdateCause::Snapshot);
+        assert_eq!((book.price_exponent, book.qty_exponent), (-1, -5));
+        assert_eq!(book.bids[0], Level { price: 199_005, qty: 125_000 });
+        assert_eq!(book.bids[1], Level { price: 198_990, qty: 1 });
+        assert_eq!(book.asks[0], Level { price: 199_200, qty: 300_000 });
+    }
+
+    #[test]
+    fn test_ignores_subscription_response() {
+        let mut book = L1FriendlyBook::new();
+        let ack = br#"{"channel":"subscriptionResponse","data":{"method":"subscribe","subscription":{"type":"l2Book","coin":"BTC"}}}"#;
+        assert_eq!(HyperliquidDriver::new().parse_message(ack, &mut book), Ok(false));
+
+        let error = br#"{"channel":"error","data":"Invalid subscription"}"#;
+        assert_eq!(
+            HyperliquidDriver::new().parse_message(error, &mut book),
+            Err(DriverError::Rejected("Invalid subscription".to_string()))
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Nasdaq TotalView-ITCH 5.0 over MoldUDP64 multicast.
+
+use crate::broker::SymbolKey;
+use crate::driver::{DriverError, ExchangeDriver, NativeScale, Transport};
+use crate::model::{BOOK_DEPTH, FullDepthBook, L1FriendlyBook, Level, OrderBookL3, Side};
+use parking_lot::RwLock;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+/// Decimal exponent of ITCH `Price (4)` fields.
+pub const PRICE_EXPONENT: i8 = -4;
+
+/// MoldUDP64 downstream header: Session (10) + SequenceNumber (8) + MessageCount (2).
+const MOLD_HEADER_LEN: usize = 20;
+
+/// MessageCount signalling the end of the session.
+const END_OF_SESSION: u16 = 0xFFFF;
+
+/// The ITCH feed subscriptions are served from.
+static FEED: RwLock<Option<ItchFeed>> = RwLock::new(None);
+
+/// Multicast group of a MoldUDP64 ITCH feed.
+#[derive(Debug, Clone, Copy)]
+pub struct ItchFeed {
+    pub group: SocketAddrV4,
+    /// The B line of the feed, arbitrated against `group` by MoldUDP64
+    /// sequence number.
+    pub group_b: Option<SocketAddrV4>,
+    /// Local interface to join the group on.
+    pub interface: Ipv4Addr,
+}
+
+/// Sets the feed [Exchange::Nasdaq](crate::broker::Exchange::Nasdaq) subscriptions join.
+pub fn configure(feed: ItchFeed) {
+    *FEED.write() = Some(feed);
+}
+
+/// Driver building one stock's book from the full ITCH feed.
+///
+/// Every session receives the whole feed and keeps only the orders of its
+/// stock, identified by the Stock Locate code learned from the Stock
+/// Directory (`R`) or the first Add Order (`A`/`F`) naming the symbol. Orders
+/// are kept in an [OrderBookL3] aggregated per price, and the best
+/// [BOOK_DEPTH] levels of each side are projected into the shared book after
+/// every packet that touches them.
+///
+/// ITCH has no snapshots: a session only knows about orders added after it
+/// joined, and a MoldUDP64 sequence gap is reported as
+/// [DriverError::SequenceGap] so the connector starts over. Prices are
+/// published with the feed's native `10^-4` exponent and sizes in shares,
+/// unless [set_book_exponents](crate::driver::set_book_exponents) asks for
+/// finer ones.
+pub struct ItchDriver {
+    stock: [u8; 8],
+    scale: NativeScale,
+    locate: Option<u16>,
+    next_seq: Option<u64>,
+    feed: String,
+    orders: OrderBookL3<u64>,
+    /// Per-price aggregates in the feed's native units.
+    levels: FullDepthBook,
+}
+
+impl ItchDriver {
+    pub fn new() -> Self {
+        Self {
+            stock: [b' '; 8],
+            scale: NativeScale::native(PRICE_EXPONENT, 0),
+            locate: None,
+            next_seq: None,
+            feed: String::new(),
+            orders: OrderBookL3::new(),
+            levels: FullDepthBook::new(),
+        }
+    }
+
+    /// Applies a single ITCH message. Returns whether the book changed.
+    fn apply(&mut self, msg: &[u8]) -> Result<bool, DriverError> {
+        let Some(&kind) = msg.first() else {
+            return Err(DriverError::Malformed);
+        };
+
+        if kind == b'R' {
+            if field(msg, 11, 8)? == self.stock {
+                self.locate = Some(u16_at(msg, 1)?);
+            }
+            return Ok(false);
+        }
+
+        if matches!(kind, b'A' | b'F') && self.locate.is_none() && field(msg, 24, 8)? == self.stock {
+            self.locate = Some(u16_at(msg, 1)?);
+        }
+        if Some(u16_at(msg, 1)?) != self.locate {
+            return Ok(false);
+        }
+
+        match kind {
+            b'A' | b'F' => {
+                let side = match field(msg, 19, 1)? {
+                    b"B" => Side::Bid,
+                    b"S" => Side::Ask,
+                    _ => return Err(DriverError::Malformed),
+                };
+                let shares = i64::from(u32_at(msg, 20)?);
+                let price = i64::from(u32_at(msg, 32)?);
+                self.orders.open(u64_at(msg, 11)?, side, price, shares, &mut self.levels);
+                Ok(true)
+            }
+            b'E' | b'C' | b'X' => {
+                let shares = i64::from(u32_at(msg, 19)?);
+                Ok(self.reduce(u64_at(msg, 11)?, shares))
+            }
+            b'D' => Ok(self.reduce(u64_at(msg, 11)?, i64::MAX)),
+            b'U' => {
+                let Some(original) = self.orders.remove(&u64_at(msg, 11)?, &mut self.levels) else {
+                    return Ok(false);
+                };
+                let shares = i64::from(u32_at(msg, 27)?);
+                let price = i64::from(u32_at(msg, 31)?);
+                self.orders.open(u64_at(msg, 19)?, original.side, price, shares, &mut self.levels);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Removes up to `shares` from an order, deleting it once nothing is left.
+    ///
+    /// Returns `false` if the order is not known.
+    fn reduce(&mut self, reference: u64, shares: i64) -> bool {
+        self.orders.resize(&reference, |remaining| remaining.saturating_sub(shares), &mut self.levels)
+    }
+
+    /// Writes the best [BOOK_DEPTH] aggregated levels of each side into `book`.
+    fn project(&self, book: &mut L1FriendlyBook) -> Result<(), DriverError> {
+        self.scale.stamp(book);
+        project_side(&mut book.bids, self.levels.bids(), &self.scale)?;
+        project_side(&mut book.asks, self.levels.asks(), &self.scale)
+    }
+}
+
+impl Default for ItchDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pads a ticker such as `AAPL` to ITCH's 8-byte, space-filled Stock field.
+pub fn stock_field(symbol: &str) -> [u8; 8] {
+    let mut stock = [b' '; 8];
+    for (slot, byte) in stock.iter_mut().zip(symbol.to_ascii_uppercase().bytes()) {
+        *slot = byte;
+    }
+    stock
+}
+
+impl ExchangeDriver for ItchDriver {
+    fn transport(&self) -> Transport {
+        Transport::Udp
+    }
+
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        let feed = FEED
+            .read()
+            .ok_or_else(|| DriverError::Rejected("no ITCH feed configured".to_string()))?;
+        self.feed = match feed.group_b {
+            Some(b) => format!("{}|{}@{}", feed.group, b, feed.interface),
+            None => format!("{}@{}", feed.group, feed.interface),
+        };
+        self.stock = stock_field(&key.symbol);
+        self.scale = NativeScale::for_key(key, PRICE_EXPONENT, 0)?;
+        self.locate = None;
+        self.next_seq = None;
+        self.orders.clear(&mut self.levels);
+        Ok(())
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        self.feed.clone()
+    }
+
+    fn subscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+        None
+    }
+
+    fn unsubscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+        None
+    }
+
+    /// MoldUDP64 numbers every message; heartbeats span none.
+    fn packet_sequence(&self, packet: &[u8]) -> Option<(u64, u64)> {
+        let seq = u64_at(packet, 10).ok()?;
+        match u16_at(packet, 18).ok()? {
+            END_OF_SESSION => Some((seq, 0)),
+            count => Some((seq, u64::from(count))),
+        }
+    }
+
+    /// Returns the MoldUDP64 sequence number of the last message applied.
+    fn update_id(&self) -> Option<u64> {
+        self.next_seq?.checked_sub(1)
+    }
+
+    /// Applies every message in a MoldUDP64 packet.
+    fn parse_message(&mut self, packet: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let seq = u64_at(packet, 10)?;
+        let count = u16_at(packet, 18)?;
+        if count == END_OF_SESSION {
+            return Ok(false);
+        }
+
+        if let Some(expected) = self.next_seq {
+            if seq > expected {
+                return Err(DriverError::SequenceGap { expected, received: seq });
+            }
+            if seq < expected {
+                // Retransmission of packets already applied
+                return Ok(false);
+            }
+        }
+        self.next_seq = Some(seq.checked_add(u64::from(count)).ok_or(DriverError::Malformed)?);
+
+        let mut modified = false;
+        let mut idx = MOLD_HEADER_LEN;
+        for _ in 0..count {
+            let len = usize::from(u16_at(packet, idx)?);
+            let msg = packet.get(idx + 2..idx + 2 + len).ok_or(DriverError::Malformed)?;
+            modified |= self.apply(msg)?;
+            idx += 2 + len;
+        }
+
+        if modified {
+            self.project(book)?;
+        }
+        Ok(modified)
+    }
+}
+
+/// Overwrites `side` with the first [BOOK_DEPTH] aggregates, rescaled.
+fn project_side(
+    side: &mut [Level; BOOK_DEPTH],
+    levels: impl Iterator<Item = Level>,
+    scale: &NativeScale,
+) -> Result<(), DriverError> {
+    let mut filled = 0;
+    for (slot, level) in side.iter_mut().zip(levels) {
+        *slot = scale.level(level.price, level.qty)?;
+        filled += 1;
+    }
+    side[filled..].fill(Level::default());
+    Ok(())
+}
+
+fn field(msg: &[u8], offset: usize, len: usize) -> Result<&[u8], DriverError> {
+    msg.get(offset..offset + len).ok_or(DriverError::Malformed)
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, DriverError> {
+    Ok(u16::from_be_bytes(field(bytes, offset, 2)?.try_into().map_err(|_| DriverError::Malformed)?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, DriverError> {
+    Ok(u32::from_be_bytes(field(bytes, offset, 4)?.try_into().map_err(|_| DriverError::Malformed)?))
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, DriverError> {
+    Ok(u64::from_be_bytes(field(bytes, offset, 8)?.try_into().map_err(|_| DriverError::Malformed)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{Rounding, ScaleFactor};
+
+    const LOCATE: u16 = 13;
+
+    fn packet(seq: u64, messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut packet = b"0000000001".to_vec();
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&(messages.len() as u16).to_be_bytes());
+        for msg in messages {
+            packet.extend_from_slice(&(msg.len() as u16).to_be_bytes());
+            packet.extend_from_slice(msg);
+        }
+        packet
+    }
+
+    fn header(kind: u8, locate: u16) -> Vec<u8> {
+        let mut msg = vec![kind];
+        msg.extend_from_slice(&locate.to_be_bytes());
+        msg.extend_from_slice(&[0; 8]); // Tracking number and timestamp
+        msg
+    }
+
+    fn add(locate: u16, reference: u64, side: u8, shares: u32, stock: &str, price: u32) -> Vec<u8> {
+        let mut msg = header(b'A', locate);
+        msg.extend_from_slice(&reference.to_be_bytes());
+        msg.push(side);
+        msg.extend_from_slice(&shares.to_be_bytes());
+        msg.extend_from_slice(&stock_field(stock));
+        msg.extend_from_slice(&price.to_be_bytes());
+        msg
+    }
+
+    fn with_shares(kind: u8, reference: u64, shares: u32) -> Vec<u8> {
+        let mut msg = header(kind, LOCATE);
+        msg.extend_from_slice(&reference.to_be_bytes());
+        msg.extend_from_slice(&shares.to_be_bytes());
+        if kind == b'E' {
+            msg.extend_from_slice(&[0; 8]); // Match number
+        }
+        msg
+    }
+
+    fn driver() -> ItchDriver {
+        ItchDriver {
+            stock: stock_field("AAPL"),
+            ..ItchDriver::new()
+        }
+    }
+
+    #[test]
+    fn test_order_lifecycle() {
+        let mut driver = driver();
+        let mut book = L1FriendlyBook::new();
+
+        let adds = packet(1, &[
+            add(LOCATE, 1, b'B', 100, "AAPL", 1_500_000),
+            add(LOCATE, 2, b'B', 50, "AAPL", 1_500_000),
+            add(LOCATE, 3, b'S', 200, "AAPL", 1_500_100),
+            add(99, 4, b'B', 10, "MSFT", 4_000_000),
+        ]);
+        assert_eq!(driver.parse_message(&adds, &mut book), Ok(true));
+        assert_eq!((book.price_exponent, book.qty_exponent), (PRICE_EXPONENT, 0));
+        assert_eq!(book.bids[0], Level { price: 1_500_000, qty: 150 });
+        assert_eq!(book.asks[0], Level { price: 1_500_100, qty: 200 });
+        assert_eq!(book.bids[1], Level::default());
+
+        let mut replace = header(b'U', LOCATE);
+        replace.extend_from_slice(&3u64.to_be_bytes());
+        replace.extend_from_slice(&5u64.to_be_bytes());
+        replace.extend_from_slice(&120u32.to_be_bytes());
+        replace.extend_from_slice(&1_500_050u32.to_be_bytes());
+
+        let mut delete = header(b'D', LOCATE);
+        delete.extend_from_slice(&2u64.to_be_bytes());
+
+        let changes = packet(5, &[with_shares(b'E', 1, 30), with_shares(b'X', 1, 20), delete, replace]);
+        assert_eq!(driver.parse_message(&changes, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 1_500_000, qty: 50 });
+        assert_eq!(book.asks[0], Level { price: 1_500_050, qty: 120 });
+        assert_eq!(book.asks[1], Level::default());
+    }
+
+    #[test]
+    fn test_learns_locate_from_directory() {
+        let mut driver = driver();
+        let mut book = L1FriendlyBook::new();
+
+        let mut directory = header(b'R', LOCATE);
+        directory.extend_from_slice(&stock_field("AAPL"));
+        directory.extend_from_slice(&[0; 20]);
+        assert_eq!(driver.parse_message(&packet(1, &[directory]), &mut book), Ok(false));
+        assert_eq!(driver.locate, Some(LOCATE));
+    }
+
+    #[test]
+    fn test_book_exponents() {
+        let mut driver = ItchDriver {
+            scale: NativeScale {
+                price: ScaleFactor::new(PRICE_EXPONENT, -8, Rounding::TowardZero).unwrap(),
+                qty: ScaleFactor::identity(0),
+            },
+            ..driver()
+        };
+        let mut book = L1FriendlyBook::new();
+        let adds = packet(1, &[add(LOCATE, 1, b'B', 100, "AAPL", 1_500_025), add(LOCATE, 2, b'S', 5, "AAPL", 1_500_100)]);
+        assert_eq!(driver.parse_message(&adds, &mut book), Ok(true));
+        assert_eq!((book.price_exponent, book.qty_exponent), (-8, 0));
+        assert_eq!(book.bids[0], Level { price: 15_000_250_000, qty: 100 });
+        assert_eq!(book.asks[0], Level { price: 15_001_000_000, qty: 5 });
+    }
+
+    #[test]
+    fn test_sequence_gap() {
+        let mut driver = driver();
+        let mut book = L1FriendlyBook::new();
+        driver.parse_message(&packet(1, &[add(LOCATE, 1, b'B', 1, "AAPL", 1)]), &mut book).unwrap();
+
+        // Retransmitted packet is ignored
+        assert_eq!(driver.parse_message(&packet(1, &[add(LOCATE, 1, b'B', 1, "AAPL", 1)]), &mut book), Ok(false));
+        assert_eq!(
+            driver.parse_message(&packet(4, &[]), &mut book),
+            Err(DriverError::SequenceGap { expected: 2, received: 4 })
+        );
+
+        // A sequence number that cannot be followed is malformed
+        let last = packet(u64::MAX, &[add(LOCATE, 2, b'B', 1, "AAPL", 1)]);
+        assert_eq!(ItchDriver::new().parse_message(&last, &mut book), Err(DriverError::Malformed));
+    }
+}
@@ -0,0 +1,419 @@
+//! Kraken WebSocket v2 `book` channel.
+
+use crate::broker::SymbolKey;
+use crate::broker::instruments::{self, Precision};
+use crate::driver::schema::{self, kraken::AssetPairs, kraken::SubscribeAck};
+use crate::driver::{
+    DriverError, ExchangeDriver, Scales, apply_level, expect_token, find, find_u64, parse_i64, parse_qty, rest_get,
+};
+use crate::json;
+use crate::model::{L1FriendlyBook, UpdateCause};
+use crate::util::{Rounding, rescale};
+use flate2::Crc;
+
+const WS_URL: &str = "wss://ws.kraken.com/v2";
+const ASSET_PAIRS_URL: &str = "https://api.kraken.com/0/public/AssetPairs";
+
+/// Levels per side covered by Kraken's book checksum.
+const CHECKSUM_LEVELS: usize = 10;
+
+/// Fixed-point scale applied to Kraken prices until the pair's
+/// `pair_decimals` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to Kraken quantities until the pair's
+/// `lot_decimals` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Book depth requested when none is specified.
+pub const DEFAULT_DEPTH: u32 = 25;
+
+/// Driver for the Kraken v2 level-2 book.
+///
+/// Only spot pairs are served here; derivatives keys are routed to
+/// [KrakenFuturesDriver](crate::driver::kraken_futures::KrakenFuturesDriver).
+///
+/// Kraken sends a full `snapshot` after subscribing, followed by `update`
+/// deltas. Levels that fall below the subscribed depth are never deleted by
+/// the venue, so the driver truncates each side back to `depth` itself.
+///
+/// Every message carries a CRC32 `checksum` of the top ten levels per side,
+/// printed at the pair's own precision. The handshake looks that precision
+/// up from the REST `AssetPairs` endpoint unless the connector already has,
+/// the book is kept at it, and each applied message is then checked against
+/// the book, failing with [DriverError::ChecksumMismatch]
+/// so the connector rebuilds it from a fresh subscription.
+pub struct KrakenDriver {
+    depth: u32,
+    scales: Scales,
+    /// `pair_decimals` and `lot_decimals` of the pair, once known.
+    precision: Option<Scales>,
+}
+
+impl KrakenDriver {
+    pub fn new() -> Self {
+        Self::with_depth(DEFAULT_DEPTH)
+    }
+
+    /// Creates a driver subscribing at `depth` levels (25 or 100).
+    ///
+    /// # Panics
+    /// Panics if `depth` is not one of the depths this driver supports.
+    pub fn with_depth(depth: u32) -> Self {
+        assert!(matches!(depth, 25 | 100), "unsupported Kraken book depth: {depth}");
+        Self {
+            depth,
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            precision: None,
+        }
+    }
+
+    /// Compares the venue's `checksum` with the book as it now stands.
+    fn verify_checksum(&self, msg: &[u8], book: &L1FriendlyBook) -> Result<(), DriverError> {
+        let Some(precision) = self.precision else {
+            return Ok(());
+        };
+        let expected = find_u64(msg, "checksum").ok_or(DriverError::Malformed)?;
+        let computed = checksum(book, self.scales, precision);
+        if expected != u64::from(computed) {
+            return Err(DriverError::ChecksumMismatch {
+                expected: expected as u32,
+                computed,
+            });
+        }
+        Ok(())
+    }
+
+    fn request(&self, method: &str, key: &SymbolKey) -> String {
+        format!(
+            r#"{{"method":"{method}","params":{{"channel":"book","symbol":["{}"],"depth":{}}}}}"#,
+            pair_symbol(&key.symbol),
+            self.depth
+        )
+    }
+}
+
+impl Default for KrakenDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `BTC-USD` into Kraken's `BTC/USD` form.
+pub fn pair_symbol(symbol: &str) -> String {
+    symbol.replace(['-', '_'], "/").to_ascii_uppercase()
+}
+
+/// Converts a symbol into the REST pair name, e.g. `BTC-USD` → `XBTUSD`.
+///
+/// The REST API still uses Kraken's legacy asset codes for bitcoin and
+/// dogecoin.
+fn rest_pair(symbol: &str) -> String {
+    pair_symbol(symbol)
+        .split('/')
+        .map(|asset| match asset {
+            "BTC" => "XBT",
+            "DOGE" => "XDG",
+            other => other,
+        })
+        .collect()
+}
+
+/// Computes Kraken's CRC32 over the top ten asks followed by the top ten bids.
+///
+/// Each level contributes its price and quantity printed at the pair's
+/// precision with the decimal point and leading zeros removed, which for a
+/// fixed-point value is just its integer form at that precision. Levels
+/// marked for removal are skipped, so the book need not be compacted first.
+fn checksum(book: &L1FriendlyBook, scales: Scales, precision: Scales) -> u32 {
+    let mut crc = Crc::new();
+    for (side, tombstones) in [(&book.asks, book.ask_tombstones), (&book.bids, book.bid_tombstones)] {
+        L1FriendlyBook::live_levels(side, tombstones)
+            .take(CHECKSUM_LEVELS)
+            .for_each(|level| {
+                // A no-op once the book is at the pair's precision
+                let price = rescale(level.price, scales.price, precision.price, Rounding::TowardZero).unwrap_or_default();
+                let qty = rescale(level.qty, scales.qty, precision.qty, Rounding::TowardZero).unwrap_or_default();
+                update_digits(&mut crc, price);
+                update_digits(&mut crc, qty);
+            });
+    }
+    crc.sum()
+}
+
+/// Feeds the decimal digits of a positive `value` into `crc` without allocating.
+fn update_digits(crc: &mut Crc, mut value: i64) {
+    let mut digits = [0u8; 20];
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    crc.update(&digits[start..]);
+}
+
+/// Reads the pair's decimals off an `AssetPairs` response.
+fn precision(info: &[u8]) -> Result<Precision, DriverError> {
+    let pairs: AssetPairs = schema::decode(info)?;
+    if let Some(error) = pairs.error.first() {
+        return Err(DriverError::Rejected(error.to_string()));
+    }
+    let pair = pairs.result.values().next().ok_or(DriverError::Malformed)?;
+    Precision::from_decimals(pair.pair_decimals, pair.lot_decimals).ok_or(DriverError::Malformed)
+}
+
+impl ExchangeDriver for KrakenDriver {
+    /// Fetches the pair's precision if the connector has not, as the
+    /// checksum cannot be verified without it.
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        let precision = instruments::detect(key, &*self)?.ok_or(DriverError::Malformed)?;
+        self.scales = Scales::from(precision);
+        self.precision = Some(self.scales);
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        let info = rest_get(&format!("{ASSET_PAIRS_URL}?pair={}", rest_pair(&key.symbol)))?;
+        precision(info.as_bytes()).map(Some)
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(self.request("subscribe", key))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(self.request("unsubscribe", key))
+    }
+
+    /// `snapshot` messages replace the book.
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if find(msg, br#""type":"snapshot""#).is_some() { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    /// Applies a `book` snapshot or update.
+    ///
+    /// ```json
+    /// {"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":45285.2,"qty":0.001}],"asks":[],"checksum":1}]}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if find(msg, br#""channel":"book""#).is_none() {
+            // Replies to requests carry a `method`; everything else is a status or heartbeat
+            if json::field(msg, 0, b"method").is_some() {
+                let ack: SubscribeAck = schema::decode(msg)?;
+                if !ack.success {
+                    return Err(DriverError::Rejected(ack.error.unwrap_or(ack.method).into_owned()));
+                }
+            }
+            return Ok(false);
+        }
+
+        self.scales.stamp(book);
+        if find(msg, br#""type":"snapshot""#).is_some() {
+            book.clear_sides();
+        } else if find(msg, br#""type":"update""#).is_none() {
+            return Ok(false);
+        }
+
+        let bids = find(msg, br#""bids":"#).ok_or(DriverError::Malformed)? + 7;
+        for_each_level(msg, bids, self.scales.price, self.scales.qty, |price, qty| {
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+        })?;
+
+        let asks = find(msg, br#""asks":"#).ok_or(DriverError::Malformed)? + 7;
+        for_each_level(msg, asks, self.scales.price, self.scales.qty, |price, qty| {
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+        })?;
+
+        // Compacted first, so the counts bound the levels to truncate
+        book.compact_sides();
+        truncate(book.bid_count, &mut book.bid_tombstones, self.depth as usize);
+        truncate(book.ask_count, &mut book.ask_tombstones, self.depth as usize);
+        self.verify_checksum(msg, book)?;
+        Ok(true)
+    }
+}
+
+/// Walks a JSON array of `{"price":p,"qty":q}` objects starting at `start`.
+///
+/// Shared with the Kraken Futures driver, which uses the same level objects.
+pub(super) fn for_each_level(
+    bytes: &[u8],
+    start: usize,
+    price_scale: u32,
+    qty_scale: u32,
+    mut on_level: impl FnMut(i64, i64),
+) -> Result<usize, DriverError> {
+    let mut idx = expect_token(bytes, start, b"[")?;
+    if bytes.get(idx) == Some(&b']') {
+        return Ok(idx + 1);
+    }
+
+    loop {
+        idx = expect_token(bytes, idx, br#"{"price":"#)?;
+        let (price, next) = parse_i64(bytes, idx, price_scale)?;
+        idx = expect_token(bytes, next, br#","qty":"#)?;
+        let (qty, next) = parse_qty(bytes, idx, qty_scale)?;
+        idx = expect_token(bytes, next, b"}")?;
+
+        on_level(price, qty);
+
+        match bytes.get(idx) {
+            Some(b',') => idx += 1,
+            Some(b']') => return Ok(idx + 1),
+            _ => return Err(DriverError::Malformed),
+        }
+    }
+}
+
+/// Marks every level of a compacted side past the first `depth` of its
+/// `count` for removal.
+fn truncate(count: u8, tombstones: &mut u32, depth: usize) {
+    for idx in depth..usize::from(count) {
+        L1FriendlyBook::mark_removal(tombstones, idx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed, ProductType};
+    use crate::model::{BOOK_DEPTH, Level};
+
+    #[test]
+    fn test_subscribe_msg() {
+        let key = SymbolKey {
+            exchange: Exchange::Kraken,
+            symbol: "BTC-USD".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        assert_eq!(
+            KrakenDriver::new().subscribe_msg(&key).unwrap(),
+            r#"{"method":"subscribe","params":{"channel":"book","symbol":["BTC/USD"],"depth":25}}"#
+        );
+    }
+
+    #[test]
+    fn test_snapshot_then_update() {
+        let mut driver = KrakenDriver::new();
+        let mut book = L1FriendlyBook::new();
+        book.bids[0] = Level { price: 1, qty: 1 };
+
+        let snapshot = br#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","bids":[{"price":45283.5,"qty":0.1},{"price":45283.4,"qty":1.5}],"asks":[{"price":45285.2,"qty":0.001}],"checksum":1}]}"#;
+        assert_eq!(driver.parse_message(snapshot, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 4_528_350_000_000, qty: 10_000_000 });
+        assert_eq!(book.bids[1].price, 4_528_340_000_000);
+        assert_eq!(book.bids[2].price, 0);
+        assert_eq!(book.asks[0].price, 4_528_520_000_000);
+
+        let update = br#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":45283.5,"qty":0}],"asks":[],"checksum":2,"timestamp":"2023-10-06T17:35:55.440295Z"}]}"#;
+        assert_eq!(driver.parse_message(update, &mut book), Ok(true));
+        L1FriendlyBook::compact(&mut book.bids, &mut book.bid_tombstones);
+        assert_eq!(book.bids[0].price, 4_528_340_000_000);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut driver = KrakenDriver::new();
+        driver.precision = Some(Scales { price: 1, qty: 8 });
+        let mut book = L1FriendlyBook::new();
+
+        let snapshot = br#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","bids":[{"price":45283.5,"qty":0.1},{"price":45283.4,"qty":1.5}],"asks":[{"price":45285.2,"qty":0.001}],"checksum":CRC}]}"#;
+        let mut crc = Crc::new();
+        crc.update(b"452852100000");
+        crc.update(b"45283510000000");
+        crc.update(b"452834150000000");
+        let expected = crc.sum();
+        let snapshot = String::from_utf8_lossy(snapshot).replace("CRC", &expected.to_string());
+        assert_eq!(driver.parse_message(snapshot.as_bytes(), &mut book), Ok(true));
+
+        // Removing the best bid without the venue agreeing
+        let update = br#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":45283.5,"qty":0}],"asks":[],"checksum":1}]}"#;
+        let mut crc = Crc::new();
+        crc.update(b"452852100000");
+        crc.update(b"452834150000000");
+        assert_eq!(
+            driver.parse_message(update, &mut book),
+            Err(DriverError::ChecksumMismatch { expected: 1, computed: crc.sum() })
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let info = br#"{"error":[],"result":{"XXBTZUSD":{"altname":"XBTUSD","wsname":"XBT/USD","pair_decimals":1,"lot_decimals":8}}}"#;
+        assert_eq!(precision(info), Ok(Precision::from_decimals(1, 8).unwrap()));
+        assert_eq!(
+            precision(br#"{"error":["EQuery:Unknown asset pair"]}"#),
+            Err(DriverError::Rejected("EQuery:Unknown asset pair".to_string()))
+        );
+
+        // Registered pairs skip the lookup and are parsed at their precision
+        let key = SymbolKey {
+            exchange: Exchange::Kraken,
+            symbol: "PRECISION-USD".to_string(),
+            product: ProductType::Spot,
+            feed: Feed::Depth,
+        };
+        instruments::register(&key, Precision::from_decimals(1, 8).unwrap());
+        let mut driver = KrakenDriver::new();
+        driver.handshake(&key).unwrap();
+        instruments::remove(&key);
+        let mut book = L1FriendlyBook::new();
+        let snapshot = br#"{"channel":"book","type":"snapshot","data":[{"symbol":"PRECISION/USD","bids":[{"price":45283.5,"qty":0.1}],"asks":[],"checksum":CRC}]}"#;
+        let mut crc = Crc::new();
+        crc.update(b"45283510000000");
+        let snapshot = String::from_utf8_lossy(snapshot).replace("CRC", &crc.sum().to_string());
+        assert_eq!(driver.parse_message(snapshot.as_bytes(), &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 452_835, qty: 10_000_000 });
+        assert_eq!(book.price_exponent, -1);
+    }
+
+    #[test]
+    fn test_rest_pair() {
+        assert_eq!(rest_pair("BTC-USD"), "XBTUSD");
+        assert_eq!(rest_pair("doge/usd"), "XDGUSD");
+        assert_eq!(rest_pair("ETH-EUR"), "ETHEUR");
+    }
+
+    #[test]
+    fn test_truncates_to_depth() {
+        let mut side = [Level::default(); BOOK_DEPTH];
+        for (idx, level) in side.iter_mut().enumerate() {
+            *level = Level { price: 100 - idx as i64, qty: 1 };
+        }
+        let mut tombstones = 0;
+        truncate(BOOK_DEPTH as u8, &mut tombstones, 25);
+        assert_eq!(tombstones, !0 << 25);
+        L1FriendlyBook::compact(&mut side, &mut tombstones);
+        assert_eq!(side[24].price, 76);
+        assert_eq!(side[25].price, 0);
+    }
+
+    #[test]
+    fn test_ignores_heartbeat() {
+        let mut book = L1FriendlyBook::new();
+        assert_eq!(KrakenDriver::new().parse_message(br#"{"channel":"heartbeat"}"#, &mut book), Ok(false));
+    }
+
+    #[test]
+    fn test_subscribe_replies() {
+        let mut driver = KrakenDriver::new();
+        let mut book = L1FriendlyBook::new();
+        let ack = br#"{"method":"subscribe","result":{"channel":"book","depth":10,"symbol":"BTC/USD"},"success":true,"time_in":"2023-09-25T09:04:31.742599Z","time_out":"2023-09-25T09:04:31.742648Z"}"#;
+        assert_eq!(driver.parse_message(ack, &mut book), Ok(false));
+
+        let error = br#"{"error":"Currency pair not supported FOO/USD","method":"subscribe","success":false,"symbol":"FOO/USD"}"#;
+        assert_eq!(
+            driver.parse_message(error, &mut book),
+            Err(DriverError::Rejected("Currency pair not supported FOO/USD".to_string()))
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Kraken Futures WebSocket v1 `book` feed.
+
+use crate::broker::instruments::Precision;
+use crate::broker::{ProductType, SymbolKey};
+use crate::driver::kraken::for_each_level;
+use crate::driver::schema::{self, kraken_futures::Event};
+use crate::driver::{DriverError, ExchangeDriver, Scales, apply_level, find, find_str, find_u64, parse_i64, parse_qty, rest_get};
+use crate::model::{L1FriendlyBook, UpdateCause};
+
+const WS_URL: &str = "wss://futures.kraken.com/ws/v1";
+const INSTRUMENTS_URL: &str = "https://futures.kraken.com/derivatives/api/v3/instruments";
+
+/// Fixed-point scale applied to Kraken Futures prices, unless the product's
+/// `tickSize` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to Kraken Futures quantities, unless the
+/// product's `contractValueTradePrecision` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Driver for the Kraken Futures level-2 book.
+///
+/// Kraken Futures is a separate venue from Kraken spot, with its own
+/// endpoint and message format. The venue sends a `book_snapshot` after
+/// subscribing, followed by one `book` message per changed level. Each
+/// message carries a per-product `seq` that increases by one, and the
+/// driver fails with [DriverError::SequenceGap] as soon as one is skipped.
+///
+/// Quantities are contracts: USD for inverse (`PI_`) products and the base
+/// currency for linear (`PF_`) ones.
+pub struct KrakenFuturesDriver {
+    scales: Scales,
+    /// `seq` of the last applied message, once the snapshot has arrived.
+    seq: Option<u64>,
+}
+
+impl KrakenFuturesDriver {
+    pub fn new() -> Self {
+        Self {
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            seq: None,
+        }
+    }
+
+    fn request(event: &str, key: &SymbolKey) -> String {
+        format!(
+            r#"{{"event":"{event}","feed":"book","product_ids":["{}"]}}"#,
+            product_id(key)
+        )
+    }
+
+    /// Checks that `seq` directly follows the last applied message.
+    fn advance(&mut self, seq: u64) -> Result<(), DriverError> {
+        if let Some(last) = self.seq
+            && seq != last + 1
+        {
+            return Err(DriverError::SequenceGap {
+                expected: last + 1,
+                received: seq,
+            });
+        }
+        self.seq = Some(seq);
+        Ok(())
+    }
+}
+
+impl Default for KrakenFuturesDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps a [SymbolKey] onto a Kraken Futures product id.
+///
+/// Perpetual keys such as `BTC-USD` become linear perpetuals (`PF_XBTUSD`),
+/// with `BTC` renamed to Kraken's `XBT`. Symbols that are already product ids
+/// (`PI_XBTUSD`, `FI_XBTUSD_240628`) are passed through upper-cased.
+pub fn product_id(key: &SymbolKey) -> String {
+    let symbol = key.symbol.to_ascii_uppercase();
+    if key.product != ProductType::Perpetual || symbol.contains('_') {
+        return symbol;
+    }
+    let pair: String = symbol.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    match pair.strip_prefix("BTC") {
+        Some(quote) => format!("PF_XBT{quote}"),
+        None => format!("PF_{pair}"),
+    }
+}
+
+/// Finds the precision of `product` in an `instruments` response.
+///
+/// A negative `contractValueTradePrecision` means sizes in tens or more,
+/// which need no decimals.
+///
+/// ```json
+/// {"result":"success","instruments":[{"symbol":"PF_XBTUSD","type":"flexible_futures","tickSize":0.5,"contractValueTradePrecision":4}]}
+/// ```
+fn precision(body: &[u8], product: &str) -> Result<Option<Precision>, DriverError> {
+    let response: schema::kraken_futures::Instruments = schema::decode(body)?;
+    let Some(inst) = response.instruments.iter().find(|inst| inst.symbol.eq_ignore_ascii_case(product)) else {
+        return Ok(None);
+    };
+    let tick = Precision::from_sizes(inst.tick_size.text(), b"1").ok_or(DriverError::Malformed)?;
+    let qty_scale = u32::try_from(inst.contract_value_trade_precision).unwrap_or(0);
+    Precision::from_decimals(tick.price_scale, qty_scale)
+        .map(|precision| Some(Precision { tick: tick.tick, ..precision }))
+        .ok_or(DriverError::Malformed)
+}
+
+impl ExchangeDriver for KrakenFuturesDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.seq = None;
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        precision(rest_get(INSTRUMENTS_URL)?.as_bytes(), &product_id(key))
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        WS_URL.to_string()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(Self::request("subscribe", key))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(Self::request("unsubscribe", key))
+    }
+
+    /// Returns the `seq` of the last message applied.
+    fn update_id(&self) -> Option<u64> {
+        self.seq
+    }
+
+    /// `book_snapshot` messages replace the book.
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if find_str(msg, "feed") == Some("book_snapshot") { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    /// Applies a `book_snapshot` or a single-level `book` update.
+    ///
+    /// ```json
+    /// {"feed":"book_snapshot","product_id":"PI_XBTUSD","timestamp":1612269825817,"seq":326072249,"tickSize":null,"bids":[{"price":34892.5,"qty":6385}],"asks":[{"price":34911.5,"qty":20598}]}
+    /// {"feed":"book","product_id":"PI_XBTUSD","side":"sell","seq":326072250,"price":34981,"qty":0,"timestamp":1612269953629}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        // Replies to requests also name the feed, so they are told apart first
+        if find(msg, br#""event":"#).is_some() {
+            return match schema::decode(msg)? {
+                Event::Error(error) => Err(DriverError::Rejected(error.message.into_owned())),
+                _ => Ok(false),
+            };
+        }
+
+        self.scales.stamp(book);
+        match find_str(msg, "feed") {
+            Some("book_snapshot") => {
+                self.seq = None;
+                self.advance(find_u64(msg, "seq").ok_or(DriverError::Malformed)?)?;
+                book.clear_sides();
+
+                let bids = find(msg, br#""bids":"#).ok_or(DriverError::Malformed)? + 7;
+                for_each_level(msg, bids, self.scales.price, self.scales.qty, |price, qty| {
+                    apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+                })?;
+                let asks = find(msg, br#""asks":"#).ok_or(DriverError::Malformed)? + 7;
+                for_each_level(msg, asks, self.scales.price, self.scales.qty, |price, qty| {
+                    apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+                })?;
+                Ok(true)
+            }
+            Some("book") => {
+                // Updates that arrive ahead of the snapshot are superseded by it
+                if self.seq.is_none() {
+                    return Ok(false);
+                }
+                self.advance(find_u64(msg, "seq").ok_or(DriverError::Malformed)?)?;
+
+                let price = find(msg, br#""price":"#).ok_or(DriverError::Malformed)? + 8;
+                let (price, _) = parse_i64(msg, price, self.scales.price)?;
+                let qty = find(msg, br#""qty":"#).ok_or(DriverError::Malformed)? + 6;
+                let (qty, _) = parse_qty(msg, qty, self.scales.qty)?;
+                match find_str(msg, "side") {
+                    Some("buy") => apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true),
+                    Some("sell") => apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false),
+                    _ => return Err(DriverError::Malformed),
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed};
+    use crate::model::Level;
+
+    fn key(symbol: &str) -> SymbolKey {
+        SymbolKey {
+            exchange: Exchange::Kraken,
+            symbol: symbol.to_string(),
+            product: ProductType::Perpetual,
+            feed: Feed::Depth,
+        }
+    }
+
+    #[test]
+    fn test_product_id() {
+        assert_eq!(product_id(&key("BTC-USD")), "PF_XBTUSD");
+        assert_eq!(product_id(&key("ETH-USD")), "PF_ETHUSD");
+        assert_eq!(product_id(&key("pi_xbtusd")), "PI_XBTUSD");
+        assert_eq!(
+            KrakenFuturesDriver::new().subscribe_msg(&key("BTC-USD")).unwrap(),
+            r#"{"event":"subscribe","feed":"book","product_ids":["PF_XBTUSD"]}"#
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let body = br#"{"result":"success","instruments":[{"symbol":"PI_XBTUSD","tickSize":0.5},{"symbol":"PF_XBTUSD","tickSize":0.5,"contractValueTradePrecision":4},{"symbol":"PF_SHIBUSD","tickSize":0.000000001,"contractValueTradePrecision":-3}]}"#;
+        let xbt = precision(body, "PF_XBTUSD").unwrap().unwrap();
+        assert_eq!((xbt.price_scale, xbt.tick), (1, 5));
+        assert_eq!((xbt.qty_scale, xbt.lot), (4, 1));
+        assert_eq!(precision(body, "PI_XBTUSD").unwrap().map(|p| p.qty_scale), Some(0));
+        assert_eq!(precision(body, "PF_SHIBUSD").unwrap().map(|p| (p.price_scale, p.qty_scale)), Some((9, 0)));
+        assert_eq!(precision(body, "PF_ETHUSD"), Ok(None));
+    }
+
+    #[test]
+    fn test_snapshot_then_updates() {
+        let mut driver = KrakenFuturesDriver::new();
+        let mut book = L1FriendlyBook::new();
+
+        let early = br#"{"feed":"book","product_id":"PI_XBTUSD","side":"buy","seq":326072248,"price":34892.5,"qty":1,"timestamp":1612269825800}"#;
+        assert_eq!(driver.parse_message(early, &mut book), Ok(false));
+
+        let snapshot = br#"{"feed":"book_snapshot","product_id":"PI_XBTUSD","timestamp":1612269825817,"seq":326072249,"tickSize":null,"bids":[{"price":34892.5,"qty":6385},{"price":34892,"qty":10924}],"asks":[{"price":34911.5,"qty":20598}]}"#;
+        assert_eq!(driver.parse_message(snapshot, &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 3_489_250_000_000, qty: 638_500_000_000 });
+        assert_eq!(book.bids[1].price, 3_489_200_000_000);
+        assert_eq!(book.asks[0].price, 3_491_150_000_000);
+
+        let update = br#"{"feed":"book","product_id":"PI_XBTUSD","side":"sell","seq":326072250,"price":34911.5,"qty":0,"timestamp":1612269953629}"#;
+        assert_eq!(driver.parse_message(update, &mut book), Ok(true));
+        L1FriendlyBook::compact(&mut book.asks, &mut book.ask_tombstones);
+        assert_eq!(book.asks[0].price, 0);
+
+        let gap = br#"{"feed":"book","product_id":"PI_XBTUSD","side":"buy","seq":326072252,"price":34890,"qty":5,"timestamp":1612269953700}"#;
+        assert_eq!(
+            driver.parse_message(gap, &mut book),
+            Err(DriverError::SequenceGap { expected: 326072251, received: 326072252 })
+        );
+    }
+
+    #[test]
+    fn test_ignores_control_frames() {
+        let mut book = L1FriendlyBook::new();
+        let subscribed = br#"{"event":"subscribed","feed":"book","product_ids":["PI_XBTUSD"]}"#;
+        assert_eq!(KrakenFuturesDriver::new().parse_message(subscribed, &mut book), Ok(false));
+
+        let error = br#"{"event":"error","message":"Invalid product id"}"#;
+        assert_eq!(
+            KrakenFuturesDriver::new().parse_message(error, &mut book),
+            Err(DriverError::Rejected("Invalid product id".to_string()))
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! KuCoin spot `/market/level2` channel.
+
+use crate::broker::SymbolKey;
+use crate::broker::instruments::Precision;
+use crate::connector::keepalive::{Keepalive, Ping};
+use crate::connector::rate_limit::RateLimit;
+use crate::driver::schema::{self, kucoin::Bullet, kucoin::Event};
+use crate::driver::{
+    DriverError, ExchangeDriver, Scales, apply_level, expect_token, find, find_u64, parse_i64, parse_qty, rest_get,
+    rest_post,
+};
+use crate::model::L1FriendlyBook;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BULLET_URL: &str = "https://api.kucoin.com/api/v1/bullet-public";
+const SYMBOLS_URL: &str = "https://api.kucoin.com/api/v2/symbols";
+
+/// Fixed-point scale applied to KuCoin prices, unless the symbol's
+/// `priceIncrement` is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to KuCoin quantities, unless the symbol's
+/// `baseIncrement` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Driver for the KuCoin level-2 market data stream.
+///
+/// KuCoin hands out websocket endpoints and tokens through the REST
+/// `bullet-public` call, so a fresh token is fetched before every connection.
+/// Each `l2update` carries a `sequenceStart..=sequenceEnd` range and every
+/// change its own sequence; a range that does not follow on from the last
+/// applied sequence is reported as [DriverError::SequenceGap].
+///
+/// The bullet response also sets the keepalive: KuCoin expects a JSON ping
+/// every `pingInterval` and closes connections that stay silent longer.
+pub struct KucoinDriver {
+    /// Websocket URL including the token, set by [ExchangeDriver::handshake].
+    url: String,
+    keepalive: Keepalive,
+    scales: Scales,
+    /// Sequence of the last applied change on this connection.
+    last_seq: Option<u64>,
+}
+
+impl KucoinDriver {
+    pub fn new() -> Self {
+        Self {
+            url: String::new(),
+            keepalive: Keepalive::WEBSOCKET,
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            last_seq: None,
+        }
+    }
+
+    fn request(&self, kind: &str, key: &SymbolKey) -> String {
+        format!(
+            r#"{{"id":1,"type":"{kind}","topic":"/market/level2:{}","privateChannel":false,"response":true}}"#,
+            market_symbol(&key.symbol)
+        )
+    }
+
+    /// Checks the update's sequence range against the last applied change.
+    fn check_sequence(&mut self, start: u64, end: u64) -> Result<(), DriverError> {
+        if let Some(last) = self.last_seq
+            && start > last + 1
+        {
+            return Err(DriverError::SequenceGap {
+                expected: last + 1,
+                received: start,
+            });
+        }
+        self.last_seq = Some(self.last_seq.map_or(end, |last| last.max(end)));
+        Ok(())
+    }
+}
+
+impl Default for KucoinDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a symbol such as `btc/usdt` into KuCoin's `BTC-USDT` form.
+pub fn market_symbol(symbol: &str) -> String {
+    symbol.replace(['/', '_'], "-").to_ascii_uppercase()
+}
+
+/// Builds the websocket URL from a `bullet-public` response body.
+fn connect_url(bullet: &[u8]) -> Result<String, DriverError> {
+    let bullet: Bullet = schema::decode(bullet)?;
+    let token = bullet.data.token;
+    let endpoint = &bullet.data.instance_servers.first().ok_or(DriverError::Malformed)?.endpoint;
+    let connect_id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    Ok(format!("{endpoint}?token={token}&connectId={connect_id}"))
+}
+
+/// Reads the ping rules from a `bullet-public` response body.
+fn keepalive(bullet: &[u8]) -> Result<Keepalive, DriverError> {
+    let bullet: Bullet = schema::decode(bullet)?;
+    let server = bullet.data.instance_servers.first().ok_or(DriverError::Malformed)?;
+    let (interval, timeout) = (server.ping_interval, server.ping_timeout);
+    Ok(Keepalive {
+        ping_interval: Some(Duration::from_millis(interval)),
+        ping: Ping::Text(r#"{"id":"keepalive","type":"ping"}"#),
+        timeout: Some(Duration::from_millis(interval + timeout)),
+    })
+}
+
+/// Reads the increments off a `symbols/<symbol>` response; `data` is null
+/// for unknown symbols.
+///
+/// ```json
+/// {"code":"200000","data":{"symbol":"BTC-USDT","baseIncrement":"0.00000001","quoteIncrement":"0.000001","priceIncrement":"0.1"}}
+/// ```
+fn precision(body: &[u8]) -> Result<Option<Precision>, DriverError> {
+    let response: schema::kucoin::SymbolInfo = schema::decode(body)?;
+    match response.data {
+        Some(symbol) => Precision::from_sizes(symbol.price_increment.as_bytes(), symbol.base_increment.as_bytes())
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+impl ExchangeDriver for KucoinDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        let bullet = rest_post(BULLET_URL)?;
+        self.url = connect_url(bullet.as_bytes())?;
+        self.keepalive = keepalive(bullet.as_bytes())?;
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.last_seq = None;
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        precision(rest_get(&format!("{SYMBOLS_URL}/{}", market_symbol(&key.symbol)))?.as_bytes())
+    }
+
+    fn endpoint(&self, _key: &SymbolKey) -> String {
+        self.url.clone()
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(self.request("subscribe", key))
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(self.request("unsubscribe", key))
+    }
+
+    fn keepalive(&self) -> Keepalive {
+        self.keepalive
+    }
+
+    /// KuCoin accepts 100 uplink messages per ten seconds.
+    fn rate_limit(&self) -> Option<RateLimit> {
+        Some(RateLimit { per_second: 10, burst: 100 })
+    }
+
+    /// Returns the `sequenceEnd` of the last change applied.
+    fn update_id(&self) -> Option<u64> {
+        self.last_seq
+    }
+
+    /// Applies a `trade.l2update` message.
+    ///
+    /// ```json
+    /// {"type":"message","topic":"/market/level2:BTC-USDT","subject":"trade.l2update","data":{"changes":{"asks":[["18906","0.00331","14103845"]],"bids":[]},"sequenceEnd":14103845,"sequenceStart":14103845,"symbol":"BTC-USDT","time":1663747970273}}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        if find(msg, br#""subject":"trade.l2update""#).is_none() {
+            return match schema::decode(msg)? {
+                Event::Error(error) => Err(DriverError::Rejected(error.data.into_owned())),
+                _ => Ok(false),
+            };
+        }
+
+        let start = find_u64(msg, "sequenceStart").ok_or(DriverError::Malformed)?;
+        let end = find_u64(msg, "sequenceEnd").ok_or(DriverError::Malformed)?;
+        let applied_before = self.last_seq.unwrap_or(0);
+        self.check_sequence(start, end)?;
+        self.scales.stamp(book);
+
+        let asks = find(msg, br#""asks":"#).ok_or(DriverError::Malformed)? + 7;
+        for_each_change(msg, asks, applied_before, self.scales, |price, qty| {
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+        })?;
+
+        let bids = find(msg, br#""bids":"#).ok_or(DriverError::Malformed)? + 7;
+        for_each_change(msg, bids, applied_before, self.scales, |price, qty| {
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+        })?;
+
+        Ok(true)
+    }
+}
+
+/// Walks a JSON array of `["price","size","sequence"]` changes starting at `start`.
+///
+/// Changes at or below `applied` were already reflected in the book and are skipped.
+fn for_each_change(
+    bytes: &[u8],
+    start: usize,
+    applied: u64,
+    scales: Scales,
+    mut on_level: impl FnMut(i64, i64),
+) -> Result<usize, DriverError> {
+    let mut idx = expect_token(bytes, start, b"[")?;
+    if bytes.get(idx) == Some(&b']') {
+        return Ok(idx + 1);
+    }
+
+    loop {
+        idx = expect_token(bytes, idx, b"[\"")?;
+        let (price, next) = parse_i64(bytes, idx, scales.price)?;
+        idx = expect_token(bytes, next, b"\",\"")?;
+        let (qty, next) = parse_qty(bytes, idx, scales.qty)?;
+        idx = expect_token(bytes, next, b"\",\"")?;
+        let (seq, next) = parse_i64(bytes, idx, 0)?;
+        idx = expect_token(bytes, next, b"\"]")?;
+
+        if seq as u64 > applied {
+            on_level(price, qty);
+        }
+
+        match bytes.get(idx) {
+            Some(b',') => idx += 1,
+            Some(b']') => return Ok(idx + 1),
+            _ => return Err(DriverError::Malformed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(start: u64, end: u64, asks: &str, bids: &str) -> String {
+        format!(
+            r#"{{"type":"message","topic":"/market/level2:BTC-USDT","subject":"trade.l2update","data":{{"changes":{{"asks":[{asks}],"bids":[{bids}]}},"sequenceEnd":{end},"sequenceStart":{start},"symbol":"BTC-USDT","time":1}}}}"#
+        )
+    }
+
+    #[test]
+    fn test_connect_url() {
+        let bullet = br#"{"code":"200000","data":{"token":"2neAiuYvAU61ZD","instanceServers":[{"endpoint":"wss://ws-api-spot.kucoin.com/","encrypt":true,"protocol":"websocket","pingInterval":18000,"pingTimeout":10000}]}}"#;
+        let url = connect_url(bullet).unwrap();
+        assert!(url.starts_with("wss://ws-api-spot.kucoin.com/?token=2neAiuYvAU61ZD&connectId="));
+        assert_eq!(connect_url(br#"{"code":"400100"}"#), Err(DriverError::Malformed));
+
+        let keepalive = keepalive(bullet).unwrap();
+        assert_eq!(keepalive.ping_interval, Some(Duration::from_secs(18)));
+        assert_eq!(keepalive.timeout, Some(Duration::from_secs(28)));
+    }
+
+    #[test]
+    fn test_applies_changes() {
+        let mut driver = KucoinDriver::new();
+        let mut book = L1FriendlyBook::new();
+
+        let msg = update(10, 11, r#"["18906","0.00331","10"]"#, r#"["18891.9","0.15688","11"]"#);
+        assert_eq!(driver.parse_message(msg.as_bytes(), &mut book), Ok(true));
+        assert_eq!(book.asks[0].price, 1_890_600_000_000);
+        assert_eq!(book.asks[0].qty, 331_000);
+        assert_eq!(book.bids[0].price, 1_889_190_000_000);
+
+        // Overlapping range: the change at 11 is stale, 12 is new
+        let msg = update(11, 12, "", r#"["18891.9","1","11"],["18890","2","12"]"#);
+        assert_eq!(driver.parse_message(msg.as_bytes(), &mut book), Ok(true));
+        assert_eq!(book.bids[0].qty, 15_688_000);
+        assert_eq!(book.bids[1].price, 1_889_000_000_000);
+    }
+
+    #[test]
+    fn test_detects_sequence_gap() {
+        let mut driver = KucoinDriver::new();
+        let mut book = L1FriendlyBook::new();
+
+        let msg = update(10, 10, r#"["1","1","10"]"#, "");
+        assert_eq!(driver.parse_message(msg.as_bytes(), &mut book), Ok(true));
+
+        let msg = update(13, 13, r#"["1","2","13"]"#, "");
+        assert_eq!(
+            driver.parse_message(msg.as_bytes(), &mut book),
+            Err(DriverError::SequenceGap { expected: 11, received: 13 })
+        );
+    }
+
+    #[test]
+    fn test_ignores_welcome() {
+        let mut book = L1FriendlyBook::new();
+        let welcome = br#"{"id":"hQvf8jkno","type":"welcome"}"#;
+        assert_eq!(KucoinDriver::new().parse_message(welcome, &mut book), Ok(false));
+
+        let error = br#"{"id":"1","type":"error","code":404,"data":"topic /market/level2:FOO-USDT is not found"}"#;
+        assert_eq!(
+            KucoinDriver::new().parse_message(error, &mut book),
+            Err(DriverError::Rejected("topic /market/level2:FOO-USDT is not found".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let body = br#"{"code":"200000","data":{"symbol":"BTC-USDT","baseIncrement":"0.00000001","quoteIncrement":"0.000001","priceIncrement":"0.1"}}"#;
+        assert_eq!(precision(body), Ok(Precision::from_decimals(1, 8)));
+        assert_eq!(precision(br#"{"code":"200000","data":null}"#), Ok(None));
+    }
+}
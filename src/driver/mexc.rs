@@ -0,0 +1,566 @@
+//! MEXC spot limit-depth (`spot@public.limit.depth.v3.api.pb`) and futures
+//! incremental depth (`sub.depth`) streams.
+
+use crate::broker::instruments::Precision;
+use crate::broker::{ProductType, SymbolKey};
+use crate::connector::keepalive::{Keepalive, Ping};
+use crate::driver::schema::{self, mexc::Event, mexc::Snapshot, mexc::SpotReply};
+use crate::driver::{
+    DriverError, ExchangeDriver, Scales, apply_level, find, find_u64, for_each_level, parse_i64, parse_qty, rest_get,
+};
+use crate::model::{L1FriendlyBook, UpdateCause};
+use std::time::Duration;
+
+const SPOT_WS_URL: &str = "wss://wbs-api.mexc.com/ws";
+const FUTURES_WS_URL: &str = "wss://contract.mexc.com/edge";
+const FUTURES_REST_URL: &str = "https://contract.mexc.com/api/v1/contract/depth";
+const SPOT_INFO_URL: &str = "https://api.mexc.com/api/v3/exchangeInfo";
+const FUTURES_DETAIL_URL: &str = "https://contract.mexc.com/api/v1/contract/detail";
+
+/// Fixed-point scale applied to MEXC prices, unless the symbol's precision
+/// is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to MEXC quantities, unless the symbol's
+/// precision is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// Levels per side requested on the spot limit-depth stream.
+pub const SPOT_DEPTH: u32 = 20;
+
+/// How often the client pings; both endpoints drop idle clients after a minute.
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// `PushDataV3ApiWrapper.publicLimitDepths`.
+const WRAPPER_LIMIT_DEPTHS: u32 = 303;
+/// `PublicLimitDepthsV3Api.asks` / `.bids`.
+const DEPTHS_ASKS: u32 = 1;
+const DEPTHS_BIDS: u32 = 2;
+/// `PublicLimitDepthV3ApiItem.price` / `.quantity`.
+const ITEM_PRICE: u32 = 1;
+const ITEM_QUANTITY: u32 = 2;
+
+/// Driver for the MEXC spot and futures depth streams.
+///
+/// Spot data is only published as protobuf. The driver subscribes to the
+/// limit-depth stream, whose every push is a full [SPOT_DEPTH]-level
+/// snapshot, so no sequencing is needed. JSON frames on that socket are
+/// subscription acks and pongs.
+///
+/// Futures use the JSON `push.depth` stream, which is incremental. On the
+/// first push the driver fetches the REST depth snapshot, drops pushes whose
+/// `version` is not newer than the snapshot's, and from then on requires
+/// each `version` to follow the last by one. Futures quantities are contract
+/// counts.
+pub struct MexcDriver {
+    market: Market,
+    /// Venue symbol, `BTCUSDT` on spot and `BTC_USDT` on futures.
+    symbol: String,
+    scales: Scales,
+    /// Futures `version` of the last applied push, or of the snapshot.
+    version: Option<u64>,
+    /// Whether the last futures push fetched the REST snapshot first.
+    replaced: bool,
+}
+
+impl MexcDriver {
+    pub fn new() -> Self {
+        Self {
+            market: Market::Spot,
+            symbol: String::new(),
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            version: None,
+            replaced: false,
+        }
+    }
+
+    /// Replaces the book with a REST `contract/depth` response.
+    fn apply_snapshot(&mut self, body: &[u8], book: &mut L1FriendlyBook) -> Result<(), DriverError> {
+        let snapshot: Snapshot = schema::decode(body)?;
+        book.clear_sides();
+        for level in &snapshot.data.bids {
+            let (price, qty) = level.parse(self.scales.price, self.scales.qty)?;
+            apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+        }
+        for level in &snapshot.data.asks {
+            let (price, qty) = level.parse(self.scales.price, self.scales.qty)?;
+            apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+        }
+        self.version = Some(snapshot.data.version);
+        self.replaced = true;
+        Ok(())
+    }
+
+    /// Applies a futures `push.depth` message on top of the snapshot.
+    fn apply_futures_update(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        let snapshotted = self.version.is_none();
+        if snapshotted {
+            let body = rest_get(&format!("{FUTURES_REST_URL}/{}", self.symbol))?;
+            self.apply_snapshot(body.as_bytes(), book)?;
+        }
+        self.apply_futures_delta(msg, book, snapshotted)
+    }
+
+    /// Applies a futures `push.depth` message on top of the snapshot in
+    /// `book`.
+    ///
+    /// A push the snapshot already covers changes nothing, but the book
+    /// still changed if `snapshotted`, as the snapshot was written for it.
+    fn apply_futures_delta(
+        &mut self,
+        msg: &[u8],
+        book: &mut L1FriendlyBook,
+        snapshotted: bool,
+    ) -> Result<bool, DriverError> {
+        let last = self.version.unwrap_or(0);
+
+        let version = find_u64(msg, "version").ok_or(DriverError::Malformed)?;
+        if version <= last {
+            return Ok(snapshotted);
+        }
+        if version != last + 1 {
+            return Err(DriverError::SequenceGap {
+                expected: last + 1,
+                received: version,
+            });
+        }
+
+        apply_json_sides(msg, book, self.scales)?;
+        self.version = Some(version);
+        Ok(true)
+    }
+}
+
+impl Default for MexcDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Which MEXC product family a key trades on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Market {
+    Spot,
+    Futures,
+}
+
+fn market(key: &SymbolKey) -> Market {
+    match key.product {
+        ProductType::Spot | ProductType::VanillaOption => Market::Spot,
+        ProductType::Perpetual | ProductType::Future => Market::Futures,
+    }
+}
+
+/// Converts a key into MEXC's symbol form: `BTCUSDT` on spot, `BTC_USDT` on futures.
+pub fn venue_symbol(key: &SymbolKey) -> String {
+    let symbol = key.symbol.to_ascii_uppercase();
+    match market(key) {
+        Market::Spot => symbol.chars().filter(|c| c.is_ascii_alphanumeric()).collect(),
+        Market::Futures => symbol.replace(['-', '/'], "_"),
+    }
+}
+
+fn spot_stream(key: &SymbolKey) -> String {
+    format!("spot@public.limit.depth.v3.api.pb@{}@{SPOT_DEPTH}", venue_symbol(key))
+}
+
+/// Finds the precision of `symbol` in a spot `exchangeInfo` response.
+///
+/// ```json
+/// {"symbols":[{"symbol":"BTCUSDT","status":"1","quotePrecision":2,"baseAssetPrecision":6,"baseSizePrecision":"0.000001"}]}
+/// ```
+fn spot_precision(info: &[u8], symbol: &str) -> Result<Option<Precision>, DriverError> {
+    let info: schema::mexc::ExchangeInfo = schema::decode(info)?;
+    match info.symbols.iter().find(|entry| entry.symbol == symbol) {
+        Some(entry) => Precision::from_decimals(entry.quote_precision, entry.base_asset_precision)
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+/// Reads the precision off a futures `contract/detail` response; volumes
+/// are contract counts.
+///
+/// ```json
+/// {"success":true,"code":0,"data":{"symbol":"BTC_USDT","priceUnit":0.1,"volUnit":1,"priceScale":1,"volScale":0,"contractSize":0.0001}}
+/// ```
+fn futures_precision(body: &[u8]) -> Result<Option<Precision>, DriverError> {
+    let detail: schema::mexc::ContractDetail = schema::decode(body)?;
+    match detail.data {
+        Some(contract) => Precision::from_decimals(contract.price_scale, contract.vol_scale)
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+impl ExchangeDriver for MexcDriver {
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.market = market(key);
+        self.symbol = venue_symbol(key);
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        self.version = None;
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        let symbol = venue_symbol(key);
+        match market(key) {
+            Market::Spot => spot_precision(rest_get(&format!("{SPOT_INFO_URL}?symbol={symbol}"))?.as_bytes(), &symbol),
+            Market::Futures => futures_precision(rest_get(&format!("{FUTURES_DETAIL_URL}?symbol={symbol}"))?.as_bytes()),
+        }
+    }
+
+    fn endpoint(&self, key: &SymbolKey) -> String {
+        match market(key) {
+            Market::Spot => SPOT_WS_URL.to_string(),
+            Market::Futures => FUTURES_WS_URL.to_string(),
+        }
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(match market(key) {
+            Market::Spot => format!(r#"{{"method":"SUBSCRIPTION","params":["{}"]}}"#, spot_stream(key)),
+            Market::Futures => format!(r#"{{"method":"sub.depth","param":{{"symbol":"{}"}}}}"#, venue_symbol(key)),
+        })
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        Some(match market(key) {
+            Market::Spot => format!(r#"{{"method":"UNSUBSCRIPTION","params":["{}"]}}"#, spot_stream(key)),
+            Market::Futures => format!(r#"{{"method":"unsub.depth","param":{{"symbol":"{}"}}}}"#, venue_symbol(key)),
+        })
+    }
+
+    /// Returns the `version` of the last update applied.
+    fn update_id(&self) -> Option<u64> {
+        self.version
+    }
+
+    /// Every spot push is a snapshot; futures pushes are deltas unless the
+    /// REST snapshot was fetched for them.
+    fn update_cause(&self, _msg: &[u8]) -> UpdateCause {
+        match self.market {
+            Market::Spot => UpdateCause::Snapshot,
+            Market::Futures if self.replaced => UpdateCause::Snapshot,
+            Market::Futures => UpdateCause::Delta,
+        }
+    }
+
+    /// Applies a protobuf spot snapshot or a JSON futures update.
+    ///
+    /// ```json
+    /// {"channel":"push.depth","data":{"asks":[[6859.5,3251,1]],"bids":[],"version":96801927},"symbol":"BTC_USDT","ts":1587442022003}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        self.scales.stamp(book);
+        self.replaced = false;
+        match self.market {
+            Market::Spot => {
+                // Subscription acks and pongs
+                if msg.first() == Some(&b'{') {
+                    let reply: SpotReply = schema::decode(msg)?;
+                    if reply.code != 0 {
+                        return Err(DriverError::Rejected(reply.msg.into_owned()));
+                    }
+                    return Ok(false);
+                }
+                apply_limit_depths(msg, book, self.scales)
+            }
+            Market::Futures => {
+                if find(msg, br#""channel":"push.depth""#).is_none() {
+                    return match schema::decode(msg)? {
+                        Event::Error(error) => Err(DriverError::Rejected(error.data.into_owned())),
+                        _ => Ok(false),
+                    };
+                }
+                self.apply_futures_update(msg, book)
+            }
+        }
+    }
+
+    /// MEXC ignores websocket ping frames and expects a JSON ping instead.
+    fn keepalive(&self) -> Keepalive {
+        Keepalive {
+            ping_interval: Some(PING_INTERVAL),
+            ping: match self.market {
+                Market::Spot => Ping::Text(r#"{"method":"PING"}"#),
+                Market::Futures => Ping::Text(r#"{"method":"ping"}"#),
+            },
+            timeout: Some(PING_INTERVAL * 3),
+        }
+    }
+}
+
+/// Applies the `bids`/`asks` arrays of a futures push.
+fn apply_json_sides(msg: &[u8], book: &mut L1FriendlyBook, scales: Scales) -> Result<(), DriverError> {
+    let bids = find(msg, br#""bids":"#).ok_or(DriverError::Malformed)? + 7;
+    for_each_level(msg, bids, scales.price, scales.qty, |price, qty| {
+        apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+    })?;
+
+    let asks = find(msg, br#""asks":"#).ok_or(DriverError::Malformed)? + 7;
+    for_each_level(msg, asks, scales.price, scales.qty, |price, qty| {
+        apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+    })?;
+    Ok(())
+}
+
+/// Replaces the book with the `publicLimitDepths` body of a push wrapper.
+///
+/// Returns `Ok(false)` for wrappers carrying any other stream.
+fn apply_limit_depths(msg: &[u8], book: &mut L1FriendlyBook, scales: Scales) -> Result<bool, DriverError> {
+    let mut depths = None;
+    for_each_field(msg, |field, value| {
+        if let (WRAPPER_LIMIT_DEPTHS, Value::Bytes(body)) = (field, value) {
+            depths = Some(body);
+        }
+        Ok(())
+    })?;
+    let Some(depths) = depths else {
+        return Ok(false);
+    };
+
+    book.clear_sides();
+    for_each_field(depths, |field, value| {
+        let (side, tombstones, descending, item) = match (field, value) {
+            (DEPTHS_BIDS, Value::Bytes(item)) => (&mut book.bids, &mut book.bid_tombstones, true, item),
+            (DEPTHS_ASKS, Value::Bytes(item)) => (&mut book.asks, &mut book.ask_tombstones, false, item),
+            _ => return Ok(()),
+        };
+
+        let (mut price, mut qty) = (None, None);
+        for_each_field(item, |field, value| {
+            match (field, value) {
+                (ITEM_PRICE, Value::Bytes(text)) => price = Some(parse_i64(text, 0, scales.price)?.0),
+                (ITEM_QUANTITY, Value::Bytes(text)) => qty = Some(parse_qty(text, 0, scales.qty)?.0),
+                _ => {}
+            }
+            Ok(())
+        })?;
+        apply_level(
+            side,
+            tombstones,
+            price.ok_or(DriverError::Malformed)?,
+            qty.ok_or(DriverError::Malformed)?,
+            descending,
+        );
+        Ok(())
+    })?;
+    Ok(true)
+}
+
+/// A decoded protobuf field value.
+enum Value<'a> {
+    Bytes(&'a [u8]),
+    /// A varint or fixed-width value; none of the fields read here use them.
+    Scalar,
+}
+
+/// Walks the top-level fields of a protobuf message.
+fn for_each_field<'a>(
+    buf: &'a [u8],
+    mut on_field: impl FnMut(u32, Value<'a>) -> Result<(), DriverError>,
+) -> Result<(), DriverError> {
+    let mut idx = 0;
+    while idx < buf.len() {
+        let tag = varint(buf, &mut idx)?;
+        let field = u32::try_from(tag >> 3).map_err(|_| DriverError::Malformed)?;
+        let value = match tag & 7 {
+            0 => {
+                varint(buf, &mut idx)?;
+                Value::Scalar
+            }
+            1 => {
+                idx += 8;
+                Value::Scalar
+            }
+            2 => {
+                let len = usize::try_from(varint(buf, &mut idx)?).map_err(|_| DriverError::Malformed)?;
+                let body = idx.checked_add(len).and_then(|end| buf.get(idx..end)).ok_or(DriverError::Malformed)?;
+                idx += len;
+                Value::Bytes(body)
+            }
+            5 => {
+                idx += 4;
+                Value::Scalar
+            }
+            _ => return Err(DriverError::Malformed),
+        };
+        if idx > buf.len() {
+            return Err(DriverError::Malformed);
+        }
+        on_field(field, value)?;
+    }
+    Ok(())
+}
+
+/// Reads a base-128 varint at `idx` and advances past it.
+fn varint(buf: &[u8], idx: &mut usize) -> Result<u64, DriverError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*idx).ok_or(DriverError::Malformed)?;
+        *idx += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DriverError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed};
+    use crate::model::Level;
+
+    fn key(symbol: &str, product: ProductType) -> SymbolKey {
+        SymbolKey {
+            exchange: Exchange::Mexc,
+            symbol: symbol.to_string(),
+            product,
+            feed: Feed::Depth,
+        }
+    }
+
+    /// Encodes a length-delimited protobuf field.
+    fn bytes_field(field: u32, body: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for mut value in [u64::from(field << 3 | 2), body.len() as u64] {
+            while value >= 0x80 {
+                out.push(value as u8 | 0x80);
+                value >>= 7;
+            }
+            out.push(value as u8);
+        }
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn item(field: u32, price: &str, qty: &str) -> Vec<u8> {
+        let body = [bytes_field(ITEM_PRICE, price.as_bytes()), bytes_field(ITEM_QUANTITY, qty.as_bytes())].concat();
+        bytes_field(field, &body)
+    }
+
+    #[test]
+    fn test_subscribe_msgs() {
+        let spot = key("BTC-USDT", ProductType::Spot);
+        assert_eq!(
+            MexcDriver::new().subscribe_msg(&spot).unwrap(),
+            r#"{"method":"SUBSCRIPTION","params":["spot@public.limit.depth.v3.api.pb@BTCUSDT@20"]}"#
+        );
+        let perp = key("BTC-USDT", ProductType::Perpetual);
+        assert_eq!(MexcDriver::new().endpoint(&perp), "wss://contract.mexc.com/edge");
+        assert_eq!(
+            MexcDriver::new().subscribe_msg(&perp).unwrap(),
+            r#"{"method":"sub.depth","param":{"symbol":"BTC_USDT"}}"#
+        );
+    }
+
+    #[test]
+    fn test_spot_limit_depths() {
+        let mut driver = MexcDriver::new();
+        let mut book = L1FriendlyBook::new();
+        driver.handshake(&key("BTC-USDT", ProductType::Spot)).unwrap();
+
+        let depths = [
+            item(DEPTHS_ASKS, "93180.18", "0.21976424"),
+            item(DEPTHS_BIDS, "93179.98", "2.82651000"),
+            item(DEPTHS_BIDS, "93179.97", "0.5"),
+            bytes_field(4, b"36913565463"),
+        ]
+        .concat();
+        let wrapper = [
+            bytes_field(1, b"spot@public.limit.depth.v3.api.pb@BTCUSDT@20"),
+            bytes_field(WRAPPER_LIMIT_DEPTHS, &depths),
+            bytes_field(3, b"BTCUSDT"),
+            vec![6 << 3, 0xe8, 0x07],
+        ]
+        .concat();
+
+        assert_eq!(driver.parse_message(&wrapper, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(&wrapper), UpdateCause::Snapshot);
+        assert_eq!(book.bids[0], Level { price: 9_317_998_000_000, qty: 282_651_000 });
+        assert_eq!(book.bids[1].price, 9_317_997_000_000);
+        assert_eq!(book.asks[0], Level { price: 9_318_018_000_000, qty: 21_976_424 });
+
+        let ack = br#"{"id":0,"code":0,"msg":"spot@public.limit.depth.v3.api.pb@BTCUSDT@20"}"#;
+        assert_eq!(driver.parse_message(ack, &mut book), Ok(false));
+        assert_eq!(driver.parse_message(&wrapper[..wrapper.len() - 2], &mut book), Err(DriverError::Malformed));
+    }
+
+    #[test]
+    fn test_futures_sync_against_snapshot() {
+        let mut driver = MexcDriver::new();
+        let mut book = L1FriendlyBook::new();
+        driver.handshake(&key("BTC-USDT", ProductType::Perpetual)).unwrap();
+
+        let snapshot = br#"{"success":true,"code":0,"data":{"asks":[[6859.5,3251,1]],"bids":[[6858.5,120,2]],"version":96801927,"timestamp":1587442022003}}"#;
+        driver.apply_snapshot(snapshot, &mut book).unwrap();
+        assert_eq!(book.bids[0], Level { price: 685_850_000_000, qty: 12_000_000_000 });
+
+        let stale = br#"{"channel":"push.depth","data":{"asks":[],"bids":[[6858.5,0,0]],"version":96801927},"symbol":"BTC_USDT","ts":1587442022003}"#;
+        assert_eq!(driver.parse_message(stale, &mut book), Ok(false));
+        // Unless the push is the one the snapshot was fetched for
+        assert_eq!(driver.apply_futures_delta(stale, &mut book, true), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 685_850_000_000, qty: 12_000_000_000 });
+
+        let next = br#"{"channel":"push.depth","data":{"asks":[[6859.5,0,0]],"bids":[],"version":96801928},"symbol":"BTC_USDT","ts":1587442022004}"#;
+        assert_eq!(driver.parse_message(next, &mut book), Ok(true));
+        assert_eq!(driver.update_cause(next), UpdateCause::Delta);
+        assert_eq!(book.ask_tombstones, 1);
+
+        let gap = br#"{"channel":"push.depth","data":{"asks":[],"bids":[],"version":96801930},"symbol":"BTC_USDT","ts":1587442022005}"#;
+        assert_eq!(
+            driver.parse_message(gap, &mut book),
+            Err(DriverError::SequenceGap { expected: 96801929, received: 96801930 })
+        );
+    }
+
+    #[test]
+    fn test_field_length_past_the_buffer() {
+        let huge = [0x0a, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert_eq!(for_each_field(&huge, |_, _| Ok(())), Err(DriverError::Malformed));
+        assert_eq!(for_each_field(&[0x0a, 0x02, 0x01], |_, _| Ok(())), Err(DriverError::Malformed));
+    }
+
+    #[test]
+    fn test_json_ping() {
+        let mut driver = MexcDriver::new();
+        driver.handshake(&key("BTC-USDT", ProductType::Perpetual)).unwrap();
+        assert_eq!(driver.keepalive().ping, Ping::Text(r#"{"method":"ping"}"#));
+        assert_eq!(driver.pending_reply(), None);
+    }
+
+    #[test]
+    fn test_errors_are_rejected() {
+        let mut book = L1FriendlyBook::new();
+        let mut spot = MexcDriver::new();
+        spot.handshake(&key("BTC-USDT", ProductType::Spot)).unwrap();
+        let ack = br#"{"id":0,"code":0,"msg":"spot@public.limit.depth.v3.api.pb@BTCUSDT@20"}"#;
+        assert_eq!(spot.parse_message(ack, &mut book), Ok(false));
+        let refused = br#"{"id":0,"code":1,"msg":"Not Subscribed successfully! [spot@public.limit.depth.v3.api.pb@FOOUSDT@20]"}"#;
+        assert!(matches!(spot.parse_message(refused, &mut book), Err(DriverError::Rejected(_))));
+
+        let mut futures = MexcDriver::new();
+        futures.handshake(&key("BTC-USDT", ProductType::Perpetual)).unwrap();
+        let error = br#"{"channel":"rs.error","data":"Contract doesn't exist!","ts":1587442022003}"#;
+        assert_eq!(
+            futures.parse_message(error, &mut book),
+            Err(DriverError::Rejected("Contract doesn't exist!".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let info = br#"{"symbols":[{"symbol":"BTCUSDT","status":"1","quotePrecision":2,"baseAssetPrecision":6,"baseSizePrecision":"0.000001"}]}"#;
+        assert_eq!(spot_precision(info, "BTCUSDT"), Ok(Precision::from_decimals(2, 6)));
+        assert_eq!(spot_precision(info, "ETHUSDT"), Ok(None));
+
+        let detail = br#"{"success":true,"code":0,"data":{"symbol":"BTC_USDT","priceUnit":0.1,"volUnit":1,"priceScale":1,"volScale":0,"contractSize":0.0001}}"#;
+        assert_eq!(futures_precision(detail), Ok(Precision::from_decimals(1, 0)));
+        assert_eq!(futures_precision(br#"{"success":false,"code":1001,"message":"Contract doesn't exist!"}"#), Ok(None));
+    }
+}
@@ -0,0 +1,1118 @@
+//! Exchange wire-protocol drivers.
+//!
+//! A driver knows how to reach a venue, which frames subscribe and
+//! unsubscribe a [SymbolKey], and how to fold a raw frame into an
+//! [L1FriendlyBook]. Drivers never bump the version or compact the book;
+//! the connector finalizes each packet once the driver has applied it.
+
+pub mod binance;
+pub mod bitfinex;
+pub mod bitmex;
+pub mod bitstamp;
+pub mod cme;
+pub mod coinbase;
+pub mod deribit;
+pub mod dydx;
+pub mod fix;
+pub mod gate;
+pub mod htx;
+pub mod hyperliquid;
+pub mod itch;
+pub mod kucoin;
+pub mod kraken;
+pub mod kraken_futures;
+pub mod mexc;
+pub mod okx;
+pub mod schema;
+
+use crate::broker::instruments::{self, Precision};
+use crate::broker::{Exchange, ProductType, SymbolKey};
+use crate::connector::keepalive::Keepalive;
+use crate::connector::rate_limit::RateLimit;
+use crate::model::{BOOK_DEPTH, FullDepthBook, L1FriendlyBook, Level, LevelChange, Side, UpdateCause};
+use crate::util::{ParseError, ParseFailure, Rounding, ScaleFactor, parse_i64_with_precision, parse_u64_with_precision};
+use parking_lot::RwLock;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, LazyLock};
+use ureq::Agent;
+
+/// Builds a fresh driver for a custom exchange.
+pub type DriverFactory = Arc<dyn Fn() -> Box<dyn ExchangeDriver> + Send + Sync>;
+
+/// Drivers registered for [Exchange::Custom] venues, by name.
+static CUSTOM_DRIVERS: LazyLock<RwLock<HashMap<&'static str, DriverFactory>>> = LazyLock::new(Default::default);
+
+/// A venue and one of its symbols.
+type Instrument = (Exchange, String);
+
+/// Book `(price, qty)` exponents set with [set_book_exponents], by instrument.
+static BOOK_EXPONENTS: LazyLock<RwLock<HashMap<Instrument, (i8, i8)>>> = LazyLock::new(Default::default);
+
+/// Errors raised while decoding an exchange frame.
+#[derive(Debug, PartialEq)]
+pub enum DriverError {
+    /// A number failed to parse where its place in the frame is unknown,
+    /// such as a field decoded by serde.
+    Parse(ParseError),
+    /// A numeric field of the frame failed to parse; carries the offset
+    /// and the bytes around it.
+    ParseAt(ParseFailure),
+    /// The frame did not have the structure the driver expected.
+    Malformed,
+    /// An update did not follow on from the last applied sequence number.
+    SequenceGap { expected: u64, received: u64 },
+    /// The book no longer matches the checksum published by the venue.
+    ChecksumMismatch { expected: u32, computed: u32 },
+    /// The best bid reached the best ask once the frame was applied, and
+    /// the [CrossedPolicy](crate::connector::CrossedPolicy) asks for a rebuild.
+    Crossed,
+    /// A REST call made by the driver failed.
+    Rest(String),
+    /// The venue rejected a request or ended the session.
+    Rejected(String),
+}
+
+impl From<ParseError> for DriverError {
+    fn from(err: ParseError) -> Self {
+        DriverError::Parse(err)
+    }
+}
+
+impl From<ParseFailure> for DriverError {
+    fn from(failure: ParseFailure) -> Self {
+        DriverError::ParseAt(failure)
+    }
+}
+
+/// How a driver's frames are carried to and from the venue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// A websocket; `endpoint` returns a `ws://` or `wss://` URL and each
+    /// websocket message is handed to the driver as one frame.
+    WebSocket,
+    /// A plain TCP byte stream; `endpoint` returns `host:port` and frames
+    /// are whatever each read returns, so the driver does its own framing.
+    Tcp,
+    /// UDP multicast; `endpoint` returns comma-separated `group:port` feeds,
+    /// optionally followed by `@interface`, and each datagram is one frame.
+    /// Nothing is ever sent back.
+    Udp,
+}
+
+/// Defines the subscription wire-protocol and parsing hook for a venue.
+///
+/// Venues this crate does not ship can be hosted by implementing the trait
+/// in another crate and registering it with [register_custom]; the
+/// connector drives it exactly like a built-in driver.
+pub trait ExchangeDriver: Send {
+    /// Returns how this driver's frames are carried.
+    fn transport(&self) -> Transport {
+        Transport::WebSocket
+    }
+
+    /// Performs any out-of-band setup needed before connecting.
+    ///
+    /// Called before every connection attempt, so drivers can fetch tokens
+    /// and reset per-connection state here. On a shared connection it is
+    /// also called when the key joins an open socket or has to resync.
+    fn handshake(&mut self, _key: &SymbolKey) -> Result<(), DriverError> {
+        Ok(())
+    }
+
+    /// Returns the websocket URL to connect to for `key`.
+    fn endpoint(&self, key: &SymbolKey) -> String;
+
+    /// Returns mirrors of [endpoint](ExchangeDriver::endpoint) to fail over
+    /// to, in order, when it keeps failing.
+    ///
+    /// Replaced by backups set with [crate::connector::failover::configure].
+    fn backup_endpoints(&self, _key: &SymbolKey) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Returns the frame to send after connecting, if the venue needs one.
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String>;
+
+    /// Returns the frame to send before disconnecting, if the venue needs one.
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String>;
+
+    /// How many keys may share one connection.
+    ///
+    /// Above one, keys with the same [endpoint](ExchangeDriver::endpoint)
+    /// are packed onto one socket until it holds this many, and the next key
+    /// opens another. Drivers that multiplex also implement
+    /// [stream_name](ExchangeDriver::stream_name) and
+    /// [frame_stream](ExchangeDriver::frame_stream) so frames can be routed.
+    fn streams_per_connection(&self) -> usize {
+        1
+    }
+
+    /// Returns the name that identifies `key`'s frames on a shared connection.
+    fn stream_name(&self, key: &SymbolKey) -> String {
+        key.symbol.clone()
+    }
+
+    /// Returns the stream name carried by `msg`, or `None` for frames that
+    /// belong to the connection, such as acks and pongs.
+    fn frame_stream<'a>(&self, _msg: &'a [u8]) -> Option<&'a [u8]> {
+        None
+    }
+
+    /// The venue's limit on subscribe requests and connection attempts.
+    ///
+    /// `None`, the default, sends them as fast as they come. Overridden per
+    /// exchange with [crate::connector::rate_limit::configure].
+    fn rate_limit(&self) -> Option<RateLimit> {
+        None
+    }
+
+    /// Returns one frame subscribing all of `keys`, if the venue accepts it.
+    ///
+    /// Used on shared connections to stay under the venue's limit on
+    /// incoming messages; `None` sends one
+    /// [subscribe_msg](ExchangeDriver::subscribe_msg) per key instead.
+    fn subscribe_batch_msg(&self, _keys: &[&SymbolKey]) -> Option<String> {
+        None
+    }
+
+    /// Applies a single frame to `book`.
+    ///
+    /// Returns `Ok(true)` if the book was modified and the packet should be
+    /// finalized (compacted and versioned), `Ok(false)` for control frames.
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError>;
+
+    /// Whether the driver serves [Feed::Private](crate::broker::Feed::Private) keys.
+    ///
+    /// Private keys are rejected unless it does and their exchange has
+    /// [credentials](crate::connector::auth::configure). Such keys never
+    /// share a connection, and their frames go to
+    /// [parse_private](ExchangeDriver::parse_private) instead of
+    /// `parse_message`.
+    fn private_streams(&self) -> bool {
+        false
+    }
+
+    /// Checks a frame of a private stream.
+    ///
+    /// Returns `Ok(true)` if it carries an event of the key's
+    /// [PrivateChannel](crate::broker::PrivateChannel), which the connector reports as a
+    /// [PrivateEvent](crate::connector::PrivateEvent), and `Ok(false)` for
+    /// acks and other channels' events.
+    fn parse_private(&mut self, _msg: &[u8]) -> Result<bool, DriverError> {
+        Ok(false)
+    }
+
+    /// Returns the first sequence number of a [Transport::Udp] datagram and
+    /// how many numbers it spans.
+    ///
+    /// Lets the connector arbitrate redundant multicast lines of a feed, see
+    /// [multicast](crate::connector::multicast). Unsequenced packets return
+    /// `None` and are always applied.
+    fn packet_sequence(&self, _packet: &[u8]) -> Option<(u64, u64)> {
+        None
+    }
+
+    /// Whether to offer permessage-deflate when opening a websocket.
+    ///
+    /// Compressed messages are inflated by the connector, so `parse_message`
+    /// always sees plain frames.
+    fn permessage_deflate(&self) -> bool {
+        false
+    }
+
+    /// Returns a frame the driver wants sent back to the venue, if any.
+    ///
+    /// Polled after every frame, so drivers can answer application-level
+    /// pings or request snapshots in response to what they just parsed, and
+    /// once whenever the socket has nothing to read.
+    fn pending_reply(&mut self) -> Option<String> {
+        None
+    }
+
+    /// The venue's keepalive rules, applied by the connector.
+    ///
+    /// Websockets default to ping frames with a timeout; raw TCP and UDP
+    /// streams default to neither. Overridden per exchange with
+    /// [crate::connector::keepalive::configure].
+    fn keepalive(&self) -> Keepalive {
+        match self.transport() {
+            Transport::WebSocket => Keepalive::WEBSOCKET,
+            Transport::Tcp | Transport::Udp => Keepalive::NONE,
+        }
+    }
+
+    /// Looks `key`'s tick and lot sizes up on the venue, see
+    /// [instruments](crate::broker::instruments).
+    ///
+    /// Called by the connector before the first handshake of an instrument
+    /// it has no precision for, which is where the driver picks its
+    /// [Scales] up. `Ok(None)` leaves the driver on its defaults.
+    fn fetch_precision(&self, _key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        Ok(None)
+    }
+
+    /// Returns the unbounded book the driver projects its levels from, for
+    /// venues that report more than [BOOK_DEPTH] levels.
+    ///
+    /// Called by the connector after every handshake, which publishes it on
+    /// the [SharedBook](crate::model::SharedBook). Drivers that fill the L1
+    /// book with [apply_level] return `None`, and the connector keeps the
+    /// full book for them if the caller set one.
+    fn full_depth(&self) -> Option<Arc<RwLock<FullDepthBook>>> {
+        None
+    }
+
+    /// Returns when the venue stamped `msg`, in nanoseconds since the Unix
+    /// epoch.
+    ///
+    /// Called by the connector for every frame that changed the book, to
+    /// set [L1FriendlyBook::exchange_ts]. `None` leaves it at 0.
+    fn exchange_ts(&self, _msg: &[u8]) -> Option<u64> {
+        None
+    }
+
+    /// Returns the venue's id of the last update applied, such as
+    /// Binance's `u` or Coinbase's `sequence`.
+    ///
+    /// Called by the connector for every frame that changed the book, to
+    /// set [L1FriendlyBook::update_id]. `None` leaves it at 0.
+    fn update_id(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns whether `msg` replaced the whole book or changed some of its
+    /// levels.
+    ///
+    /// Called by the connector for every frame that changed the book, to
+    /// set [L1FriendlyBook::update_cause] unless the frame crossed the book
+    /// or followed a resync. Defaults to [UpdateCause::Delta].
+    fn update_cause(&self, _msg: &[u8]) -> UpdateCause {
+        UpdateCause::Delta
+    }
+
+    /// Takes the `(expected, computed)` checksums of the last mismatch the
+    /// driver kept its book through, as [okx::ChecksumAction::Log] asks.
+    ///
+    /// Called by the connector after every frame, which reports it as
+    /// [ConnectionStatus::ChecksumMismatch](crate::connector::ConnectionStatus::ChecksumMismatch).
+    fn take_mismatch(&mut self) -> Option<(u32, u32)> {
+        None
+    }
+}
+
+/// The trait a custom venue's adapter implements, under the name the
+/// [register_custom] docs use; it is [ExchangeDriver] itself.
+///
+/// An adapter's connect step is [transport](ExchangeDriver::transport),
+/// [endpoint](ExchangeDriver::endpoint) and
+/// [handshake](ExchangeDriver::handshake), its subscribe step
+/// [subscribe_msg](ExchangeDriver::subscribe_msg), and its parse and apply
+/// steps are both [parse_message](ExchangeDriver::parse_message), which
+/// writes each frame straight into the book.
+pub use ExchangeDriver as ExchangeAdapter;
+
+/// Returns a fresh driver for `key`, or `None` if its venue is not supported yet.
+///
+/// Exchanges registered with [fix::register] are served over FIX instead of
+/// their native driver.
+pub fn driver_for(key: &SymbolKey) -> Option<Box<dyn ExchangeDriver>> {
+    if let Some(config) = fix::config_for(key.exchange) {
+        return Some(Box::new(fix::FixDriver::new(config)));
+    }
+
+    match key.exchange {
+        Exchange::Binance => Some(Box::new(binance::BinanceDriver::new())),
+        Exchange::Kraken => match key.product {
+            ProductType::Perpetual | ProductType::Future => Some(Box::new(kraken_futures::KrakenFuturesDriver::new())),
+            ProductType::Spot | ProductType::VanillaOption => Some(Box::new(kraken::KrakenDriver::new())),
+        },
+        Exchange::Okx => Some(Box::new(okx::OkxDriver::new())),
+        Exchange::Deribit => Some(Box::new(deribit::DeribitDriver::new())),
+        Exchange::Bitfinex => Some(Box::new(bitfinex::BitfinexDriver::new())),
+        Exchange::Kucoin => Some(Box::new(kucoin::KucoinDriver::new())),
+        Exchange::Gate => Some(Box::new(gate::GateDriver::new())),
+        Exchange::Htx => Some(Box::new(htx::HtxDriver::new())),
+        Exchange::Bitstamp => Some(Box::new(bitstamp::BitstampDriver::new())),
+        Exchange::Bitmex => Some(Box::new(bitmex::BitmexDriver::new())),
+        Exchange::Dydx => Some(Box::new(dydx::DydxDriver::new())),
+        Exchange::Hyperliquid => Some(Box::new(hyperliquid::HyperliquidDriver::new())),
+        Exchange::Coinbase => Some(Box::new(coinbase::CoinbaseDriver::new())),
+        Exchange::Cme => Some(Box::new(cme::CmeDriver::new())),
+        Exchange::Nasdaq => Some(Box::new(itch::ItchDriver::new())),
+        Exchange::Mexc => Some(Box::new(mexc::MexcDriver::new())),
+        Exchange::Custom(name) => CUSTOM_DRIVERS.read().get(name).map(|factory| factory()),
+    }
+}
+
+/// Registers the driver used for `Exchange::Custom(name)` subscriptions.
+///
+/// The driver is a custom adapter: any type implementing [ExchangeDriver],
+/// also exported as [ExchangeAdapter]. `factory` is called once per
+/// subscription, on the connector thread that will own the driver. Registering the same name again replaces the factory
+/// for future subscriptions.
+pub fn register_custom(name: &str, factory: impl Fn() -> Box<dyn ExchangeDriver> + Send + Sync + 'static) {
+    let Exchange::Custom(name) = Exchange::custom(name) else { unreachable!() };
+    CUSTOM_DRIVERS.write().insert(name, Arc::new(factory));
+}
+
+/// Removes the driver registered under `name`; future subscriptions to
+/// `Exchange::Custom(name)` fail to resolve a driver.
+pub fn unregister_custom(name: &str) {
+    CUSTOM_DRIVERS.write().remove(name);
+}
+
+/// Publishes `symbol` on `exchange` at these exponents instead of the
+/// venue's own, e.g. `-8` to line ITCH prices up with the crypto venues.
+///
+/// Honoured by the drivers of integer feeds (ITCH and CME), which rescale
+/// each value with a [NativeScale]; a subscription whose exponents would
+/// drop digits is rejected. Takes effect at the next handshake.
+pub fn set_book_exponents(exchange: Exchange, symbol: &str, price_exponent: i8, qty_exponent: i8) {
+    BOOK_EXPONENTS.write().insert((exchange, symbol.to_string()), (price_exponent, qty_exponent));
+}
+
+/// Returns `symbol` on `exchange` to the venue's own exponents.
+pub fn clear_book_exponents(exchange: Exchange, symbol: &str) {
+    BOOK_EXPONENTS.write().remove(&(exchange, symbol.to_string()));
+}
+
+/// How an integer feed's prices and sizes map onto the book.
+///
+/// Feeds such as ITCH and SBE send values already scaled to an exponent of
+/// their own, so they skip the ASCII parser; this carries them over to the
+/// book's exponents with one [ScaleFactor] each. The factors' inverses turn
+/// book values back into the venue's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NativeScale {
+    pub price: ScaleFactor,
+    pub qty: ScaleFactor,
+}
+
+impl NativeScale {
+    /// Publishes values at the venue's own exponents.
+    pub fn native(price_exponent: i8, qty_exponent: i8) -> Self {
+        Self {
+            price: ScaleFactor::identity(price_exponent),
+            qty: ScaleFactor::identity(qty_exponent),
+        }
+    }
+
+    /// Returns the scale for `key`: from the venue's exponents to those set
+    /// with [set_book_exponents], or the venue's own if none were.
+    pub fn for_key(key: &SymbolKey, price_exponent: i8, qty_exponent: i8) -> Result<Self, DriverError> {
+        let Some((book_price, book_qty)) = BOOK_EXPONENTS.read().get(&(key.exchange, key.symbol.clone())).copied() else {
+            return Ok(Self::native(price_exponent, qty_exponent));
+        };
+        let exact = |from, to| ScaleFactor::new(from, to, Rounding::TowardZero).filter(ScaleFactor::is_exact);
+        match (exact(price_exponent, book_price), exact(qty_exponent, book_qty)) {
+            (Some(price), Some(qty)) => Ok(Self { price, qty }),
+            _ => Err(DriverError::Rejected(format!(
+                "book exponents {book_price}/{book_qty} cannot hold {} values at {price_exponent}/{qty_exponent}",
+                key.symbol
+            ))),
+        }
+    }
+
+    /// Converts a price and size in the venue's exponents into a level.
+    #[inline]
+    pub fn level(&self, price: i64, qty: i64) -> Result<Level, DriverError> {
+        match (self.price.apply(price), self.qty.apply(qty)) {
+            (Some(price), Some(qty)) => Ok(Level { price, qty }),
+            _ => Err(DriverError::Parse(ParseError::Overflow)),
+        }
+    }
+
+    /// Records the book's exponents on `book`.
+    pub fn stamp(&self, book: &mut L1FriendlyBook) {
+        book.price_exponent = self.price.to_exponent();
+        book.qty_exponent = self.qty.to_exponent();
+    }
+}
+
+/// The scales a text feed parses prices and quantities at.
+///
+/// Set per instrument from its [Precision] where one is known, so the book
+/// holds exactly the decimals the venue trades in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scales {
+    pub price: u32,
+    pub qty: u32,
+}
+
+impl Scales {
+    /// Returns the scales of `key`'s instrument, or the venue's defaults if
+    /// its precision is not known.
+    pub fn for_key(key: &SymbolKey, price: u32, qty: u32) -> Self {
+        instruments::get(key).map_or(Self { price, qty }, Self::from)
+    }
+
+    /// Records the scales on `book` as its exponents.
+    pub fn stamp(&self, book: &mut L1FriendlyBook) {
+        book.price_exponent = -(self.price as i8);
+        book.qty_exponent = -(self.qty as i8);
+    }
+}
+
+impl From<Precision> for Scales {
+    fn from(precision: Precision) -> Self {
+        Self {
+            price: precision.price_scale,
+            qty: precision.qty_scale,
+        }
+    }
+}
+
+/// Applies a price level update to one side of the book.
+///
+/// A zero quantity marks the level for removal in the side's `tombstones`
+/// ([bid_tombstones](L1FriendlyBook::bid_tombstones) or
+/// [ask_tombstones](L1FriendlyBook::ask_tombstones)); the slot is
+/// reclaimed by the next [L1FriendlyBook::compact]. Levels that fall
+/// outside the top [BOOK_DEPTH] are discarded, and counted as the book's
+/// [truncated](L1FriendlyBook::truncated) levels by the connector, which
+/// also keeps every update in the book's
+/// [full depth](crate::model::SharedBook::full_depth) if it has one.
+///
+/// `descending` is `true` for bids and `false` for asks.
+pub fn apply_level(side: &mut [Level; BOOK_DEPTH], tombstones: &mut u32, price: i64, qty: i64, descending: bool) {
+    update_level(side, tombstones, None, price, qty, descending);
+}
+
+/// Like [apply_level], for venues that publish how many orders rest at
+/// each level: `orders` is the side's
+/// [bid_orders](L1FriendlyBook::bid_orders) or
+/// [ask_orders](L1FriendlyBook::ask_orders), kept in step with the
+/// levels, and `count` the level's number of orders.
+pub fn apply_counted_level(
+    side: &mut [Level; BOOK_DEPTH],
+    tombstones: &mut u32,
+    orders: &mut [u32; BOOK_DEPTH],
+    price: i64,
+    qty: i64,
+    count: u32,
+    descending: bool,
+) {
+    update_level(side, tombstones, Some((orders, count)), price, qty, descending);
+}
+
+fn update_level(
+    side: &mut [Level; BOOK_DEPTH],
+    tombstones: &mut u32,
+    mut orders: Option<(&mut [u32; BOOK_DEPTH], u32)>,
+    price: i64,
+    qty: i64,
+    descending: bool,
+) {
+    record(descending, price, qty);
+    let mut idx = find_slot(side, price, descending);
+    if idx == BOOK_DEPTH {
+        spill(descending, price, (qty != 0).then_some(qty));
+        return;
+    }
+
+    if side[idx].price == price && side[idx] != Level::default() {
+        if qty == 0 {
+            L1FriendlyBook::mark_removal(tombstones, idx);
+        } else {
+            // Revives the level if it was marked earlier in the packet
+            side[idx].qty = qty;
+            *tombstones &= !(1 << idx);
+            if let Some((orders, count)) = orders {
+                orders[idx] = count;
+            }
+        }
+        return;
+    }
+
+    if qty == 0 {
+        // Removal of a level we are not holding, which may have spilled
+        spill(descending, price, None);
+        return;
+    }
+
+    // Reclaim marked slots before shifting a live level off the end
+    if side[BOOK_DEPTH - 1] != Level::default() && *tombstones != 0 {
+        match &mut orders {
+            Some((orders, _)) => L1FriendlyBook::compact_counted(side, orders, tombstones),
+            None => L1FriendlyBook::compact(side, tombstones),
+        };
+        idx = find_slot(side, price, descending);
+        if idx == BOOK_DEPTH {
+            spill(descending, price, Some(qty));
+            return;
+        }
+    }
+
+    let worst = side[BOOK_DEPTH - 1];
+    if worst != Level::default() && *tombstones & 1 << (BOOK_DEPTH - 1) == 0 {
+        spill(descending, worst.price, Some(worst.qty));
+    }
+    side.copy_within(idx..BOOK_DEPTH - 1, idx + 1);
+    // The marks move with their levels, the last falling off the end
+    let above = !((1u32 << idx) - 1);
+    *tombstones = (*tombstones & !above) | (*tombstones & above) << 1;
+    side[idx] = Level { price, qty };
+    if let Some((orders, count)) = orders {
+        orders.copy_within(idx..BOOK_DEPTH - 1, idx + 1);
+        orders[idx] = count;
+    }
+}
+
+/// Levels the helpers above dropped past [BOOK_DEPTH] on this thread since
+/// the connector last [took](take_overflow) them, and the updates they were
+/// given since it last [took](take_changes) those.
+struct Overflow {
+    /// Number of levels with quantity dropped.
+    count: u64,
+    /// Whether the changes past the book are kept in `tail`.
+    keep: bool,
+    /// Levels dropped, and removals of prices the book does not hold.
+    tail: Vec<LevelChange>,
+    /// Whether every update is kept in `changes`.
+    record: bool,
+    /// Every update the helpers were given, in book or not.
+    changes: Vec<LevelChange>,
+}
+
+thread_local! {
+    static OVERFLOW: RefCell<Overflow> = const {
+        RefCell::new(Overflow {
+            count: 0,
+            keep: false,
+            tail: Vec::new(),
+            record: false,
+            changes: Vec::new(),
+        })
+    };
+}
+
+/// Records a change to a level past the book, `None` for a removal, and
+/// counts the levels it drops as truncated.
+#[cold]
+fn spill(descending: bool, price: i64, qty: Option<i64>) {
+    OVERFLOW.with_borrow_mut(|overflow| {
+        overflow.count += u64::from(qty.is_some());
+        if overflow.keep {
+            let side = if descending { Side::Bid } else { Side::Ask };
+            overflow.tail.push(LevelChange { side, price, qty });
+        }
+    });
+}
+
+/// Makes the helpers on this thread keep the changes past the book, not
+/// only count the levels they drop.
+pub(crate) fn keep_overflow(keep: bool) {
+    OVERFLOW.with_borrow_mut(|overflow| overflow.keep = keep);
+}
+
+/// Returns how many levels the helpers on this thread dropped since the
+/// last call, handing the changes past the book to `on_change` in the
+/// order they were made.
+pub(crate) fn take_overflow(mut on_change: impl FnMut(LevelChange)) -> u64 {
+    OVERFLOW.with_borrow_mut(|overflow| {
+        overflow.tail.drain(..).for_each(&mut on_change);
+        mem::take(&mut overflow.count)
+    })
+}
+
+/// Keeps an update for the connector's full depth book, if it wants them.
+#[inline]
+fn record(descending: bool, price: i64, qty: i64) {
+    OVERFLOW.with_borrow_mut(|overflow| {
+        if overflow.record {
+            let side = if descending { Side::Bid } else { Side::Ask };
+            overflow.changes.push(LevelChange { side, price, qty: (qty != 0).then_some(qty) });
+        }
+    });
+}
+
+/// Makes the helpers on this thread keep every update they are given.
+pub(crate) fn keep_changes(keep: bool) {
+    OVERFLOW.with_borrow_mut(|overflow| overflow.record = keep);
+}
+
+/// Hands the updates kept since the last call to `on_change`, in the order
+/// they were made.
+pub(crate) fn take_changes(on_change: impl FnMut(LevelChange)) {
+    OVERFLOW.with_borrow_mut(|overflow| overflow.changes.drain(..).for_each(on_change));
+}
+
+/// Returns the index of `price`, or of the slot it should be inserted into.
+///
+/// The levels end at the first empty slot; marked levels keep their
+/// place until compacted.
+fn find_slot(side: &[Level; BOOK_DEPTH], price: i64, descending: bool) -> usize {
+    side.iter()
+        .position(|level| {
+            *level == Level::default()
+                || (descending && level.price <= price)
+                || (!descending && level.price >= price)
+        })
+        .unwrap_or(BOOK_DEPTH)
+}
+
+/// Walks a JSON array of `["price","qty"]` pairs starting at `start`.
+///
+/// `start` must point at the opening `[` of the outer array. Prices and
+/// quantities may be strings or bare numbers. Each parsed pair is handed to
+/// `on_level`; any trailing elements after the quantity (e.g. order counts)
+/// are skipped. Returns the index just past the closing `]`.
+pub(crate) fn for_each_level(
+    bytes: &[u8],
+    start: usize,
+    price_scale: u32,
+    qty_scale: u32,
+    mut on_level: impl FnMut(i64, i64),
+) -> Result<usize, DriverError> {
+    let mut idx = expect(bytes, start, b'[')?;
+    if bytes.get(idx) == Some(&b']') {
+        return Ok(idx + 1);
+    }
+
+    loop {
+        idx = expect(bytes, idx, b'[')?;
+        let (price, next) = parse_number(bytes, idx, price_scale, parse_i64_with_precision)?;
+        idx = expect(bytes, next, b',')?;
+        let (qty, next) = parse_number(bytes, idx, qty_scale, unsigned_qty)?;
+        idx = skip_to(bytes, next, b']')? + 1;
+
+        on_level(price, qty);
+
+        match bytes.get(idx) {
+            Some(b',') => idx += 1,
+            Some(b']') => return Ok(idx + 1),
+            _ => return Err(DriverError::Malformed),
+        }
+    }
+}
+
+/// Parses a whole JSON array of `["price","qty"]` pairs starting at `start`
+/// straight into `out`, in the order sent, and returns how many levels were
+/// filled.
+///
+/// Accepts the same pairs as [for_each_level]; pairs beyond `out.len()` are
+/// skipped unparsed, and counted as [truncated](L1FriendlyBook::truncated)
+/// levels but not kept. Meant for snapshots that arrive sorted best first and
+/// replace a side wholesale, where inserting level by level with
+/// [apply_level] is wasted work.
+pub fn parse_levels(
+    bytes: &[u8],
+    start: usize,
+    price_scale: u32,
+    qty_scale: u32,
+    out: &mut [Level],
+) -> Result<usize, DriverError> {
+    let mut idx = expect(bytes, start, b'[')?;
+    if bytes.get(idx) == Some(&b']') {
+        return Ok(0);
+    }
+
+    let mut filled = 0;
+    let mut skipped = 0;
+    loop {
+        idx = expect(bytes, idx, b'[')?;
+        if let Some(slot) = out.get_mut(filled) {
+            let (price, next) = parse_number(bytes, idx, price_scale, parse_i64_with_precision)?;
+            idx = expect(bytes, next, b',')?;
+            let (qty, next) = parse_number(bytes, idx, qty_scale, unsigned_qty)?;
+            *slot = Level { price, qty };
+            filled += 1;
+            idx = next;
+        } else {
+            skipped += 1;
+        }
+        idx = skip_to(bytes, idx, b']')? + 1;
+
+        match bytes.get(idx) {
+            Some(b',') => idx += 1,
+            Some(b']') => break,
+            _ => return Err(DriverError::Malformed),
+        }
+    }
+    if skipped > 0 {
+        OVERFLOW.with_borrow_mut(|overflow| overflow.count += skipped);
+    }
+    Ok(filled)
+}
+
+/// A fixed-point parser such as [parse_i64_with_precision].
+type Parser = fn(&[u8], usize, u32) -> Result<(i64, usize), ParseError>;
+
+/// Parses a quoted or bare JSON number at `idx` with `parse` and returns
+/// the index past it.
+fn parse_number(bytes: &[u8], idx: usize, scale: u32, parse: Parser) -> Result<(i64, usize), DriverError> {
+    let start = idx + (bytes.get(idx) == Some(&b'"')) as usize;
+    let (value, next) = parse(bytes, start, scale).map_err(|err| err.at(bytes, start))?;
+    match start > idx {
+        true => Ok((value, expect(bytes, next, b'"')?)),
+        false => Ok((value, next)),
+    }
+}
+
+/// Parses a signed number at `idx`, locating any failure in `bytes`.
+pub(crate) fn parse_i64(bytes: &[u8], idx: usize, scale: u32) -> Result<(i64, usize), DriverError> {
+    Ok(parse_i64_with_precision(bytes, idx, scale).map_err(|err| err.at(bytes, idx))?)
+}
+
+/// Parses a quantity at `idx` with the unsigned fast path, since venues
+/// never sign them, and returns it as a [Level] quantity.
+pub(crate) fn parse_qty(bytes: &[u8], idx: usize, scale: u32) -> Result<(i64, usize), DriverError> {
+    Ok(unsigned_qty(bytes, idx, scale).map_err(|err| err.at(bytes, idx))?)
+}
+
+fn unsigned_qty(bytes: &[u8], idx: usize, scale: u32) -> Result<(i64, usize), ParseError> {
+    let (qty, next) = parse_u64_with_precision(bytes, idx, scale)?;
+    Ok((i64::try_from(qty).map_err(|_| ParseError::Overflow)?, next))
+}
+
+thread_local! {
+    /// REST agent of the current connector thread, if it needs a proxy.
+    static REST_AGENT: RefCell<Option<Agent>> = const { RefCell::new(None) };
+}
+
+/// Makes the REST helpers on this thread send their requests with `agent`.
+pub(crate) fn set_rest_agent(agent: Agent) {
+    REST_AGENT.set(Some(agent));
+}
+
+/// Returns this thread's REST agent, or a default one.
+fn rest_agent() -> Agent {
+    REST_AGENT
+        .with_borrow(|agent| agent.clone())
+        .unwrap_or_else(Agent::new_with_defaults)
+}
+
+/// Performs a blocking REST `GET` and returns the response body.
+pub(crate) fn rest_get(url: &str) -> Result<String, DriverError> {
+    rest_agent()
+        .get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|err| DriverError::Rest(err.to_string()))
+}
+
+/// Performs a blocking REST `POST` with an empty body and returns the response body.
+pub(crate) fn rest_post(url: &str) -> Result<String, DriverError> {
+    rest_agent()
+        .post(url)
+        .send_empty()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|err| DriverError::Rest(err.to_string()))
+}
+
+/// Performs a blocking REST `POST` with an empty body and one extra header,
+/// such as an API key, and returns the response body.
+pub(crate) fn rest_post_with_header(url: &str, name: &str, value: &str) -> Result<String, DriverError> {
+    rest_agent()
+        .post(url)
+        .header(name, value)
+        .send_empty()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|err| DriverError::Rest(err.to_string()))
+}
+
+/// Performs a blocking REST `PUT` with an empty body and one extra header
+/// and returns the response body.
+pub(crate) fn rest_put_with_header(url: &str, name: &str, value: &str) -> Result<String, DriverError> {
+    rest_agent()
+        .put(url)
+        .header(name, value)
+        .send_empty()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|err| DriverError::Rest(err.to_string()))
+}
+
+/// Performs a blocking REST `POST` with a JSON body and returns the response body.
+pub(crate) fn rest_post_json(url: &str, body: &str) -> Result<String, DriverError> {
+    rest_agent()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .send(body)
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|err| DriverError::Rest(err.to_string()))
+}
+
+/// Returns the string value of the first `"key":"value"` pair in `msg`.
+pub(crate) fn find_str<'a>(msg: &'a [u8], key: &str) -> Option<&'a str> {
+    let pattern = format!(r#""{key}":""#);
+    let start = find(msg, pattern.as_bytes())? + pattern.len();
+    let len = msg[start..].iter().position(|&b| b == b'"')?;
+    std::str::from_utf8(&msg[start..start + len]).ok()
+}
+
+/// Returns the unsigned integer value of the first `"key":123` pair in `msg`.
+///
+/// Quoted integers (`"key":"123"`) are accepted as well.
+pub(crate) fn find_u64(msg: &[u8], key: &str) -> Option<u64> {
+    let pattern = format!(r#""{key}":"#);
+    let mut idx = find(msg, pattern.as_bytes())? + pattern.len();
+    if msg.get(idx) == Some(&b'"') {
+        idx += 1;
+    }
+    let (value, _) = parse_i64_with_precision(msg, idx, 0).ok()?;
+    u64::try_from(value).ok()
+}
+
+/// Returns the index of the first occurrence of `needle` in `haystack`.
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Returns the index of the next `byte` at or after `idx`.
+fn skip_to(bytes: &[u8], idx: usize, byte: u8) -> Result<usize, DriverError> {
+    bytes
+        .get(idx..)
+        .and_then(|rest| rest.iter().position(|&b| b == byte))
+        .map(|offset| idx + offset)
+        .ok_or(DriverError::Malformed)
+}
+
+/// Checks that `bytes[idx] == byte` and returns the following index.
+pub(crate) fn expect(bytes: &[u8], idx: usize, byte: u8) -> Result<usize, DriverError> {
+    match bytes.get(idx) {
+        Some(&b) if b == byte => Ok(idx + 1),
+        _ => Err(DriverError::Malformed),
+    }
+}
+
+/// Checks that `bytes` continues with `token` at `idx` and returns the following index.
+pub(crate) fn expect_token(bytes: &[u8], idx: usize, token: &[u8]) -> Result<usize, DriverError> {
+    match bytes.get(idx..idx + token.len()) {
+        Some(slice) if slice == token => Ok(idx + token.len()),
+        _ => Err(DriverError::Malformed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prices of the levels held and not marked for removal.
+    fn prices(side: &[Level; BOOK_DEPTH], tombstones: u32) -> Vec<i64> {
+        side.iter()
+            .take_while(|l| **l != Level::default())
+            .enumerate()
+            .filter(|&(idx, _)| tombstones & 1 << idx == 0)
+            .map(|(_, l)| l.price)
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_level_keeps_sort_order() {
+        let mut bids = [Level::default(); BOOK_DEPTH];
+        let mut bid_marks = 0;
+        apply_level(&mut bids, &mut bid_marks, 100, 1, true);
+        apply_level(&mut bids, &mut bid_marks, 102, 1, true);
+        apply_level(&mut bids, &mut bid_marks, 101, 1, true);
+        assert_eq!(prices(&bids, bid_marks), vec![102, 101, 100]);
+
+        let mut asks = [Level::default(); BOOK_DEPTH];
+        let mut ask_marks = 0;
+        apply_level(&mut asks, &mut ask_marks, 102, 1, false);
+        apply_level(&mut asks, &mut ask_marks, 100, 1, false);
+        apply_level(&mut asks, &mut ask_marks, 101, 1, false);
+        assert_eq!(prices(&asks, ask_marks), vec![100, 101, 102]);
+    }
+
+    #[test]
+    fn test_apply_level_update_and_removal() {
+        let mut bids = [Level::default(); BOOK_DEPTH];
+        let mut bid_marks = 0;
+        apply_level(&mut bids, &mut bid_marks, 100, 1, true);
+        apply_level(&mut bids, &mut bid_marks, 100, 5, true);
+        assert_eq!(bids[0].qty, 5);
+
+        // Marked rather than zeroed, so the quantity survives until compaction
+        apply_level(&mut bids, &mut bid_marks, 100, 0, true);
+        assert_eq!((bids[0].qty, bid_marks), (5, 1));
+        L1FriendlyBook::compact(&mut bids, &mut bid_marks);
+        assert_eq!((prices(&bids, bid_marks), bid_marks), (Vec::<i64>::new(), 0));
+    }
+
+    #[test]
+    fn test_apply_level_at_zero_price() {
+        let mut asks = [Level::default(); BOOK_DEPTH];
+        let mut ask_marks = 0;
+        for price in [0, -2, 3] {
+            apply_level(&mut asks, &mut ask_marks, price, 1, false);
+        }
+        apply_level(&mut asks, &mut ask_marks, 0, 4, false);
+        assert_eq!(prices(&asks, ask_marks), [-2, 0, 3]);
+        assert_eq!(asks[1].qty, 4);
+
+        // Marked in place, so the level after it is still found
+        apply_level(&mut asks, &mut ask_marks, 0, 0, false);
+        apply_level(&mut asks, &mut ask_marks, 3, 2, false);
+        assert_eq!(prices(&asks, ask_marks), [-2, 3]);
+        assert_eq!((asks[1], asks[2].qty), (Level { price: 0, qty: 4 }, 2));
+    }
+
+    #[test]
+    fn test_apply_level_discards_beyond_depth() {
+        let mut bids = [Level::default(); BOOK_DEPTH];
+        let mut bid_marks = 0;
+        for p in 0..BOOK_DEPTH as i64 {
+            apply_level(&mut bids, &mut bid_marks, 1000 - p, 1, true);
+        }
+        apply_level(&mut bids, &mut bid_marks, 1, 1, true);
+        assert_eq!(bids[BOOK_DEPTH - 1].price, 1000 - (BOOK_DEPTH as i64 - 1));
+
+        // A better level pushes the worst one out
+        apply_level(&mut bids, &mut bid_marks, 2000, 1, true);
+        assert_eq!(bids[0].price, 2000);
+        assert_eq!(bids[BOOK_DEPTH - 1].price, 1000 - (BOOK_DEPTH as i64 - 2));
+    }
+
+    #[test]
+    fn test_apply_level_reclaims_marked_slots() {
+        let mut bids = [Level::default(); BOOK_DEPTH];
+        let mut bid_marks = 0;
+        for p in 0..BOOK_DEPTH as i64 {
+            apply_level(&mut bids, &mut bid_marks, 1000 - p, 1, true);
+        }
+        apply_level(&mut bids, &mut bid_marks, 1000, 0, true);
+        apply_level(&mut bids, &mut bid_marks, 2000, 1, true);
+        assert_eq!(bids[0].price, 2000);
+        assert_eq!(bids[BOOK_DEPTH - 1].price, 1000 - (BOOK_DEPTH as i64 - 1));
+    }
+
+    struct EchoDriver;
+
+    impl ExchangeDriver for EchoDriver {
+        fn endpoint(&self, key: &SymbolKey) -> String {
+            format!("wss://example.invalid/{}", key.symbol)
+        }
+
+        fn subscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+            None
+        }
+
+        fn unsubscribe_msg(&self, _key: &SymbolKey) -> Option<String> {
+            None
+        }
+
+        fn parse_message(&mut self, _msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+            apply_level(&mut book.bids, &mut book.bid_tombstones, 100, 1, true);
+            Ok(true)
+        }
+    }
+
+    fn key(exchange: Exchange, product: ProductType) -> SymbolKey {
+        SymbolKey {
+            exchange,
+            symbol: "ABC".to_string(),
+            product,
+            feed: crate::broker::Feed::Depth,
+        }
+    }
+
+    #[test]
+    fn test_custom_driver_registry() {
+        let key = key(Exchange::Custom("echo"), ProductType::Spot);
+        assert!(driver_for(&key).is_none());
+        register_custom("echo", || Box::new(EchoDriver));
+
+        let mut driver = driver_for(&key).unwrap();
+        assert_eq!(driver.endpoint(&key), "wss://example.invalid/ABC");
+
+        let mut book = L1FriendlyBook::new();
+        assert_eq!(driver.parse_message(b"", &mut book), Ok(true));
+        assert_eq!(book.bids[0], Level { price: 100, qty: 1 });
+
+        unregister_custom("echo");
+        assert!(driver_for(&key).is_none());
+    }
+
+    #[test]
+    fn test_custom_exchange_from_runtime_name() {
+        let name = String::from("crossing-engine");
+        let exchange = Exchange::custom(&name);
+        assert_eq!(exchange, Exchange::Custom("crossing-engine"));
+        assert_eq!(exchange, Exchange::custom("crossing-engine"));
+        assert_ne!(exchange, Exchange::custom("crossing-engine-2"));
+
+        register_custom(&name, || Box::new(EchoDriver));
+        assert!(driver_for(&key(Exchange::custom("crossing-engine"), ProductType::Spot)).is_some());
+        unregister_custom(&name);
+    }
+
+    #[test]
+    fn test_kraken_routes_derivatives_to_futures() {
+        let spot = driver_for(&key(Exchange::Kraken, ProductType::Spot)).unwrap();
+        assert_eq!(spot.endpoint(&key(Exchange::Kraken, ProductType::Spot)), "wss://ws.kraken.com/v2");
+        let perp = key(Exchange::Kraken, ProductType::Perpetual);
+        assert_eq!(driver_for(&perp).unwrap().endpoint(&perp), "wss://futures.kraken.com/ws/v1");
+    }
+
+    #[test]
+    fn test_find_fields() {
+        let msg = br#"{"token":"abc","seq":42,"id":"7","neg":-1}"#;
+        assert_eq!(find_str(msg, "token"), Some("abc"));
+        assert_eq!(find_str(msg, "missing"), None);
+        assert_eq!(find_u64(msg, "seq"), Some(42));
+        assert_eq!(find_u64(msg, "id"), Some(7));
+        assert_eq!(find_u64(msg, "neg"), None);
+    }
+
+    #[test]
+    fn test_for_each_level() {
+        let mut levels = Vec::new();
+        let end = for_each_level(br#"[["1.5","2"],["1.4","0.25"]],"#, 0, 2, 2, |p, q| levels.push((p, q)));
+        assert_eq!(end, Ok(28));
+        assert_eq!(levels, vec![(150, 200), (140, 25)]);
+
+        levels.clear();
+        for_each_level(br#"[["111.06","55154","0","2"]]"#, 0, 2, 0, |p, q| levels.push((p, q))).unwrap();
+        assert_eq!(levels, vec![(11106, 55154)]);
+
+        assert_eq!(for_each_level(b"[]", 0, 2, 2, |_, _| {}), Ok(2));
+        assert_eq!(for_each_level(br#"[["1.5"]]"#, 0, 2, 2, |_, _| {}), Err(DriverError::Malformed));
+        let bad = br#"[["x","1"]]"#;
+        assert_eq!(
+            for_each_level(bad, 0, 2, 2, |_, _| {}),
+            Err(DriverError::ParseAt(ParseError::InvalidFirstChar.at(bad, 3)))
+        );
+    }
+
+    #[test]
+    fn test_parse_levels() {
+        let mut out = [Level::default(); 2];
+        let msg = br#"[["1.5","2"],[1.4,0.25,"3"],["1.3","1"],["bad"]]"#;
+        assert_eq!(parse_levels(msg, 0, 2, 2, &mut out), Ok(2));
+        assert_eq!(out, [Level { price: 150, qty: 200 }, Level { price: 140, qty: 25 }]);
+        // Pairs past the end are skipped unparsed but counted
+        assert_eq!(take_overflow(|_| {}), 2);
+
+        assert_eq!(parse_levels(b"[]", 0, 2, 2, &mut out), Ok(0));
+        assert_eq!(parse_levels(br#"[["1.5","2"]"#, 0, 2, 2, &mut out), Err(DriverError::Malformed));
+        let Err(DriverError::ParseAt(failure)) = parse_levels(br#"[["1.5",,"2"]]"#, 0, 2, 2, &mut out) else {
+            panic!("expected a located parse error");
+        };
+        assert_eq!((failure.error, failure.offset), (ParseError::InvalidFirstChar, 8));
+    }
+
+    #[test]
+    fn test_native_scale() {
+        let key = key(Exchange::Custom("ints"), ProductType::Spot);
+        let native = NativeScale::for_key(&key, -4, 0).unwrap();
+        assert_eq!(native, NativeScale::native(-4, 0));
+        assert_eq!(native.level(1_500_000, 3), Ok(Level { price: 1_500_000, qty: 3 }));
+
+        set_book_exponents(key.exchange, &key.symbol, -8, -2);
+        let scale = NativeScale::for_key(&key, -4, 0).unwrap();
+        assert_eq!(scale.level(1_500_000, 3), Ok(Level { price: 15_000_000_000, qty: 300 }));
+        assert_eq!(scale.level(i64::MAX, 1), Err(DriverError::Parse(ParseError::Overflow)));
+        assert_eq!(scale.price.inverse().unwrap().apply(15_000_000_000), Some(1_500_000));
+
+        let mut book = L1FriendlyBook::new();
+        scale.stamp(&mut book);
+        assert_eq!((book.price_exponent, book.qty_exponent), (-8, -2));
+
+        // Coarser than the venue would drop digits
+        assert!(matches!(NativeScale::for_key(&key, -9, 0), Err(DriverError::Rejected(_))));
+        clear_book_exponents(key.exchange, &key.symbol);
+        assert_eq!(NativeScale::for_key(&key, -9, 0), Ok(NativeScale::native(-9, 0)));
+    }
+}
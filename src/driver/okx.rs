@@ -0,0 +1,591 @@
+//! OKX v5 public order book channels (`books5`, `books-l2-tbt`) and private
+//! account channels (`orders`, `account`, `positions`).
+
+use crate::broker::instruments::Precision;
+use crate::broker::{Feed, PrivateChannel, ProductType, SymbolKey};
+use crate::connector::auth::{self, Credentials};
+use crate::connector::rate_limit::RateLimit;
+use crate::driver::schema::{self, okx::Event};
+use crate::driver::{DriverError, ExchangeDriver, Scales, apply_level, find, find_u64, for_each_level, parse_i64, parse_levels, rest_get};
+use crate::model::{L1FriendlyBook, UpdateCause};
+use crate::util::{MAX_FORMATTED_LEN, format_i64_with_precision};
+use flate2::Crc;
+use parking_lot::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const WS_URL: &str = "wss://ws.okx.com:8443/ws/v5/public";
+const PRIVATE_WS_URL: &str = "wss://ws.okx.com:8443/ws/v5/private";
+const INSTRUMENTS_URL: &str = "https://www.okx.com/api/v5/public/instruments";
+
+/// Fixed-point scale applied to OKX prices, unless the instrument's `tickSz`
+/// is known.
+pub const PRICE_SCALE: u32 = 8;
+
+/// Fixed-point scale applied to OKX quantities, unless the instrument's
+/// `lotSz` is known.
+pub const QTY_SCALE: u32 = 8;
+
+/// The OKX book channel to subscribe to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OkxChannel {
+    /// Full 5-level snapshot on every push.
+    Books5,
+    /// Tick-by-tick 400-level snapshot followed by incremental updates.
+    BooksL2Tbt,
+}
+
+impl OkxChannel {
+    fn name(self) -> &'static str {
+        match self {
+            OkxChannel::Books5 => "books5",
+            OkxChannel::BooksL2Tbt => "books-l2-tbt",
+        }
+    }
+}
+
+/// Levels per side covered by the OKX book checksum.
+const CHECKSUM_LEVELS: usize = 25;
+
+/// Action new drivers take when a book fails its checksum.
+static CHECKSUM_ACTION: RwLock<ChecksumAction> = RwLock::new(ChecksumAction::Resync);
+
+/// What the driver does when the book disagrees with the venue's checksum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumAction {
+    /// Fail with [DriverError::ChecksumMismatch] so the connector rebuilds the book.
+    Resync,
+    /// Report the mismatch on the connector's
+    /// [status](crate::connector::ExchangeConnector::status) channel and keep
+    /// the book as it is.
+    Log,
+    /// Panic in debug builds to surface the bug at its source; resync in release builds.
+    PanicInDebug,
+}
+
+/// Sets the [ChecksumAction] of OKX drivers created from now on.
+pub fn set_checksum_action(action: ChecksumAction) {
+    *CHECKSUM_ACTION.write() = action;
+}
+
+/// Driver for the OKX public book channels.
+///
+/// Both channels are available on the public endpoint without logging in.
+///
+/// Pushes that carry a `checksum` (`books-l2-tbt`) are checked against the
+/// book once applied, and a mismatch is handled according to the
+/// [ChecksumAction] set with [set_checksum_action]. The checksum covers 25
+/// levels of the venue's 400-level book, so a burst of deletions that
+/// exposes levels beyond [BOOK_DEPTH](crate::model::BOOK_DEPTH) is reported as a mismatch as well.
+/// Their updates also link to the last push by `prevSeqId`; one that does
+/// not follow on from the last `seqId` is reported as
+/// [DriverError::SequenceGap].
+///
+/// [Feed::Private] keys connect to the private endpoint and log in with the
+/// exchange's credentials, which must carry the key's passphrase. The
+/// channel is subscribed once the login is accepted; a failed login
+/// reconnects and signs a fresh one.
+pub struct OkxDriver {
+    channel: OkxChannel,
+    checksum_action: ChecksumAction,
+    scales: Scales,
+    /// The login frame of a private stream, sent with the connection.
+    login: Option<String>,
+    /// The private channel's subscribe frame, sent once logged in.
+    subscribe: Option<String>,
+    reply: Option<String>,
+    /// A mismatch logged but not yet reported, see
+    /// [ExchangeDriver::take_mismatch].
+    mismatch: Option<(u32, u32)>,
+    /// `seqId` of the last push, which the next update's `prevSeqId` must match.
+    seq: Option<u64>,
+}
+
+impl OkxDriver {
+    pub fn new() -> Self {
+        Self::with_channel(OkxChannel::Books5)
+    }
+
+    pub fn with_channel(channel: OkxChannel) -> Self {
+        Self {
+            channel,
+            checksum_action: *CHECKSUM_ACTION.read(),
+            scales: Scales { price: PRICE_SCALE, qty: QTY_SCALE },
+            login: None,
+            subscribe: None,
+            reply: None,
+            mismatch: None,
+            seq: None,
+        }
+    }
+
+    /// Compares the push's `checksum`, if it has one, with the book as it now stands.
+    fn verify_checksum(&mut self, msg: &[u8], book: &L1FriendlyBook) -> Result<(), DriverError> {
+        let Some(idx) = find(msg, br#""checksum":"#) else {
+            return Ok(());
+        };
+        let (expected, _) = parse_i64(msg, idx + 11, 0)?;
+        let computed = checksum(book, self.scales) as i32;
+        if i64::from(computed) == expected {
+            return Ok(());
+        }
+
+        let mismatch = DriverError::ChecksumMismatch {
+            expected: expected as u32,
+            computed: computed as u32,
+        };
+        match self.checksum_action {
+            ChecksumAction::Resync => Err(mismatch),
+            ChecksumAction::Log => {
+                self.mismatch = Some((expected as u32, computed as u32));
+                Ok(())
+            }
+            ChecksumAction::PanicInDebug => {
+                debug_assert!(false, "okx: book checksum mismatch: expected {expected}, computed {computed}");
+                Err(mismatch)
+            }
+        }
+    }
+
+    /// Returns whether the push replaces the whole book: every `books5`
+    /// push does, full-depth channels send one `snapshot` then updates.
+    fn is_snapshot(&self, msg: &[u8]) -> bool {
+        self.channel == OkxChannel::Books5 || find(msg, br#""action":"snapshot""#).is_some()
+    }
+
+    fn request(&self, op: &str, key: &SymbolKey) -> String {
+        format!(
+            r#"{{"op":"{op}","args":[{{"channel":"{}","instId":"{}"}}]}}"#,
+            self.channel.name(),
+            inst_id(key)
+        )
+    }
+}
+
+impl Default for OkxDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the OKX CRC32 over the top 25 levels, interleaved as
+/// `bid:qty:ask:qty:...`.
+///
+/// OKX checksums the strings it sent, which carry no trailing zeros, so each
+/// value is printed back with its fractional part trimmed. Levels marked for
+/// removal are skipped, so the book need not be compacted first.
+fn checksum(book: &L1FriendlyBook, scales: Scales) -> u32 {
+    let mut bids = L1FriendlyBook::live_levels(&book.bids, book.bid_tombstones);
+    let mut asks = L1FriendlyBook::live_levels(&book.asks, book.ask_tombstones);
+    let mut crc = Crc::new();
+    let mut first = true;
+    for _ in 0..CHECKSUM_LEVELS {
+        for level in [bids.next(), asks.next()].into_iter().flatten() {
+            if !first {
+                crc.update(b":");
+            }
+            first = false;
+            update_decimal(&mut crc, level.price, scales.price);
+            crc.update(b":");
+            update_decimal(&mut crc, level.qty, scales.qty);
+        }
+    }
+    crc.sum()
+}
+
+/// Feeds a fixed-point `value` into `crc` as a minimal decimal string.
+fn update_decimal(crc: &mut Crc, value: i64, scale: u32) {
+    let mut buf = [0u8; MAX_FORMATTED_LEN];
+    let len = format_i64_with_precision(value, scale, &mut buf);
+    crc.update(&buf[..len]);
+}
+
+/// Maps a [SymbolKey] onto an OKX instrument id.
+///
+/// Spot keys use the pair as-is (`BTC-USDT`), perpetuals gain the `-SWAP`
+/// suffix (`BTC-USDT-SWAP`). Dated futures and options already carry their
+/// expiry in the symbol and are passed through.
+pub fn inst_id(key: &SymbolKey) -> String {
+    let pair = key.symbol.replace(['/', '_'], "-").to_ascii_uppercase();
+    match key.product {
+        ProductType::Perpetual if !pair.ends_with("-SWAP") => format!("{pair}-SWAP"),
+        _ => pair,
+    }
+}
+
+/// Reads the `tickSz` and `lotSz` of an instrument off its `instruments`
+/// response; `data` is empty for unknown instruments.
+///
+/// ```json
+/// {"code":"0","msg":"","data":[{"instType":"SPOT","instId":"BTC-USDT","tickSz":"0.1","lotSz":"0.00000001","minSz":"0.00001"}]}
+/// ```
+fn precision(body: &[u8]) -> Result<Option<Precision>, DriverError> {
+    let response: schema::okx::Instruments = schema::decode(body)?;
+    match response.data.first() {
+        Some(inst) => Precision::from_sizes(inst.tick_sz.as_bytes(), inst.lot_sz.as_bytes())
+            .map(Some)
+            .ok_or(DriverError::Malformed),
+        None => Ok(None),
+    }
+}
+
+/// Maps a product onto the `instType` of public and private requests.
+fn inst_type(product: ProductType) -> &'static str {
+    match product {
+        ProductType::Spot => "SPOT",
+        ProductType::Perpetual => "SWAP",
+        ProductType::Future => "FUTURES",
+        ProductType::VanillaOption => "OPTION",
+    }
+}
+
+/// Builds a private channel request; an empty symbol covers every
+/// instrument.
+fn private_request(op: &str, channel: PrivateChannel, key: &SymbolKey) -> String {
+    let name = match channel {
+        PrivateChannel::Orders => "orders",
+        PrivateChannel::Balances => "account",
+        PrivateChannel::Positions => "positions",
+    };
+    let arg = match channel {
+        PrivateChannel::Balances => format!(r#""channel":"{name}""#),
+        _ if key.symbol.is_empty() => format!(r#""channel":"{name}","instType":"ANY""#),
+        _ => format!(
+            r#""channel":"{name}","instType":"{}","instId":"{}""#,
+            inst_type(key.product),
+            inst_id(key)
+        ),
+    };
+    format!(r#"{{"op":"{op}","args":[{{{arg}}}]}}"#)
+}
+
+/// Builds a login request signed at `timestamp`, in seconds.
+fn login_msg(credentials: &Credentials, timestamp: u64) -> Result<String, DriverError> {
+    let passphrase = credentials
+        .passphrase
+        .as_deref()
+        .ok_or_else(|| DriverError::Rejected("OKX keys need a passphrase".to_string()))?;
+    let sign = auth::base64(&credentials.sign(&format!("{timestamp}GET/users/self/verify")));
+    Ok(format!(
+        r#"{{"op":"login","args":[{{"apiKey":"{}","passphrase":"{passphrase}","timestamp":"{timestamp}","sign":"{sign}"}}]}}"#,
+        credentials.api_key
+    ))
+}
+
+impl ExchangeDriver for OkxDriver {
+    /// Signs a private key's login; logins are only valid for 30 seconds.
+    fn handshake(&mut self, key: &SymbolKey) -> Result<(), DriverError> {
+        self.login = None;
+        self.subscribe = None;
+        self.reply = None;
+        self.seq = None;
+        self.scales = Scales::for_key(key, PRICE_SCALE, QTY_SCALE);
+        if let Feed::Private(channel) = key.feed {
+            let credentials = auth::credentials_for(key.exchange)
+                .ok_or_else(|| DriverError::Rejected("no credentials".to_string()))?;
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            self.login = Some(login_msg(&credentials, timestamp)?);
+            self.subscribe = Some(private_request("subscribe", channel, key));
+        }
+        Ok(())
+    }
+
+    fn fetch_precision(&self, key: &SymbolKey) -> Result<Option<Precision>, DriverError> {
+        let url = format!("{INSTRUMENTS_URL}?instType={}&instId={}", inst_type(key.product), inst_id(key));
+        precision(rest_get(&url)?.as_bytes())
+    }
+
+    fn endpoint(&self, key: &SymbolKey) -> String {
+        match key.feed {
+            Feed::Private(_) => PRIVATE_WS_URL.to_string(),
+            _ => WS_URL.to_string(),
+        }
+    }
+
+    /// Full-depth pushes compress several-fold.
+    fn permessage_deflate(&self) -> bool {
+        true
+    }
+
+    fn subscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        match key.feed {
+            Feed::Private(_) => self.login.clone(),
+            _ => Some(self.request("subscribe", key)),
+        }
+    }
+
+    fn unsubscribe_msg(&self, key: &SymbolKey) -> Option<String> {
+        match key.feed {
+            Feed::Private(channel) => Some(private_request("unsubscribe", channel, key)),
+            _ => Some(self.request("unsubscribe", key)),
+        }
+    }
+
+    /// OKX accepts three connection requests a second per IP.
+    fn rate_limit(&self) -> Option<RateLimit> {
+        Some(RateLimit { per_second: 3, burst: 3 })
+    }
+
+    /// Reads the push's `ts`, in milliseconds.
+    fn exchange_ts(&self, msg: &[u8]) -> Option<u64> {
+        find_u64(msg, "ts")?.checked_mul(1_000_000)
+    }
+
+    fn update_cause(&self, msg: &[u8]) -> UpdateCause {
+        if self.is_snapshot(msg) { UpdateCause::Snapshot } else { UpdateCause::Delta }
+    }
+
+    fn take_mismatch(&mut self) -> Option<(u32, u32)> {
+        self.mismatch.take()
+    }
+
+    /// Applies a book push.
+    ///
+    /// ```json
+    /// {"arg":{"channel":"books5","instId":"BTC-USDT"},"data":[{"asks":[["8476.98","415","0","13"]],"bids":[["8476.97","256","0","12"]],"ts":"1597026383085"}]}
+    /// ```
+    fn parse_message(&mut self, msg: &[u8], book: &mut L1FriendlyBook) -> Result<bool, DriverError> {
+        // Subscribe acks and errors carry an "event" and no "data"
+        if find(msg, br#""data":"#).is_none() {
+            return match schema::decode(msg)? {
+                Event::Error(error) => Err(DriverError::Rejected(error.msg.into_owned())),
+                _ => Ok(false),
+            };
+        }
+
+        self.scales.stamp(book);
+        let snapshot = self.is_snapshot(msg);
+        if let Some(seq) = find_u64(msg, "seqId") {
+            if !snapshot
+                && let Some(last) = self.seq
+            {
+                let previous = find_u64(msg, "prevSeqId").ok_or(DriverError::Malformed)?;
+                if previous != last {
+                    return Err(DriverError::SequenceGap {
+                        expected: last,
+                        received: previous,
+                    });
+                }
+            }
+            self.seq = Some(seq);
+        }
+        let asks = find(msg, br#""asks":"#).ok_or(DriverError::Malformed)? + 7;
+        let bids = find(msg, br#""bids":"#).ok_or(DriverError::Malformed)? + 7;
+        if snapshot {
+            // Snapshots come sorted best first, so they fill the sides in order
+            book.clear_sides();
+            parse_levels(msg, asks, self.scales.price, self.scales.qty, &mut book.asks)?;
+            parse_levels(msg, bids, self.scales.price, self.scales.qty, &mut book.bids)?;
+        } else {
+            for_each_level(msg, asks, self.scales.price, self.scales.qty, |price, qty| {
+                apply_level(&mut book.asks, &mut book.ask_tombstones, price, qty, false);
+            })?;
+            for_each_level(msg, bids, self.scales.price, self.scales.qty, |price, qty| {
+                apply_level(&mut book.bids, &mut book.bid_tombstones, price, qty, true);
+            })?;
+        }
+
+        self.verify_checksum(msg, book)?;
+        Ok(true)
+    }
+
+    fn private_streams(&self) -> bool {
+        true
+    }
+
+    /// Subscribes once the login is accepted, then reports every push.
+    ///
+    /// ```json
+    /// {"event":"login","code":"0","msg":"","connId":"a4d3ae55"}
+    /// {"arg":{"channel":"orders","instType":"ANY","uid":"77982378738415879"},"data":[{"instId":"BTC-USDT","ordId":"312269865356374016","state":"live"}]}
+    /// ```
+    fn parse_private(&mut self, msg: &[u8]) -> Result<bool, DriverError> {
+        if find(msg, br#""data":"#).is_some() {
+            return Ok(true);
+        }
+        match schema::decode(msg)? {
+            Event::Login => self.reply = self.subscribe.clone(),
+            Event::Error(error) => return Err(DriverError::Rejected(error.msg.into_owned())),
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    fn pending_reply(&mut self) -> Option<String> {
+        self.reply.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Exchange, Feed};
+    use crate::model::Level;
+
+    fn key(symbol: &str, product: ProductType) -> SymbolKey {
+        SymbolKey {
+            exchange: Exchange::Okx,
+            symbol: symbol.to_string(),
+            product,
+            feed: Feed::Depth,
+        }
+    }
+
+    #[test]
+    fn test_inst_id_mapping() {
+        assert_eq!(inst_id(&key("btc-usdt", ProductType::Spot)), "BTC-USDT");
+        assert_eq!(inst_id(&key("BTC-USDT", ProductType::Perpetual)), "BTC-USDT-SWAP");
+        assert_eq!(inst_id(&key("BTC-USDT-SWAP", ProductType::Perpetual)), "BTC-USDT-SWAP");
+        assert_eq!(inst_id(&key("BTC-USD-240628", ProductType::Future)), "BTC-USD-240628");
+    }
+
+    #[test]
+    fn test_subscribe_msg() {
+        let driver = OkxDriver::with_channel(OkxChannel::BooksL2Tbt);
+        assert_eq!(
+            driver.subscribe_msg(&key("BTC-USDT", ProductType::Perpetual)).unwrap(),
+            r#"{"op":"subscribe","args":[{"channel":"books-l2-tbt","instId":"BTC-USDT-SWAP"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_books5_replaces_book() {
+        let mut driver = OkxDriver::new();
+        let mut book = L1FriendlyBook::new();
+        book.bids[0] = Level { price: 1, qty: 1 };
+
+        let msg = br#"{"arg":{"channel":"books5","instId":"BTC-USDT"},"data":[{"asks":[["8476.98","415","0","13"]],"bids":[["8476.97","256","0","12"],["8475.55","101","0","1"]],"instId":"BTC-USDT","ts":"1597026383085"}]}"#;
+        assert_eq!(driver.parse_message(msg, &mut book), Ok(true));
+        assert_eq!(book.asks[0], Level { price: 847_698_000_000, qty: 41_500_000_000 });
+        assert_eq!(book.bids[0].price, 847_697_000_000);
+        assert_eq!(book.bids[1].price, 847_555_000_000);
+        assert_eq!(book.bids[2].price, 0);
+        assert_eq!(driver.exchange_ts(msg), Some(1_597_026_383_085_000_000));
+    }
+
+    #[test]
+    fn test_tbt_update_is_incremental() {
+        let mut driver = OkxDriver::with_channel(OkxChannel::BooksL2Tbt);
+        let mut book = L1FriendlyBook::new();
+
+        let snapshot = br#"{"arg":{"channel":"books-l2-tbt","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["10","1","0","1"]],"bids":[["9","1","0","1"]],"ts":"1","seqId":1}]}"#;
+        let update = br#"{"arg":{"channel":"books-l2-tbt","instId":"BTC-USDT"},"action":"update","data":[{"asks":[],"bids":[["8","2","0","1"]],"ts":"2","prevSeqId":1,"seqId":2}]}"#;
+        assert_eq!(driver.parse_message(snapshot, &mut book), Ok(true));
+        assert_eq!(driver.parse_message(update, &mut book), Ok(true));
+        assert_eq!(book.bids[0].price, 900_000_000);
+        assert_eq!(book.bids[1].price, 800_000_000);
+        assert_eq!(book.asks[0].price, 1_000_000_000);
+
+        // Updates link to the last one by `prevSeqId`
+        let gap = br#"{"arg":{"channel":"books-l2-tbt","instId":"BTC-USDT"},"action":"update","data":[{"asks":[],"bids":[],"ts":"3","prevSeqId":3,"seqId":4}]}"#;
+        assert_eq!(driver.parse_message(gap, &mut book), Err(DriverError::SequenceGap { expected: 2, received: 3 }));
+        assert_eq!(driver.parse_message(snapshot, &mut book), Ok(true));
+        assert_eq!(driver.parse_message(update, &mut book), Ok(true));
+    }
+
+    fn crc(text: &str) -> i32 {
+        let mut crc = Crc::new();
+        crc.update(text.as_bytes());
+        crc.sum() as i32
+    }
+
+    #[test]
+    fn test_checksum_matches_venue_strings() {
+        let mut book = L1FriendlyBook::new();
+        book.bids[0] = Level { price: 336_610_000_000, qty: 700_000_000 };
+        book.bids[1] = Level { price: 336_600_000_000, qty: 600_000_000 };
+        book.asks[0] = Level { price: 336_680_000_000, qty: 9_000_000 };
+        book.asks[1] = Level { price: 336_800_000_000, qty: 800_000_000 };
+        book.asks[2] = Level { price: 336_900_000_000, qty: 100_000_000 };
+        L1FriendlyBook::mark_removal(&mut book.ask_tombstones, 2);
+        let scales = Scales { price: PRICE_SCALE, qty: QTY_SCALE };
+        assert_eq!(checksum(&book, scales) as i32, crc("3366.1:7:3366.8:0.09:3366:6:3368:8"));
+
+        // Books at the instrument's own scales print the same strings
+        let scales = Scales { price: 1, qty: 2 };
+        for level in book.bids.iter_mut().chain(book.asks.iter_mut()) {
+            *level = Level { price: level.price / 10_000_000, qty: level.qty / 1_000_000 };
+        }
+        assert_eq!(checksum(&book, scales) as i32, crc("3366.1:7:3366.8:0.09:3366:6:3368:8"));
+    }
+
+    #[test]
+    fn test_precision() {
+        let body = br#"{"code":"0","msg":"","data":[{"instType":"SWAP","instId":"BTC-USDT-SWAP","tickSz":"0.1","lotSz":"0.01","ctVal":"0.01"}]}"#;
+        assert_eq!(precision(body), Ok(Precision::from_decimals(1, 2)));
+        assert_eq!(precision(br#"{"code":"51001","msg":"Instrument ID does not exist","data":[]}"#), Ok(None));
+        assert_eq!(precision(br#"{"code":"0","msg":"","data":[{"tickSz":"0","lotSz":"1"}]}"#), Err(DriverError::Malformed));
+    }
+
+    #[test]
+    fn test_checksum_mismatch_resyncs() {
+        let mut driver = OkxDriver::with_channel(OkxChannel::BooksL2Tbt);
+        let mut book = L1FriendlyBook::new();
+
+        let expected = crc("9:1:10:1");
+        let snapshot = format!(
+            r#"{{"arg":{{"channel":"books-l2-tbt","instId":"BTC-USDT"}},"action":"snapshot","data":[{{"asks":[["10","1","0","1"]],"bids":[["9","1","0","1"]],"ts":"1","checksum":{expected},"seqId":1}}]}}"#
+        );
+        assert_eq!(driver.parse_message(snapshot.as_bytes(), &mut book), Ok(true));
+
+        let update = br#"{"arg":{"channel":"books-l2-tbt","instId":"BTC-USDT"},"action":"update","data":[{"asks":[],"bids":[["8","2","0","1"]],"ts":"2","checksum":-5,"prevSeqId":1,"seqId":2}]}"#;
+        assert_eq!(
+            driver.parse_message(update, &mut book),
+            Err(DriverError::ChecksumMismatch {
+                expected: -5i32 as u32,
+                computed: crc("9:1:10:1:8:2") as u32,
+            })
+        );
+
+        driver.checksum_action = ChecksumAction::Log;
+        let update = br#"{"arg":{"channel":"books-l2-tbt","instId":"BTC-USDT"},"action":"update","data":[{"asks":[],"bids":[["8","2","0","1"]],"ts":"3","checksum":-5,"prevSeqId":2,"seqId":3}]}"#;
+        assert_eq!(driver.parse_message(update, &mut book), Ok(true));
+        assert_eq!(driver.take_mismatch(), Some((-5i32 as u32, crc("9:1:10:1:8:2") as u32)));
+        assert_eq!(driver.take_mismatch(), None);
+    }
+
+    #[test]
+    fn test_private_login() {
+        let credentials = Credentials::new("985d5b66-57ce-40fb-b714-afc0b9787083", "secret").with_passphrase("pass");
+        let login = login_msg(&credentials, 1538054050).unwrap();
+        let sign = auth::base64(&credentials.sign("1538054050GET/users/self/verify"));
+        assert_eq!(
+            login,
+            format!(
+                r#"{{"op":"login","args":[{{"apiKey":"985d5b66-57ce-40fb-b714-afc0b9787083","passphrase":"pass","timestamp":"1538054050","sign":"{sign}"}}]}}"#
+            )
+        );
+        assert!(login_msg(&Credentials::new("key", "secret"), 0).is_err());
+
+        let orders = SymbolKey {
+            feed: Feed::Private(PrivateChannel::Orders),
+            ..key("", ProductType::Spot)
+        };
+        let mut driver = OkxDriver::new();
+        driver.subscribe = Some(private_request("subscribe", PrivateChannel::Orders, &orders));
+        assert_eq!(driver.endpoint(&orders), PRIVATE_WS_URL);
+        assert_eq!(driver.pending_reply(), None);
+        assert_eq!(driver.parse_private(br#"{"event":"login","code":"0","msg":"","connId":"a4d3ae55"}"#), Ok(false));
+        assert_eq!(
+            driver.pending_reply().unwrap(),
+            r#"{"op":"subscribe","args":[{"channel":"orders","instType":"ANY"}]}"#
+        );
+
+        let push = br#"{"arg":{"channel":"orders","instType":"ANY"},"data":[{"instId":"BTC-USDT","state":"live"}]}"#;
+        assert_eq!(driver.parse_private(push), Ok(true));
+        assert!(driver.parse_private(br#"{"event":"error","code":"60009","msg":"Login failed."}"#).is_err());
+        assert_eq!(
+            private_request("unsubscribe", PrivateChannel::Positions, &key("BTC-USDT", ProductType::Perpetual)),
+            r#"{"op":"unsubscribe","args":[{"channel":"positions","instType":"SWAP","instId":"BTC-USDT-SWAP"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_ignores_subscribe_ack() {
+        let mut book = L1FriendlyBook::new();
+        let ack = br#"{"event":"subscribe","arg":{"channel":"books5","instId":"BTC-USDT"},"connId":"a4d3ae55"}"#;
+        assert_eq!(OkxDriver::new().parse_message(ack, &mut book), Ok(false));
+
+        let error = br#"{"event":"error","code":"60018","msg":"Wrong URL or channel:books5,instId:FOO-USDT doesn't exist.","connId":"a4d3ae55"}"#;
+        assert!(matches!(OkxDriver::new().parse_message(error, &mut book), Err(DriverError::Rejected(_))));
+    }
+}
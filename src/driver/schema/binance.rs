@@ -0,0 +1,131 @@
+//! Binance spot and USDⓈ-M futures messages.
+
+use super::{Decimal, Level};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A `depthUpdate` event.
+///
+/// ```json
+/// {"e":"depthUpdate","E":1672515782136,"s":"BNBBTC","U":157,"u":160,"b":[["0.0024","10"]],"a":[["0.0026","100"]]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s", borrow)]
+    pub symbol: Cow<'a, str>,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    /// Final update id of the previous event; futures only.
+    #[serde(rename = "pu")]
+    pub previous_update_id: Option<u64>,
+    #[serde(rename = "b", borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(rename = "a", borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+/// A REST depth snapshot.
+///
+/// ```json
+/// {"lastUpdateId":1027024,"bids":[["4.00000000","431.00000000"]],"asks":[["4.00000200","12.00000000"]]}
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot<'a> {
+    pub last_update_id: u64,
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+/// The reply to a `SUBSCRIBE` or `UNSUBSCRIBE` request.
+///
+/// ```json
+/// {"result":null,"id":1}
+/// {"error":{"code":2,"msg":"Invalid request: unknown variant"},"id":1}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    pub id: Option<u64>,
+    /// Set when the request was rejected.
+    #[serde(borrow)]
+    pub error: Option<Error<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    pub code: i64,
+    #[serde(borrow)]
+    pub msg: Cow<'a, str>,
+}
+
+/// The reply to a listen key request.
+///
+/// ```json
+/// {"listenKey":"pqia91ma19a5s61cv6a81va65sdf19v8a65a1a5s61cv6a81va65sdf19v8a65a1"}
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenKey<'a> {
+    #[serde(borrow)]
+    pub listen_key: Cow<'a, str>,
+}
+
+/// An `exchangeInfo` response, down to each symbol's filters.
+///
+/// ```json
+/// {"timezone":"UTC","symbols":[{"symbol":"BTCUSDT","status":"TRADING","filters":[{"filterType":"PRICE_FILTER","minPrice":"0.01000000","maxPrice":"1000000.00000000","tickSize":"0.01000000"},{"filterType":"LOT_SIZE","minQty":"0.00001000","maxQty":"9000.00000000","stepSize":"0.00001000"}]}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct ExchangeInfo<'a> {
+    #[serde(borrow)]
+    pub symbols: Vec<SymbolInfo<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SymbolInfo<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    #[serde(borrow)]
+    pub filters: Vec<Filter<'a>>,
+}
+
+/// One of a symbol's trading rules; only the sizes are kept.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter<'a> {
+    #[serde(borrow)]
+    pub filter_type: Cow<'a, str>,
+    /// Set on `PRICE_FILTER`.
+    #[serde(borrow)]
+    pub tick_size: Option<Cow<'a, str>>,
+    /// Set on `LOT_SIZE` and `MARKET_LOT_SIZE`.
+    #[serde(borrow)]
+    pub step_size: Option<Cow<'a, str>>,
+}
+
+/// A `bookTicker` event.
+///
+/// ```json
+/// {"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct BookTicker<'a> {
+    #[serde(rename = "u")]
+    pub update_id: u64,
+    #[serde(rename = "s", borrow)]
+    pub symbol: Cow<'a, str>,
+    #[serde(rename = "b", borrow)]
+    pub bid_price: Decimal<'a>,
+    #[serde(rename = "B", borrow)]
+    pub bid_qty: Decimal<'a>,
+    #[serde(rename = "a", borrow)]
+    pub ask_price: Decimal<'a>,
+    #[serde(rename = "A", borrow)]
+    pub ask_qty: Decimal<'a>,
+}
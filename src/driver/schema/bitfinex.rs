@@ -0,0 +1,63 @@
+//! Bitfinex v2 `book` channel messages.
+//!
+//! Data frames are arrays led by the channel id; control frames are objects
+//! told apart by their `event`.
+
+use super::Decimal;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// One `[price, count, amount]` entry; a negative amount is an ask and a
+/// zero count removes the price.
+#[derive(Debug, Deserialize)]
+pub struct Entry<'a>(#[serde(borrow)] pub Decimal<'a>, pub u32, #[serde(borrow)] pub Decimal<'a>);
+
+/// A single-entry update.
+///
+/// ```json
+/// [17082,[7254.7,0,1]]
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a>(pub u64, #[serde(borrow)] pub Entry<'a>);
+
+/// The book sent after subscribing.
+///
+/// ```json
+/// [17082,[[7254.7,3,3.3],[7255.1,1,-0.5]]]
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a>(pub u64, #[serde(borrow)] pub Vec<Entry<'a>>);
+
+/// An event frame.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'a> {
+    Subscribed(#[serde(borrow)] SubscribeAck<'a>),
+    Error(#[serde(borrow)] Error<'a>),
+    /// `info`, `unsubscribed` and anything newer.
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"event":"subscribed","channel":"book","chanId":17082,"symbol":"tBTCUSD","prec":"P0","freq":"F0","len":"25","pair":"BTCUSD"}
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+    pub chan_id: u64,
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+}
+
+/// ```json
+/// {"event":"error","msg":"symbol: invalid","code":10300}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub msg: Cow<'a, str>,
+    pub code: i64,
+}
@@ -0,0 +1,72 @@
+//! BitMEX `orderBookL2` messages.
+
+use super::Decimal;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A `partial`, `insert`, `update` or `delete` action.
+///
+/// ```json
+/// {"table":"orderBookL2","action":"update","data":[{"symbol":"XBTUSD","id":8799000000,"side":"Sell","size":50}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub table: Cow<'a, str>,
+    #[serde(borrow)]
+    pub action: Cow<'a, str>,
+    #[serde(borrow)]
+    pub data: Vec<Row<'a>>,
+}
+
+/// The `partial` action that starts the book.
+pub type Snapshot<'a> = DepthUpdate<'a>;
+
+/// One price level, keyed by `id`; `delete` rows carry only the key.
+#[derive(Debug, Deserialize)]
+pub struct Row<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    pub id: u64,
+    #[serde(borrow)]
+    pub side: Cow<'a, str>,
+    pub size: Option<u64>,
+    #[serde(borrow)]
+    pub price: Option<Decimal<'a>>,
+}
+
+/// ```json
+/// {"success":true,"subscribe":"orderBookL2:XBTUSD","request":{"op":"subscribe","args":["orderBookL2:XBTUSD"]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    pub success: bool,
+    #[serde(borrow)]
+    pub subscribe: Cow<'a, str>,
+}
+
+/// ```json
+/// {"status":400,"error":"Unknown table: orderBookL3","meta":{},"request":{"op":"subscribe","args":["orderBookL3:XBTUSD"]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    pub status: Option<u16>,
+    #[serde(borrow)]
+    pub error: Cow<'a, str>,
+}
+
+/// An entry of the REST `instrument` list, down to its sizes.
+///
+/// ```json
+/// {"symbol":"XBTUSD","rootSymbol":"XBT","state":"Open","tickSize":0.5,"lotSize":100}
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instrument<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    #[serde(borrow)]
+    pub tick_size: Decimal<'a>,
+    #[serde(borrow)]
+    pub lot_size: Decimal<'a>,
+}
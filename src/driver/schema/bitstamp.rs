@@ -0,0 +1,85 @@
+//! Bitstamp `diff_order_book` messages and REST order book.
+
+use super::Level;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A diff.
+///
+/// ```json
+/// {"data":{"timestamp":"1643643522","microtimestamp":"1643643522123456","bids":[["36797.17","0.00000000"]],"asks":[]},"channel":"diff_order_book_btcusd","event":"data"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub data: Snapshot<'a>,
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+}
+
+/// The REST order book, which a diff's `data` mirrors.
+///
+/// ```json
+/// {"timestamp":"1643643500","microtimestamp":"1643643500000000","bids":[["36790.00","1.00000000"]],"asks":[["36800.00","2.00000000"]]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    /// Microseconds since the epoch, as a string.
+    #[serde(borrow)]
+    pub microtimestamp: Cow<'a, str>,
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+/// A frame other than a diff.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event")]
+pub enum Event<'a> {
+    #[serde(rename = "bts:subscription_succeeded")]
+    Subscribed(#[serde(borrow)] SubscribeAck<'a>),
+    #[serde(rename = "bts:error")]
+    Error(#[serde(borrow)] Error<'a>),
+    /// `bts:request_reconnect` and anything newer.
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"event":"bts:subscription_succeeded","channel":"diff_order_book_btcusd","data":{}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+}
+
+/// ```json
+/// {"event":"bts:error","channel":"","data":{"code":null,"message":"Bad subscription string."}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub data: ErrorData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ErrorData<'a> {
+    pub code: Option<i64>,
+    #[serde(borrow)]
+    pub message: Cow<'a, str>,
+}
+
+/// An entry of the REST `trading-pairs-info` list.
+///
+/// ```json
+/// {"name":"BTC/USD","url_symbol":"btcusd","base_decimals":8,"counter_decimals":0,"minimum_order":"10.0 USD","trading":"Enabled"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct PairInfo<'a> {
+    #[serde(borrow)]
+    pub url_symbol: Cow<'a, str>,
+    pub base_decimals: u32,
+    pub counter_decimals: u32,
+}
@@ -0,0 +1,99 @@
+//! Coinbase Exchange `full` channel messages and REST level 3 book.
+
+use super::Decimal;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A `full` channel event; which fields are set depends on its `type`.
+///
+/// ```json
+/// {"type":"open","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":10,"order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","price":"200.2","remaining_size":"1.00","side":"sell"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(rename = "type", borrow)]
+    pub kind: Cow<'a, str>,
+    #[serde(borrow)]
+    pub product_id: Cow<'a, str>,
+    pub sequence: u64,
+    #[serde(borrow)]
+    pub order_id: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub side: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub price: Option<Decimal<'a>>,
+    #[serde(borrow)]
+    pub remaining_size: Option<Decimal<'a>>,
+}
+
+/// The REST level 3 book.
+///
+/// ```json
+/// {"bids":[["295.96","0.5","b1"]],"asks":[["296.00","2","a1"]],"sequence":10}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    pub sequence: u64,
+    #[serde(borrow)]
+    pub bids: Vec<Order<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Order<'a>>,
+}
+
+/// A resting `[price, size, order_id]`.
+#[derive(Debug, Deserialize)]
+pub struct Order<'a>(#[serde(borrow)] pub Decimal<'a>, #[serde(borrow)] pub Decimal<'a>, #[serde(borrow)] pub Cow<'a, str>);
+
+/// A frame that is not a `full` channel event.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Event<'a> {
+    Subscriptions(#[serde(borrow)] SubscribeAck<'a>),
+    Error(#[serde(borrow)] Error<'a>),
+    /// Order events and anything newer.
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"type":"subscriptions","channels":[{"name":"full","product_ids":["BTC-USD"]}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub channels: Vec<Channel<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Channel<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    #[serde(borrow)]
+    pub product_ids: Vec<Cow<'a, str>>,
+}
+
+/// ```json
+/// {"type":"error","message":"Failed to subscribe","reason":"ETH-BTC is delisted"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub message: Cow<'a, str>,
+    #[serde(borrow)]
+    pub reason: Option<Cow<'a, str>>,
+}
+
+/// A REST product, down to its increments.
+///
+/// ```json
+/// {"id":"BTC-USD","base_currency":"BTC","quote_currency":"USD","quote_increment":"0.01","base_increment":"0.00000001"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Product<'a> {
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+    #[serde(borrow)]
+    pub quote_increment: Cow<'a, str>,
+    #[serde(borrow)]
+    pub base_increment: Cow<'a, str>,
+}
@@ -0,0 +1,108 @@
+//! Deribit JSON-RPC messages for the `book` channel.
+
+use super::Decimal;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A `book` notification.
+///
+/// ```json
+/// {"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"change","change_id":2,"prev_change_id":1,"bids":[["delete",5042.34,0]],"asks":[["new",5042.64,40]]}}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub params: Params<'a>,
+}
+
+/// The first notification, whose `data.type` is `snapshot`.
+pub type Snapshot<'a> = DepthUpdate<'a>;
+
+#[derive(Debug, Deserialize)]
+pub struct Params<'a> {
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+    #[serde(borrow)]
+    pub data: Book<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Book<'a> {
+    #[serde(rename = "type", borrow)]
+    pub kind: Cow<'a, str>,
+    pub change_id: u64,
+    pub prev_change_id: Option<u64>,
+    #[serde(borrow)]
+    pub bids: Vec<Change<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Change<'a>>,
+}
+
+/// A `[action, price, amount]` entry, where `action` is `new`, `change` or
+/// `delete`.
+#[derive(Debug, Deserialize)]
+pub struct Change<'a>(#[serde(borrow)] pub Cow<'a, str>, #[serde(borrow)] pub Decimal<'a>, #[serde(borrow)] pub Decimal<'a>);
+
+/// A frame other than a notification: a reply, or a heartbeat request.
+///
+/// ```json
+/// {"jsonrpc":"2.0","method":"heartbeat","params":{"type":"test_request"}}
+/// {"jsonrpc":"2.0","id":1,"error":{"code":11050,"message":"bad_request"}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Control<'a> {
+    pub id: Option<u64>,
+    #[serde(borrow)]
+    pub method: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub params: Option<Heartbeat<'a>>,
+    #[serde(borrow)]
+    pub error: Option<Error<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Heartbeat<'a> {
+    #[serde(rename = "type", borrow)]
+    pub kind: Cow<'a, str>,
+}
+
+/// The reply to `public/subscribe`, listing the channels now subscribed.
+///
+/// ```json
+/// {"jsonrpc":"2.0","id":1,"result":["book.BTC-PERPETUAL.100ms"]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    pub id: u64,
+    #[serde(borrow)]
+    pub result: Vec<Cow<'a, str>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    pub code: i64,
+    #[serde(borrow)]
+    pub message: Cow<'a, str>,
+}
+
+/// The `public/get_instrument` response, down to its sizes.
+///
+/// ```json
+/// {"jsonrpc":"2.0","id":1,"result":{"instrument_name":"BTC-PERPETUAL","tick_size":0.5,"min_trade_amount":10,"contract_size":10}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct GetInstrument<'a> {
+    #[serde(borrow)]
+    pub result: Option<Instrument<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Instrument<'a> {
+    #[serde(borrow)]
+    pub instrument_name: Cow<'a, str>,
+    #[serde(borrow)]
+    pub tick_size: Decimal<'a>,
+    /// The smallest amount, which amounts are multiples of.
+    #[serde(borrow)]
+    pub min_trade_amount: Decimal<'a>,
+}
@@ -0,0 +1,108 @@
+//! dYdX v4 indexer `v4_orderbook` messages.
+
+use super::{Decimal, Level};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// An incremental update.
+///
+/// ```json
+/// {"type":"channel_data","connection_id":"c","message_id":3,"id":"BTC-USD","channel":"v4_orderbook","version":"1.0.0","contents":{"bids":[["65000","0"]]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+    pub message_id: u64,
+    #[serde(borrow)]
+    pub contents: Changes<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Changes<'a> {
+    #[serde(default, borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(default, borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+/// The `subscribed` reply, which carries the book.
+///
+/// ```json
+/// {"type":"subscribed","connection_id":"c","message_id":1,"channel":"v4_orderbook","id":"BTC-USD","contents":{"bids":[{"price":"65000","size":"1.5"}],"asks":[]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+    pub message_id: u64,
+    #[serde(borrow)]
+    pub contents: Book<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Book<'a> {
+    #[serde(default, borrow)]
+    pub bids: Vec<PriceSize<'a>>,
+    #[serde(default, borrow)]
+    pub asks: Vec<PriceSize<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceSize<'a> {
+    #[serde(borrow)]
+    pub price: Decimal<'a>,
+    #[serde(borrow)]
+    pub size: Decimal<'a>,
+}
+
+/// The reply to a subscribe that comes without a book, as for channels
+/// other than `v4_orderbook`.
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+    #[serde(borrow)]
+    pub id: Option<Cow<'a, str>>,
+}
+
+/// A frame other than `v4_orderbook` data.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    Connected,
+    Subscribed(#[serde(borrow)] SubscribeAck<'a>),
+    Error(#[serde(borrow)] Error<'a>),
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"type":"error","message":"Invalid subscribe message: channel is not valid","connection_id":"c","message_id":1}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub message: Cow<'a, str>,
+}
+
+/// The indexer's `perpetualMarkets` response, down to each market's sizes.
+///
+/// ```json
+/// {"markets":{"BTC-USD":{"ticker":"BTC-USD","status":"ACTIVE","tickSize":"1","stepSize":"0.0001"}}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct PerpetualMarkets<'a> {
+    #[serde(borrow)]
+    pub markets: HashMap<Cow<'a, str>, Market<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Market<'a> {
+    #[serde(borrow)]
+    pub tick_size: Cow<'a, str>,
+    #[serde(borrow)]
+    pub step_size: Cow<'a, str>,
+}
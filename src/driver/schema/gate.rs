@@ -0,0 +1,119 @@
+//! Gate spot and futures `order_book_update` messages.
+
+use super::{Decimal, Level};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A spot update.
+///
+/// ```json
+/// {"time":1,"channel":"spot.order_book_update","event":"update","result":{"s":"BTC_USDT","U":48791820,"u":48791830,"b":[["19137.74","0.0001"]],"a":[]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+    #[serde(borrow)]
+    pub result: Changes<'a, Level<'a>>,
+}
+
+/// A futures update, whose levels are objects.
+///
+/// ```json
+/// {"time":1,"channel":"futures.order_book_update","event":"update","result":{"s":"BTC_USDT","U":10,"u":12,"b":[{"p":"16493.50","s":0}],"a":[]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct FuturesDepthUpdate<'a> {
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+    #[serde(borrow)]
+    pub result: Changes<'a, FuturesLevel<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Changes<'a, L> {
+    #[serde(rename = "s", borrow)]
+    pub symbol: Cow<'a, str>,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub last_update_id: u64,
+    #[serde(rename = "b")]
+    pub bids: Vec<L>,
+    #[serde(rename = "a")]
+    pub asks: Vec<L>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FuturesLevel<'a> {
+    #[serde(rename = "p", borrow)]
+    pub price: Decimal<'a>,
+    #[serde(rename = "s", borrow)]
+    pub size: Decimal<'a>,
+}
+
+/// A REST spot order book.
+///
+/// ```json
+/// {"id":48791820,"current":1623898993123,"update":1623898993121,"asks":[["1.52","1.151"]],"bids":[["1.17","201.863"]]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    pub id: u64,
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+/// The reply to a subscribe or unsubscribe request.
+///
+/// ```json
+/// {"time":1,"channel":"spot.order_book_update","event":"subscribe","result":{"status":"success"}}
+/// {"time":1,"channel":"spot.order_book_update","event":"subscribe","error":{"code":2,"message":"unknown currency pair: FOO_USDT"},"result":null}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+    #[serde(borrow)]
+    pub event: Cow<'a, str>,
+    /// Set when the request was rejected.
+    #[serde(borrow)]
+    pub error: Option<Error<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    pub code: i64,
+    #[serde(borrow)]
+    pub message: Cow<'a, str>,
+}
+
+/// A spot `currency_pairs/<pair>` response, down to its precision.
+///
+/// ```json
+/// {"id":"BTC_USDT","base":"BTC","quote":"USDT","precision":2,"amount_precision":6,"trade_status":"tradable"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct CurrencyPair<'a> {
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+    /// Decimals of prices.
+    pub precision: u32,
+    /// Decimals of amounts.
+    pub amount_precision: u32,
+}
+
+/// A futures `contracts/<contract>` response, down to its price tick.
+///
+/// ```json
+/// {"name":"BTC_USDT","type":"direct","quanto_multiplier":"0.0001","order_price_round":"0.1","order_size_min":1}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Contract<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    #[serde(borrow)]
+    pub order_price_round: Cow<'a, str>,
+}
@@ -0,0 +1,102 @@
+//! HTX (Huobi) incremental market-by-price messages, after decompression.
+
+use super::Level;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// An incremental update.
+///
+/// ```json
+/// {"ch":"market.btcusdt.mbp.150","ts":1,"tick":{"seqNum":101,"prevSeqNum":100,"bids":[["20000.5","0.5"]],"asks":[]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub ch: Cow<'a, str>,
+    pub ts: u64,
+    #[serde(borrow)]
+    pub tick: Tick<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tick<'a> {
+    pub seq_num: u64,
+    /// Absent from snapshots.
+    pub prev_seq_num: Option<u64>,
+    #[serde(default, borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(default, borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+/// The reply to a `req` for the full book.
+///
+/// ```json
+/// {"id":"snapshot","rep":"market.btcusdt.mbp.150","status":"ok","data":{"seqNum":100,"bids":[["20000","1"]],"asks":[["20001","2"]]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    #[serde(borrow)]
+    pub rep: Cow<'a, str>,
+    #[serde(borrow)]
+    pub data: Tick<'a>,
+}
+
+/// The reply to a `sub`, `unsub` or `req`, told apart by its `status`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Reply<'a> {
+    Ok(#[serde(borrow)] SubscribeAck<'a>),
+    Error(#[serde(borrow)] Error<'a>),
+}
+
+/// ```json
+/// {"id":"1","status":"ok","subbed":"market.btcusdt.mbp.150","ts":1489474081631}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub subbed: Option<Cow<'a, str>>,
+}
+
+/// ```json
+/// {"id":"1","status":"error","err-code":"bad-request","err-msg":"invalid topic market.foo.mbp.150","ts":1489474081631}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(rename = "err-code", borrow)]
+    pub code: Cow<'a, str>,
+    #[serde(rename = "err-msg", borrow)]
+    pub msg: Cow<'a, str>,
+}
+
+/// A server ping, to be answered with a `pong` carrying the same value.
+///
+/// ```json
+/// {"ping":1492420473027}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Ping {
+    pub ping: u64,
+}
+
+/// The REST `common/symbols` list, down to each symbol's precision.
+///
+/// ```json
+/// {"status":"ok","data":[{"base-currency":"btc","quote-currency":"usdt","price-precision":2,"amount-precision":6,"symbol":"btcusdt"}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Symbols<'a> {
+    #[serde(borrow)]
+    pub data: Vec<SymbolInfo<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SymbolInfo<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    pub price_precision: u32,
+    pub amount_precision: u32,
+}
@@ -0,0 +1,96 @@
+//! Hyperliquid `l2Book` messages and `info` metadata.
+
+use super::Decimal;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// An `l2Book` push, which always holds the whole book.
+///
+/// ```json
+/// {"channel":"l2Book","data":{"coin":"BTC","time":1,"levels":[[{"px":"19900","sz":"1","n":1}],[{"px":"19920","sz":"1","n":1}]]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    #[serde(borrow)]
+    pub data: Book<'a>,
+}
+
+/// Every `l2Book` push replaces the book.
+pub type DepthUpdate<'a> = Snapshot<'a>;
+
+#[derive(Debug, Deserialize)]
+pub struct Book<'a> {
+    #[serde(borrow)]
+    pub coin: Cow<'a, str>,
+    pub time: u64,
+    /// Bids, then asks.
+    #[serde(borrow)]
+    pub levels: [Vec<BookLevel<'a>>; 2],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BookLevel<'a> {
+    #[serde(borrow)]
+    pub px: Decimal<'a>,
+    #[serde(borrow)]
+    pub sz: Decimal<'a>,
+    /// Number of orders at the price.
+    pub n: u32,
+}
+
+/// A push other than `l2Book`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "channel", rename_all = "camelCase")]
+pub enum Event<'a> {
+    SubscriptionResponse(#[serde(borrow)] SubscribeAck<'a>),
+    Error(#[serde(borrow)] Error<'a>),
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"channel":"subscriptionResponse","data":{"method":"subscribe","subscription":{"type":"l2Book","coin":"BTC"}}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub data: AckData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AckData<'a> {
+    #[serde(borrow)]
+    pub method: Cow<'a, str>,
+}
+
+/// ```json
+/// {"channel":"error","data":"Invalid subscription {\"type\":\"l2Book\",\"coin\":\"FOO\"}"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub data: Cow<'a, str>,
+}
+
+/// A `meta` or `spotMeta` response; perpetuals are listed in `universe`,
+/// spot tokens in `tokens`.
+///
+/// ```json
+/// {"universe":[{"szDecimals":5,"name":"BTC","maxLeverage":50}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Meta<'a> {
+    #[serde(default, borrow)]
+    pub universe: Vec<Asset<'a>>,
+    #[serde(default, borrow)]
+    pub tokens: Vec<Asset<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Asset<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    /// Absent from the spot pairs in a `spotMeta` universe.
+    pub sz_decimals: Option<u32>,
+}
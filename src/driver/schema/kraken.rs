@@ -0,0 +1,77 @@
+//! Kraken spot v2 `book` messages and the REST `AssetPairs` response.
+
+use super::Decimal;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// A `book` update.
+///
+/// ```json
+/// {"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":45285.2,"qty":0.001}],"asks":[],"checksum":1}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(rename = "type", borrow)]
+    pub kind: Cow<'a, str>,
+    #[serde(borrow)]
+    pub data: Vec<Book<'a>>,
+}
+
+/// A `book` message whose `type` is `snapshot`.
+pub type Snapshot<'a> = DepthUpdate<'a>;
+
+#[derive(Debug, Deserialize)]
+pub struct Book<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    #[serde(borrow)]
+    pub bids: Vec<PriceQty<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<PriceQty<'a>>,
+    /// CRC32 of the top ten levels after this message is applied.
+    pub checksum: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceQty<'a> {
+    #[serde(borrow)]
+    pub price: Decimal<'a>,
+    #[serde(borrow)]
+    pub qty: Decimal<'a>,
+}
+
+/// The reply to a `subscribe` or `unsubscribe` request; Kraken reports
+/// errors through the same shape.
+///
+/// ```json
+/// {"method":"subscribe","result":{"channel":"book","depth":10,"symbol":"BTC/USD"},"success":true,"time_in":"2023-09-25T09:04:31.742599Z","time_out":"2023-09-25T09:04:31.742648Z"}
+/// {"error":"Currency pair not supported FOO/USD","method":"subscribe","success":false,"symbol":"FOO/USD"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub method: Cow<'a, str>,
+    pub success: bool,
+    #[serde(borrow)]
+    pub error: Option<Cow<'a, str>>,
+}
+
+/// The REST `AssetPairs` response for one pair.
+///
+/// ```json
+/// {"error":[],"result":{"XXBTZUSD":{"altname":"XBTUSD","pair_decimals":1,"lot_decimals":8}}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct AssetPairs<'a> {
+    #[serde(default, borrow)]
+    pub error: Vec<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    pub result: HashMap<Cow<'a, str>, AssetPair>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssetPair {
+    pub pair_decimals: u32,
+    pub lot_decimals: u32,
+}
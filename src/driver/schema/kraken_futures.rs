@@ -0,0 +1,101 @@
+//! Kraken Futures `book` feed messages.
+
+use super::Decimal;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A single-level `book` update.
+///
+/// ```json
+/// {"feed":"book","product_id":"PI_XBTUSD","side":"sell","seq":326072250,"price":34981,"qty":0,"timestamp":1612269953629}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub product_id: Cow<'a, str>,
+    #[serde(borrow)]
+    pub side: Cow<'a, str>,
+    pub seq: u64,
+    #[serde(borrow)]
+    pub price: Decimal<'a>,
+    #[serde(borrow)]
+    pub qty: Decimal<'a>,
+    pub timestamp: u64,
+}
+
+/// ```json
+/// {"feed":"book_snapshot","product_id":"PI_XBTUSD","timestamp":1612269825817,"seq":326072249,"tickSize":null,"bids":[{"price":34892.5,"qty":6385}],"asks":[{"price":34911.5,"qty":20598}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    #[serde(borrow)]
+    pub product_id: Cow<'a, str>,
+    pub seq: u64,
+    pub timestamp: u64,
+    #[serde(borrow)]
+    pub bids: Vec<PriceQty<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<PriceQty<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceQty<'a> {
+    #[serde(borrow)]
+    pub price: Decimal<'a>,
+    #[serde(borrow)]
+    pub qty: Decimal<'a>,
+}
+
+/// A frame without a `feed`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'a> {
+    Subscribed(#[serde(borrow)] SubscribeAck<'a>),
+    Error(#[serde(borrow)] Error<'a>),
+    /// `info`, `unsubscribed` and anything newer.
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"event":"subscribed","feed":"book","product_ids":["PI_XBTUSD"]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub feed: Cow<'a, str>,
+    #[serde(borrow)]
+    pub product_ids: Vec<Cow<'a, str>>,
+}
+
+/// ```json
+/// {"event":"error","message":"Invalid product id"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub message: Cow<'a, str>,
+}
+
+/// The REST `instruments` list, down to each product's precision.
+///
+/// ```json
+/// {"result":"success","instruments":[{"symbol":"PF_XBTUSD","type":"flexible_futures","tickSize":0.5,"contractValueTradePrecision":4}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Instruments<'a> {
+    #[serde(borrow)]
+    pub instruments: Vec<Instrument<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instrument<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    #[serde(borrow)]
+    pub tick_size: Decimal<'a>,
+    /// Decimals of sizes; negative for sizes in tens or more.
+    #[serde(default)]
+    pub contract_value_trade_precision: i32,
+}
@@ -0,0 +1,142 @@
+//! KuCoin spot `level2` messages and the `bullet-public` response.
+
+use super::Level;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A `trade.l2update` message, whose changes are `[price, size, sequence]`.
+///
+/// ```json
+/// {"type":"message","topic":"/market/level2:BTC-USDT","subject":"trade.l2update","data":{"changes":{"asks":[["18906","0.00331","14103845"]],"bids":[]},"sequenceEnd":14103845,"sequenceStart":14103845,"symbol":"BTC-USDT","time":1663747970273}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub topic: Cow<'a, str>,
+    #[serde(borrow)]
+    pub data: Changes<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Changes<'a> {
+    #[serde(borrow)]
+    pub changes: Sides<'a>,
+    pub sequence_start: u64,
+    pub sequence_end: u64,
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Sides<'a> {
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+}
+
+/// A REST level 2 order book.
+///
+/// ```json
+/// {"code":"200000","data":{"time":1663747970273,"sequence":"14103844","bids":[["18891.9","0.15688"]],"asks":[["18906","0.00331"]]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    #[serde(borrow)]
+    pub data: Book<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Book<'a> {
+    /// The sequence as a string.
+    #[serde(borrow)]
+    pub sequence: Cow<'a, str>,
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+/// The `bullet-public` response, which says where and how to connect.
+///
+/// ```json
+/// {"code":"200000","data":{"token":"2neAiuYvAU61ZD","instanceServers":[{"endpoint":"wss://ws-api-spot.kucoin.com/","encrypt":true,"protocol":"websocket","pingInterval":18000,"pingTimeout":10000}]}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Bullet<'a> {
+    #[serde(borrow)]
+    pub data: Token<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Token<'a> {
+    #[serde(borrow)]
+    pub token: Cow<'a, str>,
+    #[serde(borrow)]
+    pub instance_servers: Vec<InstanceServer<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceServer<'a> {
+    #[serde(borrow)]
+    pub endpoint: Cow<'a, str>,
+    /// Milliseconds between client pings.
+    pub ping_interval: u64,
+    /// Milliseconds to wait for a pong.
+    pub ping_timeout: u64,
+}
+
+/// A frame other than a topic message.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Event<'a> {
+    Welcome,
+    Ack(#[serde(borrow)] SubscribeAck<'a>),
+    Pong,
+    Error(#[serde(borrow)] Error<'a>),
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"id":"1545910590801","type":"ack"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+}
+
+/// ```json
+/// {"id":"1","type":"error","code":404,"data":"topic /market/level2:FOO-USDT is not found"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub data: Cow<'a, str>,
+}
+
+/// A REST `symbols/<symbol>` response, down to its increments.
+///
+/// ```json
+/// {"code":"200000","data":{"symbol":"BTC-USDT","baseIncrement":"0.00000001","quoteIncrement":"0.000001","priceIncrement":"0.1"}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SymbolInfo<'a> {
+    #[serde(borrow)]
+    pub data: Option<Increments<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Increments<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    #[serde(borrow)]
+    pub price_increment: Cow<'a, str>,
+    #[serde(borrow)]
+    pub base_increment: Cow<'a, str>,
+}
@@ -0,0 +1,128 @@
+//! MEXC JSON messages: spot subscription replies and the futures depth feed.
+//!
+//! Spot book data comes as protobuf and has no schema here.
+
+use super::Level;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A futures `push.depth` message, whose levels are `[price, qty, orders]`.
+///
+/// ```json
+/// {"channel":"push.depth","data":{"asks":[[6859.5,3251,1]],"bids":[],"version":96801927},"symbol":"BTC_USDT","ts":1587442022003}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    pub ts: u64,
+    #[serde(borrow)]
+    pub data: Book<'a>,
+}
+
+/// The futures REST depth snapshot.
+///
+/// ```json
+/// {"success":true,"code":0,"data":{"asks":[[6859.5,3251,1]],"bids":[[6858.5,120,2]],"version":96801927,"timestamp":1587442022003}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Snapshot<'a> {
+    #[serde(borrow)]
+    pub data: Book<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Book<'a> {
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    pub version: u64,
+}
+
+/// A futures frame other than `push.depth`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "channel")]
+pub enum Event<'a> {
+    #[serde(rename = "rs.sub.depth")]
+    Subscribed(#[serde(borrow)] SubscribeAck<'a>),
+    #[serde(rename = "rs.error")]
+    Error(#[serde(borrow)] Error<'a>),
+    /// `pong` and anything newer.
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"channel":"rs.sub.depth","data":"success","ts":1587442022003}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub data: Cow<'a, str>,
+}
+
+/// ```json
+/// {"channel":"rs.error","data":"Contract doesn't exist!","ts":1587442022003}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub data: Cow<'a, str>,
+}
+
+/// The spot reply to a subscription request or ping; a nonzero `code`
+/// means it was refused.
+///
+/// ```json
+/// {"id":0,"code":0,"msg":"spot@public.limit.depth.v3.api.pb@BTCUSDT@20"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SpotReply<'a> {
+    pub code: i64,
+    #[serde(borrow)]
+    pub msg: Cow<'a, str>,
+}
+
+/// A spot `exchangeInfo` response, down to each symbol's precision.
+///
+/// ```json
+/// {"symbols":[{"symbol":"BTCUSDT","status":"1","quotePrecision":2,"baseAssetPrecision":6,"baseSizePrecision":"0.000001"}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct ExchangeInfo<'a> {
+    #[serde(borrow)]
+    pub symbols: Vec<SymbolInfo<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolInfo<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    /// Decimals of prices.
+    pub quote_precision: u32,
+    /// Decimals of quantities.
+    pub base_asset_precision: u32,
+}
+
+/// A futures `contract/detail` response; `data` is absent for unknown
+/// contracts.
+///
+/// ```json
+/// {"success":true,"code":0,"data":{"symbol":"BTC_USDT","priceUnit":0.1,"volUnit":1,"priceScale":1,"volScale":0,"contractSize":0.0001}}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct ContractDetail<'a> {
+    #[serde(borrow)]
+    pub data: Option<Contract<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Contract<'a> {
+    #[serde(borrow)]
+    pub symbol: Cow<'a, str>,
+    pub price_scale: u32,
+    pub vol_scale: u32,
+}
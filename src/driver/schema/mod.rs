@@ -0,0 +1,126 @@
+//! Typed shapes of the venues' JSON messages, for the control plane.
+//!
+//! Subscription acks, errors and REST snapshots are decoded with serde into
+//! these types instead of being picked apart by string matching. Depth
+//! updates keep going through the drivers' scanners on the hot path; their
+//! types here document the payloads and serve tools and tests.
+//!
+//! Strings borrow from the frame unless they hold escapes, and numbers keep
+//! their text as a [Decimal], so they reach the fixed-point parser without a
+//! detour through `f64`.
+
+pub mod binance;
+pub mod bitfinex;
+pub mod bitmex;
+pub mod bitstamp;
+pub mod coinbase;
+pub mod deribit;
+pub mod dydx;
+pub mod gate;
+pub mod htx;
+pub mod hyperliquid;
+pub mod kraken;
+pub mod kraken_futures;
+pub mod kucoin;
+pub mod mexc;
+pub mod okx;
+
+use crate::driver::DriverError;
+use crate::json;
+use crate::util::ParseError;
+use serde::de::{self, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Decodes a whole frame as `T`.
+pub fn decode<'a, T: Deserialize<'a>>(msg: &'a [u8]) -> Result<T, DriverError> {
+    serde_json::from_slice(msg).map_err(|_| DriverError::Malformed)
+}
+
+/// A JSON number, or a number in a string, kept as sent.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(transparent)]
+pub struct Decimal<'a>(#[serde(borrow)] &'a RawValue);
+
+impl<'a> Decimal<'a> {
+    /// Parses the number at `scale`, like [crate::util::parse_i64_with_precision].
+    pub fn parse(&self, scale: u32) -> Result<i64, ParseError> {
+        let text = self.0.get().as_bytes();
+        json::number(text, 0..text.len(), scale)
+    }
+
+    /// The number as sent, without the quotes around a number in a string.
+    pub fn text(&self) -> &'a [u8] {
+        self.0.get().trim_matches('"').as_bytes()
+    }
+}
+
+/// A `[price, qty, ...]` level; anything after the quantity is ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct Level<'a> {
+    pub price: Decimal<'a>,
+    pub qty: Decimal<'a>,
+}
+
+impl Level<'_> {
+    /// Parses the price and quantity at their scales.
+    pub fn parse(&self, price_scale: u32, qty_scale: u32) -> Result<(i64, i64), ParseError> {
+        Ok((self.price.parse(price_scale)?, self.qty.parse(qty_scale)?))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Level<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LevelVisitor<'a>(PhantomData<Level<'a>>);
+
+        impl<'de: 'a, 'a> Visitor<'de> for LevelVisitor<'a> {
+            type Value = Level<'a>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a [price, qty, ...] array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Level<'a>, A::Error> {
+                let price = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let qty = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(Level { price, qty })
+            }
+        }
+
+        deserializer.deserialize_seq(LevelVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_levels() {
+        let levels: Vec<Level> = decode(br#"[["0.0024","10"],[45285.2,0.001,"1"],["1e-3",5]]"#).unwrap();
+        let parsed: Vec<_> = levels.iter().map(|level| level.parse(4, 0).unwrap()).collect();
+        assert_eq!(parsed, [(24, 10), (452_852_000, 0), (10, 5)]);
+        assert!(decode::<Vec<Level>>(br#"[["1"]]"#).is_err());
+        assert_eq!(decode::<Level>(br#"["x","1"]"#).unwrap().price.parse(2), Err(ParseError::InvalidFirstChar));
+    }
+
+    #[test]
+    fn test_messages() {
+        let update: binance::DepthUpdate = decode(
+            br#"{"e":"depthUpdate","E":1,"s":"BNBBTC","U":157,"u":160,"pu":156,"b":[["0.0024","10"]],"a":[]}"#,
+        )
+        .unwrap();
+        assert_eq!((update.first_update_id, update.previous_update_id), (157, Some(156)));
+        assert_eq!(update.bids[0].parse(4, 0), Ok((24, 10)));
+
+        // Escaped strings are unescaped into an owned copy
+        let error: okx::Event = decode(br#"{"event":"error","code":"60012","msg":"bad \"op\"","connId":"a"}"#).unwrap();
+        assert!(matches!(error, okx::Event::Error(okx::Error { msg: Cow::Owned(msg), .. }) if msg == r#"bad "op""#));
+        assert!(matches!(decode(br#"{"event":"notice","msg":"x"}"#), Ok(okx::Event::Other)));
+        assert!(decode::<okx::Event>(br#"{"msg":"x"}"#).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+//! OKX v5 order book pushes and event replies.
+
+use super::Level;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A `books` update or a `books5` push.
+///
+/// ```json
+/// {"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update","data":[{"asks":[["8476.98","415","0","13"]],"bids":[],"ts":"1597026383085","checksum":-855196043}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate<'a> {
+    #[serde(borrow)]
+    pub arg: Arg<'a>,
+    /// `snapshot` or `update`; absent from `books5`, whose pushes are all
+    /// snapshots.
+    #[serde(borrow)]
+    pub action: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub data: Vec<Book<'a>>,
+}
+
+/// A push whose `action` is `snapshot`.
+pub type Snapshot<'a> = DepthUpdate<'a>;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Arg<'a> {
+    #[serde(borrow)]
+    pub channel: Cow<'a, str>,
+    #[serde(borrow)]
+    pub inst_id: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Book<'a> {
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    /// Milliseconds since the epoch, as a string.
+    #[serde(borrow)]
+    pub ts: Cow<'a, str>,
+    pub checksum: Option<i32>,
+}
+
+/// A frame without `data`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'a> {
+    Subscribe(#[serde(borrow)] SubscribeAck<'a>),
+    Unsubscribe(#[serde(borrow)] SubscribeAck<'a>),
+    Login,
+    Error(#[serde(borrow)] Error<'a>),
+    #[serde(other)]
+    Other,
+}
+
+/// ```json
+/// {"event":"subscribe","arg":{"channel":"books5","instId":"BTC-USDT"},"connId":"a4d3ae55"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct SubscribeAck<'a> {
+    #[serde(borrow)]
+    pub arg: Arg<'a>,
+}
+
+/// ```json
+/// {"event":"error","code":"60012","msg":"Invalid request: {\"op\": \"subscribe\"}","connId":"a4d3ae55"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(borrow)]
+    pub code: Cow<'a, str>,
+    #[serde(borrow)]
+    pub msg: Cow<'a, str>,
+}
+
+/// A `public/instruments` response.
+///
+/// ```json
+/// {"code":"0","msg":"","data":[{"instType":"SPOT","instId":"BTC-USDT","tickSz":"0.1","lotSz":"0.00000001","minSz":"0.00001"}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Instruments<'a> {
+    #[serde(borrow)]
+    pub data: Vec<Instrument<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instrument<'a> {
+    #[serde(borrow, default)]
+    pub inst_id: Cow<'a, str>,
+    #[serde(borrow)]
+    pub tick_sz: Cow<'a, str>,
+    #[serde(borrow)]
+    pub lot_sz: Cow<'a, str>,
+}
@@ -0,0 +1,249 @@
+//! Decimal fixed-point numbers that carry their own scale.
+//!
+//! Books store raw `i64`s and keep the scale once per side in
+//! [crate::model::L1FriendlyBook], which is what fits them in L1. Code that
+//! moves values between venues or into calculations should convert them to
+//! [FixedPoint] first, so mismatched scales are reconciled rather than
+//! silently mixed.
+
+use crate::util::{self, MAX_FORMATTED_LEN, ParseError, Rounding, format_i64_with_precision, parse_i64_with_precision};
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+/// Highest supported scale; `i64` holds at most 19 significant digits.
+pub const MAX_SCALE: u8 = 19;
+
+/// Powers of 10 up to the scale of a product of two [MAX_SCALE] values.
+const POWERS_OF_10: [i128; 39] = {
+    let mut powers = [1i128; 39];
+    let mut i = 1;
+    while i < powers.len() {
+        powers[i] = powers[i - 1] * 10;
+        i += 1;
+    }
+    powers
+};
+
+/// A decimal number worth `value × 10^-scale`.
+///
+/// Values at different scales compare, hash and combine by what they are
+/// worth, so `1.5` at scale 1 equals `1.50` at scale 2. Arithmetic results
+/// take the larger scale of the two operands and truncate anything below it
+/// toward zero.
+///
+/// # Panics
+/// Operations panic on a `scale` above [MAX_SCALE].
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::fixed::FixedPoint;
+///
+/// let price = FixedPoint::new(10_050, 2); // 100.50
+/// let qty = FixedPoint::new(3, 0);
+/// assert_eq!(price.checked_mul(qty), Some(FixedPoint::new(30_150, 2)));
+/// assert_eq!(FixedPoint::new(15, 1), FixedPoint::new(150, 2));
+/// assert_eq!(price.to_string(), "100.5");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FixedPoint {
+    pub value: i64,
+    /// Number of decimal places in `value`.
+    pub scale: u8,
+}
+
+impl FixedPoint {
+    pub const fn new(value: i64, scale: u8) -> Self {
+        assert!(scale <= MAX_SCALE, "scale above MAX_SCALE");
+        Self { value, scale }
+    }
+
+    /// Converts a raw book value with a decimal exponent, as kept in
+    /// [crate::model::L1FriendlyBook], or `None` if it does not fit.
+    ///
+    /// Positive exponents are folded into the value at scale 0.
+    pub fn from_exponent(value: i64, exponent: i8) -> Option<Self> {
+        if exponent <= 0 {
+            let scale = exponent.unsigned_abs();
+            return (scale <= MAX_SCALE).then_some(Self { value, scale });
+        }
+        let value = POWERS_OF_10.get(exponent as usize).and_then(|power| (value as i128).checked_mul(*power))?;
+        Some(Self { value: value.try_into().ok()?, scale: 0 })
+    }
+
+    /// Parses the whole of `bytes` at `scale`, truncating finer digits like
+    /// [parse_i64_with_precision].
+    pub fn parse(bytes: &[u8], scale: u8) -> Result<Self, ParseError> {
+        if scale > MAX_SCALE {
+            return Err(ParseError::Overflow);
+        }
+        match parse_i64_with_precision(bytes, 0, scale as u32)? {
+            (value, end) if end == bytes.len() => Ok(Self { value, scale }),
+            _ => Err(ParseError::InvalidTerminator),
+        }
+    }
+
+    /// Returns the same number at `scale`, rounded as `rounding` says when
+    /// narrowing, or `None` if it no longer fits; see [util::rescale].
+    pub fn rescale(self, scale: u8, rounding: Rounding) -> Option<Self> {
+        let value = util::rescale(self.value, self.scale as u32, scale as u32, rounding)?;
+        Some(Self::new(value, scale))
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let scale = self.scale.max(rhs.scale);
+        let value = widen(self.value as i128, self.scale, scale)? + widen(rhs.value as i128, rhs.scale, scale)?;
+        Some(Self { value: value.try_into().ok()?, scale })
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        let scale = self.scale.max(rhs.scale);
+        let value = widen(self.value as i128, self.scale, scale)? - widen(rhs.value as i128, rhs.scale, scale)?;
+        Some(Self { value: value.try_into().ok()?, scale })
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let scale = self.scale.max(rhs.scale);
+        // The product is at the sum of the scales; drop the smaller one
+        let value = self.value as i128 * rhs.value as i128 / POWERS_OF_10[self.scale.min(rhs.scale) as usize];
+        Some(Self { value: value.try_into().ok()?, scale })
+    }
+
+    /// Returns `self / rhs`, or `None` if `rhs` is zero or the quotient
+    /// does not fit.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.value == 0 {
+            return None;
+        }
+        let scale = self.scale.max(rhs.scale);
+        // Scale the dividend so the quotient comes out at `scale`
+        let shift = scale as usize + rhs.scale as usize - self.scale as usize;
+        let value = (self.value as i128).checked_mul(POWERS_OF_10[shift])? / rhs.value as i128;
+        Some(Self { value: value.try_into().ok()?, scale })
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        Some(Self { value: self.value.checked_neg()?, scale: self.scale })
+    }
+
+    /// Returns the nearest `f64`, for display and analytics rather than
+    /// further exact arithmetic.
+    pub fn to_f64(self) -> f64 {
+        self.value as f64 / POWERS_OF_10[self.scale as usize] as f64
+    }
+
+    /// Returns the value with trailing fractional zeros removed, so equal
+    /// numbers have one representation.
+    fn normalized(self) -> Self {
+        let mut normal = self;
+        while normal.scale > 0 && normal.value % 10 == 0 {
+            normal.value /= 10;
+            normal.scale -= 1;
+        }
+        normal
+    }
+}
+
+/// Moves `value` from scale `from` to `to`, truncating when narrowing, or
+/// `None` on overflow.
+fn widen(value: i128, from: u8, to: u8) -> Option<i128> {
+    match to.checked_sub(from) {
+        Some(shift) => value.checked_mul(POWERS_OF_10[shift as usize]),
+        None => Some(value / POWERS_OF_10[(from - to) as usize]),
+    }
+}
+
+impl PartialEq for FixedPoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FixedPoint {}
+
+impl PartialOrd for FixedPoint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FixedPoint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // At most 19 digits shifted by at most 19 places fits an i128
+        let scale = self.scale.max(other.scale);
+        let lhs = self.value as i128 * POWERS_OF_10[(scale - self.scale) as usize];
+        let rhs = other.value as i128 * POWERS_OF_10[(scale - other.scale) as usize];
+        lhs.cmp(&rhs)
+    }
+}
+
+impl Hash for FixedPoint {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let normal = self.normalized();
+        normal.value.hash(state);
+        normal.scale.hash(state);
+    }
+}
+
+impl fmt::Display for FixedPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = [0u8; MAX_FORMATTED_LEN];
+        let len = format_i64_with_precision(self.value, self.scale as u32, &mut buf);
+        f.pad(core::str::from_utf8(&buf[..len]).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl From<FixedPoint> for f64 {
+    fn from(value: FixedPoint) -> f64 {
+        value.to_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn fp(text: &str, scale: u8) -> FixedPoint {
+        FixedPoint::parse(text.as_bytes(), scale).unwrap()
+    }
+
+    #[test]
+    fn test_compares_across_scales() {
+        assert_eq!(fp("1.5", 1), fp("1.5", 8));
+        assert!(fp("1.25", 2) > fp("1.2", 1));
+        assert!(fp("-0.001", 3) < FixedPoint::default());
+        assert_eq!(fp("9223372036854775807", 0).cmp(&fp("0.1", 19)), Ordering::Greater);
+        let set: HashSet<_> = [fp("2", 0), fp("2.00", 2), fp("2.01", 2)].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(fp("1.5", 1).checked_add(fp("0.25", 2)), Some(fp("1.75", 2)));
+        assert_eq!(fp("1", 0).checked_sub(fp("1.5", 1)), Some(fp("-0.5", 1)));
+        assert_eq!(fp("1.5", 1).checked_mul(fp("0.25", 2)), Some(fp("0.37", 2))); // 0.375 truncated
+        assert_eq!(fp("1", 0).checked_div(fp("3", 0)), Some(fp("0", 0)));
+        assert_eq!(fp("1.00", 2).checked_div(fp("3", 0)), Some(fp("0.33", 2)));
+        assert_eq!(fp("-7.5", 1).checked_div(fp("2.5", 1)), Some(fp("-3", 0)));
+        assert_eq!(fp("1", 0).checked_div(fp("0", 4)), None);
+        assert_eq!(FixedPoint::new(i64::MAX, 0).checked_add(fp("1", 0)), None);
+        assert_eq!(FixedPoint::new(i64::MIN, 0).checked_neg(), None);
+        assert_eq!(fp("-1", 0).checked_sub(FixedPoint::new(i64::MIN, 0)), Some(FixedPoint::new(i64::MAX, 0)));
+        assert_eq!(FixedPoint::new(i64::MAX, 0).checked_mul(fp("2", 0)), None);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(fp("1.23", 2).rescale(4, Rounding::TowardZero), Some(FixedPoint::new(12_300, 4)));
+        assert_eq!(fp("1.239", 3).rescale(1, Rounding::TowardZero), Some(FixedPoint::new(12, 1)));
+        assert_eq!(fp("1.25", 3).rescale(1, Rounding::HalfEven), Some(FixedPoint::new(12, 1)));
+        assert_eq!(fp("10", 0).rescale(MAX_SCALE, Rounding::TowardZero), None);
+        assert_eq!(FixedPoint::from_exponent(12_345, -4), Some(fp("1.2345", 4)));
+        assert_eq!(FixedPoint::from_exponent(12, 3), Some(fp("12000", 0)));
+        assert_eq!(FixedPoint::from_exponent(1, -20), None);
+        assert_eq!(fp("-0.125", 3).to_f64(), -0.125);
+        assert_eq!(format!("{:>6}", fp("1.50", 2)), "   1.5");
+        assert_eq!(FixedPoint::parse(b"1.5x", 2), Err(ParseError::InvalidTerminator));
+    }
+}
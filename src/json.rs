@@ -0,0 +1,300 @@
+//! Zero-copy scanning of JSON payloads.
+//!
+//! Walks one object's fields without building a DOM or allocating, and
+//! returns where each value sits in the input, so numbers go straight to
+//! [parse_i64_with_precision] and arrays of levels to the drivers' level
+//! parsers. Nested values are skipped, not validated; malformed input just
+//! ends the scan.
+//!
+//! Messages that need a full parse, such as REST snapshots and control
+//! replies, can go through [Parser] instead with the `simd-json` feature.
+
+use crate::util::{ParseError, parse_i64_with_precision};
+use std::ops::Range;
+
+/// One field of an object: its raw key, without quotes, and the position of
+/// its value in the scanned input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field<'a> {
+    pub key: &'a [u8],
+    /// Byte range of the value, including the quotes of a string.
+    pub value: Range<usize>,
+}
+
+/// Iterates over the fields of the object at a position; see [fields].
+pub struct Fields<'a> {
+    bytes: &'a [u8],
+    idx: usize,
+    done: bool,
+}
+
+/// Returns the fields of the object starting at or after whitespace from
+/// `start`.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::json;
+///
+/// let msg = br#"{"e":"depthUpdate","u":160,"b":[["0.0024","10"]]}"#;
+/// let keys: Vec<_> = json::fields(msg, 0).map(|field| field.key).collect();
+/// assert_eq!(keys, [&b"e"[..], b"u", b"b"]);
+/// ```
+pub fn fields(bytes: &[u8], start: usize) -> Fields<'_> {
+    let idx = skip_whitespace(bytes, start);
+    let opens = bytes.get(idx) == Some(&b'{');
+    Fields { bytes, idx: idx + 1, done: !opens }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Field<'a>;
+
+    fn next(&mut self) -> Option<Field<'a>> {
+        if self.done {
+            return None;
+        }
+        let field = self.scan();
+        self.done = field.is_none();
+        field
+    }
+}
+
+impl<'a> Fields<'a> {
+    /// Reads the field at `idx` and moves past the separator after it.
+    fn scan(&mut self) -> Option<Field<'a>> {
+        let bytes = self.bytes;
+        let mut idx = skip_whitespace(bytes, self.idx);
+        match bytes.get(idx)? {
+            b'"' => {}
+            // `}` ends the object; anything else is malformed
+            _ => return None,
+        }
+        let key_end = skip_value(bytes, idx)?;
+        let key = &bytes[idx + 1..key_end - 1];
+
+        idx = skip_whitespace(bytes, key_end);
+        if bytes.get(idx) != Some(&b':') {
+            return None;
+        }
+        let start = skip_whitespace(bytes, idx + 1);
+        let end = skip_value(bytes, start)?;
+
+        idx = skip_whitespace(bytes, end);
+        match bytes.get(idx) {
+            Some(b',') => self.idx = idx + 1,
+            Some(b'}') => {
+                // The next call finds no key and stops
+                self.idx = idx;
+            }
+            _ => return None,
+        }
+        Some(Field { key, value: start..end })
+    }
+}
+
+/// Returns the position of the value of `key` in the object at `start`.
+///
+/// Only the object's own fields are matched, never those of nested values.
+pub fn field(bytes: &[u8], start: usize, key: &[u8]) -> Option<Range<usize>> {
+    fields(bytes, start).find(|field| field.key == key).map(|field| field.value)
+}
+
+/// Parses the number, quoted or not, that spans `value` at `scale`.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::json;
+///
+/// let msg = br#"{"b":"25.35190000","u":400900217}"#;
+/// let price = json::field(msg, 0, b"b").unwrap();
+/// assert_eq!(json::number(msg, price, 8), Ok(2_535_190_000));
+/// ```
+pub fn number(bytes: &[u8], value: Range<usize>, scale: u32) -> Result<i64, ParseError> {
+    let (start, end) = match bytes.get(value.start) {
+        Some(b'"') => (value.start + 1, value.end - 1),
+        _ => (value.start, value.end),
+    };
+    match parse_i64_with_precision(&bytes[..end], start, scale)? {
+        (number, next) if next == end => Ok(number),
+        _ => Err(ParseError::InvalidTerminator),
+    }
+}
+
+/// Returns the contents of the string that spans `value`, or `None` if it
+/// is not a string or contains escapes.
+pub fn string(bytes: &[u8], value: Range<usize>) -> Option<&str> {
+    let contents = bytes.get(value)?.strip_prefix(b"\"")?.strip_suffix(b"\"")?;
+    if contents.contains(&b'\\') {
+        return None;
+    }
+    std::str::from_utf8(contents).ok()
+}
+
+/// A simd-json parser that keeps its buffers and tape between messages.
+///
+/// Meant to live alongside one connection's driver, so full parses stop
+/// allocating once it has seen its largest message.
+#[cfg(feature = "simd-json")]
+pub struct Parser {
+    /// simd-json parses in place, so each message is copied in here first.
+    input: Vec<u8>,
+    buffers: simd_json::Buffers,
+    tape: simd_json::Tape<'static>,
+}
+
+#[cfg(feature = "simd-json")]
+impl Parser {
+    pub fn new() -> Self {
+        Self {
+            input: Vec::new(),
+            buffers: simd_json::Buffers::default(),
+            tape: simd_json::Tape::null(),
+        }
+    }
+
+    /// Parses `msg` and hands its root value to `read`.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "simd-json")] {
+    /// use rs_orderbook_streamer::json::Parser;
+    /// use simd_json::prelude::*;
+    ///
+    /// let mut parser = Parser::new();
+    /// let id = parser.parse(br#"{"lastUpdateId":160,"bids":[]}"#, |snapshot| {
+    ///     snapshot.get("lastUpdateId").and_then(|id| id.as_u64())
+    /// });
+    /// assert_eq!(id.unwrap(), Some(160));
+    /// # }
+    /// ```
+    pub fn parse<R>(&mut self, msg: &[u8], read: impl FnOnce(simd_json::tape::Value<'_, '_>) -> R) -> Result<R, simd_json::Error> {
+        self.input.clear();
+        self.input.extend_from_slice(msg);
+        let mut tape = std::mem::replace(&mut self.tape, simd_json::Tape::null()).reset();
+        let result = simd_json::fill_tape(&mut self.input, &mut self.buffers, &mut tape).map(|()| read(tape.as_value()));
+        self.tape = tape.reset();
+        result
+    }
+}
+
+#[cfg(feature = "simd-json")]
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the index just past the value starting at `idx`, or `None` if it
+/// is cut off.
+pub fn skip_value(bytes: &[u8], idx: usize) -> Option<usize> {
+    match bytes.get(idx)? {
+        b'"' => skip_string(bytes, idx),
+        b'{' | b'[' => {
+            let mut depth = 0usize;
+            let mut idx = idx;
+            loop {
+                match bytes.get(idx)? {
+                    b'"' => {
+                        idx = skip_string(bytes, idx)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(idx + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                idx += 1;
+            }
+        }
+        // Numbers, `true`, `false` and `null` run up to the next delimiter
+        _ => {
+            let len = bytes[idx..]
+                .iter()
+                .position(|&b| matches!(b, b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r'))
+                .unwrap_or(bytes.len() - idx);
+            (len > 0).then_some(idx + len)
+        }
+    }
+}
+
+/// Returns the index just past the string starting at `idx`.
+fn skip_string(bytes: &[u8], idx: usize) -> Option<usize> {
+    let mut idx = idx + 1;
+    loop {
+        match bytes.get(idx)? {
+            b'"' => return Some(idx + 1),
+            b'\\' => idx += 2,
+            _ => idx += 1,
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut idx: usize) -> usize {
+    while matches!(bytes.get(idx), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+        idx += 1;
+    }
+    idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        let msg = br#" { "stream" : "a\"b", "data":{"u":1,"x":[{"u":2}]} , "n":null,"t":true }"#;
+        let found: Vec<_> = fields(msg, 0).map(|field| (field.key, &msg[field.value])).collect();
+        assert_eq!(
+            found,
+            [
+                (&b"stream"[..], &br#""a\"b""#[..]),
+                (b"data", br#"{"u":1,"x":[{"u":2}]}"#),
+                (b"n", b"null"),
+                (b"t", b"true"),
+            ]
+        );
+
+        // Nested fields are only found from their own object
+        assert_eq!(field(msg, 0, b"u"), None);
+        let data = field(msg, 0, b"data").unwrap();
+        let u = field(msg, data.start, b"u").unwrap();
+        assert_eq!(number(msg, u, 0), Ok(1));
+        assert_eq!(string(msg, field(msg, 0, b"stream").unwrap()), None);
+
+        assert_eq!(fields(b"{}", 0).count(), 0);
+        assert_eq!(fields(b"[1]", 0).count(), 0);
+        // A cut-off message yields the fields before the cut
+        assert_eq!(fields(br#"{"a":1,"b":[1,2"#, 0).count(), 1);
+    }
+
+    #[test]
+    fn test_values() {
+        let msg = br#"{"p":"1.5","q":2,"s":"BTCUSDT","x":"1.5x","e":-3e2}"#;
+        let value = |key| field(msg, 0, key).unwrap();
+        assert_eq!(number(msg, value(b"p"), 2), Ok(150));
+        assert_eq!(number(msg, value(b"q"), 2), Ok(200));
+        assert_eq!(number(msg, value(b"e"), 0), Ok(-300));
+        assert_eq!(number(msg, value(b"x"), 2), Err(ParseError::InvalidTerminator));
+        assert_eq!(string(msg, value(b"s")), Some("BTCUSDT"));
+        assert_eq!(string(msg, value(b"q")), None);
+    }
+
+    #[cfg(feature = "simd-json")]
+    #[test]
+    fn test_parser_reuse() {
+        use simd_json::prelude::*;
+
+        let mut parser = Parser::new();
+        let long = format!(r#"{{"bids":[{}],"id":1}}"#, vec![r#"["1.0","2.0"]"#; 500].join(","));
+        let count = parser.parse(long.as_bytes(), |value| value.get("bids").and_then(|bids| bids.as_array()).map(|bids| bids.len()));
+        assert_eq!(count.unwrap(), Some(500));
+        let reply = parser.parse(br#"{"error":{"msg":"bad \"symbol\""}}"#, |value| {
+            Some(value.get("error")?.get("msg")?.as_str()?.to_string())
+        });
+        assert_eq!(reply.unwrap().as_deref(), Some(r#"bad "symbol""#));
+        assert!(parser.parse(b"{", |_| ()).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+// Without `std`, only the parser and book types build: `util`, `fixed` and
+// `model` less `SharedBook`, for gateways with no OS or allocator.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "std")]
+pub mod broker;
+#[cfg(feature = "std")]
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod connector;
+#[cfg(feature = "std")]
+pub mod driver;
+pub mod fixed;
+#[cfg(feature = "std")]
+pub mod json;
+pub mod model;
+pub mod util;
+#[cfg(feature = "std")]
+pub mod wait;
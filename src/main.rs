@@ -1,8 +1,3 @@
-mod model;
-mod util;
-mod broker;
-mod connector;
-
 fn main() {
     println!("Hello, world!");
 }
@@ -0,0 +1,208 @@
+//! Every level of a book, not only the [BOOK_DEPTH] best.
+
+use crate::model::{DEFAULT_EXPONENT, L1FriendlyBook, Level, Side, BOOK_DEPTH};
+use std::collections::BTreeMap;
+
+/// An unbounded L2 book keyed by fixed-point price.
+///
+/// Drivers whose feed reports more than [BOOK_DEPTH] levels keep one and
+/// [project](FullDepthBook::project) its best levels into the shared
+/// [L1FriendlyBook] after every update, so the L1 book always mirrors the
+/// top of this one. For the others the connector keeps one from their level
+/// updates, once set with
+/// [SharedBook::set_full_depth](crate::model::SharedBook::set_full_depth).
+/// Readers get hold of it through
+/// [SharedBook::full_depth](crate::model::SharedBook::full_depth).
+#[derive(Clone, Debug)]
+pub struct FullDepthBook {
+    bids: BTreeMap<i64, i64>,
+    asks: BTreeMap<i64, i64>,
+    pub price_exponent: i8,
+    pub qty_exponent: i8,
+}
+
+impl FullDepthBook {
+    pub fn new() -> Self {
+        Self {
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            price_exponent: DEFAULT_EXPONENT,
+            qty_exponent: DEFAULT_EXPONENT,
+        }
+    }
+
+    /// Sets the level at `price` to `qty`, removing it if `qty` is not
+    /// positive.
+    pub fn apply(&mut self, side: Side, price: i64, qty: i64) {
+        let levels = self.side_mut(side);
+        if qty > 0 {
+            levels.insert(price, qty);
+        } else {
+            levels.remove(&price);
+        }
+    }
+
+    /// Adds `delta` to the level at `price`, removing it once nothing is left.
+    pub fn add(&mut self, side: Side, price: i64, delta: i64) {
+        let levels = self.side_mut(side);
+        let qty = levels.entry(price).or_insert(0);
+        *qty += delta;
+        if *qty <= 0 {
+            levels.remove(&price);
+        }
+    }
+
+    /// Removes the levels of `side` at or better than `price`.
+    pub fn trim(&mut self, side: Side, price: i64) {
+        match side {
+            Side::Bid => drop(self.bids.split_off(&price)),
+            Side::Ask => self.asks = self.asks.split_off(&price.saturating_add(1)),
+        }
+    }
+
+    /// Removes every level of both sides.
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    /// Returns the number of levels on `side`.
+    pub fn len(&self, side: Side) -> usize {
+        match side {
+            Side::Bid => self.bids.len(),
+            Side::Ask => self.asks.len(),
+        }
+    }
+
+    /// Returns whether both sides are empty.
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /// Returns the bids, best (highest) first.
+    pub fn bids(&self) -> impl Iterator<Item = Level> + '_ {
+        self.bids.iter().rev().map(|(&price, &qty)| Level { price, qty })
+    }
+
+    /// Returns the asks, best (lowest) first.
+    pub fn asks(&self) -> impl Iterator<Item = Level> + '_ {
+        self.asks.iter().map(|(&price, &qty)| Level { price, qty })
+    }
+
+    /// Writes the best [BOOK_DEPTH] levels of each side and the exponents
+    /// into `book`, clearing the slots past the last level and the order
+    /// counts, which an L2 book does not hold.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use rs_orderbook_streamer::model::{FullDepthBook, L1FriendlyBook, Level, Side, BOOK_DEPTH};
+    /// let mut depth = FullDepthBook::new();
+    /// for price in 1..=100 {
+    ///     depth.apply(Side::Bid, price, 1);
+    /// }
+    /// let mut book = L1FriendlyBook::new();
+    /// depth.project(&mut book);
+    /// assert_eq!(book.bids[0], Level { price: 100, qty: 1 });
+    /// assert_eq!(book.bids[BOOK_DEPTH - 1].price, 69);
+    /// assert_eq!(book.asks[0], Level::default());
+    /// ```
+    pub fn project(&self, book: &mut L1FriendlyBook) {
+        book.bid_count = project_side(&mut book.bids, self.bids());
+        book.ask_count = project_side(&mut book.asks, self.asks());
+        book.bid_orders = [0; BOOK_DEPTH];
+        book.ask_orders = [0; BOOK_DEPTH];
+        book.price_exponent = self.price_exponent;
+        book.qty_exponent = self.qty_exponent;
+        #[cfg(feature = "notional")]
+        book.refresh_notionals();
+    }
+
+    fn side_mut(&mut self, side: Side) -> &mut BTreeMap<i64, i64> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+}
+
+impl Default for FullDepthBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Overwrites `side` with the first [BOOK_DEPTH] of `levels`, returning
+/// how many it holds.
+fn project_side(side: &mut [Level; BOOK_DEPTH], levels: impl Iterator<Item = Level>) -> u8 {
+    let mut filled = 0;
+    for (slot, level) in side.iter_mut().zip(levels) {
+        *slot = level;
+        filled += 1;
+    }
+    side[filled..].fill(Level::default());
+    filled as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_add() {
+        let mut depth = FullDepthBook::new();
+        depth.apply(Side::Ask, 101, 5);
+        depth.apply(Side::Ask, 103, 1);
+        depth.add(Side::Ask, 101, 2);
+        depth.add(Side::Ask, 102, 4);
+        assert_eq!(depth.asks().collect::<Vec<_>>(), [
+            Level { price: 101, qty: 7 },
+            Level { price: 102, qty: 4 },
+            Level { price: 103, qty: 1 },
+        ]);
+
+        depth.add(Side::Ask, 102, -4);
+        depth.apply(Side::Ask, 103, 0);
+        assert_eq!(depth.len(Side::Ask), 1);
+        assert_eq!(depth.len(Side::Bid), 0);
+
+        depth.apply(Side::Ask, 104, 2);
+        depth.apply(Side::Ask, 105, 3);
+        depth.trim(Side::Ask, 104);
+        assert_eq!(depth.asks().collect::<Vec<_>>(), [Level { price: 105, qty: 3 }]);
+        depth.apply(Side::Bid, 99, 1);
+        depth.apply(Side::Bid, 98, 1);
+        depth.trim(Side::Bid, 99);
+        assert_eq!(depth.bids().collect::<Vec<_>>(), [Level { price: 98, qty: 1 }]);
+
+        depth.clear();
+        assert!(depth.is_empty());
+    }
+
+    #[test]
+    fn test_project_keeps_top_levels() {
+        let mut depth = FullDepthBook::new();
+        for i in 0..2 * BOOK_DEPTH as i64 {
+            depth.apply(Side::Bid, 1_000 - i, 1);
+            depth.apply(Side::Ask, 1_001 + i, 1);
+        }
+        depth.price_exponent = -2;
+
+        let mut book = L1FriendlyBook::new();
+        depth.project(&mut book);
+        assert_eq!(book.bids[BOOK_DEPTH - 1].price, 1_000 - (BOOK_DEPTH as i64 - 1));
+        assert_eq!(book.asks[BOOK_DEPTH - 1].price, 1_001 + (BOOK_DEPTH as i64 - 1));
+        assert_eq!(book.price_exponent, -2);
+
+        // Levels below the top stay in the full book only
+        depth.apply(Side::Bid, 1_000, 0);
+        depth.project(&mut book);
+        assert_eq!(book.bids[0].price, 999);
+        assert_eq!(book.bids[BOOK_DEPTH - 1].price, 1_000 - BOOK_DEPTH as i64);
+        assert_eq!(depth.len(Side::Bid), 2 * BOOK_DEPTH - 1);
+
+        depth.clear();
+        depth.project(&mut book);
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.asks[0], Level::default());
+    }
+}
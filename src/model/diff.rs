@@ -0,0 +1,197 @@
+//! Level-wise differences between two copies of a book.
+
+use crate::model::{Level, Side};
+
+/// A level that differs between two copies of a book, from [diff].
+///
+/// A `qty` of `None` means the level is gone; `Some(0)` is a level held
+/// without quantity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelChange {
+    pub side: Side,
+    pub price: i64,
+    pub qty: Option<i64>,
+}
+
+/// Returns the levels that changed from `old` to `new`, bids then asks,
+/// each best first.
+///
+/// Both are copies laid out like
+/// [read_consistent](crate::model::L1FriendlyBook::read_consistent) fills
+/// them, bids in the first half and asks in the second, and must be the
+/// same length. Empty slots are skipped, so only the levels added,
+/// resized or removed come out; replaying them on a book holding
+/// `old` with [apply_change](crate::model::L1FriendlyBook::apply_change)
+/// leaves it holding `new`.
+///
+/// # Examples
+/// ```rust
+/// # use rs_orderbook_streamer::model::{diff, Level, LevelChange, Side};
+/// let old = [Level { price: 99, qty: 1 }, Level { price: 101, qty: 2 }];
+/// let new = [Level { price: 99, qty: 1 }, Level { price: 100, qty: 3 }];
+/// let changes: Vec<_> = diff(&old, &new).collect();
+/// assert_eq!(changes, [
+///     LevelChange { side: Side::Ask, price: 100, qty: Some(3) },
+///     LevelChange { side: Side::Ask, price: 101, qty: None },
+/// ]);
+/// ```
+pub fn diff<'a>(old: &'a [Level], new: &'a [Level]) -> LevelDiff<'a> {
+    assert_eq!(old.len(), new.len(), "copies of different depths");
+    LevelDiff {
+        old,
+        new,
+        side: Side::Bid,
+        old_idx: 0,
+        new_idx: 0,
+    }
+}
+
+/// Iterator over the [LevelChange]s between two copies of a book.
+pub struct LevelDiff<'a> {
+    old: &'a [Level],
+    new: &'a [Level],
+    side: Side,
+    old_idx: usize,
+    new_idx: usize,
+}
+
+impl<'a> LevelDiff<'a> {
+    /// Returns the current side of both copies.
+    fn sides(&self) -> (&'a [Level], &'a [Level]) {
+        let half = self.old.len() / 2;
+        let range = match self.side {
+            Side::Bid => 0..half,
+            Side::Ask => half..2 * half,
+        };
+        (&self.old[range.clone()], &self.new[range])
+    }
+
+    fn change(&self, level: Level, qty: Option<i64>) -> LevelChange {
+        LevelChange {
+            side: self.side,
+            price: level.price,
+            qty,
+        }
+    }
+}
+
+impl Iterator for LevelDiff<'_> {
+    type Item = LevelChange;
+
+    fn next(&mut self) -> Option<LevelChange> {
+        loop {
+            let (old, new) = self.sides();
+            while old.get(self.old_idx).is_some_and(|level| *level == Level::default()) {
+                self.old_idx += 1;
+            }
+            while new.get(self.new_idx).is_some_and(|level| *level == Level::default()) {
+                self.new_idx += 1;
+            }
+
+            // Whichever level comes first in the side's order is the one
+            // missing from the other copy
+            let removed = match (old.get(self.old_idx), new.get(self.new_idx)) {
+                (None, None) => {
+                    if self.side == Side::Ask {
+                        return None;
+                    }
+                    self.side = Side::Ask;
+                    (self.old_idx, self.new_idx) = (0, 0);
+                    continue;
+                }
+                (Some(was), Some(now)) if was.price == now.price => {
+                    self.old_idx += 1;
+                    self.new_idx += 1;
+                    if was.qty != now.qty {
+                        return Some(self.change(*now, Some(now.qty)));
+                    }
+                    continue;
+                }
+                (Some(was), Some(now)) => match self.side {
+                    Side::Bid => was.price > now.price,
+                    Side::Ask => was.price < now.price,
+                },
+                (was, _) => was.is_some(),
+            };
+            return Some(if removed {
+                self.old_idx += 1;
+                self.change(old[self.old_idx - 1], None)
+            } else {
+                self.new_idx += 1;
+                self.change(new[self.new_idx - 1], Some(new[self.new_idx - 1].qty))
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{L1FriendlyBook, BOOK_DEPTH};
+
+    fn copy(book: &L1FriendlyBook) -> [Level; 2 * BOOK_DEPTH] {
+        let mut out = [Level::default(); 2 * BOOK_DEPTH];
+        book.read_consistent(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_diff_replays_onto_old_book() {
+        let mut book = L1FriendlyBook::new();
+        for i in 0..BOOK_DEPTH as i64 {
+            book.apply_bid(1_000 - 2 * i, 1);
+            book.apply_ask(1_001 + 2 * i, 1);
+        }
+        let old = copy(&book);
+        let mut replica = L1FriendlyBook::new();
+        for change in diff(&[Level::default(); 2 * BOOK_DEPTH], &old) {
+            replica.apply_change(change);
+        }
+        assert_eq!(copy(&replica), old);
+
+        // A new best bid pushes the worst out, one resize, one removal
+        book.apply_bid(1_001, 4);
+        book.apply_bid(998, 7);
+        book.apply_ask(1_003, 0);
+        let new = copy(&book);
+        let changes: Vec<_> = diff(&old, &new).collect();
+        assert_eq!(changes, [
+            LevelChange { side: Side::Bid, price: 1_001, qty: Some(4) },
+            LevelChange { side: Side::Bid, price: 998, qty: Some(7) },
+            LevelChange { side: Side::Bid, price: 1_000 - 2 * (BOOK_DEPTH as i64 - 1), qty: None },
+            LevelChange { side: Side::Ask, price: 1_003, qty: None },
+        ]);
+
+        for change in changes {
+            replica.apply_change(change);
+        }
+        assert_eq!(copy(&replica), new);
+        assert_eq!(diff(&new, &new).count(), 0);
+    }
+
+    #[test]
+    fn test_diff_keeps_zero_qty_levels() {
+        let mut book = L1FriendlyBook::new();
+        book.apply_bid(100, 5);
+        book.apply_bid(99, 1);
+        let old = copy(&book);
+        let mut replica = L1FriendlyBook::new();
+        replica.apply_bid(100, 5);
+        replica.apply_bid(99, 1);
+
+        // A snapshot holding the best bid without quantity
+        let mut new = old;
+        new[0].qty = 0;
+        let changes: Vec<_> = diff(&old, &new).collect();
+        assert_eq!(changes, [LevelChange { side: Side::Bid, price: 100, qty: Some(0) }]);
+        for change in changes {
+            replica.apply_change(change);
+        }
+        assert_eq!(copy(&replica), new);
+
+        let changes: Vec<_> = diff(&new, &old).collect();
+        assert_eq!(changes, [LevelChange { side: Side::Bid, price: 100, qty: Some(5) }]);
+        let removed: Vec<_> = diff(&new, &[Level::default(); 2 * BOOK_DEPTH]).collect();
+        assert_eq!(removed[0], LevelChange { side: Side::Bid, price: 100, qty: None });
+    }
+}
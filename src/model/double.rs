@@ -0,0 +1,186 @@
+//! A book that readers see whole without retrying.
+
+use crate::model::L1FriendlyBook;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// An [L1FriendlyBook] kept in two copies: readers hold the front one while
+/// the writer applies each packet to the back one and then flips them.
+///
+/// Where [L1FriendlyBook::read_consistent] retries while a packet is being
+/// applied, a [read](DoubleBufferedBook::read) here never sees a packet
+/// half-done, at the cost of copying the book once per packet. The writer
+/// waits instead, for readers still holding the back copy, so it suits
+/// readers that hold on briefly and care little for latency.
+pub struct DoubleBufferedBook {
+    buffers: [UnsafeCell<L1FriendlyBook>; 2],
+    /// Index of the buffer new readers get.
+    front: AtomicUsize,
+    /// Readers holding each buffer.
+    readers: [AtomicUsize; 2],
+}
+
+// SAFETY: The single-writer contract is upheld by callers of `write`, which
+// only touches the buffer no reader holds.
+unsafe impl Sync for DoubleBufferedBook {}
+
+impl DoubleBufferedBook {
+    pub fn new() -> Self {
+        Self {
+            buffers: [UnsafeCell::new(L1FriendlyBook::new()), UnsafeCell::new(L1FriendlyBook::new())],
+            front: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+        }
+    }
+
+    /// Returns the latest published book, which stays unchanged until the
+    /// guard is dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use rs_orderbook_streamer::model::{DoubleBufferedBook, Level};
+    /// let book = DoubleBufferedBook::new();
+    /// // SAFETY: The only writer.
+    /// unsafe { book.write(|book| book.apply_bid(100, 5)) };
+    /// assert_eq!(book.read().bids[0], Level { price: 100, qty: 5 });
+    /// ```
+    pub fn read(&self) -> ReadGuard<'_> {
+        loop {
+            let index = self.front.load(Ordering::SeqCst);
+            self.readers[index].fetch_add(1, Ordering::SeqCst);
+            // The writer may have flipped and started on this buffer before
+            // the count went up; it cannot once the count is seen
+            if self.front.load(Ordering::SeqCst) == index {
+                return ReadGuard { book: self, index };
+            }
+            self.readers[index].fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Applies one packet with `update` to a copy of the published book and
+    /// publishes the result, returning what `update` returned.
+    ///
+    /// Waits first for readers still holding the back copy.
+    ///
+    /// # Safety
+    /// The caller must be the sole writer for this book.
+    pub unsafe fn write<R>(&self, update: impl FnOnce(&mut L1FriendlyBook) -> R) -> R {
+        let front = self.front.load(Ordering::Relaxed);
+        let back = 1 - front;
+        while self.readers[back].load(Ordering::SeqCst) != 0 {
+            spin_loop();
+        }
+        // SAFETY: No reader holds the back buffer and new ones get the
+        // front, which only this writer reads.
+        let (book, published) = unsafe { (&mut *self.buffers[back].get(), &*self.buffers[front].get()) };
+        book.bids = published.bids;
+        book.asks = published.asks;
+        book.bid_count = published.bid_count;
+        book.ask_count = published.ask_count;
+        book.bid_tombstones = published.bid_tombstones;
+        book.ask_tombstones = published.ask_tombstones;
+        book.bid_orders = published.bid_orders;
+        book.ask_orders = published.ask_orders;
+        #[cfg(feature = "notional")]
+        {
+            book.bid_notional = published.bid_notional;
+            book.ask_notional = published.ask_notional;
+        }
+        book.price_exponent = published.price_exponent;
+        book.qty_exponent = published.qty_exponent;
+        book.stale.store(published.is_stale(), Ordering::Relaxed);
+        book.set_update_cause(published.update_cause());
+        book.gap_count.store(published.gap_count(), Ordering::Relaxed);
+        book.truncated.store(published.truncated(), Ordering::Relaxed);
+        for (field, value) in [
+            (&book.update_id, &published.update_id),
+            (&book.exchange_ts, &published.exchange_ts),
+            (&book.recv_ts, &published.recv_ts),
+            (&book.publish_ts, &published.publish_ts),
+        ] {
+            field.store(value.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        book.version.store(published.version.load(Ordering::Relaxed), Ordering::Relaxed);
+
+        book.begin_write();
+        let result = update(book);
+        book.end_write();
+        self.front.store(back, Ordering::SeqCst);
+        result
+    }
+}
+
+impl Default for DoubleBufferedBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A published book held by a reader; see [DoubleBufferedBook::read].
+pub struct ReadGuard<'a> {
+    book: &'a DoubleBufferedBook,
+    index: usize,
+}
+
+impl Deref for ReadGuard<'_> {
+    type Target = L1FriendlyBook;
+
+    fn deref(&self) -> &L1FriendlyBook {
+        // SAFETY: The writer leaves a buffer alone while it has readers.
+        unsafe { &*self.book.buffers[self.index].get() }
+    }
+}
+
+impl Drop for ReadGuard<'_> {
+    fn drop(&mut self) {
+        self.book.readers[self.index].fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::tests::{fill, race};
+    use crate::model::Level;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_tear_free_reads() {
+        let book = Arc::new(DoubleBufferedBook::new());
+        let mut last = 0;
+        // SAFETY: The race's writer thread is the only writer.
+        let write = |book: &DoubleBufferedBook, packet| unsafe { book.write(|book| fill(book, packet)) };
+        race(&book, 5_000, write, |book| {
+            let read = book.read();
+            let packet = read.bids[0].qty;
+            assert!(read.bids.iter().chain(&read.asks).all(|level| level.qty == packet));
+            // Versions are published in order and always even
+            let version = read.version.load(Ordering::Acquire);
+            assert!(version >= last && version & 1 == 0);
+            last = version;
+        });
+        let read = book.read();
+        assert_eq!((read.bids[0].qty, read.version.load(Ordering::Acquire)), (5_000, 10_000));
+    }
+
+    #[test]
+    fn test_writes_start_from_published_stamps() {
+        let book = DoubleBufferedBook::new();
+        // SAFETY: This thread is the only writer.
+        unsafe {
+            book.write(|book| {
+                book.update_id.store(7, Ordering::Relaxed);
+                book.exchange_ts.store(1, Ordering::Relaxed);
+                book.recv_ts.store(2, Ordering::Relaxed);
+                book.publish_ts.store(3, Ordering::Relaxed);
+            });
+            book.write(|book| book.bids[0] = Level { price: 100, qty: 1 });
+        }
+        let read = book.read();
+        let stamps = [&read.exchange_ts, &read.recv_ts, &read.publish_ts].map(|stamp| stamp.load(Ordering::Relaxed));
+        assert_eq!(stamps, [1, 2, 3]);
+        assert_eq!(read.update_id.load(Ordering::Relaxed), 7);
+    }
+}
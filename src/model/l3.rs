@@ -0,0 +1,162 @@
+//! Market-by-order book state.
+
+use crate::model::{FullDepthBook, Side};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A resting order in an [OrderBookL3].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Order {
+    pub side: Side,
+    pub price: i64,
+    pub qty: i64,
+}
+
+/// An order-by-order book for venues that publish every order, such as the
+/// Coinbase `full` channel or ITCH.
+///
+/// Orders are keyed by the venue's order id, `String` for JSON feeds or a
+/// numeric reference for binary ones. Every change is also added to a
+/// per-price [FullDepthBook] passed in by the caller, so a driver can keep
+/// that aggregate shared with readers and
+/// [project](FullDepthBook::project) it into the L1 book.
+#[derive(Clone, Debug)]
+pub struct OrderBookL3<K = String> {
+    orders: HashMap<K, Order>,
+}
+
+impl<K: Hash + Eq> OrderBookL3<K> {
+    pub fn new() -> Self {
+        Self { orders: HashMap::new() }
+    }
+
+    /// Adds a resting order and its quantity to `levels`, replacing any
+    /// order already resting under `id`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use rs_orderbook_streamer::model::{FullDepthBook, Level, OrderBookL3, Side};
+    /// let mut orders = OrderBookL3::<u64>::new();
+    /// let mut levels = FullDepthBook::new();
+    /// orders.open(1, Side::Bid, 100, 5, &mut levels);
+    /// orders.open(2, Side::Bid, 100, 3, &mut levels);
+    /// orders.resize(&1, |qty| qty - 2, &mut levels);
+    /// assert_eq!(levels.bids().next(), Some(Level { price: 100, qty: 6 }));
+    /// ```
+    pub fn open(&mut self, id: K, side: Side, price: i64, qty: i64, levels: &mut FullDepthBook) {
+        if qty <= 0 {
+            return;
+        }
+        levels.add(side, price, qty);
+        if let Some(old) = self.orders.insert(id, Order { side, price, qty }) {
+            levels.add(old.side, old.price, -old.qty);
+        }
+    }
+
+    /// Sets the quantity of order `id` to `new_qty(qty)`, removing it once
+    /// nothing is left.
+    ///
+    /// Returns `false` if the order is not resting on the book.
+    pub fn resize<Q>(&mut self, id: &Q, new_qty: impl FnOnce(i64) -> i64, levels: &mut FullDepthBook) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(order) = self.orders.get_mut(id) else {
+            return false;
+        };
+        let qty = new_qty(order.qty).max(0);
+        levels.add(order.side, order.price, qty - order.qty);
+        order.qty = qty;
+        if qty == 0 {
+            self.orders.remove(id);
+        }
+        true
+    }
+
+    /// Removes order `id` and its quantity from `levels`.
+    pub fn remove<Q>(&mut self, id: &Q, levels: &mut FullDepthBook) -> Option<Order>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let order = self.orders.remove(id)?;
+        levels.add(order.side, order.price, -order.qty);
+        Some(order)
+    }
+
+    /// Returns the order resting under `id`.
+    pub fn get<Q>(&self, id: &Q) -> Option<&Order>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.orders.get(id)
+    }
+
+    /// Returns the number of resting orders.
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// Removes every order, and every level of `levels`.
+    pub fn clear(&mut self, levels: &mut FullDepthBook) {
+        self.orders.clear();
+        levels.clear();
+    }
+
+    /// Rebuilds the per-price aggregate from the resting orders.
+    pub fn aggregate(&self) -> FullDepthBook {
+        let mut levels = FullDepthBook::new();
+        for order in self.orders.values() {
+            levels.add(order.side, order.price, order.qty);
+        }
+        levels
+    }
+}
+
+impl<K: Hash + Eq> Default for OrderBookL3<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{L1FriendlyBook, Level};
+
+    #[test]
+    fn test_orders_aggregate_into_levels() {
+        let mut orders = OrderBookL3::<String>::new();
+        let mut levels = FullDepthBook::new();
+        orders.open("a".into(), Side::Ask, 101, 4, &mut levels);
+        orders.open("b".into(), Side::Ask, 101, 1, &mut levels);
+        orders.open("c".into(), Side::Bid, 99, 2, &mut levels);
+        orders.open("d".into(), Side::Ask, 102, 0, &mut levels);
+        assert_eq!(orders.len(), 3);
+
+        // Replacing an order moves its quantity to the new price
+        orders.open("b".into(), Side::Ask, 103, 2, &mut levels);
+        assert_eq!(levels.asks().collect::<Vec<_>>(), [Level { price: 101, qty: 4 }, Level { price: 103, qty: 2 }]);
+
+        assert!(orders.resize("a", |qty| qty - 10, &mut levels));
+        assert!(!orders.resize("a", |_| 1, &mut levels));
+        assert_eq!(orders.remove("c", &mut levels), Some(Order { side: Side::Bid, price: 99, qty: 2 }));
+        assert_eq!(orders.get("b").map(|order| order.qty), Some(2));
+
+        let mut book = L1FriendlyBook::new();
+        levels.project(&mut book);
+        assert_eq!(book.best_ask(), Some(Level { price: 103, qty: 2 }));
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(orders.aggregate().asks().collect::<Vec<_>>(), levels.asks().collect::<Vec<_>>());
+
+        orders.clear(&mut levels);
+        assert!(orders.is_empty() && levels.is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+//! The book as shared between a connector and its readers.
+
+use crate::model::{FullDepthBook, L1FriendlyBook};
+use crate::wait::{Park, WaitStrategy};
+use parking_lot::{Mutex, RwLock};
+use std::cell::UnsafeCell;
+use std::hint::spin_loop;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// An [L1FriendlyBook] shared between one pinned writer and many readers.
+///
+/// The broker hands the same `SharedBook` to every subscriber and to the
+/// connector that owns the stream. Readers go through `Deref` and use the
+/// `version` counter to detect updates, or wait for one with
+/// [SharedBook::wait_for_update]; only the connector thread may write.
+pub struct SharedBook {
+    inner: UnsafeCell<L1FriendlyBook>,
+    /// Readers parked in [SharedBook::wait_for_update].
+    waiters: Mutex<Vec<Thread>>,
+    /// Length of `waiters`, read by the writer without locking.
+    parked: AtomicUsize,
+    /// Every level behind the book, for drivers that keep one.
+    full_depth: Mutex<Option<Arc<RwLock<FullDepthBook>>>>,
+    /// The levels dropped past [BOOK_DEPTH](crate::model::BOOK_DEPTH), if
+    /// they are being kept.
+    overflow: Mutex<Option<Arc<RwLock<FullDepthBook>>>>,
+}
+
+// SAFETY: The single-writer contract is upheld by the connector, which is the
+// only caller of `writer`. Readers synchronise on `version` (Release/Acquire).
+unsafe impl Sync for SharedBook {}
+
+impl SharedBook {
+    pub fn new() -> Self {
+        Self {
+            inner: UnsafeCell::new(L1FriendlyBook::new()),
+            waiters: Mutex::new(Vec::new()),
+            parked: AtomicUsize::new(0),
+            full_depth: Mutex::new(None),
+            overflow: Mutex::new(None),
+        }
+    }
+
+    /// Waits until the version moves past `seen` to a finalized (even)
+    /// version and returns it, or `None` once `timeout` has elapsed.
+    ///
+    /// Parked readers are woken by the connector as it publishes, so
+    /// [WaitStrategy::Block] costs no CPU while the book is quiet.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use rs_orderbook_streamer::model::SharedBook;
+    /// # use rs_orderbook_streamer::wait::WaitStrategy;
+    /// # use std::time::Duration;
+    /// let book = SharedBook::new();
+    /// let seen = book.version.load(std::sync::atomic::Ordering::Acquire);
+    /// let update = book.wait_for_update(seen, WaitStrategy::Block, Duration::from_millis(1));
+    /// assert_eq!(update, None);
+    /// ```
+    pub fn wait_for_update(&self, seen: u64, strategy: WaitStrategy, timeout: Duration) -> Option<u64> {
+        let deadline = Instant::now() + timeout;
+        let mut idle = 0u32;
+        loop {
+            let version = self.version.load(Ordering::Acquire);
+            if version != seen && version & 1 == 0 {
+                return Some(version);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            match strategy.park(idle, true) {
+                Park::No => spin_loop(),
+                Park::For(limit) => self.park(seen, limit.min(deadline - now)),
+                Park::UntilWoken => self.park(seen, deadline - now),
+            }
+            idle = idle.saturating_add(1);
+        }
+    }
+
+    /// Parks the calling reader for at most `limit` unless the version has
+    /// already moved past `seen`.
+    fn park(&self, seen: u64, limit: Duration) {
+        self.waiters.lock().push(thread::current());
+        self.parked.fetch_add(1, Ordering::SeqCst);
+        // Pairs with the fence in `wake_readers`: either the writer sees this
+        // reader parked, or this reader sees the new version
+        if self.version.load(Ordering::SeqCst) == seen {
+            thread::park_timeout(limit);
+        }
+        self.parked.fetch_sub(1, Ordering::SeqCst);
+        let id = thread::current().id();
+        self.waiters.lock().retain(|waiter| waiter.id() != id);
+    }
+
+    /// Wakes readers parked in [SharedBook::wait_for_update].
+    ///
+    /// Called by the writer after [L1FriendlyBook::end_write]; costs
+    /// a fence and one load while nobody is parked.
+    pub fn wake_readers(&self) {
+        fence(Ordering::SeqCst);
+        if self.parked.load(Ordering::Relaxed) > 0 {
+            for waiter in self.waiters.lock().iter() {
+                waiter.unpark();
+            }
+        }
+    }
+
+    /// Returns the [FullDepthBook] this book is a projection of, if the
+    /// driver feeding it keeps one or one was [set](SharedBook::set_full_depth).
+    ///
+    /// It is updated under the write lock before the L1 version is
+    /// published, so a reader holding the read lock sees a book at least as
+    /// new as the L1 version it last read.
+    pub fn full_depth(&self) -> Option<Arc<RwLock<FullDepthBook>>> {
+        self.full_depth.lock().clone()
+    }
+
+    /// Has the connector keep every level of this book in `depth`, from the
+    /// next time it subscribes the book.
+    ///
+    /// A driver that keeps its own full book, see
+    /// [ExchangeDriver::full_depth](crate::driver::ExchangeDriver::full_depth),
+    /// replaces it with that one. Otherwise the connector applies every
+    /// update the driver makes through
+    /// [apply_level](crate::driver::apply_level) to it, and makes its top
+    /// match the book after every snapshot, resync or trade-through. Levels
+    /// a driver skips while parsing a whole side at once never reach it.
+    /// It is cleared with the book on every resync.
+    pub fn set_full_depth(&self, depth: Option<Arc<RwLock<FullDepthBook>>>) {
+        *self.full_depth.lock() = depth;
+    }
+
+    /// Returns the cold tail of levels the book dropped past
+    /// [BOOK_DEPTH](crate::model::BOOK_DEPTH), if one was set.
+    pub fn overflow(&self) -> Option<Arc<RwLock<FullDepthBook>>> {
+        self.overflow.lock().clone()
+    }
+
+    /// Has the connector keep the levels this book drops in `tail`, from
+    /// the next time it subscribes the book.
+    ///
+    /// The tail holds the levels strictly worse than the book's worst:
+    /// those pushed off its end, and updates to prices beyond it. Levels
+    /// never move back up from it, and levels a driver skips while parsing
+    /// a whole side at once are counted but not kept, so it is a best
+    /// effort view of the depth beyond the book rather than a full one. It
+    /// is cleared by every snapshot and resync.
+    pub fn set_overflow(&self, tail: Option<Arc<RwLock<FullDepthBook>>>) {
+        *self.overflow.lock() = tail;
+    }
+
+    /// Returns a mutable view of the book for the owning connector.
+    ///
+    /// # Safety
+    /// The caller must be the sole writer for this book, and must not hold
+    /// the returned reference across calls that hand out another one.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn writer(&self) -> &mut L1FriendlyBook {
+        unsafe { &mut *self.inner.get() }
+    }
+}
+
+impl Default for SharedBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for SharedBook {
+    type Target = L1FriendlyBook;
+
+    fn deref(&self) -> &L1FriendlyBook {
+        // SAFETY: Readers only observe the book; see the type-level contract.
+        unsafe { &*self.inner.get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::tests::{fill, race};
+    use crate::model::{BookSnapshot, Level, BOOK_DEPTH};
+
+    #[test]
+    fn test_read_consistent() {
+        let book = Arc::new(SharedBook::new());
+        let mut out = [Level::default(); 2 * BOOK_DEPTH];
+        let write = |book: &SharedBook, packet| {
+            // SAFETY: The race's writer thread is the only writer.
+            let levels = unsafe { book.writer() };
+            levels.begin_write();
+            fill(levels, packet);
+            levels.end_write();
+        };
+        // Every copy holds one packet, whatever the interleaving
+        race(&book, 20_000, write, |book| {
+            let version = book.read_consistent(&mut out);
+            assert_eq!(version % 2, 0);
+            assert!(out.iter().all(|level| level.qty == out[0].qty));
+        });
+        assert_eq!(book.read_consistent(&mut out), 40_000);
+        assert!(out.iter().all(|level| level.qty == 20_000));
+    }
+
+    #[test]
+    fn test_copy_snapshot() {
+        let book = Arc::new(SharedBook::new());
+        let mut snapshot = BookSnapshot::default();
+        let write = |book: &SharedBook, packet| {
+            // SAFETY: The race's writer thread is the only writer.
+            let levels = unsafe { book.writer() };
+            levels.begin_write();
+            fill(levels, packet);
+            levels.bid_count = (packet % BOOK_DEPTH as i64) as u8;
+            levels.update_id.store(packet as u64, Ordering::Relaxed);
+            levels.publish_ts.store(packet as u64, Ordering::Relaxed);
+            levels.end_write();
+        };
+        // The levels and metadata always come from the same packet
+        race(&book, 20_000, write, |book| {
+            book.copy_snapshot(&mut snapshot);
+            let packet = snapshot.asks[0].qty;
+            assert_eq!(snapshot.version, 2 * packet as u64);
+            assert_eq!(snapshot.bid_count, (packet % BOOK_DEPTH as i64) as u8);
+            assert_eq!((snapshot.update_id, snapshot.publish_ts), (packet as u64, packet as u64));
+            assert!(snapshot.bids.iter().chain(&snapshot.asks).all(|level| level.qty == packet));
+        });
+        book.copy_snapshot(&mut snapshot);
+        assert_eq!(snapshot.version, 40_000);
+    }
+}
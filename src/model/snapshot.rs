@@ -0,0 +1,224 @@
+//! A book copied out of its seqlock, and its fixed binary layout.
+//!
+//! The layout is [SNAPSHOT_LEN] little-endian bytes: a 64-byte header,
+//! then the [BOOK_DEPTH] bids and asks as `price` and `qty` pairs of
+//! `i64`, empty slots included, so every snapshot is the same size and
+//! each field sits at a fixed offset:
+//!
+//! | Offset | Field |
+//! |-------:|-------|
+//! | 0 | [SNAPSHOT_MAGIC] (`u32`) |
+//! | 4 | [SNAPSHOT_LAYOUT] (`u16`) |
+//! | 6 | `bid_count`, `ask_count` (`u8`) |
+//! | 8 | `price_exponent`, `qty_exponent` (`i8`) |
+//! | 10 | flags (`u8`): bit 0 stale |
+//! | 11 | [UpdateCause] (`u8`) |
+//! | 16 | `version`, `update_id`, `exchange_ts`, `recv_ts`, `publish_ts` (`u64`) |
+//! | 64 | bids |
+//! | 64 + 16 × [BOOK_DEPTH] | asks |
+//!
+//! Unused header bytes are zero. Order counts and notionals are not
+//! carried.
+
+use crate::model::{Level, UpdateCause, BOOK_DEPTH};
+
+/// First bytes of every snapshot, `OBS1` in ASCII.
+pub const SNAPSHOT_MAGIC: u32 = u32::from_le_bytes(*b"OBS1");
+/// Revision of the layout, bumped by any change to it.
+pub const SNAPSHOT_LAYOUT: u16 = 1;
+
+const HEADER_LEN: usize = 64;
+const LEVEL_LEN: usize = 16;
+/// Size of an encoded [BookSnapshot].
+pub const SNAPSHOT_LEN: usize = HEADER_LEN + 2 * BOOK_DEPTH * LEVEL_LEN;
+
+/// Header flag set while the book was being rebuilt.
+const STALE: u8 = 1;
+
+/// Why [BookSnapshot::from_bytes] rejected its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// Fewer than [SNAPSHOT_LEN] bytes.
+    Truncated,
+    /// Not a snapshot, or one of a layout this build does not know.
+    UnknownLayout { magic: u32, layout: u16 },
+    /// A level count past [BOOK_DEPTH], or an unknown [UpdateCause].
+    Malformed,
+}
+
+/// An owned, coherent copy of an [L1FriendlyBook](crate::model::L1FriendlyBook)
+/// and its metadata, filled by
+/// [copy_snapshot](crate::model::L1FriendlyBook::copy_snapshot).
+///
+/// Strategies keep one around and refill it, holding no borrow of the
+/// shared book in between. [to_bytes](BookSnapshot::to_bytes) and
+/// [from_bytes](BookSnapshot::from_bytes) carry it in the one fixed layout
+/// shared-memory publishers, recorders and re-publishers agree on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BookSnapshot {
+    pub bids: [Level; BOOK_DEPTH],
+    pub asks: [Level; BOOK_DEPTH],
+    pub bid_count: u8,
+    pub ask_count: u8,
+    pub price_exponent: i8,
+    pub qty_exponent: i8,
+    pub stale: bool,
+    pub cause: UpdateCause,
+    /// The book's version when copied, always even.
+    pub version: u64,
+    pub update_id: u64,
+    pub exchange_ts: u64,
+    pub recv_ts: u64,
+    pub publish_ts: u64,
+    pub bid_orders: [u32; BOOK_DEPTH],
+    pub ask_orders: [u32; BOOK_DEPTH],
+    #[cfg(feature = "notional")]
+    pub bid_notional: [i64; BOOK_DEPTH],
+    #[cfg(feature = "notional")]
+    pub ask_notional: [i64; BOOK_DEPTH],
+}
+
+impl BookSnapshot {
+    /// Returns the populated bids, best first.
+    pub fn bids(&self) -> &[Level] {
+        &self.bids[..usize::from(self.bid_count)]
+    }
+
+    /// Returns the populated asks, best first.
+    pub fn asks(&self) -> &[Level] {
+        &self.asks[..usize::from(self.ask_count)]
+    }
+}
+
+impl BookSnapshot {
+    /// Encodes the snapshot in the fixed layout.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use rs_orderbook_streamer::model::{BookSnapshot, L1FriendlyBook, SNAPSHOT_LEN};
+    /// let mut book = L1FriendlyBook::new();
+    /// book.apply_bid(99, 1);
+    /// let mut snapshot = BookSnapshot::default();
+    /// book.copy_snapshot(&mut snapshot);
+    ///
+    /// let bytes = snapshot.to_bytes();
+    /// assert_eq!(bytes.len(), SNAPSHOT_LEN);
+    /// assert_eq!(BookSnapshot::from_bytes(&bytes).unwrap().bids(), snapshot.bids());
+    /// ```
+    pub fn to_bytes(&self) -> [u8; SNAPSHOT_LEN] {
+        let mut bytes = [0; SNAPSHOT_LEN];
+        bytes[0..4].copy_from_slice(&SNAPSHOT_MAGIC.to_le_bytes());
+        bytes[4..6].copy_from_slice(&SNAPSHOT_LAYOUT.to_le_bytes());
+        bytes[6] = self.bid_count;
+        bytes[7] = self.ask_count;
+        bytes[8] = self.price_exponent as u8;
+        bytes[9] = self.qty_exponent as u8;
+        bytes[10] = if self.stale { STALE } else { 0 };
+        bytes[11] = self.cause as u8;
+        let stamps = [self.version, self.update_id, self.exchange_ts, self.recv_ts, self.publish_ts];
+        for (i, value) in stamps.into_iter().enumerate() {
+            bytes[16 + 8 * i..24 + 8 * i].copy_from_slice(&value.to_le_bytes());
+        }
+        let levels = self.bids.iter().chain(&self.asks);
+        for (entry, level) in bytes[HEADER_LEN..].chunks_exact_mut(LEVEL_LEN).zip(levels) {
+            entry[..8].copy_from_slice(&level.price.to_le_bytes());
+            entry[8..].copy_from_slice(&level.qty.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes a snapshot from the start of `bytes`, as written by
+    /// [to_bytes](Self::to_bytes); the order counts come back zero.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let bytes = bytes.get(..SNAPSHOT_LEN).ok_or(SnapshotError::Truncated)?;
+        let (magic, layout) = (read_u32(bytes, 0), u16::from_le_bytes([bytes[4], bytes[5]]));
+        if magic != SNAPSHOT_MAGIC || layout != SNAPSHOT_LAYOUT {
+            return Err(SnapshotError::UnknownLayout { magic, layout });
+        }
+        if usize::from(bytes[6].max(bytes[7])) > BOOK_DEPTH {
+            return Err(SnapshotError::Malformed);
+        }
+        let cause = UpdateCause::from_u8(bytes[11]).ok_or(SnapshotError::Malformed)?;
+
+        let mut snapshot = Self {
+            bid_count: bytes[6],
+            ask_count: bytes[7],
+            price_exponent: bytes[8] as i8,
+            qty_exponent: bytes[9] as i8,
+            stale: bytes[10] & STALE != 0,
+            cause,
+            version: read_u64(bytes, 16),
+            update_id: read_u64(bytes, 24),
+            exchange_ts: read_u64(bytes, 32),
+            recv_ts: read_u64(bytes, 40),
+            publish_ts: read_u64(bytes, 48),
+            ..Self::default()
+        };
+        let levels = snapshot.bids.iter_mut().chain(&mut snapshot.asks);
+        for (level, entry) in levels.zip(bytes[HEADER_LEN..].chunks_exact(LEVEL_LEN)) {
+            *level = Level {
+                price: read_u64(entry, 0) as i64,
+                qty: read_u64(entry, 8) as i64,
+            };
+        }
+        Ok(snapshot)
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{L1FriendlyBook, Stamps};
+
+    #[test]
+    fn test_round_trip() {
+        let mut book = L1FriendlyBook::new();
+        book.price_exponent = -2;
+        book.qty_exponent = -8;
+        let bids: [Level; BOOK_DEPTH] = core::array::from_fn(|i| Level { price: -(i as i64), qty: 1 + i as i64 });
+        let stamps = Stamps { update_id: 1, exchange_ts: 2, recv_ts: 3, publish_ts: u64::MAX };
+        book.apply_snapshot(&bids, &[Level { price: 1, qty: 2 }], stamps);
+        book.stale.store(true, core::sync::atomic::Ordering::Relaxed);
+        let mut snapshot = BookSnapshot::default();
+        book.copy_snapshot(&mut snapshot);
+
+        let bytes = snapshot.to_bytes();
+        assert_eq!(&bytes[..4], b"OBS1");
+        assert_eq!(read_u64(&bytes, HEADER_LEN + BOOK_DEPTH * LEVEL_LEN), 1);
+        assert_eq!(BookSnapshot::from_bytes(&bytes), Ok(snapshot));
+
+        // Read from a larger buffer, e.g. a shared-memory slot
+        let mut slot = [0xff; SNAPSHOT_LEN + 8];
+        slot[..SNAPSHOT_LEN].copy_from_slice(&bytes);
+        assert_eq!(BookSnapshot::from_bytes(&slot), Ok(snapshot));
+    }
+
+    #[test]
+    fn test_rejects_foreign_bytes() {
+        let bytes = BookSnapshot::default().to_bytes();
+        assert_eq!(BookSnapshot::from_bytes(&bytes[..SNAPSHOT_LEN - 1]), Err(SnapshotError::Truncated));
+
+        let mut newer = bytes;
+        newer[4] = 2;
+        assert_eq!(
+            BookSnapshot::from_bytes(&newer),
+            Err(SnapshotError::UnknownLayout { magic: SNAPSHOT_MAGIC, layout: 2 })
+        );
+
+        let mut overfull = bytes;
+        overfull[7] = BOOK_DEPTH as u8 + 1;
+        assert_eq!(BookSnapshot::from_bytes(&overfull), Err(SnapshotError::Malformed));
+
+        let mut cause = bytes;
+        cause[11] = 4;
+        assert_eq!(BookSnapshot::from_bytes(&cause), Err(SnapshotError::Malformed));
+    }
+}
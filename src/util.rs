@@ -1,16 +1,177 @@
+use core::fmt;
+use core::ops::{Add, Div, Mul};
+
 /// Pre-computed powers of 10 for rapid scaling.
-const POWERS_OF_10: [i64; 16] = [
+const POWERS_OF_10: [u64; 20] = [
     1, 10, 100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000,
     100_000_000, 1_000_000_000, 10_000_000_000, 100_000_000_000,
-    1_000_000_000_000, 10_000_000_000_000, 100_000_000_000_000, 1_000_000_000_000_000
+    1_000_000_000_000, 10_000_000_000_000, 100_000_000_000_000, 1_000_000_000_000_000,
+    10_000_000_000_000_000, 100_000_000_000_000_000, 1_000_000_000_000_000_000,
+    10_000_000_000_000_000_000
 ];
 
-#[derive(Debug, PartialEq)]
+/// Pre-computed powers of 10 for 128-bit values.
+const WIDE_POWERS_OF_10: [u128; 39] = {
+    let mut powers = [1u128; 39];
+    let mut i = 1;
+    while i < powers.len() {
+        powers[i] = powers[i - 1] * 10;
+        i += 1;
+    }
+    powers
+};
+
+/// Unsigned integers the parser accumulates digits into.
+trait Mantissa: Copy + PartialEq + Add<Output = Self> + Mul<Output = Self> + Div<Output = Self> + From<u64> {
+    /// Highest scale whose power of 10 fits.
+    const MAX_SCALE: u32;
+
+    /// Returns `10^exponent`; `exponent` must not exceed [Mantissa::MAX_SCALE].
+    fn pow10(exponent: usize) -> Self;
+
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+}
+
+impl Mantissa for u64 {
+    const MAX_SCALE: u32 = 19;
+
+    #[inline]
+    fn pow10(exponent: usize) -> Self {
+        POWERS_OF_10[exponent]
+    }
+
+    fn checked_mul(self, rhs: Self) -> Option<Self> {
+        u64::checked_mul(self, rhs)
+    }
+
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        u64::checked_add(self, rhs)
+    }
+}
+
+impl Mantissa for u128 {
+    const MAX_SCALE: u32 = 38;
+
+    #[inline]
+    fn pow10(exponent: usize) -> Self {
+        WIDE_POWERS_OF_10[exponent]
+    }
+
+    fn checked_mul(self, rhs: Self) -> Option<Self> {
+        u128::checked_mul(self, rhs)
+    }
+
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        u128::checked_add(self, rhs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ParseError {
     EmptyInput,
     InvalidFirstChar,
     NoDigits,
     InvalidTerminator,
+    /// The value does not fit the result type at the requested scale.
+    Overflow,
+}
+
+/// Bytes of the input kept on either side of a [ParseFailure]'s offset.
+const CONTEXT_RADIUS: usize = 16;
+
+impl ParseError {
+    /// Locates the error in the input, given where the number started, so
+    /// it can be reported with the bytes around it.
+    ///
+    /// The offset is the first byte that does not continue the number, or
+    /// the start of the number if it overflowed.
+    ///
+    /// # Examples
+    /// ```
+    /// use rs_orderbook_streamer::util::{parse_i64_with_precision, ParseError};
+    ///
+    /// let msg = br#"{"p":"1.5.3","q":"2"}"#;
+    /// let failure = parse_i64_with_precision(msg, 6, 2).unwrap_err().at(msg, 6);
+    /// assert_eq!((failure.error, failure.offset), (ParseError::InvalidTerminator, 9));
+    /// assert_eq!(failure.context(), msg);
+    /// ```
+    pub fn at(self, bytes: &[u8], start_idx: usize) -> ParseFailure {
+        let start = start_idx.min(bytes.len());
+        let offset = match self {
+            ParseError::Overflow => start,
+            _ => start + number_len(&bytes[start..]),
+        };
+        let context_start = offset.saturating_sub(CONTEXT_RADIUS);
+        let context_end = bytes.len().min(offset + CONTEXT_RADIUS);
+        let mut context = [0; 2 * CONTEXT_RADIUS];
+        context[..context_end - context_start].copy_from_slice(&bytes[context_start..context_end]);
+        ParseFailure {
+            error: self,
+            offset,
+            context_start,
+            context,
+            context_len: (context_end - context_start) as u8,
+        }
+    }
+}
+
+/// Returns the length of the well-formed number at the start of `bytes`:
+/// a sign, digits, a fraction and an exponent, each where present.
+fn number_len(bytes: &[u8]) -> usize {
+    let digits = |from: usize| from + bytes[from..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let mut idx = digits(matches!(bytes.first(), Some(b'-' | b'+')) as usize);
+    if bytes.get(idx) == Some(&b'.') {
+        idx = digits(idx + 1);
+    }
+    if matches!(bytes.get(idx), Some(b'e' | b'E')) {
+        let sign = matches!(bytes.get(idx + 1), Some(b'-' | b'+')) as usize;
+        let end = digits(idx + 1 + sign);
+        if end > idx + 1 + sign {
+            idx = end;
+        }
+    }
+    idx
+}
+
+/// A [ParseError] with where in the input it happened, for logging which
+/// field of which frame failed.
+///
+/// Keeps up to 16 bytes either side of the offset inline, so it can be
+/// raised on the hot path without allocating.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ParseFailure {
+    pub error: ParseError,
+    /// Index in the input of the offending byte.
+    pub offset: usize,
+    context_start: usize,
+    context: [u8; 2 * CONTEXT_RADIUS],
+    context_len: u8,
+}
+
+impl ParseFailure {
+    /// The input around [ParseFailure::offset].
+    pub fn context(&self) -> &[u8] {
+        &self.context[..self.context_len as usize]
+    }
+
+    /// Index in the input of the first byte of [ParseFailure::context].
+    pub fn context_start(&self) -> usize {
+        self.context_start
+    }
+}
+
+/// Shows the context as escaped text, e.g. `context: "{\"p\":\"1.5.3\"}"`.
+impl fmt::Debug for ParseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParseFailure")
+            .field("error", &self.error)
+            .field("offset", &self.offset)
+            .field("context_start", &self.context_start)
+            .field("context", &format_args!("\"{}\"", self.context().escape_ascii()))
+            .finish()
+    }
 }
 
 /// Parses a number into a fixed-point `i64` and returns the value and the index of the first non-numeric byte.
@@ -18,6 +179,10 @@ pub enum ParseError {
 /// If the input has fewer decimal places than `precision`, it is padded with zeros.
 /// If it has more, the extra digits are ignored (truncated).
 ///
+/// An exponent suffix (`1.2e-05`, `3E+2`) is folded into the scaling, so
+/// digits it shifts above `precision` are kept and those it shifts below
+/// are truncated like any other.
+///
 /// # Examples
 /// ```
 /// use rs_orderbook_streamer::util::{parse_i64_with_precision, ParseError};
@@ -29,39 +194,355 @@ pub enum ParseError {
 /// let (val2, next_idx2) = parse_i64_with_precision(b"45.67,next_field", 0, 4).unwrap();
 /// assert_eq!(val2, 456_700);
 /// assert_eq!(next_idx2, 5); // Stopped at the comma
+///
+/// let (val3, _) = parse_i64_with_precision(b"1.2e-05", 0, 8).unwrap();
+/// assert_eq!(val3, 1_200);
 /// ```
 pub fn parse_i64_with_precision(bytes: &[u8], start_idx: usize, target_scale: u32) -> Result<(i64, usize), ParseError> {
+    let (negative, magnitude, idx) = parse_signed::<u64>(bytes, start_idx, target_scale)?;
+    let value = match negative {
+        true => 0i64.checked_sub_unsigned(magnitude),
+        false => i64::try_from(magnitude).ok(),
+    };
+    Ok((value.ok_or(ParseError::Overflow)?, idx))
+}
+
+/// Parses a number like [parse_i64_with_precision], or returns `None` for
+/// a field the venue left blank: JSON `null` or the empty string `""`.
+///
+/// `start_idx` is the start of the value, so an empty string is matched by
+/// its opening quote; the returned index is past whichever was read.
+/// Anything else that is not a number, including a truncated `nul` or an
+/// empty input, is still an error.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::util::parse_opt_i64_with_precision;
+///
+/// assert_eq!(parse_opt_i64_with_precision(b"1.5,", 0, 2), Ok((Some(150), 3)));
+/// assert_eq!(parse_opt_i64_with_precision(b"null,", 0, 2), Ok((None, 4)));
+/// assert_eq!(parse_opt_i64_with_precision(br#""","#, 0, 2), Ok((None, 2)));
+/// assert!(parse_opt_i64_with_precision(b"nan", 0, 2).is_err());
+/// ```
+pub fn parse_opt_i64_with_precision(bytes: &[u8], start_idx: usize, target_scale: u32) -> Result<(Option<i64>, usize), ParseError> {
+    let rest = bytes.get(start_idx..).unwrap_or_default();
+    let blank = match rest {
+        [b'n', b'u', b'l', b'l', ..] => 4,
+        [b'"', b'"', ..] => 2,
+        _ => {
+            let (value, idx) = parse_i64_with_precision(bytes, start_idx, target_scale)?;
+            return Ok((Some(value), idx));
+        }
+    };
+    if rest.get(blank).is_some_and(u8::is_ascii_alphanumeric) {
+        return Err(ParseError::InvalidTerminator);
+    }
+    Ok((None, start_idx + blank))
+}
+
+/// Parses a number into a fixed-point `i128`, like [parse_i64_with_precision]
+/// with room for 38 digits.
+///
+/// For values that overflow `i64` at a useful scale, such as token amounts
+/// quoted with 18 decimals. `target_scale` may go up to 38.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::util::parse_i128_with_precision;
+///
+/// let (val, _) = parse_i128_with_precision(b"1234567.000000000000000001", 0, 18).unwrap();
+/// assert_eq!(val, 1_234_567_000_000_000_000_000_001);
+/// ```
+pub fn parse_i128_with_precision(bytes: &[u8], start_idx: usize, target_scale: u32) -> Result<(i128, usize), ParseError> {
+    let (negative, magnitude, idx) = parse_signed::<u128>(bytes, start_idx, target_scale)?;
+    let value = match negative {
+        true => 0i128.checked_sub_unsigned(magnitude),
+        false => i128::try_from(magnitude).ok(),
+    };
+    Ok((value.ok_or(ParseError::Overflow)?, idx))
+}
+
+/// Parses the magnitude of a number, returning whether it had a leading `-`.
+fn parse_signed<T: Mantissa>(bytes: &[u8], start_idx: usize, target_scale: u32) -> Result<(bool, T, usize), ParseError> {
+    match bytes.get(start_idx) {
+        None => Err(ParseError::EmptyInput),
+        Some(b'-') => {
+            let (magnitude, idx) = parse_unsigned(bytes, start_idx + 1, target_scale).map_err(|err| match err {
+                // e.g. "-" or "-a"
+                ParseError::EmptyInput | ParseError::InvalidFirstChar => ParseError::NoDigits,
+                err => err,
+            })?;
+            Ok((true, magnitude, idx))
+        }
+        Some(_) => {
+            let (magnitude, idx) = parse_unsigned(bytes, start_idx, target_scale)?;
+            Ok((false, magnitude, idx))
+        }
+    }
+}
+
+/// Input [parse_i64_with_options] accepts beyond, or short of, what
+/// [parse_i64_with_precision] does.
+///
+/// The default accepts the same input, so call sites opt into each
+/// tolerance, or into strictness, on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Accept a leading `+`, as some venues and FIX fields send.
+    pub allow_plus: bool,
+    /// Skip spaces and tabs before the number, and after it so the returned
+    /// index is that of the next field.
+    pub skip_whitespace: bool,
+    /// Reject what the lenient parser lets through: a number without integer
+    /// digits (`.5`), a dot without fraction digits (`1.`, `1.a`) and a
+    /// number running into a letter, digit or dot (`1.5x`). For validation
+    /// paths; the hot path stays lenient.
+    pub strict: bool,
+    /// Bytes the number may end at; anything else after it is an
+    /// [ParseError::InvalidTerminator]. The end of input is always accepted.
+    pub terminators: Terminators,
+}
+
+/// A set of bytes a number may be followed by, for
+/// [ParseOptions::terminators].
+///
+/// Catches payloads corrupted mid-number, such as `"1.5?3"`, that would
+/// otherwise parse to a truncated value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Terminators([u64; 4]);
+
+impl Terminators {
+    /// Any byte, as [parse_i64_with_precision] accepts.
+    pub const ANY: Self = Self([u64::MAX; 4]);
+
+    /// The bytes in `bytes`, e.g. `Terminators::of(b",\"]")` for values in
+    /// a JSON level array.
+    pub const fn of(bytes: &[u8]) -> Self {
+        let mut mask = [0; 4];
+        let mut i = 0;
+        while i < bytes.len() {
+            mask[(bytes[i] >> 6) as usize] |= 1 << (bytes[i] & 63);
+            i += 1;
+        }
+        Self(mask)
+    }
+
+    pub const fn contains(&self, byte: u8) -> bool {
+        self.0[(byte >> 6) as usize] & (1 << (byte & 63)) != 0
+    }
+}
+
+impl Default for Terminators {
+    fn default() -> Self {
+        Self::ANY
+    }
+}
+
+/// Parses a number like [parse_i64_with_precision], tolerating the input
+/// that `options` allows.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::util::{parse_i64_with_options, ParseOptions, Terminators};
+///
+/// let padded = ParseOptions { allow_plus: true, skip_whitespace: true, ..Default::default() };
+/// assert_eq!(parse_i64_with_options(b"  +1.5 |", 0, 2, padded), Ok((150, 7)));
+/// assert!(parse_i64_with_options(b"+1.5", 0, 2, ParseOptions::default()).is_err());
+///
+/// let strict = ParseOptions { strict: true, ..Default::default() };
+/// assert_eq!(parse_i64_with_options(b"1.,", 0, 2, ParseOptions::default()), Ok((100, 2)));
+/// assert!(parse_i64_with_options(b"1.,", 0, 2, strict).is_err());
+///
+/// let json = ParseOptions { terminators: Terminators::of(b",\"]"), ..Default::default() };
+/// assert_eq!(parse_i64_with_options(b"1.5\"", 0, 2, json), Ok((150, 3)));
+/// assert!(parse_i64_with_options(b"1.5?3\"", 0, 2, json).is_err());
+/// ```
+pub fn parse_i64_with_options(bytes: &[u8], start_idx: usize, target_scale: u32, options: ParseOptions) -> Result<(i64, usize), ParseError> {
+    let mut idx = start_idx;
+    if options.skip_whitespace {
+        idx = skip_blanks(bytes, idx);
+    }
+    let signed = matches!(bytes.get(idx), Some(b'-')) || (options.allow_plus && bytes.get(idx) == Some(&b'+'));
+    let digits = idx + signed as usize;
+    let (negative, magnitude, mut idx) = match bytes.get(idx) {
+        Some(b'+') if options.allow_plus => {
+            let (magnitude, idx) = parse_unsigned::<u64>(bytes, idx + 1, target_scale).map_err(|err| match err {
+                ParseError::EmptyInput | ParseError::InvalidFirstChar => ParseError::NoDigits,
+                err => err,
+            })?;
+            (false, magnitude, idx)
+        }
+        _ => parse_signed::<u64>(bytes, idx, target_scale)?,
+    };
+    if options.strict {
+        check_strict(bytes, digits, idx)?;
+    }
+    if options.skip_whitespace {
+        idx = skip_blanks(bytes, idx);
+    }
+    match bytes.get(idx) {
+        Some(&b) if !options.terminators.contains(b) => return Err(ParseError::InvalidTerminator),
+        _ => {}
+    }
+    let value = match negative {
+        true => 0i64.checked_sub_unsigned(magnitude),
+        false => i64::try_from(magnitude).ok(),
+    };
+    Ok((value.ok_or(ParseError::Overflow)?, idx))
+}
+
+/// Checks the unsigned number in `bytes[start..end]`, and what follows it,
+/// against [ParseOptions::strict].
+fn check_strict(bytes: &[u8], start: usize, end: usize) -> Result<(), ParseError> {
+    let integer_end = start + bytes[start..end].iter().take_while(|b| b.is_ascii_digit()).count();
+    if integer_end == start {
+        return Err(ParseError::NoDigits);
+    }
+    if bytes.get(integer_end) == Some(&b'.') && !bytes.get(integer_end + 1).is_some_and(u8::is_ascii_digit) {
+        return Err(ParseError::InvalidTerminator);
+    }
+    match bytes.get(end) {
+        Some(b) if b.is_ascii_alphanumeric() || *b == b'.' => Err(ParseError::InvalidTerminator),
+        _ => Ok(()),
+    }
+}
+
+fn skip_blanks(bytes: &[u8], mut idx: usize) -> usize {
+    while matches!(bytes.get(idx), Some(b' ' | b'\t')) {
+        idx += 1;
+    }
+    idx
+}
+
+/// Parses an unsigned number into a fixed-point `u64`, like
+/// [parse_i64_with_precision] without the sign.
+///
+/// Suits fields that are never negative, such as quantities: it skips the
+/// sign branch and holds values up to `u64::MAX`. A leading `-` is an
+/// [ParseError::InvalidFirstChar].
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::util::parse_u64_with_precision;
+///
+/// assert_eq!(parse_u64_with_precision(b"0.25\"", 0, 8), Ok((25_000_000, 4)));
+/// assert!(parse_u64_with_precision(b"-1", 0, 8).is_err());
+/// ```
+pub fn parse_u64_with_precision(bytes: &[u8], start_idx: usize, target_scale: u32) -> Result<(u64, usize), ParseError> {
+    parse_unsigned(bytes, start_idx, target_scale)
+}
+
+/// Parses an unsigned number, with any exponent, into `T`; see
+/// [parse_u64_with_precision].
+fn parse_unsigned<T: Mantissa>(bytes: &[u8], start_idx: usize, target_scale: u32) -> Result<(T, usize), ParseError> {
+    let (value, idx) = match parse_mantissa::<T>(bytes, start_idx, target_scale) {
+        // Too big at this scale, unless an exponent scales it back down
+        Err(ParseError::Overflow) => {
+            let end = start_idx + bytes[start_idx..].iter().take_while(|b| b.is_ascii_digit() || **b == b'.').count();
+            match bytes.get(end) {
+                Some(b'e' | b'E') => (T::from(0), end),
+                _ => return Err(ParseError::Overflow),
+            }
+        }
+        parsed => parsed?,
+    };
+    if !matches!(bytes.get(idx), Some(b'e' | b'E')) {
+        return Ok((value, idx));
+    }
+
+    let (exponent, end) = parse_exponent(bytes, idx + 1)?;
+    // Reparse the mantissa at the shifted scale rather than scaling `value`,
+    // whose digits beyond `target_scale` are already gone
+    let scale = target_scale as i64 + exponent;
+    let max = T::MAX_SCALE as i64;
+    let value = if scale < 0 {
+        let (whole, _) = parse_mantissa::<T>(bytes, start_idx, 0)?;
+        match -scale <= max {
+            true => whole / T::pow10(scale.unsigned_abs() as usize),
+            false => T::from(0),
+        }
+    } else if scale <= max {
+        parse_mantissa(bytes, start_idx, scale as u32)?.0
+    } else {
+        let (mantissa, _) = parse_mantissa::<T>(bytes, start_idx, max as u32)?;
+        (scale - max <= max)
+            .then(|| T::pow10((scale - max) as usize))
+            .and_then(|factor| mantissa.checked_mul(factor))
+            .ok_or(ParseError::Overflow)?
+    };
+    Ok((value, end))
+}
+
+/// Parses the signed decimal exponent after an `e`, returning it and the
+/// index past it.
+///
+/// Exponents far beyond any `u64` are clamped, which still over- or
+/// underflows the scaling.
+fn parse_exponent(bytes: &[u8], start_idx: usize) -> Result<(i64, usize), ParseError> {
+    let mut idx = start_idx;
+    let sign = match bytes.get(idx) {
+        Some(b'-') => {
+            idx += 1;
+            -1
+        }
+        Some(b'+') => {
+            idx += 1;
+            1
+        }
+        _ => 1,
+    };
+
+    let digits = idx;
+    let mut exponent = 0i64;
+    while let Some(&b) = bytes.get(idx)
+        && b.is_ascii_digit()
+    {
+        exponent = (exponent * 10 + (b - b'0') as i64).min(1_000);
+        idx += 1;
+    }
+    if idx == digits {
+        // A bare `e` ends no number
+        return Err(ParseError::InvalidTerminator);
+    }
+    Ok((exponent * sign, idx))
+}
+
+/// Parses an unsigned number without exponent at `target_scale`; see
+/// [parse_u64_with_precision].
+fn parse_mantissa<T: Mantissa>(bytes: &[u8], start_idx: usize, target_scale: u32) -> Result<(T, usize), ParseError> {
     if start_idx >= bytes.len() {
         return Err(ParseError::EmptyInput);
     }
 
     let mut idx = start_idx;
-    let mut sign = 1i64;
 
-    // 1. Sign or First Digit
+    // 1. First Digit
     match bytes[idx] {
-        b'-' => {
-            sign = -1;
-            idx += 1;
-            if idx >= bytes.len() {
-                return Err(ParseError::NoDigits);
-            }
-        }
         b'0'..=b'9' | b'.' => {
             // OK, proceed to parsing
         }
         _ => return Err(ParseError::InvalidFirstChar),
     }
 
-    let mut res = 0i64;
+    let mut res = T::from(0);
     let mut digits_seen = false;
 
     // 2. Parse integer portion
     while idx < bytes.len() {
+        if let Some((value, len)) = digit_block(&bytes[idx..])
+            && len > 0
+        {
+            res = shift_in(res, len, value)?;
+            digits_seen = true;
+            idx += len;
+            if len == DIGIT_BLOCK {
+                continue;
+            }
+        }
         let b = bytes[idx];
         match b {
             b'0'..=b'9' => {
-                res = res * 10 + (b - b'0') as i64;
+                res = shift_in(res, 1, (b - b'0') as u64)?;
                 digits_seen = true;
                 idx += 1;
             }
@@ -77,10 +558,9 @@ pub fn parse_i64_with_precision(bytes: &[u8], start_idx: usize, target_scale: u3
             _ => {
                 // Terminator reached
                 if !digits_seen {
-                    // e.g. "-" followed by non-digit
                     return Err(ParseError::NoDigits);
                 }
-                return Ok((res * POWERS_OF_10[target_scale as usize] * sign, idx));
+                return Ok((shift_in(res, target_scale as usize, 0)?, idx));
             }
         }
     }
@@ -90,18 +570,31 @@ pub fn parse_i64_with_precision(bytes: &[u8], start_idx: usize, target_scale: u3
         if !digits_seen {
             return Err(ParseError::NoDigits);
         }
-        return Ok((res * POWERS_OF_10[target_scale as usize] * sign, idx));
+        return Ok((shift_in(res, target_scale as usize, 0)?, idx));
     }
 
     // 3. Parse fractional portion
     // We are here because we hit '.'
     let mut digits_after_decimal = 0u32;
     while idx < bytes.len() {
+        if let Some((value, len)) = digit_block(&bytes[idx..])
+            && len > 0
+        {
+            // Digits beyond the scale are truncated, as below
+            let keep = len.min((target_scale - digits_after_decimal) as usize);
+            res = shift_in(res, keep, value / POWERS_OF_10[len - keep])?;
+            digits_after_decimal += keep as u32;
+            digits_seen = true;
+            idx += len;
+            if len == DIGIT_BLOCK {
+                continue;
+            }
+        }
         let b = bytes[idx];
         match b {
             b'0'..=b'9' => {
                 if digits_after_decimal < target_scale {
-                    res = res * 10 + (b - b'0') as i64;
+                    res = shift_in(res, 1, (b - b'0') as u64)?;
                     digits_after_decimal += 1;
                 }
                 digits_seen = true;
@@ -119,10 +612,364 @@ pub fn parse_i64_with_precision(bytes: &[u8], start_idx: usize, target_scale: u3
         return Err(ParseError::NoDigits);
     }
 
-    let final_val = res * POWERS_OF_10[(target_scale - digits_after_decimal) as usize] * sign;
+    let final_val = shift_in(res, (target_scale - digits_after_decimal) as usize, 0)?;
     Ok((final_val, idx))
 }
 
+/// Returns `res` followed by `digits` more decimal digits holding `value`,
+/// or [ParseError::Overflow] if that does not fit.
+#[inline]
+fn shift_in<T: Mantissa>(res: T, digits: usize, value: u64) -> Result<T, ParseError> {
+    res.checked_mul(T::pow10(digits))
+        .and_then(|res| res.checked_add(T::from(value)))
+        .ok_or(ParseError::Overflow)
+}
+
+/// How [rescale] treats the digits that narrowing drops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Drops them, as the parser does.
+    TowardZero,
+    /// Rounds toward negative infinity, e.g. to keep bids on the tick grid.
+    Down,
+    /// Rounds toward positive infinity, e.g. to keep asks on the tick grid.
+    Up,
+    /// Rounds to the nearest value, ties away from zero.
+    HalfAwayFromZero,
+    /// Rounds to the nearest value, ties to the even neighbour.
+    HalfEven,
+}
+
+/// Converts a fixed-point `value` from `from_scale` decimals to `to_scale`,
+/// or `None` if the result does not fit.
+///
+/// Widening is exact; narrowing rounds as `rounding` says.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::util::{rescale, Rounding};
+///
+/// assert_eq!(rescale(125, 2, 8, Rounding::TowardZero), Some(125_000_000));
+/// assert_eq!(rescale(-1_250, 3, 1, Rounding::Down), Some(-13));
+/// assert_eq!(rescale(1_250, 3, 1, Rounding::HalfEven), Some(12));
+/// ```
+pub fn rescale(value: i64, from_scale: u32, to_scale: u32, rounding: Rounding) -> Option<i64> {
+    if to_scale >= from_scale {
+        let factor = 10i128.checked_pow(to_scale - from_scale)?;
+        return i64::try_from((value as i128).checked_mul(factor)?).ok();
+    }
+
+    // Any divisor past 10^19 leaves a quotient of 0 and a remainder below
+    // half of it, so capping keeps i128 from overflowing without changing
+    // the result
+    let divisor = 10i128.pow((from_scale - to_scale).min(38));
+    divide(value, divisor, rounding)
+}
+
+/// Divides `value` by `divisor`, rounding as `rounding` says.
+fn divide(value: i64, divisor: i128, rounding: Rounding) -> Option<i64> {
+    let value = value as i128;
+    let (quotient, remainder) = (value / divisor, value % divisor);
+    let away = match rounding {
+        Rounding::TowardZero => false,
+        Rounding::Down => remainder < 0,
+        Rounding::Up => remainder > 0,
+        Rounding::HalfAwayFromZero => remainder.abs() * 2 >= divisor,
+        Rounding::HalfEven => match (remainder.abs() * 2).cmp(&divisor) {
+            core::cmp::Ordering::Greater => true,
+            core::cmp::Ordering::Equal => quotient % 2 != 0,
+            core::cmp::Ordering::Less => false,
+        },
+    };
+    // The remainder carries the sign of the value, so this steps away from zero
+    let rounded = if away { quotient + remainder.signum() } else { quotient };
+    i64::try_from(rounded).ok()
+}
+
+/// A conversion between two decimal exponents, fixed once per instrument.
+///
+/// For venues that send prices or sizes as integers already scaled to an
+/// exponent of their own, such as ITCH's `10^-4` prices: each value then
+/// costs one multiplication or division instead of a trip through the
+/// ASCII parser. Same results as [rescale], with exponents (`value ×
+/// 10^exponent`) as the book records them instead of scales.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::util::{Rounding, ScaleFactor};
+///
+/// let itch = ScaleFactor::new(-4, -8, Rounding::TowardZero).unwrap();
+/// assert_eq!(itch.apply(1_234_500), Some(12_345_000_000));
+///
+/// let back = itch.inverse().unwrap();
+/// assert_eq!(back.apply(12_345_678_901), Some(1_234_567));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScaleFactor {
+    from_exponent: i8,
+    to_exponent: i8,
+    /// `10^|from_exponent - to_exponent|`, capped like [rescale]'s divisor.
+    factor: i128,
+    rounding: Rounding,
+}
+
+impl ScaleFactor {
+    /// Converts values at `10^from_exponent` to `10^to_exponent`; narrowing
+    /// rounds as `rounding` says.
+    ///
+    /// Returns `None` if widening multiplies by more than `10^18`, which
+    /// no value but zero survives.
+    pub fn new(from_exponent: i8, to_exponent: i8, rounding: Rounding) -> Option<Self> {
+        let shift = i32::from(from_exponent) - i32::from(to_exponent);
+        if shift > 18 {
+            return None;
+        }
+        Some(Self {
+            from_exponent,
+            to_exponent,
+            factor: 10i128.pow(shift.unsigned_abs().min(38)),
+            rounding,
+        })
+    }
+
+    /// Leaves values as they are.
+    pub fn identity(exponent: i8) -> Self {
+        Self {
+            from_exponent: exponent,
+            to_exponent: exponent,
+            factor: 1,
+            rounding: Rounding::TowardZero,
+        }
+    }
+
+    /// Converts `value`, or returns `None` if the result does not fit.
+    #[inline]
+    pub fn apply(&self, value: i64) -> Option<i64> {
+        match self.is_exact() {
+            true => i64::try_from(value as i128 * self.factor).ok(),
+            false => divide(value, self.factor, self.rounding),
+        }
+    }
+
+    /// Whether every value converts without rounding, i.e. the target
+    /// exponent is at least as fine as the source.
+    pub fn is_exact(&self) -> bool {
+        self.to_exponent <= self.from_exponent
+    }
+
+    /// The conversion back, rounding the same way.
+    pub fn inverse(&self) -> Option<Self> {
+        Self::new(self.to_exponent, self.from_exponent, self.rounding)
+    }
+
+    pub fn from_exponent(&self) -> i8 {
+        self.from_exponent
+    }
+
+    pub fn to_exponent(&self) -> i8 {
+        self.to_exponent
+    }
+}
+
+/// Longest output of [format_i64_with_precision]: a sign, 19 digits or a
+/// `0` and up to 19 decimals, and the point.
+pub const MAX_FORMATTED_LEN: usize = 22;
+
+/// Writes a fixed-point `value` at `scale` into `buf` as a decimal string,
+/// and returns its length; the inverse of [parse_i64_with_precision].
+///
+/// The output is minimal: trailing fractional zeros are dropped, along with
+/// the point when nothing follows it, so `1.20000000` at scale 8 prints as
+/// `1.2`. Nothing is allocated.
+///
+/// # Panics
+/// If `scale` is above 19 or `buf` is too short for the output; a buffer of
+/// [MAX_FORMATTED_LEN] bytes always fits.
+///
+/// # Examples
+/// ```
+/// use rs_orderbook_streamer::util::{format_i64_with_precision, MAX_FORMATTED_LEN};
+///
+/// let mut buf = [0u8; MAX_FORMATTED_LEN];
+/// let len = format_i64_with_precision(-120_000_000, 8, &mut buf);
+/// assert_eq!(&buf[..len], b"-1.2");
+///
+/// let len = format_i64_with_precision(4_500, 2, &mut buf);
+/// assert_eq!(&buf[..len], b"45");
+/// ```
+pub fn format_i64_with_precision(value: i64, scale: u32, buf: &mut [u8]) -> usize {
+    let divisor = POWERS_OF_10[scale as usize];
+    let magnitude = value.unsigned_abs();
+    let (mut int, mut frac) = (magnitude / divisor, magnitude % divisor);
+    // Digits are produced right to left, so build them at the end
+    let mut digits = [0u8; MAX_FORMATTED_LEN];
+    let mut start = digits.len();
+
+    if frac != 0 {
+        let mut places = scale;
+        while frac % 10 == 0 {
+            frac /= 10;
+            places -= 1;
+        }
+        for _ in 0..places {
+            start -= 1;
+            digits[start] = b'0' + (frac % 10) as u8;
+            frac /= 10;
+        }
+        start -= 1;
+        digits[start] = b'.';
+    }
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (int % 10) as u8;
+        int /= 10;
+        if int == 0 {
+            break;
+        }
+    }
+    if value < 0 {
+        start -= 1;
+        digits[start] = b'-';
+    }
+
+    let len = digits.len() - start;
+    buf[..len].copy_from_slice(&digits[start..]);
+    len
+}
+
+/// Bytes examined per vector digit scan.
+const DIGIT_BLOCK: usize = 16;
+
+/// Returns the value and count of the ASCII digits `bytes` starts with, up
+/// to [DIGIT_BLOCK] of them, scanned and converted a block at a time.
+///
+/// `None` means the caller should fall back to the scalar loop: fewer than
+/// [DIGIT_BLOCK] bytes are left, or the CPU has no vector unit to use. A
+/// block covers any realistic price or quantity, so AVX2's wider registers
+/// would only add a second, emptier lane.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn digit_block(bytes: &[u8]) -> Option<(u64, usize)> {
+    if bytes.len() >= DIGIT_BLOCK && has_sse41() {
+        // SAFETY: SSE4.1 is available and a whole block is readable.
+        return Some(unsafe { sse41_digits(bytes) });
+    }
+    None
+}
+
+/// Detects SSE4.1 at runtime, or without `std`, only if the build targets it.
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+#[inline]
+fn has_sse41() -> bool {
+    std::is_x86_feature_detected!("sse4.1")
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+#[inline]
+fn has_sse41() -> bool {
+    cfg!(target_feature = "sse4.1")
+}
+
+/// See the x86-64 version; NEON is always present on AArch64.
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+#[inline]
+fn digit_block(bytes: &[u8]) -> Option<(u64, usize)> {
+    // SAFETY: A whole block is readable.
+    (bytes.len() >= DIGIT_BLOCK).then(|| unsafe { neon_digits(bytes) })
+}
+
+#[cfg(not(any(target_arch = "x86_64", all(target_arch = "aarch64", target_feature = "neon"))))]
+#[inline]
+fn digit_block(_bytes: &[u8]) -> Option<(u64, usize)> {
+    None
+}
+
+/// Scans and converts a block of digits with SSE4.1.
+///
+/// The digits are right-aligned with a shuffle, so the leading lanes read
+/// as zeros, then folded pairwise: ones into tens, tens into hundreds and
+/// so on until two 8-digit halves remain.
+///
+/// # Safety
+/// The CPU must support SSE4.1 and `bytes` must hold [DIGIT_BLOCK] bytes.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+unsafe fn sse41_digits(bytes: &[u8]) -> (u64, usize) {
+    use core::arch::x86_64::*;
+
+    /// Shuffle masks right-aligning `len` digits, at offset `len`; 0x80
+    /// lanes are zeroed.
+    static ALIGN: [u8; 32] = [
+        0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80,
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    ];
+
+    // SAFETY: The caller guarantees a whole block.
+    let chunk = unsafe { _mm_loadu_si128(bytes.as_ptr().cast()) };
+    let digits = _mm_sub_epi8(chunk, _mm_set1_epi8(b'0' as i8));
+    // Anything but a digit wraps above 9
+    let is_digit = _mm_cmpeq_epi8(_mm_min_epu8(digits, _mm_set1_epi8(9)), digits);
+    let len = (_mm_movemask_epi8(is_digit) as u32).trailing_ones() as usize;
+    if len == 0 {
+        return (0, 0);
+    }
+
+    // SAFETY: `len` is at most 16, so the mask stays inside `ALIGN`.
+    let align = unsafe { _mm_loadu_si128(ALIGN.as_ptr().add(len).cast()) };
+    let digits = _mm_shuffle_epi8(digits, align);
+    let pairs = _mm_maddubs_epi16(digits, _mm_set_epi8(1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10));
+    let quads = _mm_madd_epi16(pairs, _mm_set_epi16(1, 100, 1, 100, 1, 100, 1, 100));
+    let quads = _mm_packus_epi32(quads, quads);
+    let halves = _mm_madd_epi16(quads, _mm_set_epi16(1, 10_000, 1, 10_000, 1, 10_000, 1, 10_000));
+    let high = _mm_cvtsi128_si32(halves) as u32 as u64;
+    let low = _mm_extract_epi32::<1>(halves) as u32 as u64;
+    (high * 100_000_000 + low, len)
+}
+
+/// Scans a block of digits with NEON and converts it eight digits at a
+/// time with [swar_digits].
+///
+/// # Safety
+/// `bytes` must hold [DIGIT_BLOCK] bytes.
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+#[target_feature(enable = "neon")]
+unsafe fn neon_digits(bytes: &[u8]) -> (u64, usize) {
+    use core::arch::aarch64::*;
+
+    // SAFETY: The caller guarantees a whole block.
+    let chunk = unsafe { vld1q_u8(bytes.as_ptr()) };
+    let is_digit = vcleq_u8(vsubq_u8(chunk, vdupq_n_u8(b'0')), vdupq_n_u8(9));
+    let halves = vreinterpretq_u64_u8(is_digit);
+    let (first, second) = (vgetq_lane_u64::<0>(halves), vgetq_lane_u64::<1>(halves));
+    let len = match first {
+        u64::MAX => 8 + (second.trailing_ones() / 8) as usize,
+        first => (first.trailing_ones() / 8) as usize,
+    };
+
+    let word = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+    let value = match len {
+        0..=8 => swar_digits(word(0), len),
+        _ => swar_digits(word(0), 8) * POWERS_OF_10[len - 8] + swar_digits(word(8), len - 8),
+    };
+    (value, len)
+}
+
+/// Converts the first `len` (at most 8) ASCII digits of a little-endian
+/// word with three multiplies instead of eight.
+#[cfg_attr(not(all(target_arch = "aarch64", target_feature = "neon")), allow(dead_code))]
+fn swar_digits(word: u64, len: usize) -> u64 {
+    if len == 0 {
+        return 0;
+    }
+    // Shift the digits to the top and pad the bottom with '0's
+    let word = word << (8 * (8 - len)) | 0x3030_3030_3030_3030u64.checked_shr(8 * len as u32).unwrap_or(0);
+    let digits = word.wrapping_sub(0x3030_3030_3030_3030);
+    let pairs = digits.wrapping_mul(10).wrapping_add(digits >> 8);
+    let quads = (pairs & 0x0000_00FF_0000_00FF).wrapping_mul(100 + (1_000_000 << 32));
+    let rest = ((pairs >> 16) & 0x0000_00FF_0000_00FF).wrapping_mul(1 + (10_000 << 32));
+    quads.wrapping_add(rest) >> 32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,6 +999,217 @@ mod tests {
         assert_eq!(parse_i64_with_precision(b"1.23,456", 0, 2), Ok((123, 4)));
     }
 
+    #[test]
+    fn test_exponents() {
+        assert_eq!(parse_i64_with_precision(b"1.2e-05", 0, 8), Ok((1_200, 7)));
+        assert_eq!(parse_i64_with_precision(b"1.2E-05,", 0, 8), Ok((1_200, 7)));
+        assert_eq!(parse_i64_with_precision(b"-3e+2", 0, 0), Ok((-300, 5)));
+        // Digits shifted above the scale are kept, those shifted below truncated
+        assert_eq!(parse_i64_with_precision(b"1.2345e2", 0, 2), Ok((12345, 8)));
+        assert_eq!(parse_i64_with_precision(b"12345e-2", 0, 0), Ok((123, 8)));
+        assert_eq!(parse_i64_with_precision(b"1e-20", 0, 8), Ok((0, 5)));
+        assert_eq!(parse_i64_with_precision(b"1e10", 0, 8), Ok((1_000_000_000_000_000_000, 4)));
+        assert_eq!(parse_i64_with_precision(b"1e11", 0, 8), Err(ParseError::Overflow));
+        // A mantissa too big at the scale is fine once the exponent shifts it back
+        assert_eq!(parse_i64_with_precision(b"2000000000E-1", 0, 10), Ok((2_000_000_000_000_000_000, 13)));
+        assert_eq!(parse_i64_with_precision(b"1e", 0, 2), Err(ParseError::InvalidTerminator));
+        assert_eq!(parse_i64_with_precision(b"1e-", 0, 2), Err(ParseError::InvalidTerminator));
+    }
+
+    #[test]
+    fn test_unsigned() {
+        assert_eq!(parse_u64_with_precision(b"1.5", 0, 8), Ok((150_000_000, 3)));
+        assert_eq!(parse_u64_with_precision(b"100000000000", 0, 8), Ok((10_000_000_000_000_000_000, 12)));
+        assert_eq!(parse_i64_with_precision(b"100000000000", 0, 8), Err(ParseError::Overflow));
+        // Mantissas beyond `u64` overflow rather than wrap
+        assert_eq!(parse_u64_with_precision(b"1000000000000", 0, 8), Err(ParseError::Overflow));
+        assert_eq!(parse_u64_with_precision(b"393\x01", 0, 17), Err(ParseError::Overflow));
+        assert_eq!(parse_u64_with_precision(b"18446744073709551616", 0, 0), Err(ParseError::Overflow));
+        assert_eq!(parse_u64_with_precision(b"2.5", 0, 19), Err(ParseError::Overflow));
+        assert_eq!(parse_u64_with_precision(b"1e3,", 0, 2), Ok((100_000, 3)));
+        assert_eq!(parse_u64_with_precision(b"-1", 0, 2), Err(ParseError::InvalidFirstChar));
+        assert_eq!(parse_u64_with_precision(b"1-2", 0, 2), Err(ParseError::InvalidTerminator));
+        assert_eq!(parse_i64_with_precision(b"-9223372036854775808", 0, 0), Ok((i64::MIN, 20)));
+    }
+
+    #[test]
+    fn test_optional() {
+        assert_eq!(parse_opt_i64_with_precision(b"-0.25]", 0, 4), Ok((Some(-2_500), 5)));
+        assert_eq!(parse_opt_i64_with_precision(b"0", 0, 4), Ok((Some(0), 1)));
+        assert_eq!(parse_opt_i64_with_precision(br#"{"qty":null}"#, 7, 4), Ok((None, 11)));
+        assert_eq!(parse_opt_i64_with_precision(br#"["",1]"#, 1, 4), Ok((None, 3)));
+        assert_eq!(parse_opt_i64_with_precision(b"null", 0, 4), Ok((None, 4)));
+        // Not blank, just not numbers
+        assert_eq!(parse_opt_i64_with_precision(b"nullx", 0, 4), Err(ParseError::InvalidTerminator));
+        assert_eq!(parse_opt_i64_with_precision(b"nul", 0, 4), Err(ParseError::InvalidFirstChar));
+        assert_eq!(parse_opt_i64_with_precision(br#""1""#, 0, 4), Err(ParseError::InvalidFirstChar));
+        assert_eq!(parse_opt_i64_with_precision(b"", 0, 4), Err(ParseError::EmptyInput));
+        assert_eq!(parse_opt_i64_with_precision(b"1", 5, 4), Err(ParseError::EmptyInput));
+    }
+
+    #[test]
+    fn test_failure_location() {
+        let at = |bytes: &[u8], start: usize| {
+            let err = parse_i64_with_precision(bytes, start, 2).unwrap_err();
+            let failure = err.at(bytes, start);
+            (failure.error, failure.offset)
+        };
+        assert_eq!(at(b"[1.5.2]", 1), (ParseError::InvalidTerminator, 4));
+        assert_eq!(ParseError::InvalidTerminator.at(b"[-1.5e-2x]", 1).offset, 8);
+        assert_eq!(at(b"1e", 0), (ParseError::InvalidTerminator, 1));
+        assert_eq!(at(b"[x]", 1), (ParseError::InvalidFirstChar, 1));
+        assert_eq!(at(b"-,", 0), (ParseError::NoDigits, 1));
+        assert_eq!(at(b"[", 1), (ParseError::EmptyInput, 1));
+        assert_eq!(at(b"[99999999999999999999]", 1), (ParseError::Overflow, 1));
+        assert_eq!(ParseError::EmptyInput.at(b"12", 5).offset, 2);
+
+        // Context is clipped to 16 bytes either side
+        let msg = br#"{"e":"depthUpdate","s":"BTCUSDT","b":[["1.5x","2"]]}"#;
+        let failure = ParseError::InvalidTerminator.at(msg, 40);
+        assert_eq!(failure.offset, 43);
+        assert_eq!(failure.context_start(), 27);
+        assert_eq!(failure.context(), &msg[27..]);
+        assert_eq!(
+            format!("{failure:?}"),
+            r#"ParseFailure { error: InvalidTerminator, offset: 43, context_start: 27, context: "USDT\",\"b\":[[\"1.5x\",\"2\"]]}" }"#
+        );
+    }
+
+    #[test]
+    fn test_wide() {
+        let wei = b"123456789012.345678901234567891,";
+        assert_eq!(parse_i128_with_precision(wei, 0, 18), Ok((123_456_789_012_345_678_901_234_567_891, 31)));
+        assert_eq!(parse_i128_with_precision(b"-1e20", 0, 18), Ok((-100_000_000_000_000_000_000_000_000_000_000_000_000, 5)));
+        assert_eq!(parse_i128_with_precision(b"1e21", 0, 18), Err(ParseError::Overflow));
+        assert_eq!(parse_i128_with_precision(b"-170141183460469231731687303715884105728", 0, 0), Ok((i128::MIN, 40)));
+        assert_eq!(parse_i128_with_precision(b"-", 0, 18), Err(ParseError::NoDigits));
+        // Same results as the narrow parser wherever both fit
+        for input in ["0.1", "-42.195", "1.5e-3", "123456789.123456789"] {
+            let narrow = parse_i64_with_precision(input.as_bytes(), 0, 9).unwrap();
+            assert_eq!(parse_i128_with_precision(input.as_bytes(), 0, 9), Ok((narrow.0 as i128, narrow.1)));
+        }
+    }
+
+    #[test]
+    fn test_digit_blocks() {
+        let padding = ",".repeat(DIGIT_BLOCK);
+        for digits in ["", "7", "1234567", "12345678", "123456789", "1234567890123456", "12345678901234567"] {
+            let input = format!("{digits}{padding}");
+            let len = digits.len().min(DIGIT_BLOCK);
+            if let Some(block) = digit_block(input.as_bytes()) {
+                assert_eq!(block, (digits[..len].parse().unwrap_or(0), len));
+            }
+            let word = u64::from_le_bytes(input.as_bytes()[..8].try_into().unwrap());
+            assert_eq!(swar_digits(word, len.min(8)), digits[..len.min(8)].parse().unwrap_or(0));
+        }
+        assert_eq!(digit_block(b"1234"), None);
+
+        // Long inputs take the vector path, short ones the scalar loop
+        let msg = format!("12345678901234567890.123456789{padding}");
+        assert_eq!(parse_u64_with_precision(msg.as_bytes(), 0, 0), Ok((12_345_678_901_234_567_890, 30)));
+        let msg = format!("-123456.7890123456789{padding}");
+        assert_eq!(parse_i64_with_precision(msg.as_bytes(), 0, 8), Ok((-12_345_678_901_234, 21)));
+        assert_eq!(parse_i64_with_precision(msg.as_bytes(), 0, 0), Ok((-123_456, 21)));
+    }
+
+    #[test]
+    fn test_rescale() {
+        assert_eq!(rescale(5, 0, 18, Rounding::TowardZero), Some(5_000_000_000_000_000_000));
+        assert_eq!(rescale(10, 0, 18, Rounding::TowardZero), None);
+        assert_eq!(rescale(7, 3, 3, Rounding::Up), Some(7));
+
+        let cases = [
+            // value at scale 1, then TowardZero, Down, Up, HalfAwayFromZero, HalfEven at scale 0
+            (15, [1, 1, 2, 2, 2]),
+            (25, [2, 2, 3, 3, 2]),
+            (-25, [-2, -3, -2, -3, -2]),
+            (-14, [-1, -2, -1, -1, -1]),
+            (16, [1, 1, 2, 2, 2]),
+            (30, [3, 3, 3, 3, 3]),
+        ];
+        let modes = [Rounding::TowardZero, Rounding::Down, Rounding::Up, Rounding::HalfAwayFromZero, Rounding::HalfEven];
+        for (value, expected) in cases {
+            for (rounding, expected) in modes.into_iter().zip(expected) {
+                assert_eq!(rescale(value, 1, 0, rounding), Some(expected), "{value} {rounding:?}");
+            }
+        }
+
+        // Narrowing past every digit
+        assert_eq!(rescale(i64::MAX, 0, 0, Rounding::Up), Some(i64::MAX));
+        assert_eq!(rescale(i64::MIN, 40, 0, Rounding::Down), Some(-1));
+        assert_eq!(rescale(i64::MAX, 40, 0, Rounding::HalfEven), Some(0));
+        assert_eq!(rescale(i64::MIN, 19, 0, Rounding::HalfAwayFromZero), Some(-1));
+    }
+
+    #[test]
+    fn test_scale_factor() {
+        let cme = ScaleFactor::new(-9, -8, Rounding::HalfEven).unwrap();
+        assert!(!cme.is_exact());
+        assert_eq!(cme.apply(4_512_250_000_000), Some(451_225_000_000));
+        assert_eq!(cme.apply(-25), Some(-2));
+        assert_eq!(cme.inverse().unwrap().apply(451_225_000_000), Some(4_512_250_000_000));
+
+        let widen = ScaleFactor::new(0, -18, Rounding::TowardZero).unwrap();
+        assert_eq!(widen.apply(9), Some(9_000_000_000_000_000_000));
+        assert_eq!(widen.apply(10), None);
+        assert_eq!(ScaleFactor::new(0, -19, Rounding::TowardZero), None);
+        // Narrowing past every digit is fine
+        assert_eq!(ScaleFactor::new(-60, 60, Rounding::Up).unwrap().apply(1), Some(1));
+
+        assert_eq!(ScaleFactor::identity(-4).apply(i64::MIN), Some(i64::MIN));
+        // Same results as rescale
+        for (value, from, to) in [(-1_250, -3, -1), (7, -2, -8), (i64::MAX, -19, 0), (15, -1, 0)] {
+            for rounding in [Rounding::Down, Rounding::Up, Rounding::HalfEven] {
+                let factor = ScaleFactor::new(from, to, rounding).unwrap();
+                assert_eq!(factor.apply(value), rescale(value, -from as u32, -to as u32, rounding));
+            }
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let mut buf = [0u8; MAX_FORMATTED_LEN];
+        let mut format = |value, scale| {
+            let len = format_i64_with_precision(value, scale, &mut buf);
+            String::from_utf8(buf[..len].to_vec()).unwrap()
+        };
+        assert_eq!(format(120_000_000, 8), "1.2");
+        assert_eq!(format(-50, 2), "-0.5");
+        assert_eq!(format(1_005, 3), "1.005");
+        assert_eq!(format(0, 8), "0");
+        assert_eq!(format(7, 0), "7");
+        assert_eq!(format(i64::MIN, 0), "-9223372036854775808");
+        assert_eq!(format(i64::MIN, 19), "-0.9223372036854775808");
+        assert_eq!(format(i64::MAX, 10), "922337203.6854775807");
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let mut buf = [0u8; MAX_FORMATTED_LEN];
+        // xorshift, so failures reproduce
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let edges = [0, 1, -1, 10, -10, i64::MAX, i64::MIN, i64::MIN + 1];
+        for i in 0..10_000 {
+            let raw = next();
+            // Mix in short values, whose leading and trailing zeros matter most
+            let value = match i % 3 {
+                0 => raw as i64,
+                1 => (raw % 1_000_000) as i64 - 500_000,
+                _ => edges[i % edges.len()],
+            };
+            for scale in 0..=19 {
+                let len = format_i64_with_precision(value, scale, &mut buf);
+                assert_eq!(parse_i64_with_precision(&buf[..len], 0, scale), Ok((value, len)), "{value} at {scale}");
+            }
+        }
+    }
+
     #[test]
     fn test_errors() {
         assert_eq!(parse_i64_with_precision(b"", 0, 2), Err(ParseError::EmptyInput));
@@ -163,4 +1221,121 @@ mod tests {
         assert_eq!(parse_i64_with_precision(b"1-2", 0, 2), Err(ParseError::InvalidTerminator));
         assert_eq!(parse_i64_with_precision(b"1.2-3", 0, 2), Err(ParseError::InvalidTerminator));
     }
+
+    #[test]
+    fn test_options() {
+        let lenient = ParseOptions { allow_plus: true, skip_whitespace: true, ..Default::default() };
+        assert_eq!(parse_i64_with_options(b"+1.5", 0, 2, lenient), Ok((150, 4)));
+        assert_eq!(parse_i64_with_options(b"\t -0.25 ,x", 0, 2, lenient), Ok((-25, 8)));
+        assert_eq!(parse_i64_with_options(b"+2e1", 0, 0, lenient), Ok((20, 4)));
+        assert_eq!(parse_i64_with_options(b"+", 0, 2, lenient), Err(ParseError::NoDigits));
+        assert_eq!(parse_i64_with_options(b"+-1", 0, 2, lenient), Err(ParseError::NoDigits));
+        assert_eq!(parse_i64_with_options(b"   ", 0, 2, lenient), Err(ParseError::EmptyInput));
+
+        // The default matches parse_i64_with_precision
+        let plain = ParseOptions::default();
+        assert_eq!(parse_i64_with_options(b"1.5 ", 0, 2, plain), Ok((150, 3)));
+        assert_eq!(parse_i64_with_options(b"+1.5", 0, 2, plain), Err(ParseError::InvalidFirstChar));
+        assert_eq!(parse_i64_with_options(b" 1.5", 0, 2, plain), Err(ParseError::InvalidFirstChar));
+        assert_eq!(parse_i64_with_options(b"1.5", 0, 2, ParseOptions { allow_plus: true, ..plain }), Ok((150, 3)));
+    }
+
+    #[test]
+    fn test_strict() {
+        let strict = ParseOptions { strict: true, ..Default::default() };
+        assert_eq!(parse_i64_with_options(b"-1.25,", 0, 2, strict), Ok((-125, 5)));
+        assert_eq!(parse_i64_with_options(b"12\"", 0, 2, strict), Ok((1200, 2)));
+        assert_eq!(parse_i64_with_options(b"1.5e2]", 0, 0, strict), Ok((150, 5)));
+        assert_eq!(parse_i64_with_options(b"1.", 0, 2, strict), Err(ParseError::InvalidTerminator));
+        assert_eq!(parse_i64_with_options(b"1.a", 0, 2, strict), Err(ParseError::InvalidTerminator));
+        assert_eq!(parse_i64_with_options(b"1.5x", 0, 2, strict), Err(ParseError::InvalidTerminator));
+        assert_eq!(parse_i64_with_options(b".5", 0, 2, strict), Err(ParseError::NoDigits));
+        assert_eq!(parse_i64_with_options(b"-.5", 0, 2, strict), Err(ParseError::NoDigits));
+
+        // Lenient parses of the same input
+        assert_eq!(parse_i64_with_precision(b"1.a", 0, 2), Ok((100, 2)));
+        assert_eq!(parse_i64_with_precision(b".5", 0, 2), Ok((50, 2)));
+
+        let padded = ParseOptions { allow_plus: true, skip_whitespace: true, strict: true, ..Default::default() };
+        assert_eq!(parse_i64_with_options(b" +1.5 ,", 0, 2, padded), Ok((150, 6)));
+        assert_eq!(parse_i64_with_options(b" +1. ,", 0, 2, padded), Err(ParseError::InvalidTerminator));
+    }
+
+    #[test]
+    fn test_terminator_set() {
+        let level = ParseOptions { terminators: Terminators::of(b",\"]"), ..Default::default() };
+        assert_eq!(parse_i64_with_options(b"1.5,", 0, 2, level), Ok((150, 3)));
+        assert_eq!(parse_i64_with_options(b"-2]", 0, 2, level), Ok((-200, 2)));
+        assert_eq!(parse_i64_with_options(b"7", 0, 0, level), Ok((7, 1)));
+        assert_eq!(parse_i64_with_options(b"1.5?3\"", 0, 2, level), Err(ParseError::InvalidTerminator));
+        assert_eq!(parse_i64_with_options(b"1.5 ,", 0, 2, level), Err(ParseError::InvalidTerminator));
+        assert_eq!(parse_i64_with_precision(b"1.5?3\"", 0, 2), Ok((150, 3)));
+
+        // With padding skipped, the terminator is the byte after the blanks
+        let padded = ParseOptions { skip_whitespace: true, ..level };
+        assert_eq!(parse_i64_with_options(b"1.5 ,", 0, 2, padded), Ok((150, 4)));
+
+        let soh = Terminators::of(&[0x01, 0xff]);
+        assert!(soh.contains(0x01) && soh.contains(0xff));
+        assert!(!soh.contains(b',') && !soh.contains(0x41));
+        assert!((0..=255).all(|b| Terminators::ANY.contains(b)));
+    }
+
+    /// Cross-checks against `rust_decimal`, whose `Decimal` holds every
+    /// generated number exactly.
+    mod oracle {
+        use super::*;
+        use proptest::prelude::*;
+        use rust_decimal::{Decimal, RoundingStrategy};
+
+        fn strategy(rounding: Rounding) -> RoundingStrategy {
+            match rounding {
+                Rounding::TowardZero => RoundingStrategy::ToZero,
+                Rounding::Down => RoundingStrategy::ToNegativeInfinity,
+                Rounding::Up => RoundingStrategy::ToPositiveInfinity,
+                Rounding::HalfAwayFromZero => RoundingStrategy::MidpointAwayFromZero,
+                Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+            }
+        }
+
+        /// `decimal` as a fixed-point `i64` at `scale`, or `None` if it does not fit.
+        fn fixed(decimal: Decimal, scale: u32) -> Option<i64> {
+            let mut decimal = decimal;
+            decimal.rescale(scale);
+            i64::try_from(decimal.mantissa()).ok()
+        }
+
+        proptest! {
+            #[test]
+            fn parse_matches_decimal(text in "-?[0-9]{1,10}(\\.[0-9]{1,14})?([eE][+-]?[0-6])?", scale in 0u32..=10) {
+                let decimal = match text.contains(['e', 'E']) {
+                    true => Decimal::from_scientific(&text),
+                    false => Decimal::from_str_exact(&text),
+                }
+                .unwrap();
+                // The parser drops the digits beyond the scale
+                let expected = fixed(decimal.round_dp_with_strategy(scale, RoundingStrategy::ToZero), scale)
+                    .map(|value| (value, text.len()))
+                    .ok_or(ParseError::Overflow);
+                prop_assert_eq!(parse_i64_with_precision(text.as_bytes(), 0, scale), expected);
+            }
+
+            #[test]
+            fn rescale_matches_decimal(
+                value in any::<i64>(),
+                (from, to) in (0u32..=18).prop_flat_map(|from| (Just(from), 0..=from)),
+                rounding in prop::sample::select(vec![
+                    Rounding::TowardZero,
+                    Rounding::Down,
+                    Rounding::Up,
+                    Rounding::HalfAwayFromZero,
+                    Rounding::HalfEven,
+                ]),
+            ) {
+                let decimal = Decimal::from_i128_with_scale(value.into(), from);
+                let expected = fixed(decimal.round_dp_with_strategy(to, strategy(rounding)), to);
+                prop_assert_eq!(rescale(value, from, to, rounding), expected);
+            }
+        }
+    }
 }
\ No newline at end of file
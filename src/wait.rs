@@ -0,0 +1,103 @@
+//! How threads wait for work: by spinning, by parking, or a mix of both.
+//!
+//! The same [WaitStrategy] tunes the connector's worker loop and readers
+//! waiting on a [SharedBook](crate::model::SharedBook), trading latency
+//! against the CPU burnt while nothing happens.
+
+use std::time::Duration;
+
+/// What a thread does once a round finds nothing to do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WaitStrategy {
+    /// Spins with `spin_loop` and never gives up the core.
+    ///
+    /// Lowest latency; the core shows 100% busy even when the market is
+    /// quiet, so pair it with an isolated core.
+    BusySpin,
+    /// Spins for `spins` idle rounds, then parks for at most `park` at a
+    /// time until work arrives.
+    ///
+    /// Bursts are served at spinning latency while quiet periods cost
+    /// little CPU. Work arriving while parked waits up to `park` unless it
+    /// wakes the thread itself.
+    SpinThenPark { spins: u32, park: Duration },
+    /// Parks as soon as there is nothing to do, provided whatever it waits
+    /// for can wake it, and spins otherwise.
+    ///
+    /// A connector with live streams keeps spinning, since sockets cannot
+    /// wake it; use the [event loop](crate::connector::ExchangeConnector::event_loop)
+    /// to sleep on those.
+    #[default]
+    Block,
+}
+
+/// How long a thread should park after an idle round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Park {
+    /// Keep spinning.
+    No,
+    /// Park for at most this long.
+    For(Duration),
+    /// Park until woken.
+    UntilWoken,
+}
+
+impl WaitStrategy {
+    /// Decides how to wait after `idle` rounds in a row found nothing to do.
+    ///
+    /// `wakeable` says whether everything the thread waits for will wake it
+    /// when it arrives.
+    pub(crate) fn park(&self, idle: u32, wakeable: bool) -> Park {
+        match *self {
+            WaitStrategy::BusySpin => Park::No,
+            WaitStrategy::SpinThenPark { spins, .. } if idle < spins => Park::No,
+            WaitStrategy::SpinThenPark { .. } if wakeable => Park::UntilWoken,
+            WaitStrategy::SpinThenPark { park, .. } => Park::For(park),
+            WaitStrategy::Block if wakeable => Park::UntilWoken,
+            WaitStrategy::Block => Park::No,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::SharedBook;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_park() {
+        let park = Duration::from_millis(1);
+        let hybrid = WaitStrategy::SpinThenPark { spins: 10, park };
+        assert_eq!(WaitStrategy::BusySpin.park(u32::MAX, true), Park::No);
+        assert_eq!(hybrid.park(9, true), Park::No);
+        assert_eq!(hybrid.park(10, true), Park::UntilWoken);
+        assert_eq!(hybrid.park(10, false), Park::For(park));
+        assert_eq!(WaitStrategy::Block.park(0, true), Park::UntilWoken);
+        assert_eq!(WaitStrategy::Block.park(0, false), Park::No);
+    }
+
+    #[test]
+    fn test_wakes_parked_readers() {
+        let book = Arc::new(SharedBook::new());
+        let strategies = [
+            WaitStrategy::BusySpin,
+            WaitStrategy::SpinThenPark { spins: 100, park: Duration::from_secs(10) },
+            WaitStrategy::Block,
+        ];
+        for (packet, strategy) in strategies.into_iter().enumerate() {
+            let seen = 2 * packet as u64;
+            let reader = {
+                let book = Arc::clone(&book);
+                thread::spawn(move || book.wait_for_update(seen, strategy, Duration::from_secs(10)))
+            };
+            thread::sleep(Duration::from_millis(20));
+            book.begin_write();
+            thread::sleep(Duration::from_millis(5));
+            book.end_write();
+            book.wake_readers();
+            assert_eq!(reader.join().unwrap(), Some(seen + 2));
+        }
+    }
+}